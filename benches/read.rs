@@ -23,7 +23,9 @@ fn prep_repo(number_commits: usize, number_measurements: usize) -> tempfile::Tem
         empty_commit();
 
         let measurements = [10.0].repeat(number_measurements);
-        git_perf::measurement_storage::add_multiple("test_measurement", &measurements, &[])
+        let measurements =
+            git_perf::measurement_storage::new_measurements("test_measurement", &measurements, &[]);
+        git_perf::measurement_storage::add_multiple(&measurements)
             .expect("Could not add measurements");
     }
 
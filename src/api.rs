@@ -0,0 +1,125 @@
+//! Stable interface for recording and auditing measurements from other Rust programs.
+//!
+//! Unlike the rest of the crate, the items of this module follow semantic versioning. All
+//! functions operate on the git repository of the current working directory and honor its
+//! .gitperfconfig, exactly like the `git perf` commands of the same names.
+//!
+//! ```no_run
+//! use git_perf::api::{self, AuditOptions, AuditStatus};
+//!
+//! api::record("build_time", &[12.5], &[("os".to_owned(), "linux".to_owned())])?;
+//! let result = api::audit("build_time", &AuditOptions::default())?;
+//! assert_ne!(result.status, AuditStatus::Fail);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+
+use crate::{audit, data::Selector, measurement_retrieval, measurement_storage};
+
+pub use crate::audit::{AuditResult, AuditStatus, Coverage, Trend};
+pub use crate::data::{MeasurementData as Measurement, ReductionFunc};
+pub use crate::measurement_retrieval::Commit;
+pub use crate::stats::{ChangePoint, Stats};
+
+/// Add the `values` of `measurement` with the given `key_values` to HEAD in a single write.
+pub fn record(measurement: &str, values: &[f64], key_values: &[(String, String)]) -> Result<()> {
+    measurement_storage::add_multiple(&measurement_storage::new_measurements(
+        measurement,
+        values,
+        key_values,
+    ))
+}
+
+/// Settings of [`audit`]. The defaults match those of `git perf audit`.
+#[derive(Debug, Clone)]
+pub struct AuditOptions {
+    /// Commit to audit against its ancestors
+    pub commit: String,
+    /// Number of commits considered, including the audited commit
+    pub max_count: usize,
+    /// Minimum number of tail measurements, otherwise the audit is skipped
+    pub min_count: u16,
+    /// Key-value pairs the measurements have to match
+    pub selectors: Vec<(String, String)>,
+    /// What to aggregate the measurements of each commit with
+    pub aggregate_by: ReductionFunc,
+    /// Multiple of the tail's standard deviation the audited commit may deviate by
+    pub sigma: f64,
+    /// Multiple of the tail's standard deviation beyond which the audit results in
+    /// [`AuditStatus::Warn`] instead of passing
+    pub warn_sigma: Option<f64>,
+    /// Minimum fraction of the tail commits that need measurements
+    pub min_coverage: f64,
+    /// Build the tail from the first-parent history of this ref instead of the audited commit
+    pub baseline: Option<String>,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        Self {
+            commit: "HEAD".to_owned(),
+            max_count: 40,
+            min_count: 2,
+            selectors: Vec::new(),
+            aggregate_by: ReductionFunc::Min,
+            sigma: 4.0,
+            warn_sigma: None,
+            min_coverage: 0.0,
+            baseline: None,
+        }
+    }
+}
+
+/// Audit `measurement` of the configured commit against its tail measurements. A failed audit is
+/// not an error but reported as [`AuditStatus::Fail`].
+pub fn audit(measurement: &str, options: &AuditOptions) -> Result<AuditResult> {
+    if options.max_count < options.min_count.into() {
+        bail!(
+            "The minimal number of measurements ({}) cannot be more than the maximum number of measurements ({})",
+            options.min_count,
+            options.max_count
+        );
+    }
+    audit::audit_measurement(
+        measurement,
+        &options.commit,
+        options.max_count,
+        options.min_count,
+        &options
+            .selectors
+            .iter()
+            .map(|(k, v)| Selector::equals(k, v))
+            .collect_vec(),
+        options.aggregate_by,
+        options.sigma,
+        options.warn_sigma,
+        options.min_coverage,
+        options.baseline.as_deref(),
+    )
+}
+
+/// Measurements of the first-parent history starting at `commit`, newest first, for at most
+/// `max_count` commits. Commits without measurements are included with none.
+pub fn query(commit: &str, max_count: usize) -> Result<Vec<Commit>> {
+    measurement_retrieval::walk_commits_from(commit, max_count)?.try_collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reject_more_min_than_max_measurements() {
+        let options = AuditOptions {
+            max_count: 2,
+            min_count: 3,
+            ..Default::default()
+        };
+        assert!(audit("timer", &options)
+            .unwrap_err()
+            .to_string()
+            .starts_with("The minimal number of measurements (3)"));
+    }
+}
@@ -1,66 +1,1282 @@
 use crate::{
-    data::{MeasurementData, ReductionFunc},
+    basic_measure::{self, MeasureOptions, MeasureOutput},
+    config,
+    data::{self, MeasurementData, OutputFormat, ReductionFunc, Selector},
+    events::Event,
+    git_interop,
     measurement_retrieval::{self, summarize_measurements},
-    stats,
+    relink, sparkline,
+    stats::{self, ChangePoint, Stats},
+    trailers::{self, PERF_EXPECT_TRAILER},
 };
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use glob::{MatchOptions, Pattern};
 use itertools::Itertools;
-use std::iter;
+use readable::num::Float;
+use regex::Regex;
+use serde::Serialize;
+use std::{env, fmt::Display, fs::OpenOptions, io::Write, iter, path::Path};
+use thiserror::Error;
 
-pub fn audit(
+#[derive(Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditStatus {
+    Pass,
+    /// Deviates by more than the warning sigma, but not enough to fail
+    Warn,
+    Fail,
+    Skip,
+    /// Too many of the tail commits lack measurements for a meaningful audit
+    LowCoverage,
+}
+
+/// How many of the tail commits had measurements.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Coverage {
+    pub with_data: usize,
+    pub total: usize,
+}
+
+impl Coverage {
+    fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.with_data as f64 / self.total as f64
+        }
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} commits had data", self.with_data, self.total)
+    }
+}
+
+/// Drift of a measurement over the audited commits.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Trend {
+    /// Slope of the tail and HEAD values relative to their mean
+    pub pct_per_commit: f64,
+    /// Configured `max_trend_pct_per_commit`
+    pub max_pct_per_commit: f64,
+}
+
+impl Trend {
+    /// Whether the drift in either direction is steeper than the configured maximum.
+    pub fn exceeded(&self) -> bool {
+        self.pct_per_commit.abs() > self.max_pct_per_commit
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditResult {
+    pub measurement: String,
+    pub head: Option<f64>,
+    pub tail: Option<Stats>,
+    pub coverage: Option<Coverage>,
+    pub z_score: Option<f64>,
+    pub sigma: f64,
+    /// Multiple of the tail's standard deviation beyond which HEAD is reported as a warning
+    pub warn_sigma: Option<f64>,
+    pub status: AuditStatus,
+    pub skip_reason: Option<String>,
+    /// Commit trailer that accepted an otherwise failing deviation
+    pub accepted_by: Option<String>,
+    /// Step change within the tail measurements, which skews the comparison with HEAD
+    pub change_point: Option<ChangePoint>,
+    /// Drift over the tail and HEAD if a maximum is configured for the measurement
+    pub trend: Option<Trend>,
+    /// Number of times the measurement was rerun to verify a failure
+    pub reruns: u16,
+    /// Tail values with measurements, oldest first
+    #[serde(skip)]
+    pub history: Vec<f64>,
+}
+
+impl AuditStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            AuditStatus::Pass => "pass",
+            AuditStatus::Warn => "warn",
+            AuditStatus::Fail => "fail",
+            AuditStatus::Skip => "skip",
+            AuditStatus::LowCoverage => "low_coverage",
+        }
+    }
+}
+
+impl AuditResult {
+    fn severity(&self) -> Option<Severity> {
+        match self.status {
+            AuditStatus::Fail => Some(Severity::Error),
+            // Deliberately skipped as not affected by the audited commit
+            AuditStatus::Skip if self.head.is_none() => None,
+            AuditStatus::Warn | AuditStatus::Skip | AuditStatus::LowCoverage => {
+                Some(Severity::Warn)
+            }
+            AuditStatus::Pass if self.accepted_by.is_some() || self.change_point.is_some() => {
+                Some(Severity::Warn)
+            }
+            AuditStatus::Pass => None,
+        }
+    }
+
+    /// Values of the trend (tail oldest first, then HEAD), the range HEAD has to be within to pass,
+    /// and the color of HEAD's verdict.
+    fn sparkline_data(&self) -> (Vec<f64>, Option<(f64, f64)>, &'static str) {
+        let values = self.history.iter().copied().chain(self.head).collect_vec();
+        let band = self.tail.as_ref().map(|t| {
+            (
+                t.mean - self.sigma * t.stddev,
+                t.mean + self.sigma * t.stddev,
+            )
+        });
+        let color = match self.status {
+            AuditStatus::Pass => sparkline::PASS_COLOR,
+            AuditStatus::Warn => sparkline::WARN_COLOR,
+            AuditStatus::Fail => sparkline::FAIL_COLOR,
+            AuditStatus::Skip | AuditStatus::LowCoverage => sparkline::SKIP_COLOR,
+        };
+        (values, band, color)
+    }
+
+    /// Trend leading up to HEAD as an inline markdown image.
+    pub fn markdown_sparkline(&self, options: &sparkline::Options) -> String {
+        let (values, band, color) = self.sparkline_data();
+        sparkline::markdown_image(&values, band, color, options)
+    }
+
+    /// Trend leading up to HEAD as an inline HTML image.
+    pub fn html_sparkline(&self, options: &sparkline::Options) -> String {
+        let (values, band, color) = self.sparkline_data();
+        sparkline::html_image(&values, band, color, options)
+    }
+
+    fn print_text(&self, commit: &str) {
+        let measurement = &self.measurement;
+        match self.status {
+            AuditStatus::Skip | AuditStatus::LowCoverage => {
+                eprintln!(
+                    "{measurement}: {}",
+                    self.skip_reason.as_deref().unwrap_or_default()
+                );
+            }
+            AuditStatus::Fail => {
+                if self.reruns > 0 {
+                    eprintln!(
+                        "{measurement}: Still failing after {} verification reruns.",
+                        self.reruns
+                    );
+                }
+                if let Some(trend) = self.trend.as_ref().filter(|t| t.exceeded()) {
+                    eprintln!(
+                        "{measurement}: {commit} continues a drift of {:.2}% per commit, more than the max_trend_pct_per_commit of {}%.",
+                        trend.pct_per_commit, trend.max_pct_per_commit
+                    );
+                }
+                if self.z_score.is_none_or(|z| z > self.sigma) {
+                    eprintln!(
+                        "{measurement}: {commit} differs significantly from tail measurements.\nHead: {}\nTail: {}\nCoverage: {}",
+                        stats::aggregate_measurements(self.head.into_iter()),
+                        self.tail
+                            .as_ref()
+                            .expect("Failed audits have tail measurements"),
+                        self.coverage
+                            .as_ref()
+                            .expect("Failed audits have a coverage")
+                    );
+                }
+            }
+            AuditStatus::Warn => {
+                eprintln!(
+                    "⚠️ {measurement}: {commit} deviates from tail measurements by more than the warning threshold of {} sigma.\nHead: {}\nTail: {}",
+                    self.warn_sigma.expect("Warnings have a warning sigma"),
+                    stats::aggregate_measurements(self.head.into_iter()),
+                    self.tail.as_ref().expect("Warnings have tail measurements"),
+                );
+            }
+            AuditStatus::Pass => {
+                if self.reruns > 0 {
+                    eprintln!(
+                        "{measurement}: Passed after {} verification reruns.",
+                        self.reruns
+                    );
+                }
+                if let Some(accepted_by) = &self.accepted_by {
+                    eprintln!(
+                        "{measurement}: {commit} differs significantly from tail measurements, accepted by '{accepted_by}'."
+                    );
+                }
+                if let Some(coverage) = self.coverage.as_ref().filter(|c| c.fraction() < 1.0) {
+                    eprintln!("{measurement}: Coverage: {coverage}");
+                }
+            }
+        }
+        if let Some(change_point) = &self.change_point {
+            eprintln!(
+                "{measurement}: Tail measurements contain a step change from {} to {} after {} commits with data. Consider starting a new epoch.",
+                Float::from(change_point.mean_before),
+                Float::from(change_point.mean_after),
+                change_point.index
+            );
+        }
+    }
+}
+
+/// Table of the audit results with a trend per measurement, e.g. for pull request comments.
+fn as_markdown(results: &[AuditResult], sparkline: &sparkline::Options) -> String {
+    let mut out = String::from("| Measurement | Status | HEAD | Tail | z-score | Trend |\n");
+    out.push_str("|---|---|---|---|---|---|\n");
+    for result in results {
+        let status = match result.status {
+            AuditStatus::Pass if result.accepted_by.is_some() => "accepted",
+            AuditStatus::Pass => "pass",
+            AuditStatus::Warn => "⚠️ warn",
+            AuditStatus::Fail => "**fail**",
+            AuditStatus::Skip => "skip",
+            AuditStatus::LowCoverage => "low coverage",
+        };
+        out.push_str(&format!(
+            "| {} | {status} | {} | {} | {} | {} |\n",
+            result.measurement.replace('|', "\\|"),
+            result
+                .head
+                .map_or("-".to_string(), |h| Float::from(h).to_string()),
+            result.tail.as_ref().map_or("-".to_string(), |t| format!(
+                "{} ± {}",
+                Float::from(t.mean),
+                Float::from(t.stddev)
+            )),
+            result
+                .z_score
+                .map_or("-".to_string(), |z| format!("{z:.2}")),
+            result.markdown_sparkline(sparkline),
+        ));
+    }
+    out
+}
+
+/// Environment variable with the path of the job summary file of a GitHub Actions step.
+const GITHUB_STEP_SUMMARY: &str = "GITHUB_STEP_SUMMARY";
+
+/// Append the results as a markdown table to the GitHub Actions step summary at `path`.
+fn append_github_summary(
+    path: &Path,
+    results: &[AuditResult],
+    commit: &str,
+    sparkline: &sparkline::Options,
+) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open step summary '{}'", path.display()))?;
+    write!(
+        file,
+        "### git perf audit of {commit}\n\n{}\n",
+        as_markdown(results, sparkline)
+    )
+    .with_context(|| format!("Failed to write step summary '{}'", path.display()))
+}
+
+/// Workflow command annotating a failed or warning result in GitHub Actions, e.g.
+/// `::error title=git perf audit::timer: ...`. `None` for results without any severity.
+fn github_annotation(result: &AuditResult, commit: &str) -> Option<String> {
+    let command = match result.severity()? {
+        Severity::Error => "error",
+        Severity::Warn => "warning",
+    };
+    let z_score = result
+        .z_score
+        .map(|z| format!(" (z-score {z:.2})"))
+        .unwrap_or_default();
+    let message = match result.status {
+        AuditStatus::Fail => {
+            format!("{commit} differs significantly from tail measurements{z_score}")
+        }
+        AuditStatus::Warn => format!(
+            "{commit} deviates from tail measurements by more than the warning threshold{z_score}"
+        ),
+        AuditStatus::Skip | AuditStatus::LowCoverage => {
+            result.skip_reason.clone().unwrap_or_default()
+        }
+        AuditStatus::Pass => match &result.accepted_by {
+            Some(accepted_by) => format!(
+                "{commit} differs significantly from tail measurements, accepted by '{accepted_by}'"
+            ),
+            None => "Tail measurements contain a step change".to_owned(),
+        },
+    };
+    // Data of workflow commands must not contain raw newlines
+    let message = format!("{}: {message}", result.measurement)
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    Some(format!("::{command} title=git perf audit::{message}"))
+}
+
+/// Reason for an unsuccessful audit, each with its own exit code of `git perf audit`.
+#[derive(Debug, Error)]
+pub enum AuditError {
+    /// Measurements differ significantly from their tail or the audit policy was violated
+    #[error("{0}")]
+    Regression(String),
+    /// Measurements are missing or, with `strict`, were skipped for lack of data
+    #[error("{0}")]
+    InsufficientData(String),
+    /// The audit could not be run, e.g. due to invalid arguments or configuration
+    #[error(transparent)]
+    Invalid(#[from] anyhow::Error),
+}
+
+impl AuditError {
+    pub const REGRESSION_EXIT_CODE: u8 = 1;
+    pub const INSUFFICIENT_DATA_EXIT_CODE: u8 = 2;
+    pub const INVALID_EXIT_CODE: u8 = 3;
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AuditError::Regression(_) => Self::REGRESSION_EXIT_CODE,
+            AuditError::InsufficientData(_) => Self::INSUFFICIENT_DATA_EXIT_CODE,
+            AuditError::Invalid(_) => Self::INVALID_EXIT_CODE,
+        }
+    }
+}
+
+/// Severity of an audit outcome for deciding whether the audit as a whole fails.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Deviations beyond the warning sigma, audits skipped for lack of data, deviations accepted
+    /// by a commit trailer and step changes within the tail measurements
+    Warn,
+    /// Failed audits
+    #[default]
+    Error,
+}
+
+/// When the audit of several measurements fails as a whole.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AuditPolicy {
+    /// Number of failed measurements that are tolerated
+    pub max_failures: usize,
+    /// Outcomes of at least this severity count as failures
+    pub fail_on: Severity,
+    /// Fail if the tail measurements themselves contain a step change
+    pub fail_on_change_point: bool,
+    /// Rerun the configured command of a failed measurement up to this many times and audit
+    /// again before declaring the failure
+    pub verify_reruns: u16,
+    /// Fail if measurements were skipped for lack of data
+    pub strict: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn audit_single(
     measurement: &str,
+    commit: &str,
     max_count: usize,
     min_count: u16,
-    selectors: &[(String, String)],
+    selectors: &[Selector],
     summarize_by: ReductionFunc,
     sigma: f64,
+    warn_sigma: Option<f64>,
+    min_coverage: f64,
+    baseline: Option<&str>,
+    changed: Option<&[String]>,
+    verify_reruns: u16,
+) -> Result<AuditResult> {
+    if let Some(changed) = changed {
+        if !is_affected(
+            measurement,
+            &config::determine_audit_paths_from_config(),
+            changed,
+        )? {
+            return Ok(AuditResult {
+                measurement: measurement.to_owned(),
+                head: None,
+                tail: None,
+                coverage: None,
+                z_score: None,
+                sigma,
+                warn_sigma,
+                status: AuditStatus::Skip,
+                skip_reason: Some(format!(
+                    "No paths mapped to measurement '{measurement}' changed in {commit}. Skipping test."
+                )),
+                accepted_by: None,
+                change_point: None,
+                trend: None,
+                reruns: 0,
+                history: Vec::new(),
+            });
+        }
+    }
+
+    let audit_again = || {
+        audit_measurement(
+            measurement,
+            commit,
+            max_count,
+            min_count,
+            selectors,
+            summarize_by,
+            sigma,
+            warn_sigma,
+            min_coverage,
+            baseline,
+        )
+    };
+    let result = audit_again()?;
+    if result.status != AuditStatus::Fail || verify_reruns == 0 {
+        return Ok(result);
+    }
+    verify_by_rerunning(
+        measurement,
+        commit,
+        selectors,
+        verify_reruns,
+        result,
+        audit_again,
+    )
+}
+
+/// Rerun the configured command of the failed measurement up to `reruns` times until `audit`
+/// passes. Each rerun adds a sample to HEAD with the exact `key=value` selectors and
+/// `rerun=<n>` as metadata, which is aggregated with the existing samples of HEAD. The value of
+/// each rerun is its wall time or, with an `extract_regex` configured, extracted from its output.
+fn verify_by_rerunning(
+    measurement: &str,
+    commit: &str,
+    selectors: &[Selector],
+    reruns: u16,
+    failed: AuditResult,
+    audit: impl Fn() -> Result<AuditResult>,
+) -> Result<AuditResult> {
+    let Some(command) = config::determine_command_from_config(measurement) else {
+        eprintln!("{measurement}: No command configured to verify the failure by rerunning it.");
+        return Ok(failed);
+    };
+    if git_interop::resolve_commit(commit)? != git_interop::get_head_revision()? {
+        eprintln!(
+            "{measurement}: Only failures of HEAD can be verified by rerunning the measurement."
+        );
+        return Ok(failed);
+    }
+    let output = match config::determine_extract_regex_from_config(measurement) {
+        Some(regex) => MeasureOutput::Extract(
+            Regex::new(&regex)
+                .with_context(|| format!("Invalid extract_regex of measurement '{measurement}'"))?,
+        ),
+        None => MeasureOutput::WallTime,
+    };
+
+    let mut result = failed;
+    for rerun in 1..=reruns {
+        let key_values = selectors
+            .iter()
+            .filter_map(Selector::as_key_value)
+            .chain(iter::once(("rerun".to_owned(), rerun.to_string())))
+            .collect_vec();
+        basic_measure::measure(
+            measurement,
+            1,
+            1,
+            &command,
+            &key_values,
+            MeasureOptions {
+                output: output.clone(),
+                ..MeasureOptions::default()
+            },
+        )?;
+        result = AuditResult {
+            reruns: rerun,
+            ..audit()?
+        };
+        if result.status != AuditStatus::Fail {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+/// Multiple of the tail's standard deviation HEAD has to be within without configuration.
+pub const DEFAULT_SIGMA: f64 = 4.0;
+
+/// Aggregation and sigma of the audit of the measurement with the `selectors`. Without
+/// `summarize_by` and `sigma`, they are as configured for the measurement and selectors, by
+/// default the minimum and [`DEFAULT_SIGMA`]. Fails if the `warn_sigma` is not less than the
+/// sigma.
+pub(crate) fn resolve_audit_params(
+    measurement: &str,
+    selectors: &[Selector],
+    summarize_by: Option<ReductionFunc>,
+    sigma: Option<f64>,
+    warn_sigma: Option<f64>,
+) -> Result<(ReductionFunc, f64)> {
+    let summarize_by = match summarize_by {
+        Some(summarize_by) => summarize_by,
+        None => {
+            config::determine_aggregate_by_from_config(measurement)?.unwrap_or(ReductionFunc::Min)
+        }
+    };
+    let sigma = match sigma {
+        Some(sigma) => sigma,
+        None => {
+            config::determine_sigma_from_config(measurement, selectors)?.unwrap_or(DEFAULT_SIGMA)
+        }
+    };
+    if let Some(warn_sigma) = warn_sigma.filter(|w| *w >= sigma) {
+        return Err(AuditError::Invalid(anyhow!(
+            "The warning sigma ({warn_sigma}) must be less than the sigma ({sigma})"
+        ))
+        .into());
+    }
+    Ok((summarize_by, sigma))
+}
+
+/// Audit each of the measurements. With a single measurement, JSON output is a single object,
+/// otherwise an array of objects. Without `summarize_by` and `sigma`, each measurement is
+/// audited as configured for it and the `selectors`, see [`resolve_audit_params`]. Groups of
+/// measurements are audited separately as determined by [`groups_of_commit`].
+#[allow(clippy::too_many_arguments)]
+pub fn audit(
+    measurements: &[String],
+    commit: &str,
+    max_count: usize,
+    min_count: u16,
+    selectors: &[Selector],
+    separate_by: &[String],
+    separate_by_matrix: bool,
+    summarize_by: Option<ReductionFunc>,
+    sigma: Option<f64>,
+    warn_sigma: Option<f64>,
+    min_coverage: f64,
+    baseline: Option<&str>,
+    output_format: OutputFormat,
+    sparkline: &sparkline::Options,
+    gh_summary: bool,
+    changed_only: bool,
+    policy: AuditPolicy,
 ) -> Result<()> {
-    let all = measurement_retrieval::walk_commits(max_count)?;
+    let changed = if changed_only {
+        Some(git_interop::changed_paths(commit)?)
+    } else {
+        None
+    };
+    if let Some(baseline) = baseline {
+        git_interop::resolve_commit(baseline)?;
+    }
+
+    let results: Vec<_> = measurements
+        .iter()
+        .map(|measurement| {
+            groups_of_commit(
+                commit,
+                measurement,
+                selectors,
+                separate_by,
+                separate_by_matrix,
+            )?
+            .into_iter()
+            .map(|group| {
+                let selectors = selectors
+                    .iter()
+                    .cloned()
+                    .chain(group.iter().map(|(k, v)| Selector::equals(k, v)))
+                    .collect_vec();
+                let (summarize_by, sigma) =
+                    resolve_audit_params(measurement, &selectors, summarize_by, sigma, warn_sigma)?;
+                let mut result = audit_single(
+                    measurement,
+                    commit,
+                    max_count,
+                    min_count,
+                    &selectors,
+                    summarize_by,
+                    sigma,
+                    warn_sigma,
+                    min_coverage,
+                    baseline,
+                    changed.as_deref(),
+                    policy.verify_reruns,
+                )?;
+                if !group.is_empty() {
+                    result.measurement = format!(
+                        "{measurement} ({})",
+                        group.iter().map(|(k, v)| format!("{k}={v}")).join(",")
+                    );
+                }
+                Ok::<_, anyhow::Error>(result)
+            })
+            .collect::<Result<Vec<_>>>()
+        })
+        .flatten_ok()
+        .try_collect()?;
 
-    let filter_by = |m: &MeasurementData| {
-        m.name == measurement
-            && selectors
+    for result in &results {
+        Event::info(
+            "audit",
+            result.status.name(),
+            result.skip_reason.clone().unwrap_or_default(),
+        )
+        .measurement(&result.measurement)
+        .emit();
+    }
+    match output_format {
+        OutputFormat::Text => results.iter().for_each(|r| r.print_text(commit)),
+        OutputFormat::Json => match results.as_slice() {
+            [result] => println!("{}", serde_json::to_string_pretty(result)?),
+            results => println!("{}", serde_json::to_string_pretty(results)?),
+        },
+        OutputFormat::Markdown => print!("{}", as_markdown(&results, sparkline)),
+    }
+    if let Some(path) = env::var_os(GITHUB_STEP_SUMMARY).filter(|p| gh_summary && !p.is_empty()) {
+        append_github_summary(Path::new(&path), &results, commit, sparkline)?;
+        for annotation in results.iter().filter_map(|r| github_annotation(r, commit)) {
+            println!("{annotation}");
+        }
+    }
+
+    if results.iter().any(|r| {
+        r.head.is_some() && matches!(r.status, AuditStatus::Skip | AuditStatus::LowCoverage)
+    }) {
+        if let Ok(commits) = measurement_retrieval::walk_commits_from(commit, max_count)
+            .and_then(|commits| commits.try_collect::<_, Vec<_>, _>())
+        {
+            relink::warn_about_orphaned_measurements(&commits);
+        }
+    }
+
+    let count = |status: AuditStatus| results.iter().filter(|r| r.status == status).count();
+    if results.len() > 1 {
+        eprintln!(
+            "Audited {} measurements: {} passed, {} failed, {} skipped, {} with warnings",
+            results.len(),
+            count(AuditStatus::Pass),
+            count(AuditStatus::Fail),
+            count(AuditStatus::Skip) + count(AuditStatus::LowCoverage),
+            count(AuditStatus::Warn),
+        );
+    }
+
+    let failed = results
+        .iter()
+        .filter(|r| r.severity().is_some_and(|s| s >= policy.fail_on))
+        .count();
+    let counted = match policy.fail_on {
+        Severity::Warn => format!("{failed} measurements with warnings or failures"),
+        Severity::Error => format!("{failed} failed measurements"),
+    };
+    if failed > policy.max_failures {
+        let message = match policy.fail_on {
+            Severity::Warn => format!(
+                "{failed} audits finished with warnings or failures (at most {} tolerated).",
+                policy.max_failures
+            ),
+            Severity::Error => format!(
+                "{commit} differs significantly from tail measurements for {failed} of {} measurements (at most {} tolerated).",
+                results.len(),
+                policy.max_failures
+            ),
+        };
+        return Err(AuditError::Regression(message).into());
+    }
+    if policy.fail_on_change_point {
+        let changed = results
+            .iter()
+            .filter(|r| r.change_point.is_some())
+            .map(|r| &r.measurement)
+            .join(", ");
+        if !changed.is_empty() {
+            return Err(AuditError::Regression(format!(
+                "Tail measurements contain a step change for: {changed}"
+            ))
+            .into());
+        }
+    }
+    if policy.strict {
+        let skipped = results
+            .iter()
+            .filter(|r| {
+                r.head.is_some() && matches!(r.status, AuditStatus::Skip | AuditStatus::LowCoverage)
+            })
+            .map(|r| &r.measurement)
+            .join(", ");
+        if !skipped.is_empty() {
+            return Err(AuditError::InsufficientData(format!(
+                "Skipped for lack of data: {skipped}"
+            ))
+            .into());
+        }
+    }
+    if failed > 0 {
+        eprintln!(
+            "Tolerating {counted} within the budget of {}.",
+            policy.max_failures
+        );
+    }
+
+    Ok(())
+}
+
+/// Values further than this factor outside of the historical range are considered implausible.
+const PLAUSIBILITY_FACTOR: f64 = 1000.0;
+
+/// Number of commits, including HEAD, considered for the plausibility check.
+const PLAUSIBILITY_HISTORY: usize = 40;
+
+/// Check the measurements of HEAD, where new measurements are recorded, against the range of
+/// the same measurements in the preceding commits. Refuses values that are implausibly far
+/// outside this range as they most likely stem from broken measurements.
+pub fn audit_plausibility() -> Result<()> {
+    let mut commits = measurement_retrieval::walk_commits(PLAUSIBILITY_HISTORY)?;
+    let head = match commits.next() {
+        Some(head) => head?,
+        None => return Ok(()),
+    };
+    let tail: Vec<_> = commits
+        .map_ok(|c| c.measurements)
+        .flatten_ok()
+        .try_collect()?;
+
+    let implausible = implausible_measurements(&head.measurements, &tail, PLAUSIBILITY_FACTOR);
+    if !implausible.is_empty() {
+        bail!(
+            "Refusing to publish implausible measurements for HEAD:\n{}",
+            implausible.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Descriptions of all `head` measurements more than `factor` times outside of the range of the
+/// `history` measurements with the same name and epoch.
+fn implausible_measurements(
+    head: &[MeasurementData],
+    history: &[MeasurementData],
+    factor: f64,
+) -> Vec<String> {
+    head.iter()
+        .filter_map(|m| {
+            let (min, max) = history
                 .iter()
-                .all(|s| m.key_values.get(&s.0).map(|v| *v == s.1).unwrap_or(false))
+                .filter(|h| h.name == m.name && h.epoch == m.epoch)
+                .map(|h| h.val)
+                .minmax()
+                .into_option()?;
+            let too_large = m.val > max.max(0.0) * factor;
+            let too_small = min > 0.0 && m.val < min / factor;
+            (too_large || too_small).then(|| {
+                format!(
+                    "{}: {} outside of historical range [{}; {}]",
+                    m.name, m.val, min, max
+                )
+            })
+        })
+        .collect()
+}
+
+/// Whether a path mapped to `measurement` is among the `changed` paths. Measurements without
+/// any mapping cannot be attributed to a component and are always considered affected.
+fn is_affected(
+    measurement: &str,
+    mappings: &[(String, Vec<String>)],
+    changed: &[String],
+) -> Result<bool> {
+    let mut mapped = false;
+    for (pattern, globs) in mappings {
+        let regex = Regex::new(&format!("^(?:{pattern})$"))
+            .with_context(|| format!("Invalid measurement pattern '{pattern}'"))?;
+        if !regex.is_match(measurement) {
+            continue;
+        }
+        mapped = true;
+        for glob in globs {
+            let glob = Pattern::new(glob).with_context(|| format!("Invalid path glob '{glob}'"))?;
+            // `*` stays within a directory, unlike `**`
+            let options = MatchOptions {
+                require_literal_separator: true,
+                ..Default::default()
+            };
+            if changed.iter().any(|path| glob.matches_with(path, options)) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(!mapped)
+}
+
+/// Names of all measurements of `commit` matching the `selectors`, in alphabetical order. Fails
+/// if there are none.
+pub fn measurements_of_commit(commit: &str, selectors: &[Selector]) -> Result<Vec<String>> {
+    let head = measurement_retrieval::walk_commits_from(commit, 1)?
+        .next()
+        .ok_or_else(|| anyhow!("No commit at {commit}"))??;
+    let names = head
+        .measurements
+        .into_iter()
+        .filter(|m| m.matches_selectors(selectors))
+        .map(|m| m.name)
+        .unique()
+        .sorted()
+        .collect_vec();
+    if names.is_empty() {
+        return Err(AuditError::InsufficientData(format!("No measurements for {commit}.")).into());
+    }
+    Ok(names)
+}
+
+/// Distinct values of the `separate_by` keys among the measurements named `measurement` of
+/// `commit` that match the `selectors`, sorted, each paired with its key. With
+/// `separate_by_matrix`, the keys are all build matrix dimensions of these measurements instead.
+/// A single empty group without any keys. Fails if no measurement has values for all of the keys.
+fn groups_of_commit(
+    commit: &str,
+    measurement: &str,
+    selectors: &[Selector],
+    separate_by: &[String],
+    separate_by_matrix: bool,
+) -> Result<Vec<Vec<(String, String)>>> {
+    if separate_by.is_empty() && !separate_by_matrix {
+        return Ok(vec![Vec::new()]);
+    }
+    let head = measurement_retrieval::walk_commits_from(commit, 1)?
+        .next()
+        .ok_or_else(|| anyhow!("No commit at {commit}"))??;
+    let measurements = head
+        .measurements
+        .iter()
+        .filter(|m| m.name == measurement && m.matches_selectors(selectors))
+        .collect_vec();
+    let separate_by = if separate_by_matrix {
+        let dimensions = data::matrix_dimensions(measurements.iter().copied());
+        if dimensions.is_empty() {
+            return Err(AuditError::InsufficientData(format!(
+                "No build matrix dimensions recorded for {measurement} at {commit}."
+            ))
+            .into());
+        }
+        dimensions
+    } else {
+        separate_by.to_vec()
     };
+    let groups = measurements
+        .iter()
+        .filter_map(|m| {
+            separate_by
+                .iter()
+                .map(|k| Some((k.clone(), m.key_values.get(k)?.clone())))
+                .collect::<Option<Vec<_>>>()
+        })
+        .unique()
+        .sorted()
+        .collect_vec();
+    if groups.is_empty() {
+        return Err(AuditError::InsufficientData(format!(
+            "No measurements of {measurement} for {commit} with values for {}.",
+            separate_by.join(", ")
+        ))
+        .into());
+    }
+    Ok(groups)
+}
+
+/// Names of the measurements of `commit` whose audit against the last `max_count` commits fails
+/// with `sigma` like `git perf audit` with its defaults otherwise, each aggregated as configured
+/// for it. Measurements lacking data do not fail.
+pub fn failing_measurements(commit: &str, max_count: usize, sigma: f64) -> Result<Vec<String>> {
+    measurements_of_commit(commit, &[])?
+        .into_iter()
+        .filter_map(|measurement| {
+            let summarize_by = match config::determine_aggregate_by_from_config(&measurement) {
+                Ok(summarize_by) => summarize_by.unwrap_or(ReductionFunc::Min),
+                Err(e) => return Some(Err(e)),
+            };
+            let result = audit_measurement(
+                &measurement,
+                commit,
+                max_count,
+                2,
+                &[],
+                summarize_by,
+                sigma,
+                None,
+                0.0,
+                None,
+            );
+            match result {
+                Ok(result) if result.status == AuditStatus::Fail => Some(Ok(measurement)),
+                Ok(_) => None,
+                Err(e) if matches!(e.downcast_ref(), Some(AuditError::InsufficientData(_))) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .collect()
+}
+
+/// Minimum number of tail measurements on either side of a step change to report it.
+pub const CHANGE_POINT_MIN_SEGMENT: usize = 3;
+
+/// Minimum number of values, including HEAD, to fit a trend to.
+const TREND_MIN_VALUES: usize = 3;
+
+#[allow(clippy::too_many_arguments)]
+pub fn audit_measurement(
+    measurement: &str,
+    commit: &str,
+    max_count: usize,
+    min_count: u16,
+    selectors: &[Selector],
+    summarize_by: ReductionFunc,
+    sigma: f64,
+    warn_sigma: Option<f64>,
+    min_coverage: f64,
+    baseline: Option<&str>,
+) -> Result<AuditResult> {
+    let all: Box<dyn Iterator<Item = _>> = match baseline {
+        Some(baseline) => Box::new(measurement_retrieval::walk_commits_against_baseline(
+            commit, baseline, max_count,
+        )?),
+        None => Box::new(measurement_retrieval::walk_commits_from(commit, max_count)?),
+    };
+
+    let filter_by = |m: &MeasurementData| m.name == measurement && m.matches_selectors(selectors);
 
     let mut aggregates = summarize_measurements(all, &summarize_by, &filter_by);
 
     let head = aggregates
         .next()
-        .ok_or(anyhow!("No commit at HEAD"))
+        .ok_or_else(|| anyhow!("No commit at {commit}"))
         .and_then(|s| {
             s.and_then(|cs| {
-                cs.measurement
-                    .map(|m| m.val)
-                    .ok_or(anyhow!("No measurement for HEAD."))
+                cs.measurement.map(|m| m.val).ok_or_else(|| {
+                    AuditError::InsufficientData(format!("No measurement for {commit}.")).into()
+                })
             })
         })?;
 
     let tail: Vec<_> = aggregates
-        .filter_map_ok(|cs| cs.measurement.map(|m| m.val))
+        .map_ok(|cs| cs.measurement.map(|m| m.val))
         .take(max_count)
         .try_collect()?;
+    let coverage = Coverage {
+        with_data: tail.iter().flatten().count(),
+        total: tail.len(),
+    };
 
+    // Oldest first
+    let chronological = tail.iter().flatten().rev().copied().collect_vec();
     let head_summary = stats::aggregate_measurements(iter::once(head));
-    let tail_summary = stats::aggregate_measurements(tail.into_iter());
+    let tail_summary = stats::aggregate_measurements(tail.into_iter().flatten());
 
     if tail_summary.len < min_count.into() {
-        // TODO(kaihowl) handle with explicit return? Print text somewhere else?
         let number_measurements = tail_summary.len;
         let plural_s = if number_measurements > 1 { "s" } else { "" };
-        eprintln!("Only {number_measurements} measurement{plural_s} found. Less than requested min_measurements of {min_count}. Skipping test.");
-        return Ok(());
+        return Ok(AuditResult {
+            measurement: measurement.to_owned(),
+            head: Some(head),
+            tail: Some(tail_summary),
+            coverage: Some(coverage),
+            z_score: None,
+            sigma,
+            warn_sigma,
+            status: AuditStatus::Skip,
+            skip_reason: Some(format!("Only {number_measurements} measurement{plural_s} found. Less than requested min_measurements of {min_count}. Skipping test.")),
+            accepted_by: None,
+            change_point: None,
+            trend: None,
+            reruns: 0,
+            history: chronological,
+        });
     }
 
-    if head_summary.significantly_different_from(&tail_summary, sigma) {
-        // TODO(kaihowl) print details
-        bail!(
-            "HEAD differs significantly from tail measurements.\nHead: {}\nTail: {}",
-            &head_summary,
-            &tail_summary
+    if coverage.fraction() < min_coverage {
+        return Ok(AuditResult {
+            measurement: measurement.to_owned(),
+            head: Some(head),
+            tail: Some(tail_summary),
+            skip_reason: Some(format!(
+                "Only {coverage}. Less than requested min_coverage of {min_coverage}. Skipping test."
+            )),
+            coverage: Some(coverage),
+            z_score: None,
+            sigma,
+            warn_sigma,
+            status: AuditStatus::LowCoverage,
+            accepted_by: None,
+            change_point: None,
+            trend: None,
+            reruns: 0,
+            history: chronological,
+        });
+    }
+
+    let mut accepted_by = None;
+    let status = if head_summary.significantly_different_from(&tail_summary, sigma) {
+        let trailers = git_interop::commit_trailers(commit, PERF_EXPECT_TRAILER)?;
+        match trailers::expectation_for(measurement, &trailers)? {
+            Some(expectation) if expectation.allows(tail_summary.mean, head) => {
+                accepted_by = Some(expectation.to_string());
+                AuditStatus::Pass
+            }
+            _ => AuditStatus::Fail,
+        }
+    } else if warn_sigma
+        .is_some_and(|w| head_summary.significantly_different_from(&tail_summary, w))
+    {
+        AuditStatus::Warn
+    } else {
+        AuditStatus::Pass
+    };
+
+    let trend = config::determine_max_trend_from_config(measurement).and_then(|max| {
+        let values = chronological
+            .iter()
+            .copied()
+            .chain(iter::once(head))
+            .collect_vec();
+        (values.len() >= TREND_MIN_VALUES).then_some(())?;
+        Some(Trend {
+            pct_per_commit: stats::trend_pct_per_commit(&values)?,
+            max_pct_per_commit: max,
+        })
+    });
+    // A deviation accepted by a commit trailer would otherwise still fail as a steep trend
+    let status = match status {
+        AuditStatus::Pass | AuditStatus::Warn
+            if accepted_by.is_none() && trend.as_ref().is_some_and(Trend::exceeded) =>
+        {
+            AuditStatus::Fail
+        }
+        status => status,
+    };
+
+    Ok(AuditResult {
+        measurement: measurement.to_owned(),
+        head: Some(head),
+        z_score: Some(head_summary.z_score(&tail_summary)),
+        tail: Some(tail_summary),
+        coverage: Some(coverage),
+        sigma,
+        warn_sigma,
+        status,
+        skip_reason: None,
+        accepted_by,
+        change_point: stats::detect_change_point(&chronological, sigma, CHANGE_POINT_MIN_SEGMENT),
+        trend,
+        reruns: 0,
+        history: chronological,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    fn measurement(name: &str, val: f64) -> MeasurementData {
+        MeasurementData {
+            epoch: 0,
+            name: name.to_string(),
+            timestamp: 0.0,
+            val,
+            key_values: Default::default(),
+        }
+    }
+
+    #[test]
+    fn markdown_table_with_trends() {
+        let failed = AuditResult {
+            measurement: "timer".to_string(),
+            head: Some(20.0),
+            tail: Some(Stats {
+                mean: 10.0,
+                stddev: 1.0,
+                len: 3,
+            }),
+            coverage: None,
+            z_score: Some(10.0),
+            sigma: 4.0,
+            warn_sigma: None,
+            status: AuditStatus::Fail,
+            skip_reason: None,
+            accepted_by: None,
+            change_point: None,
+            trend: None,
+            reruns: 0,
+            history: vec![9.0, 10.0, 11.0],
+        };
+        let skipped = AuditResult {
+            measurement: "a|b".to_string(),
+            head: None,
+            tail: None,
+            coverage: None,
+            z_score: None,
+            sigma: 4.0,
+            warn_sigma: None,
+            status: AuditStatus::Skip,
+            skip_reason: None,
+            accepted_by: None,
+            change_point: None,
+            trend: None,
+            reruns: 0,
+            history: Vec::new(),
+        };
+        let markdown = as_markdown(&[skipped, failed], &sparkline::Options::default());
+        let rows = markdown.lines().collect_vec();
+        assert_eq!(rows.len(), 4);
+        assert!(rows[2].starts_with("| a\\|b | skip | - | - | - | ![]("));
+        assert!(
+            rows[3].starts_with("| timer | **fail** | 20.000 | 10.000 ± 1.000 | 10.00 | ![▁▂▂█](")
         );
+        assert!(rows[3].contains(&sparkline::data_uri(&sparkline::svg(
+            &[9.0, 10.0, 11.0, 20.0],
+            Some((6.0, 14.0)),
+            sparkline::FAIL_COLOR
+        ))));
     }
 
-    Ok(())
+    #[test]
+    fn warnings_do_not_fail_by_default() {
+        let warned = AuditResult {
+            measurement: "timer".to_string(),
+            head: Some(13.0),
+            tail: Some(Stats {
+                mean: 10.0,
+                stddev: 1.0,
+                len: 3,
+            }),
+            coverage: None,
+            z_score: Some(3.0),
+            sigma: 4.0,
+            warn_sigma: Some(2.0),
+            status: AuditStatus::Warn,
+            skip_reason: None,
+            accepted_by: None,
+            change_point: None,
+            trend: None,
+            reruns: 0,
+            history: vec![9.0, 10.0, 11.0],
+        };
+        assert_eq!(warned.severity(), Some(Severity::Warn));
+        assert!(Severity::default() != Severity::Warn);
+        assert!(as_markdown(&[warned], &sparkline::Options::default())
+            .contains("| timer | ⚠️ warn | 13.000 |"));
+    }
+
+    #[test]
+    fn github_summary_and_annotations() {
+        let failed = AuditResult {
+            measurement: "timer".to_string(),
+            head: Some(20.0),
+            tail: Some(Stats {
+                mean: 10.0,
+                stddev: 1.0,
+                len: 3,
+            }),
+            coverage: None,
+            z_score: Some(10.0),
+            sigma: 4.0,
+            warn_sigma: None,
+            status: AuditStatus::Fail,
+            skip_reason: None,
+            accepted_by: None,
+            change_point: None,
+            trend: None,
+            reruns: 0,
+            history: vec![9.0, 10.0, 11.0],
+        };
+        let skipped = AuditResult {
+            measurement: "memory".to_string(),
+            head: Some(1.0),
+            tail: None,
+            coverage: None,
+            z_score: None,
+            sigma: 4.0,
+            warn_sigma: None,
+            status: AuditStatus::Skip,
+            skip_reason: Some("Only 1 measurement.\nNeed more.".to_string()),
+            accepted_by: None,
+            change_point: None,
+            trend: None,
+            reruns: 0,
+            history: Vec::new(),
+        };
+        assert_eq!(
+            github_annotation(&failed, "HEAD").unwrap(),
+            "::error title=git perf audit::timer: HEAD differs significantly from tail measurements (z-score 10.00)"
+        );
+        assert_eq!(
+            github_annotation(&skipped, "HEAD").unwrap(),
+            "::warning title=git perf audit::memory: Only 1 measurement.%0ANeed more."
+        );
+        let unaffected = AuditResult {
+            head: None,
+            ..skipped
+        };
+        assert_eq!(github_annotation(&unaffected, "HEAD"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("summary.md");
+        fs::write(&path, "previous step\n").unwrap();
+        append_github_summary(&path, &[failed], "HEAD", &sparkline::Options::default()).unwrap();
+        let summary = fs::read_to_string(&path).unwrap();
+        assert!(summary.starts_with("previous step\n### git perf audit of HEAD\n\n| Measurement |"));
+        assert!(summary.contains("| timer | **fail** | 20.000 |"));
+    }
+
+    #[test]
+    fn implausible_outside_historical_range() {
+        let history = vec![
+            measurement("timer", 10.0),
+            measurement("timer", 20.0),
+            measurement("other", 1.0),
+        ];
+        let head = vec![
+            measurement("timer", 15.0),
+            measurement("timer", 20_001.0),
+            measurement("timer", 0.009),
+            measurement("timer", 0.011),
+            measurement("new", 1e12),
+        ];
+        let implausible = implausible_measurements(&head, &history, 1000.0);
+        assert_eq!(implausible.len(), 2);
+        assert!(implausible[0].starts_with("timer: 20001"));
+        assert!(implausible[1].starts_with("timer: 0.009"));
+    }
+
+    #[test]
+    fn affected_by_changed_paths() {
+        let mappings = vec![
+            (
+                "bench::parser::.*".to_string(),
+                vec!["src/parser/**".to_string()],
+            ),
+            (
+                "bench::lexer::.*".to_string(),
+                vec!["src/lexer/*".to_string()],
+            ),
+        ];
+        let changed = vec!["src/parser/nested/mod.rs".to_string()];
+
+        assert!(is_affected("bench::parser::parse", &mappings, &changed).unwrap());
+        assert!(!is_affected("bench::lexer::lex", &mappings, &changed).unwrap());
+        // Unmapped measurements are always audited
+        assert!(is_affected("build_time", &mappings, &changed).unwrap());
+        // Patterns must match the full measurement name
+        assert!(is_affected("xbench::lexer::lex", &mappings, &changed).unwrap());
+
+        let nested = vec!["src/lexer/sub/tokens.rs".to_string()];
+        assert!(!is_affected("bench::lexer::lex", &mappings, &nested).unwrap());
+        let direct = vec!["src/lexer/tokens.rs".to_string()];
+        assert!(is_affected("bench::lexer::lex", &mappings, &direct).unwrap());
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let mappings = vec![("(".to_string(), vec![])];
+        assert!(is_affected("timer", &mappings, &[]).is_err());
+    }
 }
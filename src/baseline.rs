@@ -0,0 +1,194 @@
+//! Baselines pinned to a commit, e.g. a release tag, which later commits are audited against
+//! with a maximum relative regression instead of statistically against their ancestors.
+
+use anyhow::{anyhow, bail, Result};
+use itertools::Itertools;
+use readable::num::Float;
+
+use crate::{
+    audit::AuditError,
+    config,
+    data::{MeasurementData, ReductionFunc, Selector},
+    git_interop,
+    measurement_retrieval::{self, ReductionFuncIterator},
+};
+
+/// Maximum relative increase in percent over the baseline if neither given nor configured.
+pub const DEFAULT_MAX_REGRESSION_PCT: f64 = 5.0;
+
+/// Aggregated values of the measurements at a pinned commit, stored in the `[baseline]` section
+/// of .gitperfconfig.
+#[derive(Debug, PartialEq)]
+pub struct Baseline {
+    /// Ref the baseline was pinned with, e.g. `v1.2.0`
+    pub reference: String,
+    /// Commit the ref resolved to
+    pub commit: String,
+    /// How the measurements of a commit are aggregated, for the baseline and the audited commit
+    pub aggregate_by: ReductionFunc,
+    /// Selectors the measurements were selected by
+    pub selectors: Vec<Selector>,
+    /// Aggregated value per measurement, ordered by name
+    pub values: Vec<(String, f64)>,
+}
+
+impl Baseline {
+    fn value_of(&self, measurement: &str) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|(name, _)| name == measurement)
+            .map(|(_, val)| *val)
+    }
+}
+
+/// Aggregated value per measurement of `commit` matching the `selectors`, ordered by name.
+/// Without `measurements`, all measurements of the commit are aggregated.
+fn aggregate(
+    commit: &str,
+    measurements: &[String],
+    selectors: &[Selector],
+    aggregate_by: ReductionFunc,
+) -> Result<Vec<(String, f64)>> {
+    let selected = |m: &MeasurementData| {
+        (measurements.is_empty() || measurements.contains(&m.name))
+            && m.matches_selectors(selectors)
+    };
+    Ok(measurement_retrieval::measurements_of(commit)?
+        .iter()
+        .filter(|m| selected(m))
+        .into_group_map_by(|m| m.name.clone())
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .filter_map(|(name, ms)| {
+            ms.into_iter()
+                .reduce_by(aggregate_by)
+                .map(|summary| (name, summary.val))
+        })
+        .collect())
+}
+
+/// Pin the aggregated values of `measurements` at `reference` or, without measurements, of all
+/// measurements of the commit matching the `selectors`. Replaces any previous baseline.
+pub fn set(
+    reference: &str,
+    measurements: &[String],
+    selectors: &[Selector],
+    aggregate_by: ReductionFunc,
+) -> Result<Baseline> {
+    let commit = git_interop::resolve_commit(reference)?;
+    let values = aggregate(&commit, measurements, selectors, aggregate_by)?;
+    if values.is_empty() {
+        bail!("No measurements at '{reference}' to pin");
+    }
+    if let Some(missing) = measurements
+        .iter()
+        .find(|m| !values.iter().any(|(name, _)| name == *m))
+    {
+        bail!("No measurement '{missing}' at '{reference}' to pin");
+    }
+    let baseline = Baseline {
+        reference: reference.to_owned(),
+        commit,
+        aggregate_by,
+        selectors: selectors.to_vec(),
+        values,
+    };
+    config::set_baseline(&baseline)?;
+    Ok(baseline)
+}
+
+/// Relative change in percent from the `baseline` value to `head`.
+fn change_percent(baseline: f64, head: f64) -> f64 {
+    (head - baseline) / baseline.abs() * 100.0
+}
+
+/// Audit the `measurements` of `commit` against the pinned baseline. Fails if any of them
+/// increased by more than `max_regression` percent, defaulting to `max_regression_pct` of the
+/// measurement in .gitperfconfig or [`DEFAULT_MAX_REGRESSION_PCT`].
+pub fn audit_against_baseline(
+    measurements: &[String],
+    commit: &str,
+    selectors: &[Selector],
+    max_regression: Option<f64>,
+) -> Result<()> {
+    let baseline = config::determine_baseline_from_config()?.ok_or_else(|| {
+        anyhow!("No baseline pinned. Pin one with 'git perf baseline set <ref>'.")
+    })?;
+    if !selectors.is_empty()
+        && selectors
+            .iter()
+            .map(Selector::to_string)
+            .sorted()
+            .ne(baseline.selectors.iter().map(Selector::to_string).sorted())
+    {
+        bail!(
+            "The baseline {} was pinned for the selectors '{}'",
+            baseline.reference,
+            baseline.selectors.iter().join(" ")
+        );
+    }
+    let head = aggregate(
+        &git_interop::resolve_commit(commit)?,
+        measurements,
+        &baseline.selectors,
+        baseline.aggregate_by,
+    )?;
+
+    let mut regressed = Vec::new();
+    for measurement in measurements {
+        let pinned = baseline.value_of(measurement).ok_or_else(|| {
+            anyhow!(
+                "No value of '{measurement}' pinned in the baseline {}",
+                baseline.reference
+            )
+        })?;
+        let Some(&(_, head)) = head.iter().find(|(name, _)| name == measurement) else {
+            return Err(
+                AuditError::InsufficientData(format!("No measurement for {commit}.")).into(),
+            );
+        };
+        let max = max_regression
+            .or_else(|| config::determine_max_regression_from_config(measurement))
+            .unwrap_or(DEFAULT_MAX_REGRESSION_PCT);
+        let change = change_percent(pinned, head);
+        if change > max {
+            eprintln!(
+                "{measurement}: {commit} regressed by {change:.2}% against the baseline {} ({} -> {}), more than the allowed {max}%.",
+                baseline.reference,
+                Float::from(pinned),
+                Float::from(head)
+            );
+            regressed.push(measurement.as_str());
+        } else {
+            eprintln!(
+                "{measurement}: {change:+.2}% against the baseline {} ({} -> {}), within {max}%.",
+                baseline.reference,
+                Float::from(pinned),
+                Float::from(head)
+            );
+        }
+    }
+
+    if !regressed.is_empty() {
+        return Err(AuditError::Regression(format!(
+            "{commit} regressed against the baseline {} for: {}",
+            baseline.reference,
+            regressed.join(", ")
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn relative_change() {
+        assert_eq!(change_percent(10.0, 11.0), 10.0);
+        assert_eq!(change_percent(10.0, 9.0), -10.0);
+        assert_eq!(change_percent(-10.0, -9.0), 10.0);
+        assert!(change_percent(0.0, 1.0) > DEFAULT_MAX_REGRESSION_PCT);
+    }
+}
@@ -1,35 +1,846 @@
-use std::{process, time::Instant};
+use std::{
+    fs,
+    path::Path,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use itertools::Itertools;
+use regex::Regex;
 
-use crate::measurement_storage::{self};
+use crate::{
+    config,
+    events::Event,
+    measurement_storage::{self, MeasurementWriter},
+    stats::RunningStats,
+    units,
+};
 
+/// Resource usage of the child process to record in addition to its runtime.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResourceSelection {
+    /// Peak resident set size as `<measurement>::max_rss` in bytes
+    pub memory: bool,
+    /// User and system CPU time as `<measurement>::cpu_time` in nanoseconds
+    pub cpu: bool,
+    /// Energy billed to the process as `<measurement>::energy` in joules and its
+    /// `<measurement>::average_power` in watts. Only available on macOS.
+    pub energy: bool,
+}
+
+impl ResourceSelection {
+    fn any(&self) -> bool {
+        self.memory || self.cpu || self.energy
+    }
+
+    fn check_supported(&self) -> Result<()> {
+        if self.energy && !cfg!(target_os = "macos") {
+            bail!("Measuring energy usage is only supported on macOS");
+        }
+        Ok(())
+    }
+}
+
+/// What is stored as the value of the measurement itself.
+#[derive(Debug, Default, Clone)]
+pub enum MeasureOutput {
+    /// Runtime of the command in nanoseconds
+    #[default]
+    WallTime,
+    /// Runtime of the command in a time unit of the given size in nanoseconds, e.g. `1e6` for
+    /// milliseconds
+    ScaledWallTime(f64),
+    /// Number printed by the command to stdout, as captured by the first group of the regex or,
+    /// without groups, by the whole match
+    Extract(Regex),
+}
+
+impl MeasureOutput {
+    /// Divisor of the runtime before storing it as the value of the measurement.
+    fn wall_time_divisor(&self) -> f64 {
+        match self {
+            MeasureOutput::ScaledWallTime(nanoseconds) => *nanoseconds,
+            _ => 1.0,
+        }
+    }
+
+    fn extract(&self, stdout: &[u8]) -> Result<Option<f64>> {
+        let MeasureOutput::Extract(regex) = self else {
+            return Ok(None);
+        };
+        let stdout = String::from_utf8_lossy(stdout);
+        let captures = regex
+            .captures(&stdout)
+            .ok_or_else(|| anyhow!("No match for '{regex}' in the output of the command"))?;
+        let value = captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .expect("Matches have a whole match group")
+            .as_str()
+            .trim();
+        let value = value
+            .parse()
+            .with_context(|| format!("Extracted value '{value}' is not a number"))?;
+        Ok(Some(value))
+    }
+}
+
+/// Check `unit` of the values of `measurement` against the unit configured for it and, for
+/// runtimes, convert them from nanoseconds to the time `unit`.
+pub fn validate_unit(
+    measurement: &str,
+    unit: &str,
+    output: MeasureOutput,
+) -> Result<MeasureOutput> {
+    if let Some(configured) = config::determine_unit_from_config(measurement) {
+        if configured != unit {
+            bail!("Unit '{unit}' of '{measurement}' conflicts with its configured unit '{configured}'");
+        }
+    }
+    match output {
+        MeasureOutput::Extract(_) => Ok(output),
+        MeasureOutput::WallTime | MeasureOutput::ScaledWallTime(_) => {
+            let nanoseconds = units::nanoseconds_per(unit).ok_or_else(|| {
+                anyhow!("Unit '{unit}' of the runtime of '{measurement}' is not a time unit (ns, us, ms, s)")
+            })?;
+            Ok(MeasureOutput::ScaledWallTime(nanoseconds))
+        }
+    }
+}
+
+/// How to run the command and which of its runs to keep.
+#[derive(Debug, Default, Clone)]
+pub struct MeasureOptions {
+    pub output: MeasureOutput,
+    pub resources: ResourceSelection,
+    /// Runs before the measured ones whose results are discarded
+    pub warmup: u16,
+    /// Percentage of the measured runs with the lowest and, separately, with the highest
+    /// values to discard
+    pub discard_outliers: f64,
+}
+
+/// Runtime and resource usage of a single run of the command.
+struct Sample {
+    wall_time: f64,
+    /// Value extracted from the output of the command
+    extracted: Option<f64>,
+    max_rss: Option<f64>,
+    cpu_time: Option<f64>,
+    /// Energy in joules
+    energy: Option<f64>,
+}
+
+impl Sample {
+    /// Value of the measurement itself
+    fn value(&self) -> f64 {
+        self.extracted.unwrap_or(self.wall_time)
+    }
+
+    fn extra_measurements(
+        &self,
+        measurement: &str,
+        resources: ResourceSelection,
+    ) -> Vec<(String, f64)> {
+        let mut extra = Vec::new();
+        if let (true, Some(max_rss)) = (resources.memory, self.max_rss) {
+            extra.push((format!("{measurement}::max_rss"), max_rss));
+        }
+        if let (true, Some(cpu_time)) = (resources.cpu, self.cpu_time) {
+            extra.push((format!("{measurement}::cpu_time"), cpu_time));
+        }
+        if let (true, Some(energy)) = (resources.energy, self.energy) {
+            extra.push((format!("{measurement}::energy"), energy));
+            extra.push((
+                format!("{measurement}::average_power"),
+                energy / (self.wall_time / 1e9),
+            ));
+        }
+        extra
+    }
+}
+
+fn check_success(exe: &str, success: bool, stdout: &[u8], stderr: &[u8]) -> Result<()> {
+    if !success {
+        let stdout = String::from_utf8_lossy(stdout);
+        let stderr = String::from_utf8_lossy(stderr);
+        bail!(
+            "Command '{}' failed to run:\nstdout:\n{}\nstderr:\n{}",
+            exe,
+            stdout,
+            stderr,
+        );
+    }
+    Ok(())
+}
+
+/// Run the command once and return its runtime in nanoseconds and the value extracted from its
+/// output.
+fn run_once(exe: &str, args: &[String], options: &MeasureOptions) -> Result<Sample> {
+    if options.resources.any() {
+        return run_once_with_rusage(exe, args, options);
+    }
+    let mut process = process::Command::new(exe);
+    process.args(args);
+    let start = Instant::now();
+    let output = process.output().context("Command failed to spawn")?;
+    let duration = start.elapsed();
+    check_success(exe, output.status.success(), &output.stdout, &output.stderr)?;
+    Ok(Sample {
+        wall_time: duration.as_nanos() as f64,
+        extracted: options.output.extract(&output.stdout)?,
+        max_rss: None,
+        cpu_time: None,
+        energy: None,
+    })
+}
+
+/// Run the command once and collect the resource usage of exactly this child with `wait4`.
+#[cfg(unix)]
+fn run_once_with_rusage(exe: &str, args: &[String], options: &MeasureOptions) -> Result<Sample> {
+    use std::io::Read;
+
+    fn read_all(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            // Output is only used for error reporting and value extraction
+            let _ = pipe.read_to_end(&mut buf);
+            buf
+        })
+    }
+
+    let start = Instant::now();
+    let mut child = process::Command::new(exe)
+        .args(args)
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .context("Command failed to spawn")?;
+    let stdout = read_all(child.stdout.take().expect("stdout is piped"));
+    let stderr = read_all(child.stderr.take().expect("stderr is piped"));
+
+    let pid = child.id() as libc::pid_t;
+    let energy = if options.resources.energy {
+        Some(energy_of_exited_child(pid)?)
+    } else {
+        None
+    };
+    let mut status = 0;
+    // SAFETY: rusage is plain old data that wait4 fills in
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) };
+    let duration = start.elapsed();
+    if ret != pid {
+        return Err(std::io::Error::last_os_error()).context("Failed to wait for command");
+    }
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    let success = libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0;
+    check_success(exe, success, &stdout, &stderr)?;
+
+    // Linux reports kilobytes, macOS bytes
+    let max_rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+    let timeval_nanos = |t: libc::timeval| t.tv_sec as f64 * 1e9 + t.tv_usec as f64 * 1e3;
+
+    Ok(Sample {
+        wall_time: duration.as_nanos() as f64,
+        extracted: options.output.extract(&stdout)?,
+        max_rss: Some((rusage.ru_maxrss as i64 * max_rss_unit) as f64),
+        cpu_time: Some(timeval_nanos(rusage.ru_utime) + timeval_nanos(rusage.ru_stime)),
+        energy,
+    })
+}
+
+/// Wait for the child to exit without reaping it and return the energy in joules billed to it.
+/// Energy spent in further processes spawned by the child is not included.
+#[cfg(target_os = "macos")]
+fn energy_of_exited_child(pid: libc::pid_t) -> Result<f64> {
+    // The usage of a process can only be queried until it is reaped.
+    // SAFETY: siginfo_t is plain old data that waitid fills in
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let ret = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            pid as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOWAIT,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to wait for command");
+    }
+
+    // SAFETY: rusage_info_v4 is plain old data that proc_pid_rusage fills in
+    let mut usage: libc::rusage_info_v4 = unsafe { std::mem::zeroed() };
+    let ret = unsafe {
+        libc::proc_pid_rusage(
+            pid,
+            libc::RUSAGE_INFO_V4,
+            &mut usage as *mut libc::rusage_info_v4 as *mut libc::rusage_info_t,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error()).context(
+            "Failed to read the energy usage of the command. \
+             Ensure git-perf is allowed to inspect the processes it starts.",
+        );
+    }
+    if usage.ri_billed_energy == 0 {
+        eprintln!(
+            "No energy usage reported for the command. Energy is only reported on Apple Silicon."
+        );
+    }
+
+    Ok(usage.ri_billed_energy as f64 / 1e9)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn energy_of_exited_child(_pid: libc::pid_t) -> Result<f64> {
+    bail!("Measuring energy usage is only supported on macOS")
+}
+
+#[cfg(not(unix))]
+fn run_once_with_rusage(_exe: &str, _args: &[String], _options: &MeasureOptions) -> Result<Sample> {
+    bail!("Measuring memory, CPU, and energy usage is only supported on Unix")
+}
+
+fn warm_up(exe: &str, args: &[String], options: &MeasureOptions) -> Result<()> {
+    for _ in 0..options.warmup {
+        run_once(exe, args, options)?;
+    }
+    Ok(())
+}
+
+/// Discard `percent` of the samples with the lowest and with the highest values.
+fn trim_outliers(mut samples: Vec<Sample>, percent: f64) -> Vec<Sample> {
+    let trimmed_per_side = (samples.len() as f64 * percent / 100.0).floor() as usize;
+    if trimmed_per_side == 0 {
+        return samples;
+    }
+    samples.sort_by(|a, b| a.value().total_cmp(&b.value()));
+    samples.truncate(samples.len() - trimmed_per_side);
+    samples.split_off(trimmed_per_side)
+}
+
+/// Queue the values and the selected resource usage of all samples.
+fn queue_samples(
+    writer: &MeasurementWriter,
+    measurement: &str,
+    samples: &[Sample],
+    key_values: &[(String, String)],
+    options: &MeasureOptions,
+) {
+    let divisor = options.output.wall_time_divisor();
+    let values = samples
+        .iter()
+        .map(|s| s.extracted.unwrap_or(s.wall_time / divisor))
+        .collect_vec();
+    writer.queue_all(measurement_storage::new_measurements(
+        measurement,
+        &values,
+        key_values,
+    ));
+
+    let extra = samples
+        .iter()
+        .flat_map(|s| s.extra_measurements(measurement, options.resources))
+        .into_group_map();
+    for (name, vals) in extra.into_iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+        writer.queue_all(measurement_storage::new_measurements(
+            &name, &vals, key_values,
+        ));
+    }
+}
+
+/// Run the command `repetitions` times on up to `jobs` concurrent workers. The samples are
+/// stored together once all repetitions finished.
 pub fn measure(
     measurement: &str,
     repetitions: u16,
+    jobs: u16,
+    command: &[String],
+    key_values: &[(String, String)],
+    options: MeasureOptions,
+) -> Result<()> {
+    measure_command(
+        measurement,
+        Repetitions::Count { repetitions, jobs },
+        command,
+        key_values,
+        options,
+    )
+}
+
+fn queue_repeated(
+    writer: &MeasurementWriter,
+    measurement: &str,
+    repetitions: u16,
+    jobs: u16,
+    command: &[String],
+    key_values: &[(String, String)],
+    options: &MeasureOptions,
+) -> Result<()> {
+    let exe = command.first().unwrap();
+    let args = &command[1..];
+    let resources = options.resources;
+    resources.check_supported()?;
+
+    warm_up(exe, args, options)?;
+
+    let started = AtomicUsize::new(0);
+    let samples: Vec<Sample> = thread::scope(|s| {
+        let workers = (0..jobs.min(repetitions))
+            .map(|_| {
+                s.spawn(|| -> Result<Vec<Sample>> {
+                    let mut samples = Vec::new();
+                    while started.fetch_add(1, Ordering::Relaxed) < repetitions.into() {
+                        samples.push(run_once(exe, args, options)?);
+                    }
+                    Ok(samples)
+                })
+            })
+            .collect_vec();
+        workers
+            .into_iter()
+            .map(|w| w.join().expect("Measurement worker panicked"))
+            .flatten_ok()
+            .try_collect()
+    })?;
+
+    let samples = trim_outliers(samples, options.discard_outliers);
+    queue_samples(writer, measurement, &samples, key_values, options);
+    Ok(())
+}
+
+/// Repeat the command until `budget` has elapsed. No new iteration is started after the budget
+/// is exhausted, but the command runs at least once. All runtimes are stored together with the
+/// derived measurement `<measurement>::iterations_per_second`. Warm-up runs do not count against
+/// the budget, discarded outliers still count as iterations.
+pub fn measure_for_duration(
+    measurement: &str,
+    budget: Duration,
+    command: &[String],
+    key_values: &[(String, String)],
+    options: MeasureOptions,
+) -> Result<()> {
+    measure_command(
+        measurement,
+        Repetitions::Duration(budget),
+        command,
+        key_values,
+        options,
+    )
+}
+
+fn queue_for_duration(
+    writer: &MeasurementWriter,
+    measurement: &str,
+    budget: Duration,
     command: &[String],
     key_values: &[(String, String)],
+    options: &MeasureOptions,
 ) -> Result<()> {
     let exe = command.first().unwrap();
     let args = &command[1..];
-    for _ in 0..repetitions {
-        let mut process = process::Command::new(exe);
-        process.args(args);
-        let start = Instant::now();
-        let output = process.output().context("Command failed to spawn")?;
-        if !output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            bail!(
-                "Command '{}' failed to run:\nstdout:\n{}\nstderr:\n{}",
-                exe,
-                stdout,
-                stderr,
-            );
-        }
-        let duration = start.elapsed();
-        let duration_usec = duration.as_nanos() as f64;
-        measurement_storage::add(measurement, duration_usec, key_values)?;
+    let resources = options.resources;
+    resources.check_supported()?;
+
+    warm_up(exe, args, options)?;
+
+    let start = Instant::now();
+    let mut samples = Vec::new();
+    loop {
+        samples.push(run_once(exe, args, options)?);
+        if start.elapsed() >= budget {
+            break;
+        }
+    }
+    let elapsed = start.elapsed();
+    let iterations_per_second = samples.len() as f64 / elapsed.as_secs_f64();
+
+    let samples = trim_outliers(samples, options.discard_outliers);
+    queue_samples(writer, measurement, &samples, key_values, options);
+    writer.queue_all(measurement_storage::new_measurements(
+        &format!("{measurement}::iterations_per_second"),
+        &[iterations_per_second],
+        key_values,
+    ));
+    Ok(())
+}
+
+/// Repetitions of [`measure_until_stable`] before the coefficient of variation is considered.
+const MIN_STABLE_REPETITIONS: usize = 3;
+
+/// When [`measure_until_stable`] stops repeating the command.
+#[derive(Debug, Clone, Copy)]
+pub struct StabilityTarget {
+    /// Coefficient of variation of the values in percent to reach
+    pub cv_percent: f64,
+    /// Stop after this many repetitions even if the target was not reached
+    pub max_repetitions: u16,
+    /// Do not start a new repetition after this wall-clock budget is used up
+    pub budget: Option<Duration>,
+}
+
+/// Whether to stop after the samples with the running statistics `stats`, which took `elapsed`.
+fn stable_or_exhausted(stats: &RunningStats, target: &StabilityTarget, elapsed: Duration) -> bool {
+    let stable = stats.len() >= MIN_STABLE_REPETITIONS
+        && stats
+            .coefficient_of_variation()
+            .is_some_and(|cv| cv * 100.0 <= target.cv_percent);
+    stable
+        || stats.len() >= target.max_repetitions.into()
+        || target.budget.is_some_and(|budget| elapsed >= budget)
+}
+
+/// Repeat the command until the coefficient of variation of its values drops to the target or
+/// the maximum number of repetitions or the time budget is reached. The command runs at least
+/// three times unless fewer repetitions are allowed. The achieved coefficient of variation in
+/// percent is stored as the key-value `cv` of all samples.
+pub fn measure_until_stable(
+    measurement: &str,
+    target: StabilityTarget,
+    command: &[String],
+    key_values: &[(String, String)],
+    options: MeasureOptions,
+) -> Result<()> {
+    measure_command(
+        measurement,
+        Repetitions::UntilStable(target),
+        command,
+        key_values,
+        options,
+    )
+}
+
+fn queue_until_stable(
+    writer: &MeasurementWriter,
+    measurement: &str,
+    target: StabilityTarget,
+    command: &[String],
+    key_values: &[(String, String)],
+    options: &MeasureOptions,
+) -> Result<()> {
+    let exe = command.first().unwrap();
+    let args = &command[1..];
+    let resources = options.resources;
+    resources.check_supported()?;
+
+    warm_up(exe, args, options)?;
+
+    let start = Instant::now();
+    let mut stats = RunningStats::default();
+    let mut samples = Vec::new();
+    loop {
+        let sample = run_once(exe, args, options)?;
+        stats.add(sample.value());
+        samples.push(sample);
+        if stable_or_exhausted(&stats, &target, start.elapsed()) {
+            break;
+        }
+    }
+
+    let cv = stats.coefficient_of_variation().map(|cv| cv * 100.0);
+    let mut key_values = key_values.to_vec();
+    match cv {
+        Some(cv) if cv <= target.cv_percent => {}
+        Some(cv) => eprintln!(
+            "{measurement}: Stopped after {} repetitions with a coefficient of variation of \
+             {cv:.2}% above the target of {}%",
+            stats.len(),
+            target.cv_percent
+        ),
+        None => eprintln!(
+            "{measurement}: Stopped after {} repetitions without a defined coefficient of variation",
+            stats.len()
+        ),
+    }
+    if let Some(cv) = cv {
+        key_values.push(("cv".to_owned(), format!("{cv:.2}")));
+    }
+
+    let samples = trim_outliers(samples, options.discard_outliers);
+    queue_samples(writer, measurement, &samples, &key_values, options);
+    Ok(())
+}
+
+/// How often each measured command runs.
+#[derive(Debug, Clone, Copy)]
+pub enum Repetitions {
+    /// A fixed number of runs on up to `jobs` concurrent workers, see [`measure`]
+    Count { repetitions: u16, jobs: u16 },
+    /// Runs until the wall-clock budget is used up, see [`measure_for_duration`]
+    Duration(Duration),
+    /// Runs until the values are stable, see [`measure_until_stable`]
+    UntilStable(StabilityTarget),
+}
+
+fn queue_measured(
+    writer: &MeasurementWriter,
+    measurement: &str,
+    repetitions: Repetitions,
+    command: &[String],
+    key_values: &[(String, String)],
+    options: &MeasureOptions,
+) -> Result<()> {
+    let start = Instant::now();
+    let result = match repetitions {
+        Repetitions::Count { repetitions, jobs } => queue_repeated(
+            writer,
+            measurement,
+            repetitions,
+            jobs,
+            command,
+            key_values,
+            options,
+        ),
+        Repetitions::Duration(budget) => {
+            queue_for_duration(writer, measurement, budget, command, key_values, options)
+        }
+        Repetitions::UntilStable(target) => {
+            queue_until_stable(writer, measurement, target, command, key_values, options)
+        }
+    };
+    Event::outcome_of("measure", &result, format!("Measured {measurement}"))
+        .measurement(measurement)
+        .duration(start.elapsed())
+        .emit();
+    result
+}
+
+/// Measure the command as often as given by `repetitions` and store all samples together.
+pub fn measure_command(
+    measurement: &str,
+    repetitions: Repetitions,
+    command: &[String],
+    key_values: &[(String, String)],
+    options: MeasureOptions,
+) -> Result<()> {
+    let writer = MeasurementWriter::new();
+    queue_measured(
+        &writer,
+        measurement,
+        repetitions,
+        command,
+        key_values,
+        &options,
+    )?;
+    writer.commit()?;
+    Ok(())
+}
+
+/// Commands measured together by [`measure_suite`], as pairs of measurement name and command.
+pub type Suite = Vec<(String, Vec<String>)>;
+
+/// Read a suite from a TOML file mapping each measurement name to its command, e.g.
+/// `build = ["cargo", "build", "--release"]`. The commands are ordered by name.
+pub fn read_suite(path: &Path) -> Result<Suite> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read suite '{}'", path.display()))?;
+    parse_suite(&content).with_context(|| format!("Invalid suite '{}'", path.display()))
+}
+
+fn parse_suite(content: &str) -> Result<Suite> {
+    let table: toml::Table = content.parse()?;
+    let suite: Suite = table
+        .into_iter()
+        .map(|(name, command)| {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                bail!("Measurement name '{name}' must not be empty or contain whitespace");
+            }
+            let command: Vec<String> = command
+                .try_into()
+                .with_context(|| format!("Command of '{name}' is not an array of strings"))?;
+            if command.is_empty() {
+                bail!("Command of '{name}' is empty");
+            }
+            Ok((name, command))
+        })
+        .try_collect()?;
+    if suite.is_empty() {
+        bail!("No commands to measure");
     }
+    Ok(suite)
+}
+
+/// Measure each command of the suite one after the other as given by `repetitions`, sharing
+/// the options and key-values. The samples of all commands are stored in a single write.
+pub fn measure_suite(
+    suite: &Suite,
+    repetitions: Repetitions,
+    key_values: &[(String, String)],
+    options: MeasureOptions,
+) -> Result<()> {
+    let unit = key_values
+        .iter()
+        .find(|(k, _)| k == "unit")
+        .map(|(_, unit)| unit);
+    let writer = MeasurementWriter::new();
+    for (measurement, command) in suite {
+        let options = match unit {
+            Some(unit) => MeasureOptions {
+                output: validate_unit(measurement, unit, options.output.clone())?,
+                ..options.clone()
+            },
+            None => options.clone(),
+        };
+        queue_measured(
+            &writer,
+            measurement,
+            repetitions,
+            command,
+            key_values,
+            &options,
+        )
+        .with_context(|| format!("Failed to measure '{measurement}'"))?;
+    }
+    writer.commit()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn samples(wall_times: &[f64]) -> Vec<Sample> {
+        wall_times
+            .iter()
+            .map(|&wall_time| Sample {
+                wall_time,
+                extracted: None,
+                max_rss: None,
+                cpu_time: None,
+                energy: None,
+            })
+            .collect()
+    }
+
+    fn wall_times(samples: &[Sample]) -> Vec<f64> {
+        samples.iter().map(|s| s.wall_time).collect()
+    }
+
+    #[test]
+    fn stop_when_stable_or_exhausted() {
+        let target = StabilityTarget {
+            cv_percent: 5.0,
+            max_repetitions: 5,
+            budget: None,
+        };
+        let stats = |values: &[f64]| {
+            let mut stats = RunningStats::default();
+            values.iter().for_each(|v| stats.add(*v));
+            stats
+        };
+        let elapsed = Duration::ZERO;
+        assert!(!stable_or_exhausted(
+            &stats(&[100.0, 101.0]),
+            &target,
+            elapsed
+        ));
+        assert!(stable_or_exhausted(
+            &stats(&[100.0, 101.0, 100.0]),
+            &target,
+            elapsed
+        ));
+        assert!(!stable_or_exhausted(
+            &stats(&[100.0, 150.0, 100.0]),
+            &target,
+            elapsed
+        ));
+        assert!(stable_or_exhausted(
+            &stats(&[100.0, 150.0, 100.0, 150.0, 100.0]),
+            &target,
+            elapsed
+        ));
+        let budget = StabilityTarget {
+            budget: Some(Duration::from_secs(1)),
+            ..target
+        };
+        assert!(stable_or_exhausted(
+            &stats(&[100.0]),
+            &budget,
+            Duration::from_secs(2)
+        ));
+    }
+
+    #[test]
+    fn trim_both_ends() {
+        let trimmed = trim_outliers(
+            samples(&[5.0, 100.0, 3.0, 4.0, 1.0, 2.0, 6.0, 7.0, 8.0, 9.0]),
+            10.0,
+        );
+        assert_eq!(
+            wall_times(&trimmed),
+            [2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]
+        );
+    }
+
+    #[test]
+    fn trim_nothing_for_few_samples() {
+        let trimmed = trim_outliers(samples(&[3.0, 1.0, 2.0]), 20.0);
+        assert_eq!(wall_times(&trimmed), [3.0, 1.0, 2.0]);
+        let trimmed = trim_outliers(samples(&[3.0, 1.0, 2.0]), 0.0);
+        assert_eq!(wall_times(&trimmed), [3.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn extract_value_from_output() {
+        let group = MeasureOutput::Extract(Regex::new(r"size: (\d+)").unwrap());
+        assert_eq!(
+            group.extract(b"binary\nsize: 1234 bytes\n").unwrap(),
+            Some(1234.0)
+        );
+        let whole = MeasureOutput::Extract(Regex::new(r"[0-9.]+").unwrap());
+        assert_eq!(whole.extract(b"rss 12.5 MiB").unwrap(), Some(12.5));
+        assert_eq!(MeasureOutput::WallTime.extract(b"42").unwrap(), None);
+        assert!(group.extract(b"no size").is_err());
+        let not_a_number = MeasureOutput::Extract(Regex::new(r"size: (\w+)").unwrap());
+        assert!(not_a_number.extract(b"size: big").is_err());
+    }
+
+    #[test]
+    fn parse_suite_of_commands() {
+        let suite = parse_suite(
+            r#"
+            test = ["cargo", "test"]
+            build = ["cargo", "build", "--release"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            suite,
+            [
+                (
+                    "build".to_owned(),
+                    vec![
+                        "cargo".to_owned(),
+                        "build".to_owned(),
+                        "--release".to_owned()
+                    ]
+                ),
+                (
+                    "test".to_owned(),
+                    vec!["cargo".to_owned(), "test".to_owned()]
+                ),
+            ]
+        );
+        assert!(parse_suite("").is_err());
+        assert!(parse_suite("build = []").is_err());
+        assert!(parse_suite("build = \"cargo build\"").is_err());
+        assert!(parse_suite("\"my build\" = [\"cargo\"]").is_err());
+    }
+
+    #[test]
+    fn convert_runtime_to_unit() {
+        let output = validate_unit("unconfigured", "ms", MeasureOutput::WallTime).unwrap();
+        assert_eq!(output.wall_time_divisor(), 1e6);
+        assert!(validate_unit("unconfigured", "MiB", MeasureOutput::WallTime).is_err());
+        let extract = MeasureOutput::Extract(Regex::new(r"\d+").unwrap());
+        let output = validate_unit("unconfigured", "MiB", extract).unwrap();
+        assert_eq!(output.wall_time_divisor(), 1.0);
+    }
+}
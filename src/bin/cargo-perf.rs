@@ -0,0 +1,180 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+};
+
+use anyhow::{bail, Context, Result};
+use git_perf::cli;
+
+/// Cargo invokes external subcommands as `cargo-perf perf <args>`. Strip the subcommand name so
+/// that `cargo perf <args>` behaves like `git perf <args>`. Running the binary directly as
+/// `cargo-perf <args>` works as well.
+fn forwarded_args(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args = args.into_iter().skip(1).peekable();
+    if args.peek().is_some_and(|a| a == "perf") {
+        args.next();
+    }
+    std::iter::once(OsString::from("cargo perf"))
+        .chain(args)
+        .collect()
+}
+
+/// Split cargo's `--manifest-path <path>` or `--manifest-path=<path>` off the `args`. Arguments
+/// after `--`, such as a measured command, are left alone.
+fn take_manifest_path(args: Vec<OsString>) -> Result<(Option<PathBuf>, Vec<OsString>)> {
+    let mut manifest_path = None;
+    let mut forwarded = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            forwarded.push(arg);
+            forwarded.extend(args.by_ref());
+            break;
+        }
+        let path = if arg == "--manifest-path" {
+            args.next()
+                .context("The argument '--manifest-path' requires a value")?
+        } else if let Some(path) = arg
+            .to_str()
+            .and_then(|arg| arg.strip_prefix("--manifest-path="))
+        {
+            OsString::from(path)
+        } else {
+            forwarded.push(arg);
+            continue;
+        };
+        if manifest_path.replace(PathBuf::from(path)).is_some() {
+            bail!("The argument '--manifest-path' was given more than once");
+        }
+    }
+    Ok((manifest_path, forwarded))
+}
+
+/// Root directory of the cargo workspace of `manifest_path`, by default of the current
+/// directory, as found by `cargo locate-project`.
+fn workspace_root(manifest_path: Option<&Path>) -> Result<PathBuf> {
+    // Set by cargo for its subcommands
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    let mut command = Command::new(cargo);
+    command.args(["locate-project", "--workspace", "--message-format", "plain"]);
+    if let Some(manifest_path) = manifest_path {
+        command.arg("--manifest-path").arg(manifest_path);
+    }
+    let output = command
+        .output()
+        .context("Failed to run cargo locate-project")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to locate the cargo workspace: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let manifest = PathBuf::from(String::from_utf8(output.stdout)?.trim());
+    match manifest.parent() {
+        Some(root) => Ok(root.to_owned()),
+        None => bail!("Invalid workspace manifest '{}'", manifest.display()),
+    }
+}
+
+/// Like cargo, run from the root of the workspace, also when invoked in a member directory or
+/// with `--manifest-path`. Outside of cargo workspaces, the current directory is kept.
+fn change_to_workspace_root(manifest_path: Option<&Path>) -> Result<()> {
+    let root = match workspace_root(manifest_path) {
+        Ok(root) => root,
+        Err(_) if manifest_path.is_none() => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    env::set_current_dir(&root).with_context(|| {
+        format!(
+            "Failed to change to the workspace root '{}'",
+            root.display()
+        )
+    })
+}
+
+fn main() -> ExitCode {
+    let result =
+        take_manifest_path(forwarded_args(env::args_os())).and_then(|(manifest_path, args)| {
+            change_to_workspace_root(manifest_path.as_deref())?;
+            cli::handle_calls_from(args)
+        });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(cli::exit_code(&e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn strips_cargo_subcommand_name() {
+        assert_eq!(
+            forwarded_args(args(&["cargo-perf", "perf", "audit", "-m", "perf"])),
+            args(&["cargo perf", "audit", "-m", "perf"])
+        );
+    }
+
+    #[test]
+    fn direct_invocation() {
+        assert_eq!(
+            forwarded_args(args(&["cargo-perf", "audit"])),
+            args(&["cargo perf", "audit"])
+        );
+    }
+
+    #[test]
+    fn takes_manifest_path() {
+        let (path, rest) = take_manifest_path(args(&[
+            "cargo perf",
+            "--manifest-path",
+            "member/Cargo.toml",
+            "audit",
+        ]))
+        .unwrap();
+        assert_eq!(path, Some(PathBuf::from("member/Cargo.toml")));
+        assert_eq!(rest, args(&["cargo perf", "audit"]));
+
+        let (path, rest) = take_manifest_path(args(&[
+            "cargo perf",
+            "report",
+            "--manifest-path=Cargo.toml",
+        ]))
+        .unwrap();
+        assert_eq!(path, Some(PathBuf::from("Cargo.toml")));
+        assert_eq!(rest, args(&["cargo perf", "report"]));
+
+        let measured = args(&[
+            "cargo perf",
+            "measure",
+            "-m",
+            "build",
+            "--",
+            "cargo",
+            "build",
+            "--manifest-path",
+            "x/Cargo.toml",
+        ]);
+        let (path, rest) = take_manifest_path(measured.clone()).unwrap();
+        assert_eq!(path, None);
+        assert_eq!(rest, measured);
+
+        assert!(take_manifest_path(args(&["cargo perf", "--manifest-path"])).is_err());
+        assert!(take_manifest_path(args(&[
+            "cargo perf",
+            "--manifest-path=a",
+            "--manifest-path=b"
+        ]))
+        .is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::{collections::HashMap, process};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    targets: Vec<Target>,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    name: String,
+}
+
+/// Crates of a cargo workspace, looked up by the names of the crates and their targets.
+#[derive(Debug, Default, PartialEq)]
+pub struct WorkspaceCrates {
+    /// Owning crate per crate and target name, with `-` replaced by `_`
+    owners: HashMap<String, String>,
+}
+
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+impl WorkspaceCrates {
+    /// Crates of the output of `cargo metadata --no-deps --format-version 1`, which only lists
+    /// the members of the workspace. Target names shared by several crates are ignored.
+    fn from_metadata(metadata: &str) -> Result<Self> {
+        let metadata: Metadata =
+            serde_json::from_str(metadata).context("Failed to parse cargo metadata")?;
+        let mut target_owners: HashMap<String, Option<String>> = HashMap::new();
+        for package in &metadata.packages {
+            for target in &package.targets {
+                target_owners
+                    .entry(normalize(&target.name))
+                    .and_modify(|owner| {
+                        if owner.as_ref() != Some(&package.name) {
+                            *owner = None
+                        }
+                    })
+                    .or_insert_with(|| Some(package.name.clone()));
+            }
+        }
+        let mut owners: HashMap<_, _> = target_owners
+            .into_iter()
+            .filter_map(|(target, owner)| Some((target, owner?)))
+            .collect();
+        // Crate names take precedence over target names of other crates
+        owners.extend(
+            metadata
+                .packages
+                .into_iter()
+                .map(|package| (normalize(&package.name), package.name)),
+        );
+        Ok(WorkspaceCrates { owners })
+    }
+
+    /// Crates of the cargo workspace of the current directory.
+    pub fn detect() -> Result<Self> {
+        let output = process::Command::new("cargo")
+            .args(["metadata", "--no-deps", "--format-version", "1"])
+            .output()
+            .context("Failed to run cargo metadata")?;
+        if !output.status.success() {
+            bail!(
+                "cargo metadata failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Self::from_metadata(&String::from_utf8(output.stdout)?)
+    }
+
+    /// Crate owning the test or benchmark `id`, such as `my-crate::tests::parse` of a test
+    /// classname or `my_crate/sort/1000` of a benchmark id, determined by the first segment of the
+    /// id up to `::` or `/`.
+    pub fn owner_of(&self, id: &str) -> Option<&str> {
+        let first = id.split("::").next()?.split('/').next()?;
+        self.owners.get(&normalize(first)).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const METADATA: &str = r#"{
+  "packages": [
+    {"name": "crate-a", "version": "0.1.0", "targets": [
+      {"name": "crate_a", "kind": ["lib"]},
+      {"name": "parsing", "kind": ["bench"]},
+      {"name": "integration", "kind": ["test"]}
+    ]},
+    {"name": "crate-b", "version": "0.1.0", "targets": [
+      {"name": "crate-b", "kind": ["bin"]},
+      {"name": "integration", "kind": ["test"]},
+      {"name": "crate-a", "kind": ["example"]}
+    ]}
+  ],
+  "workspace_members": ["crate-a 0.1.0", "crate-b 0.1.0"],
+  "version": 1
+}"#;
+
+    #[test]
+    fn owners_of_ids() {
+        let crates = WorkspaceCrates::from_metadata(METADATA).unwrap();
+        assert_eq!(crates.owner_of("crate-a::tests::parse"), Some("crate-a"));
+        assert_eq!(crates.owner_of("crate_a/sort/1000"), Some("crate-a"));
+        assert_eq!(crates.owner_of("parsing/json"), Some("crate-a"));
+        assert_eq!(crates.owner_of("crate-b"), Some("crate-b"));
+        assert_eq!(
+            crates.owner_of("integration::smoke"),
+            None,
+            "target of several crates"
+        );
+        assert_eq!(crates.owner_of("sort/1000"), None);
+    }
+
+    #[test]
+    fn reject_invalid_metadata() {
+        assert!(WorkspaceCrates::from_metadata("{}").is_err());
+    }
+}
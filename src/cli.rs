@@ -1,34 +1,207 @@
 use anyhow::anyhow;
-use anyhow::Result;
-use clap::{error::ErrorKind::ArgumentConflict, Args, Parser};
+use anyhow::{bail, Context, Result};
+use clap::{error::ErrorKind::ArgumentConflict, ArgGroup, Args, Parser};
 use clap::{CommandFactory, Subcommand};
-use std::path::PathBuf;
+use itertools::Itertools;
+use regex::Regex;
+use std::{ffi::OsString, io, path::PathBuf, process, time::Duration};
 
-use crate::audit;
-use crate::basic_measure::measure;
-use crate::config::bump_epoch;
-use crate::data::ReductionFunc;
+use crate::audit::{self, AuditError, AuditPolicy, Severity};
+use crate::baseline;
+use crate::basic_measure::{
+    measure_command, measure_suite, read_suite, validate_unit, MeasureOptions, MeasureOutput,
+    Repetitions, ResourceSelection, StabilityTarget,
+};
+use crate::compare::compare;
+use crate::config::{
+    bump_epoch, determine_audit_max_failures_from_config, determine_audit_warn_sigma_from_config,
+    determine_baseline_from_config, determine_capture_env_from_config,
+    determine_push_remotes_from_config, determine_report_sections_from_config,
+    determine_retention_policy_from_config, determine_sparkline_options_from_config,
+    determine_temp_ref_max_age_from_config, get_setting, set_setting,
+};
+use crate::config_check;
+use crate::data::{OutputFormat, ReductionFunc, Selector, MATRIX_KEY};
+use crate::demo::demo;
+use crate::digest::{digest, DigestFormat};
+use crate::doctor::doctor;
+use crate::environment;
+use crate::events::{self, LogFormat};
+use crate::export::{export, ExportFormat};
 use crate::git_interop;
-use crate::git_interop::{prune, pull, push};
-use crate::measurement_storage::add;
-use crate::reporting::report;
+use crate::git_interop::{
+    gc_temp_refs, prune, pull_from_remotes, push_to_remotes, DEFAULT_REMOTE,
+    DEFAULT_TEMP_REF_MAX_AGE,
+};
+use crate::import::{import, Dedupe, ImportFormat};
+use crate::measurement_retrieval;
+use crate::measurement_storage::{add, add_batch, migrate, remove};
+use crate::progress;
+use crate::relink::relink;
+use crate::report_diff::report_diff;
+use crate::reporting::{report, ChartType, CsvMode, ReportFormat, XAxis};
+use crate::retention::gc;
+use crate::serialization::Format;
+use crate::serve::serve;
+use crate::size::size;
+use crate::sparkline;
+use crate::summary::{summary, SummaryFormat};
+use crate::units::{parse_duration, parse_period};
 
 #[derive(Parser)]
 #[command(version)]
 struct Cli {
+    /// Never access the network. Commands needing a remote fail, as do commands reading
+    /// measurements that are not available locally.
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Fail any command as soon as it would fetch or push, while reading local measurements
+    /// works as usual. Useful for tests and air-gapped analysis.
+    #[arg(long, global = true)]
+    no_network: bool,
+
+    /// Never show progress bars. They are only shown if stderr is a terminal.
+    #[arg(long, global = true)]
+    no_progress: bool,
+
+    /// Format of the events of operations on stderr. As `json`, every push, pull, measurement,
+    /// and audit is logged as one JSON object per line, e.g. for CI log aggregation.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    log_format: LogFormat,
+
+    /// Number of threads parsing the stored measurements, e.g. to leave CPUs to other jobs of a
+    /// CI machine. Defaults to the number of CPUs.
+    #[arg(long, global = true, value_name = "n", value_parser=clap::value_parser!(u16).range(1..))]
+    parse_jobs: Option<u16>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Subcommand)]
+enum BaselineCommand {
+    /// Record the aggregated values of the measurements at the ref, replacing the pinned
+    /// baseline
+    Set {
+        /// Ref to pin, e.g. a release tag
+        reference: String,
+
+        /// Measurements to pin. Can be given repeatedly. Defaults to all measurements of the ref.
+        #[arg(short, long, value_parser=parse_spaceless_string)]
+        measurement: Vec<String>,
+
+        /// Selector to subselect measurements: key=value, key!=value, or key=~regex with no
+        /// whitespaces. Can be given repeatedly.
+        #[arg(short, long)]
+        selectors: Vec<Selector>,
+
+        /// What to aggregate the measurements of the ref with: min, max, median, mean, p90, p95,
+        /// p99, trimmed_mean(<percent>), or geometric_mean
+        #[arg(short, long, default_value = "min")]
+        aggregate_by: ReductionFunc,
+    },
+
+    /// Print the pinned baseline
+    Show {},
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Check .gitperfconfig for unknown sections and settings, invalid values, and
+    /// per-measurement overrides that repeat the defaults or have no effect, and print how to fix
+    /// them. Fails if any setting is unknown or invalid.
+    Check {},
+
+    /// Print the value of a setting, given as a dotted path quoted like the keys in
+    /// .gitperfconfig, e.g. `measurement."build".unit`. Fails if it is not set.
+    Get {
+        /// Dotted path of the setting
+        key: String,
+    },
+
+    /// Set a setting, keeping the comments and formatting of .gitperfconfig. Refuses settings
+    /// that `git perf config check` would report as unknown or invalid.
+    Set {
+        /// Dotted path of the setting, e.g. `measurement."build".max_trend_pct_per_commit`
+        key: String,
+
+        /// TOML value, e.g. `5.0`, `true` or `["make", "bench"]`. Taken as a string otherwise.
+        value: String,
+    },
+}
+
 #[derive(Args)]
 struct CliMeasurement {
     /// Name of the measurement
-    #[arg(short = 'm', long = "measurement", value_parser=parse_spaceless_string)]
-    name: String,
+    #[arg(short = 'm', long = "measurement", value_parser=parse_spaceless_string, required_unless_present = "suite")]
+    name: Option<String>,
+
+    #[command(flatten)]
+    metadata: CliMetadata,
+}
 
+#[derive(Args)]
+struct CliMetadata {
     /// Key-value pairs separated by '='
     #[arg(short, long, value_parser=parse_key_value)]
     key_value: Vec<(String, String)>,
+
+    /// Build matrix dimensions as comma separated key-value pairs, e.g. `os=linux,rust=stable`.
+    /// All dimensions are stamped onto the measurement like key-value pairs. Their names are
+    /// recorded as `matrix=os,rust` for `--separate-by-matrix`.
+    #[arg(long, value_parser=parse_key_value, value_delimiter = ',')]
+    matrix: Vec<(String, String)>,
+
+    /// Additionally record the machine as key-value pairs: `host_kind` (local, ci,
+    /// github-hosted, or self-hosted), `cpu` (model), `cores`, `os`, `arch`, and `runner` (CI
+    /// runner tags or image, if known). Explicitly given key-value pairs take precedence.
+    /// Defaults to `capture_env` in the `[measure]` section of .gitperfconfig.
+    #[arg(long)]
+    capture_env: bool,
+}
+
+impl CliMetadata {
+    /// All key-value pairs including the matrix dimensions and the captured environment. Each
+    /// key may only be given once.
+    fn key_values(&self) -> Vec<(String, String)> {
+        let mut key_values = self.given_key_values();
+        key_values.extend(self.captured_key_values(&key_values));
+        key_values
+    }
+
+    /// The key-value pairs and matrix dimensions given on the command line, as well as the names
+    /// of the matrix dimensions.
+    fn given_key_values(&self) -> Vec<(String, String)> {
+        let dimensions = self.matrix.iter().map(|(k, _)| k).sorted().join(",");
+        let key_values = self
+            .key_value
+            .iter()
+            .chain(self.matrix.iter())
+            .cloned()
+            .chain((!dimensions.is_empty()).then(|| (MATRIX_KEY.to_owned(), dimensions)))
+            .collect_vec();
+        if let Some((key, _)) = key_values.iter().duplicates_by(|(k, _)| k).next() {
+            Cli::command()
+                .error(
+                    ArgumentConflict,
+                    format!("The key '{key}' was specified more than once"),
+                )
+                .exit()
+        }
+        key_values
+    }
+
+    /// The fingerprint of the machine if enabled, without the keys of `given`.
+    fn captured_key_values(&self, given: &[(String, String)]) -> Vec<(String, String)> {
+        if !self.capture_env && !determine_capture_env_from_config() {
+            return Vec::new();
+        }
+        environment::fingerprint()
+            .into_iter()
+            .filter(|(key, _)| given.iter().all(|(k, _)| k != key))
+            .collect()
+    }
 }
 
 #[derive(Args)]
@@ -39,43 +212,237 @@ struct CliReportHistory {
     max_count: usize,
 }
 
+#[derive(Args)]
+struct CliTempGc {
+    /// Do not remove stale temporary refs left behind by crashed operations.
+    /// The maximum age is configured via `gc.temp_ref_max_age` (seconds) in .gitperfconfig.
+    #[arg(long)]
+    no_temp_gc: bool,
+}
+
+#[derive(Args)]
+struct CliSparkline {
+    /// Maximum number of points of the trend sparklines in markdown output and step summaries.
+    /// Older values are averaged to fit. Defaults to `width` in the `[sparkline]` section of
+    /// .gitperfconfig, otherwise all values are drawn.
+    #[arg(long, value_name = "n")]
+    sparkline_width: Option<usize>,
+
+    /// Scale of the trend sparklines. Defaults to `scale` in the `[sparkline]` section of
+    /// .gitperfconfig or linear.
+    #[arg(long, value_enum)]
+    sparkline_scale: Option<sparkline::Scale>,
+
+    /// Clamp the trend sparklines to the range between two percentiles of their values, e.g.
+    /// `5,95`, so that a single outlier does not hide small regressions. Defaults to
+    /// `clamp_percentiles` in the `[sparkline]` section of .gitperfconfig.
+    #[arg(long, value_name = "low,high", value_parser=parse_percentile_range)]
+    sparkline_clamp: Option<(f64, f64)>,
+}
+
+impl CliSparkline {
+    /// Options of .gitperfconfig overridden by the ones given on the command line.
+    fn options(&self) -> Result<sparkline::Options> {
+        let mut options = determine_sparkline_options_from_config()?;
+        options.width = self.sparkline_width.or(options.width);
+        options.scale = self.sparkline_scale.unwrap_or(options.scale);
+        options.clamp = self.sparkline_clamp.or(options.clamp);
+        options.validate()?;
+        Ok(options)
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
-    /// Measure the runtime of the supplied command (in nanoseconds)
+    /// Measure the runtime of the supplied command (in nanoseconds) or a value it prints
     Measure {
         /// Repetitions
         #[arg(short = 'n', long, value_parser=clap::value_parser!(u16).range(1..), default_value = "1")]
         repetitions: u16,
 
+        /// Instead of a fixed number of repetitions, repeat the command until this wall-clock
+        /// budget is used up (e.g. `30s`, `500ms`, `2m`). Additionally records
+        /// `<measurement>::iterations_per_second`. With `--target-cv`, the budget after which to
+        /// stop even if the target was not reached.
+        #[arg(long, value_parser=parse_duration, conflicts_with = "repetitions")]
+        duration: Option<Duration>,
+
+        /// Run up to this many repetitions concurrently. Only use for commands that do not
+        /// interfere with each other or compete for the measured resource.
+        #[arg(short, long, value_parser=clap::value_parser!(u16).range(1..), default_value = "1", conflicts_with = "duration")]
+        jobs: u16,
+
+        /// Instead of a fixed number of repetitions, repeat the command until the coefficient of
+        /// variation of its values drops to this percentage (e.g. `2%`), but at most
+        /// `--max-repetitions` times or until the `--duration` budget is used up. The achieved
+        /// coefficient of variation is stored as the key-value `cv`.
+        #[arg(long, value_name = "pct", value_parser=parse_target_cv, conflicts_with_all = ["repetitions", "jobs"])]
+        target_cv: Option<f64>,
+
+        /// Maximum number of repetitions with `--target-cv`
+        #[arg(long, value_parser=clap::value_parser!(u16).range(1..), default_value = "100", requires = "target_cv")]
+        max_repetitions: u16,
+
+        /// Run the command this many times before the measured repetitions and discard the
+        /// results, e.g. to warm up caches
+        #[arg(long, value_name = "k", default_value = "0")]
+        warmup: u16,
+
+        /// Discard this percentage of the repetitions with the lowest and, separately, the
+        /// highest runtimes before storing the measurements
+        #[arg(long, value_name = "pct", value_parser=parse_outlier_percentage, default_value = "0")]
+        discard_outliers: f64,
+
+        /// Additionally record the peak resident set size of the command as
+        /// `<measurement>::max_rss` in bytes
+        #[arg(long)]
+        with_memory: bool,
+
+        /// Additionally record the user and system CPU time of the command as
+        /// `<measurement>::cpu_time` in nanoseconds
+        #[arg(long)]
+        with_cpu: bool,
+
+        /// Additionally record the energy used by the command as `<measurement>::energy` in
+        /// joules and `<measurement>::average_power` in watts. Only supported on macOS.
+        #[arg(long)]
+        with_energy: bool,
+
+        /// Instead of the runtime, record the number printed by the command to stdout, e.g. a
+        /// binary size. The first capture group of the regex, or without groups the whole match,
+        /// is parsed as the value.
+        #[arg(long, value_name = "regex")]
+        extract_regex: Option<String>,
+
+        /// Unit of the values, stored as the key-value pair `unit=<unit>` like a unit given with
+        /// `-k unit=<unit>`. Runtimes are converted from nanoseconds to the given time unit
+        /// (ns, us, ms, s). Must match the unit configured for the measurement, if any.
+        #[arg(long, value_parser=parse_spaceless_string)]
+        unit: Option<String>,
+
+        /// Measure each command of this TOML file, which maps measurement names to commands,
+        /// e.g. `build = ["cargo", "build"]`, instead of a single command. All commands share the
+        /// other options and their measurements are stored in a single write.
+        #[arg(long, value_name = "path.toml", conflicts_with_all = ["name", "command"])]
+        suite: Option<PathBuf>,
+
         #[command(flatten)]
         measurement: CliMeasurement,
 
         /// Command to measure
-        #[arg(required(true), last(true))]
+        #[arg(required_unless_present = "suite", last(true))]
         command: Vec<String>,
     },
 
     /// Add single measurement
     Add {
         /// Measured value to be added
-        value: f64,
+        #[arg(required_unless_present = "stdin")]
+        value: Option<f64>,
+
+        /// Name of the measurement
+        #[arg(short = 'm', long = "measurement", value_parser=parse_spaceless_string, required_unless_present = "stdin", conflicts_with = "stdin")]
+        name: Option<String>,
+
+        /// Add all measurements read from stdin in a single write, one per line as
+        /// `name value key=value...` or as JSON, e.g. `{"name": "timer", "value": 1.5}`.
+        /// The key-value pairs given on the command line are added to each measurement.
+        #[arg(long, conflicts_with = "value")]
+        stdin: bool,
 
         #[command(flatten)]
-        measurement: CliMeasurement,
+        metadata: CliMetadata,
+    },
+
+    /// Import the results of a benchmark framework for HEAD
+    Import {
+        /// Format of the input
+        format: ImportFormat,
+
+        /// Input files or quoted glob patterns (e.g. 'target/**/results.json'). All files are
+        /// imported in a single write. Reads from stdin if omitted or '-'.
+        files: Vec<String>,
+
+        /// Print a summary of the imported, skipped, and duplicate entries
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Write a summary of the import as JSON to this file
+        #[arg(long, value_name = "path.json")]
+        report: Option<PathBuf>,
+
+        /// Additionally import the relative changes against this named baseline as
+        /// `<statistic>_change` in percent (criterion-json only, other formats detected by `auto`
+        /// ignore it). Run criterion with `--baseline <name>` to include them in its output.
+        #[arg(long, value_name = "name")]
+        compare_baseline: Option<String>,
+
+        /// Prefix the names of benchmarks owned by a crate of the cargo workspace in the current
+        /// directory with `<crate>::` and add the crate as `crate` key-value. The owning crate is
+        /// detected from the first segment of the benchmark id (up to '::' or '/') matching the
+        /// name of a crate or one of its targets.
+        #[arg(long)]
+        workspace_crates: bool,
+
+        /// Skip measurements with the same name and key-values as one already stored for HEAD,
+        /// e.g. when a retried CI job imports the same results again
+        #[arg(long)]
+        dedupe: bool,
+
+        /// Replace the measurements of HEAD with the same name and key-values as an imported one
+        /// instead of skipping the imported one as with `--dedupe`
+        #[arg(long, conflicts_with = "dedupe")]
+        replace: bool,
+
+        #[command(flatten)]
+        metadata: CliMetadata,
     },
 
     /// Publish performance results to remote
-    Push {},
+    Push {
+        /// Remotes to push to, each with independent retries. Defaults to the `push` list in the
+        /// `[remote]` section of .gitperfconfig or 'origin'.
+        #[arg(long)]
+        remote: Vec<String>,
+
+        /// Refuse to publish if a measurement of HEAD is implausibly far (more than 1000x)
+        /// outside of the range of the same measurement in the previous commits
+        #[arg(long)]
+        audit_first: bool,
+
+        /// Only print the notes refs that would be pushed to each remote
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        temp_gc: CliTempGc,
+    },
 
     /// Pull performance results from remote
-    Pull {},
+    Pull {
+        /// Remotes to pull from. Defaults to 'origin'.
+        #[arg(long)]
+        remote: Vec<String>,
+
+        #[command(flatten)]
+        temp_gc: CliTempGc,
+    },
 
     /// Create an HTML performance report
     Report {
-        /// HTML output file
+        /// Output file. The format is inferred from the extension (html, csv, json, md, svg,
+        /// png). Use '-' to write to stdout (CSV unless `--format` is given).
         #[arg(short, long, default_value = "output.html")]
         output: PathBuf,
 
+        /// Output format, overrides the format inferred from the output file name
+        #[arg(short, long, value_enum)]
+        format: Option<ReportFormat>,
+
+        /// Report the first-parent history of this ref instead of HEAD, e.g. `origin/main`
+        #[arg(long = "ref", value_name = "ref")]
+        start: Option<String>,
+
         #[command(flatten)]
         report_history: CliReportHistory,
 
@@ -83,32 +450,177 @@ enum Commands {
         #[arg(short, long)]
         measurement: Vec<String>,
 
-        /// Key-value pairs separated by '=', select only matching measurements
-        #[arg(short, long, value_parser=parse_key_value)]
-        key_value: Vec<(String, String)>,
+        /// Select only measurements matching all of these key=value, key!=value, or key=~regex
+        /// selectors
+        #[arg(short, long)]
+        key_value: Vec<Selector>,
+
+        /// Create individual traces in the graph by grouping with the value of this selector.
+        /// Multiple selectors (e.g. all build matrix dimensions) can be given repeatedly or comma
+        /// separated.
+        #[arg(short, long, value_parser=parse_spaceless_string, value_delimiter = ',')]
+        separate_by: Vec<String>,
 
-        /// Create individual traces in the graph by grouping with the value of this selector
-        #[arg(short, long, value_parser=parse_spaceless_string)]
-        separate_by: Option<String>,
+        /// Separate by all build matrix dimensions recorded with `--matrix` for the reported
+        /// measurements
+        #[arg(long, conflicts_with = "separate_by")]
+        separate_by_matrix: bool,
 
-        /// What to aggregate the measurements in each group with
+        /// What to aggregate the measurements in each group with: min, max, median, mean, p90, p95,
+        /// p99, trimmed_mean(<percent>), or geometric_mean
         #[arg(short, long)]
         aggregate_by: Option<ReductionFunc>,
+
+        /// Shade the range each commit would have had to fall into to pass an audit against its
+        /// older commits, with the sigma and aggregation configured for the audit of each
+        /// measurement. Only shown in HTML reports.
+        #[arg(long)]
+        threshold_bands: bool,
+
+        /// Rows of CSV reports: raw samples or one aggregated value per commit.
+        /// Defaults to 'summary' if `--aggregate-by` is given, otherwise 'raw'.
+        #[arg(long, value_enum)]
+        csv_mode: Option<CsvMode>,
+
+        /// Show values of measurements with a configured unit (`[measurement."name"] unit = "ns"`)
+        /// as they are instead of in a unit fitting their magnitude. Only affects HTML reports.
+        #[arg(long)]
+        no_auto_scale: bool,
+
+        /// Compute the `--threshold-bands` ranges over the full history instead of stopping at
+        /// the last epoch bump, e.g. for long-horizon trends. The epochs are still reported.
+        #[arg(long)]
+        ignore_epochs: bool,
+
+        /// Number of characters of the commit hashes on the x-axis of HTML reports. Hover texts
+        /// always show the full hash. Defaults to `sha_length` in the `[report]` section of
+        /// .gitperfconfig or 6. Use 40 to always show full hashes.
+        #[arg(long, value_name = "n", value_parser=clap::value_parser!(u16).range(1..))]
+        sha_length: Option<u16>,
+
+        /// How HTML reports chart the measurements. 'bar-latest' compares the values of the
+        /// newest commit (see `--ref`) side by side, e.g. for release snapshots, grouped by
+        /// `--separate-by`.
+        #[arg(long, value_enum, default_value_t = ChartType::TimeSeries)]
+        chart_type: ChartType,
+
+        /// What the x-axis of HTML time series spans. 'time' places the commits at their
+        /// committer times instead of equally spaced, so that long gaps in development are
+        /// visible.
+        #[arg(long, value_enum, default_value_t = XAxis::Commit)]
+        x_axis: XAxis,
+
+        /// Report a section per `[report.section.<id>]` block of .gitperfconfig, each with the
+        /// optional keys `title`, `filter` (a regex matching the measurement names), `ref` (see
+        /// `--ref`) or `range` (a revision range such as `v1.0..release/2.x`), `separate_by`,
+        /// `aggregate_by`, `depth` (number of commits), `show_changes` (mark step changes) and
+        /// `chart` ("time-series" or "bar", see `--chart-type`). Sections without `ref` or
+        /// `range` report `--ref`. Only for HTML and markdown reports.
+        #[arg(long, conflicts_with_all = ["measurement", "separate_by", "separate_by_matrix", "aggregate_by", "csv_mode", "chart_type"])]
+        sections: bool,
+    },
+
+    /// Serve reports generated on demand on a local web server until interrupted.
+    /// The query string selects what to report, e.g. `/?m=timer&k=os=linux&s=arch&a=median`
+    /// with the repeatable parameters `m` (measurement), `k` (key=value), and `s` (separate by),
+    /// as well as `a` (aggregate by), `n` (number of commits), `start`, `threshold_bands`, and
+    /// `format` (html, csv, json, md, svg, or png).
+    /// Only requests with the listening address (or `localhost` on a loopback address) as
+    /// Host header are served.
+    Serve {
+        /// Address to listen on. Use port 0 for any free port.
+        #[arg(long, default_value = "127.0.0.1:8000")]
+        address: String,
+
+        #[command(flatten)]
+        report_history: CliReportHistory,
+    },
+
+    /// Export all stored measurements for offline analysis.
+    /// Unlike `report`, this is not limited to the history of HEAD.
+    Export {
+        /// Output file, '-' for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "json")]
+        format: ExportFormat,
+
+        /// Only export commits in this revision range, e.g. `main~10..main`
+        #[arg(short, long)]
+        range: Option<String>,
+
+        /// Select individual measurements instead of all
+        #[arg(short, long)]
+        measurement: Vec<String>,
+
+        /// Select only measurements matching all of these key=value, key!=value, or key=~regex
+        /// selectors
+        #[arg(short = 'k', long = "filter")]
+        key_value: Vec<Selector>,
+    },
+
+    /// Summarize the differences between two JSON reports as markdown: new measurements,
+    /// disappeared measurements, and significant shifts.
+    ReportDiff {
+        /// Older JSON report
+        old: PathBuf,
+
+        /// Newer JSON report
+        new: PathBuf,
+
+        /// Threshold for Welch's t statistic above which a shift is significant
+        #[arg(short = 'd', long, default_value = "4.0")]
+        sigma: f64,
     },
 
     /// For a given measurement, check perfomance deviations of the HEAD commit
     /// against `<n>` previous commits. Group previous results and aggregate their
     /// results before comparison.
+    ///
+    /// Exit codes: 0 if the audit passed, 1 if a regression was detected or the audit policy
+    /// was violated, 2 if measurements are missing or, with `--strict`, were skipped for lack of
+    /// data, and 3 for invalid arguments, configuration, or other errors.
     Audit {
-        #[arg(short, long, value_parser=parse_spaceless_string)]
-        measurement: String,
+        /// Measurements to audit. Can be given repeatedly.
+        #[arg(short, long, required_unless_present = "all", value_parser=parse_spaceless_string)]
+        measurement: Vec<String>,
+
+        /// Audit every measurement of the audited commit that matches the selectors instead of
+        /// the given ones, e.g. for CI setups with frequently changing measurements
+        #[arg(long, conflicts_with = "measurement")]
+        all: bool,
+
+        /// Commit to audit against its ancestors
+        #[arg(long, default_value = "HEAD")]
+        commit: String,
 
         #[command(flatten)]
         report_history: CliReportHistory,
 
-        /// Key-value pair separated by "=" with no whitespaces to subselect measurements
-        #[arg(short, long, value_parser=parse_key_value)]
-        selectors: Vec<(String, String)>,
+        /// Consider the commits of this period (units: h, d, w) up to the audited commit, e.g.
+        /// `30d`, instead of a fixed number of commits. The period ends at the commit time of the
+        /// audited commit or, with `--baseline`, of the baseline.
+        #[arg(long, value_parser=parse_period, conflicts_with = "max_count")]
+        since: Option<Duration>,
+
+        /// Selector to subselect measurements: key=value, key!=value, or key=~regex with no
+        /// whitespaces. Can be given repeatedly.
+        #[arg(short, long)]
+        selectors: Vec<Selector>,
+
+        /// Audit each group of measurements with the same values of these keys separately, e.g.
+        /// `os` to compare the HEAD measurements of each OS against the tail of the same OS. The
+        /// groups are taken from the audited commit. Multiple keys can be given repeatedly or
+        /// comma separated.
+        #[arg(long, value_parser=parse_spaceless_string, value_delimiter = ',', conflicts_with = "against_baseline")]
+        separate_by: Vec<String>,
+
+        /// Separate by all build matrix dimensions recorded with `--matrix` for the audited
+        /// measurement of the audited commit
+        #[arg(long, conflicts_with_all = ["separate_by", "against_baseline"])]
+        separate_by_matrix: bool,
 
         /// Minimum number of measurements needed. If less, pass test and assume
         /// more measurements are needed.
@@ -118,31 +630,329 @@ enum Commands {
         #[arg(long, value_parser=clap::value_parser!(u16).range(1..), default_value="2")]
         min_measurements: u16,
 
-        /// What to aggregate the measurements in each group with
-        #[arg(short, long, default_value = "min")]
-        aggregate_by: ReductionFunc,
+        /// What to aggregate the measurements of each commit with: min, max, median, mean, p90,
+        /// p95, p99, trimmed_mean(<percent>), or geometric_mean. Defaults to `aggregate_by` in
+        /// the `[measurement."name"]` section of .gitperfconfig or min.
+        #[arg(short, long)]
+        aggregate_by: Option<ReductionFunc>,
 
         /// Multiple of the stddev after which a outlier is detected.
         /// If the HEAD measurement is within `[mean-<d>*sigma; mean+<d>*sigma]`,
-        /// it is considered acceptable.
+        /// it is considered acceptable. Defaults to the `sigma` configured for one of the
+        /// `--selectors` in a `[measurement."name".selector."key=value"]` section of
+        /// .gitperfconfig, then to the `sigma` of the `[measurement."name"]` section, otherwise
+        /// 4.0.
+        #[arg(short = 'd', long)]
+        sigma: Option<f64>,
+
+        /// Multiple of the stddev after which HEAD is reported with the status 'warn' instead of
+        /// passing. Must be less than `--sigma`. Warnings only fail the audit with
+        /// `--fail-on warn`. Defaults to `warn_sigma` in the `[audit]` section of .gitperfconfig.
+        #[arg(long, value_name = "d")]
+        warn_sigma: Option<f64>,
+
+        /// Minimum fraction of the tail commits that need measurements. If less, the audit is
+        /// skipped with the distinct status 'low_coverage'.
+        #[arg(long, value_parser=parse_fraction, default_value = "0.0")]
+        min_coverage: f64,
+
+        /// Build the tail from the first-parent history of this ref (e.g. `origin/main`)
+        /// instead of the ancestors of the audited commit. Useful to audit a pull request
+        /// against its target branch.
+        #[arg(long, value_name = "ref")]
+        baseline: Option<String>,
+
+        /// Format of the audit result
+        #[arg(long, value_enum, default_value_t)]
+        output_format: OutputFormat,
+
+        /// When run in GitHub Actions (GITHUB_STEP_SUMMARY is set), append a markdown table of
+        /// the results to the step summary and annotate failures and warnings with
+        /// `::error::`/`::warning::` workflow commands
+        #[arg(long)]
+        gh_summary: bool,
+
+        #[command(flatten)]
+        sparkline: CliSparkline,
+
+        /// Skip the audit if none of the paths mapped to the measurement in the `[audit.paths]`
+        /// section of .gitperfconfig changed in the audited commit. Unmapped measurements are always audited.
+        #[arg(long)]
+        changed_only: bool,
+
+        /// Number of failed measurements, or with `--fail-on warn` also measurements with
+        /// warnings, to tolerate. The failures are still printed.
+        /// Defaults to `max_failures` in the `[audit]` section of .gitperfconfig or 0.
+        #[arg(long, value_name = "n")]
+        max_failures: Option<usize>,
+
+        /// Minimum severity that fails the audit. Warnings are deviations beyond `--warn-sigma`,
+        /// audits skipped for lack of data, deviations accepted by a commit trailer and step
+        /// changes within the tail measurements. Outcomes of this severity and above count
+        /// against `--max-failures`.
+        #[arg(long, value_enum, default_value_t)]
+        fail_on: Severity,
+
+        /// Fail if the tail measurements themselves contain a step change, which makes them an
+        /// unreliable baseline for HEAD. Otherwise only a warning is printed.
+        #[arg(long)]
+        fail_on_change_point: bool,
+
+        /// Before declaring a measurement as failed, rerun its command configured as
+        /// `[measurement."name"] command = [...]` in .gitperfconfig up to this many times and
+        /// audit again. The rerun samples are added to HEAD with the metadata `rerun=<n>`.
+        /// Measurements taken with `--extract-regex` need the same regex configured as
+        /// `extract_regex` in the section of the measurement.
+        #[arg(long, value_name = "k", default_value = "0")]
+        verify_reruns: u16,
+
+        /// Fail with exit code 2 if measurements were skipped for lack of data. Measurements
+        /// skipped by `--changed-only` do not count.
+        #[arg(long)]
+        strict: bool,
+
+        /// Compare against the values pinned with `git perf baseline set` instead of the tail
+        /// measurements. Fails if a measurement increased by more than `--max-regression`. The
+        /// measurements are selected and aggregated as when the baseline was pinned.
+        #[arg(long, conflicts_with = "baseline")]
+        against_baseline: bool,
+
+        /// Maximum relative increase in percent over the pinned baseline. Defaults to
+        /// `max_regression_pct` in the `[measurement."name"]` section of .gitperfconfig or 5.
+        #[arg(long, value_name = "pct", requires = "against_baseline")]
+        max_regression: Option<f64>,
+    },
+
+    /// Pin the measurements of a commit, e.g. a release tag, as the baseline for
+    /// `git perf audit --against-baseline`. Stored in the `[baseline]` section of .gitperfconfig.
+    Baseline {
+        #[command(subcommand)]
+        command: BaselineCommand,
+    },
+
+    /// Compare the measurements of two arbitrary refs (branches, tags, commits).
+    /// For each ref, `<n>` commits are considered and each commit's measurements are
+    /// aggregated before comparison.
+    Compare {
+        /// Base revision to compare against
+        base: String,
+
+        /// Revision to compare with the base
+        head: String,
+
+        /// Measurements to compare
+        #[arg(short, long, required = true, value_parser=parse_spaceless_string)]
+        measurement: Vec<String>,
+
+        #[command(flatten)]
+        report_history: CliReportHistory,
+
+        /// Selector to subselect measurements: key=value, key!=value, or key=~regex with no
+        /// whitespaces. Can be given repeatedly.
+        #[arg(short, long)]
+        selectors: Vec<Selector>,
+
+        /// What to aggregate the measurements in each group with: min, max, median, mean, p90, p95,
+        /// p99, trimmed_mean(<percent>), or geometric_mean
+        #[arg(short, long, default_value = "min")]
+        aggregate_by: ReductionFunc,
+
+        /// Threshold for Welch's t statistic above which a difference is significant
         #[arg(short = 'd', long, default_value = "4.0")]
         sigma: f64,
     },
 
+    /// Print summary statistics of each measurement in the last commits
+    Stats {
+        #[command(flatten)]
+        report_history: CliReportHistory,
+
+        /// Select individual measurements instead of all
+        #[arg(short, long, value_parser=parse_spaceless_string)]
+        measurement: Vec<String>,
+
+        /// Selector to subselect measurements: key=value, key!=value, or key=~regex with no
+        /// whitespaces. Can be given repeatedly.
+        #[arg(short, long)]
+        selectors: Vec<Selector>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t)]
+        format: SummaryFormat,
+    },
+
+    /// Write a compact summary of recent regressions, improvements, failed audits and data
+    /// coverage, e.g. to mail it from a cron job
+    Digest {
+        /// Only consider measurements taken within this period (units: h, d, w), e.g. `7d`
+        #[arg(long, value_parser=parse_period, default_value = "7d")]
+        since: Duration,
+
+        #[command(flatten)]
+        report_history: CliReportHistory,
+
+        /// Multiple of the stddev after which the audit of HEAD fails
+        #[arg(short = 'd', long, default_value = "4.0")]
+        sigma: f64,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t)]
+        format: DigestFormat,
+
+        /// Output file, '-' for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
+    },
+
     /// Accept HEAD commit's measurement for audit, even if outside of range.
     /// This is allows to accept expected performance changes.
     /// This is accomplished by starting a new epoch for the given measurement.
     /// The epoch is configured in the git perf config file.
     /// A change to the epoch therefore has to be committed and will result in a new HEAD for which
     /// new measurements have to be taken.
+    #[command(group(ArgGroup::new("target").required(true).args(["measurement", "all_failing"])))]
     BumpEpoch {
         #[arg(short = 'm', long = "measurement", value_parser=parse_spaceless_string)]
-        measurement: String,
+        measurement: Option<String>,
+
+        /// Bump the epochs of all measurements of HEAD that currently fail their audit
+        #[arg(long)]
+        all_failing: bool,
+
+        /// Limit the number of previous commits the audit of `--all-failing` considers
+        #[arg(short = 'n', long, default_value = "40")]
+        max_count: usize,
+
+        /// Multiple of the tail's standard deviation beyond which the audit of `--all-failing`
+        /// fails
+        #[arg(short = 'd', long, default_value = "4.0")]
+        sigma: f64,
+
+        /// Why the performance changed, e.g. "switched allocator". Stored with the new epoch in
+        /// .gitperfconfig and shown in the hover texts of HTML reports.
+        #[arg(long)]
+        reason: Option<String>,
+    },
+
+    /// Print how many bytes the measurements take up in the notes, e.g. to plan retention
+    /// policies
+    Size {
+        /// List the commits with the most measurement bytes
+        #[arg(long)]
+        by_commit: bool,
+
+        /// Number of commits listed with `--by-commit`
+        #[arg(long, value_name = "n", default_value = "10", requires = "by_commit")]
+        top: usize,
+
+        /// Show the size at the end of each month in which measurements changed and its growth
+        #[arg(long)]
+        trend: bool,
     },
 
     /// Remove all performance measurements for non-existent/unreachable objects.
     /// Will refuse to work if run on a shallow clone.
-    Prune {},
+    Prune {
+        /// Only print the objects whose notes would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove measurement series from all commits, e.g. after renaming a measurement, or the
+    /// measurements of single commits, e.g. after a bad CI run
+    #[command(group(ArgGroup::new("selection").required(true).multiple(true).args(["measurement", "filter", "commit"])))]
+    Remove {
+        /// Remove all measurements with this name
+        #[arg(short, long, value_parser=parse_spaceless_string)]
+        measurement: Vec<String>,
+
+        /// Remove all measurements whose name fully matches this regular expression
+        #[arg(long, value_name = "regex")]
+        filter: Option<String>,
+
+        /// Only remove measurements of this commit. Can be given repeatedly. Without a measurement
+        /// or filter, all measurements of the commit are removed.
+        #[arg(long, value_name = "commit")]
+        commit: Vec<String>,
+
+        /// Only print the measurements that would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove measurements according to the retention policies in the [retention] section of
+    /// .gitperfconfig in a single pass: `max_age` (e.g. "90d"), `max_points_per_measurement`
+    /// (keeps the newest per measurement name), and `keep_tagged_commits`
+    Gc {
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Instead of measurements, remove the temporary refs left behind by crashed operations
+        /// that were last written more than `gc.temp_ref_max_age` (seconds, default one day)
+        /// ago, regardless of the date of the commit they point to. Push and pull
+        /// do this as well unless run with --no-temp-gc.
+        #[arg(long)]
+        refs: bool,
+    },
+
+    /// Move the measurements of the single notes ref into per-year shards, configured with
+    /// `[storage] shards = "year"` in .gitperfconfig. Push afterwards to share the shards.
+    MigrateShards {},
+
+    /// Rewrite the stored measurements of all commits in another serialization format. Set
+    /// `[storage] format` in .gitperfconfig to the same format to also add new measurements in
+    /// it, and push afterwards. The compact v4 format stores one line per measurement name,
+    /// epoch, and key-values instead of one per measurement, but cannot be read by versions of
+    /// git-perf without v4 support.
+    MigrateStorage {
+        /// Format to convert to
+        #[arg(long, value_enum, default_value_t = Format::V4)]
+        to: Format,
+
+        /// Only report how many commits would be rewritten
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Create a scratch repository with synthetic measurement history to try out reports and
+    /// audits without collecting data first
+    Demo {
+        /// Number of commits to generate
+        #[arg(long, default_value = "100", value_parser=clap::value_parser!(u16).range(1..))]
+        commits: u16,
+
+        /// Directory of the new repository. Must not exist or be empty.
+        #[arg(short = 'C', long, default_value = "git-perf-demo")]
+        directory: PathBuf,
+
+        /// Seed for the generated noise
+        #[arg(long, default_value = "0")]
+        seed: u64,
+    },
+
+    /// Copy measurements of rewritten commits (e.g. after a rebase or amend) to their new
+    /// versions. Commits are matched by their Change-Id trailer or their patch id.
+    Relink {
+        /// Range of the old commits with measurements, e.g. `main@{1}~10..main@{1}`
+        #[arg(long, value_name = "old-range")]
+        from: String,
+
+        /// Range of the rewritten commits, e.g. `main~10..main`
+        #[arg(long, value_name = "new-range")]
+        to: String,
+    },
+
+    /// Inspect the settings in .gitperfconfig
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Check the environment for problems, such as an outdated git, a missing remote, a shallow
+    /// clone, stale temporary refs, or invalid settings in .gitperfconfig, and print how to fix
+    /// them. Fails if any check fails.
+    Doctor {},
 
     /// Generate the manpage content
     #[command(hide = true)]
@@ -158,6 +968,13 @@ fn parse_key_value(s: &str) -> Result<(String, String)> {
     Ok((key, value))
 }
 
+fn parse_percentile_range(s: &str) -> Result<(f64, f64)> {
+    let (low, high) = s
+        .split_once(',')
+        .ok_or_else(|| anyhow!("invalid range: expected '<low>,<high>' but got '{}'", s))?;
+    Ok((low.trim().parse()?, high.trim().parse()?))
+}
+
 fn parse_spaceless_string(s: &str) -> Result<String> {
     if s.split_whitespace().count() > 1 {
         Err(anyhow!("invalid string/key/value: found space in '{}'", s))
@@ -166,63 +983,634 @@ fn parse_spaceless_string(s: &str) -> Result<String> {
     }
 }
 
+fn or_default_remote(remotes: Vec<String>) -> Vec<String> {
+    if remotes.is_empty() {
+        vec![DEFAULT_REMOTE.to_owned()]
+    } else {
+        remotes
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f64> {
+    let fraction: f64 = s
+        .parse()
+        .map_err(|_| anyhow!("invalid fraction '{}': cannot parse number", s))?;
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(anyhow!("invalid fraction '{}': must be between 0 and 1", s));
+    }
+    Ok(fraction)
+}
+
+fn parse_target_cv(s: &str) -> Result<f64> {
+    let number = s.strip_suffix('%').unwrap_or(s);
+    let percentage: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid percentage '{}': cannot parse number", s))?;
+    if percentage <= 0.0 {
+        return Err(anyhow!("invalid percentage '{}': must be positive", s));
+    }
+    Ok(percentage)
+}
+
+fn parse_outlier_percentage(s: &str) -> Result<f64> {
+    let percentage: f64 = s
+        .parse()
+        .map_err(|_| anyhow!("invalid percentage '{}': cannot parse number", s))?;
+    if !(0.0..50.0).contains(&percentage) {
+        return Err(anyhow!(
+            "invalid percentage '{}': must be at least 0 and less than 50",
+            s
+        ));
+    }
+    Ok(percentage)
+}
+
+/// Exit code of the process for an `error` returned by [`handle_calls`].
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    error
+        .downcast_ref::<AuditError>()
+        .map_or(1, AuditError::exit_code)
+}
+
+/// Exit for an invalid command line like clap does, except for the audit command, which has its
+/// own exit code for usage errors.
+fn exit_on_parse_error(error: clap::Error, args: &[OsString]) -> ! {
+    let is_audit = Cli::command()
+        .ignore_errors(true)
+        .try_get_matches_from(args)
+        .is_ok_and(|matches| matches.subcommand_name() == Some("audit"));
+    if is_audit && error.use_stderr() {
+        let _ = error.print();
+        process::exit(AuditError::INVALID_EXIT_CODE.into());
+    }
+    error.exit()
+}
+
 pub fn handle_calls() -> Result<()> {
-    git_interop::check_git_version()?;
+    handle_calls_from(std::env::args_os())
+}
 
-    let cli = Cli::parse();
+/// Run the CLI with explicitly supplied arguments. The first argument is the binary name.
+pub fn handle_calls_from<I, T>(args: I) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = args.into_iter().map(Into::into).collect_vec();
+    let parsed = Cli::try_parse_from(&args);
+    // The doctor reports an outdated git itself
+    if !matches!(&parsed, Ok(cli) if matches!(cli.command, Commands::Doctor {})) {
+        git_interop::check_git_version()?;
+    }
+    let cli = parsed.unwrap_or_else(|e| exit_on_parse_error(e, &args));
+    if cli.offline {
+        git_interop::set_offline();
+    }
+    if cli.no_network {
+        git_interop::set_no_network();
+    }
+    if cli.no_progress {
+        progress::disable();
+    }
+    events::set_log_format(cli.log_format);
+    if let Some(jobs) = cli.parse_jobs {
+        measurement_retrieval::set_jobs(jobs.into())?;
+    }
     match cli.command {
         Commands::Measure {
             repetitions,
+            duration,
+            jobs,
+            target_cv,
+            max_repetitions,
+            warmup,
+            discard_outliers,
+            with_memory,
+            with_cpu,
+            with_energy,
+            extract_regex,
+            unit,
+            suite,
             command,
             measurement,
-        } => Ok(measure(
-            &measurement.name,
-            repetitions,
-            &command,
-            &measurement.key_value,
+        } => {
+            let output = match extract_regex {
+                Some(regex) => MeasureOutput::Extract(
+                    Regex::new(&regex).context("Invalid regex to extract values")?,
+                ),
+                None => MeasureOutput::WallTime,
+            };
+            let mut key_values = measurement.metadata.key_values();
+            if let Some(unit) = unit {
+                if key_values.iter().any(|(k, _)| k == "unit") {
+                    Cli::command()
+                        .error(
+                            ArgumentConflict,
+                            "The key 'unit' was specified more than once",
+                        )
+                        .exit()
+                }
+                key_values.push(("unit".to_owned(), unit));
+            }
+            let repetitions = match (target_cv, duration) {
+                (Some(cv_percent), budget) => Repetitions::UntilStable(StabilityTarget {
+                    cv_percent,
+                    max_repetitions,
+                    budget,
+                }),
+                (None, Some(duration)) => Repetitions::Duration(duration),
+                (None, None) => Repetitions::Count { repetitions, jobs },
+            };
+            let options = MeasureOptions {
+                output,
+                resources: ResourceSelection {
+                    memory: with_memory,
+                    cpu: with_cpu,
+                    energy: with_energy,
+                },
+                warmup,
+                discard_outliers,
+            };
+            match (measurement.name, suite) {
+                (Some(name), _) => {
+                    let output = match key_values.iter().find(|(k, _)| k == "unit") {
+                        Some((_, unit)) => validate_unit(&name, unit, options.output)?,
+                        None => options.output,
+                    };
+                    Ok(measure_command(
+                        &name,
+                        repetitions,
+                        &command,
+                        &key_values,
+                        MeasureOptions { output, ..options },
+                    )?)
+                }
+                (None, Some(suite)) => Ok(measure_suite(
+                    &read_suite(&suite)?,
+                    repetitions,
+                    &key_values,
+                    options,
+                )?),
+                (None, None) => unreachable!("Either a measurement or a suite is required"),
+            }
+        }
+        Commands::Add {
+            value,
+            name,
+            stdin,
+            metadata,
+        } => {
+            if stdin {
+                let input = io::read_to_string(io::stdin()).context("Failed to read stdin")?;
+                let key_values = metadata.given_key_values();
+                let captured = metadata.captured_key_values(&key_values);
+                let added = add_batch(&input, &key_values, &captured)?;
+                eprintln!("Added {added} measurements");
+                Ok(())
+            } else {
+                Ok(add(
+                    &name.expect("Required without --stdin"),
+                    value.expect("Required without --stdin"),
+                    &metadata.key_values(),
+                )?)
+            }
+        }
+        Commands::Import {
+            format,
+            files,
+            verbose,
+            report,
+            compare_baseline,
+            workspace_crates,
+            dedupe,
+            replace,
+            metadata,
+        } => Ok(import(
+            format,
+            &files,
+            &metadata.key_values(),
+            compare_baseline.as_deref(),
+            workspace_crates,
+            match (dedupe, replace) {
+                (_, true) => Dedupe::Replace,
+                (true, false) => Dedupe::Skip,
+                (false, false) => Dedupe::Off,
+            },
+            verbose,
+            report.as_deref(),
         )?),
-        Commands::Add { value, measurement } => {
-            Ok(add(&measurement.name, value, &measurement.key_value)?)
+        Commands::Push {
+            remote,
+            audit_first,
+            dry_run,
+            temp_gc,
+        } => {
+            if audit_first {
+                audit::audit_plausibility()?;
+            }
+            let remotes = or_default_remote(if remote.is_empty() {
+                determine_push_remotes_from_config()
+            } else {
+                remote
+            });
+            if dry_run {
+                for remote in &remotes {
+                    for line in git_interop::push_dry_run(None, remote)? {
+                        println!("{remote}\t{line}");
+                    }
+                }
+                return Ok(());
+            }
+            maybe_gc_temp_refs(&temp_gc)?;
+            Ok(push_to_remotes(None, &remotes)?)
+        }
+        Commands::Pull { remote, temp_gc } => {
+            maybe_gc_temp_refs(&temp_gc)?;
+            Ok(pull_from_remotes(None, &or_default_remote(remote))?)
         }
-        Commands::Push {} => Ok(push(None)?),
-        Commands::Pull {} => Ok(pull(None)?),
         Commands::Report {
             output,
             separate_by,
+            separate_by_matrix,
+            start,
             report_history,
             measurement,
             key_value,
             aggregate_by,
-        } => Ok(report(
+            threshold_bands,
+            format,
+            csv_mode,
+            no_auto_scale,
+            ignore_epochs,
+            sha_length,
+            chart_type,
+            x_axis,
+            sections,
+        } => {
+            let sections = if sections {
+                let sections = determine_report_sections_from_config()?;
+                if sections.is_empty() {
+                    bail!(
+                        "No report sections configured as [report.section.<id>] in .gitperfconfig"
+                    );
+                }
+                sections
+            } else {
+                Vec::new()
+            };
+            Ok(report(
+                output,
+                &separate_by,
+                separate_by_matrix,
+                start.as_deref(),
+                report_history.max_count,
+                &measurement,
+                &key_value,
+                aggregate_by,
+                threshold_bands,
+                format,
+                csv_mode,
+                !no_auto_scale,
+                ignore_epochs,
+                sha_length.map(usize::from),
+                chart_type,
+                x_axis,
+                &sections,
+            )?)
+        }
+        Commands::Serve {
+            address,
+            report_history,
+        } => Ok(serve(&address, report_history.max_count)?),
+        Commands::Export {
             output,
-            separate_by,
-            report_history.max_count,
+            format,
+            range,
+            measurement,
+            key_value,
+        } => Ok(export(
+            &output,
+            format,
+            range.as_deref(),
             &measurement,
             &key_value,
-            aggregate_by,
         )?),
+        Commands::ReportDiff { old, new, sigma } => Ok(report_diff(&old, &new, sigma)?),
         Commands::Audit {
             measurement,
+            all,
+            commit,
             report_history,
+            since,
             selectors,
+            separate_by,
+            separate_by_matrix,
             min_measurements,
             aggregate_by,
             sigma,
+            warn_sigma,
+            min_coverage,
+            baseline,
+            output_format,
+            gh_summary,
+            sparkline,
+            changed_only,
+            max_failures,
+            fail_on,
+            fail_on_change_point,
+            verify_reruns,
+            strict,
+            against_baseline,
+            max_regression,
         } => {
-            if report_history.max_count < min_measurements.into() {
-                Cli::command().error(ArgumentConflict, format!("The minimal number of measurements ({}) cannot be more than the maximum number of measurements ({})", min_measurements, report_history.max_count)).exit()
+            let max_count = match since {
+                // The audited commit precedes the baseline history
+                Some(since) => {
+                    git_interop::count_commits_within(baseline.as_deref().unwrap_or(&commit), since)
+                        .map_err(AuditError::Invalid)?
+                        + usize::from(baseline.is_some())
+                }
+                None => report_history.max_count,
+            };
+            if since.is_none() && max_count < min_measurements.into() {
+                return Err(AuditError::Invalid(anyhow!("The minimal number of measurements ({}) cannot be more than the maximum number of measurements ({})", min_measurements, max_count)).into());
             }
-            Ok(audit::audit(
-                &measurement,
-                report_history.max_count,
-                min_measurements,
-                &selectors,
-                aggregate_by,
-                sigma,
-            )?)
+            let warn_sigma = warn_sigma.or_else(determine_audit_warn_sigma_from_config);
+            let measurements = if all {
+                audit::measurements_of_commit(&commit, &selectors)
+            } else {
+                Ok(measurement)
+            };
+            measurements
+                .and_then(|measurement| {
+                    if against_baseline {
+                        return baseline::audit_against_baseline(
+                            &measurement,
+                            &commit,
+                            &selectors,
+                            max_regression,
+                        );
+                    }
+                    audit::audit(
+                        &measurement,
+                        &commit,
+                        max_count,
+                        min_measurements,
+                        &selectors,
+                        &separate_by,
+                        separate_by_matrix,
+                        aggregate_by,
+                        sigma,
+                        warn_sigma,
+                        min_coverage,
+                        baseline.as_deref(),
+                        output_format,
+                        &sparkline.options()?,
+                        gh_summary,
+                        changed_only,
+                        AuditPolicy {
+                            max_failures: max_failures
+                                .or_else(determine_audit_max_failures_from_config)
+                                .unwrap_or(0),
+                            fail_on,
+                            fail_on_change_point,
+                            verify_reruns,
+                            strict,
+                        },
+                    )
+                })
+                .map_err(|e| match e.downcast::<AuditError>() {
+                    Ok(e) => e.into(),
+                    Err(e) => AuditError::Invalid(e).into(),
+                })
         }
-        Commands::BumpEpoch { measurement } => Ok(bump_epoch(&measurement)?),
-        Commands::Prune {} => Ok(prune()?),
+        Commands::Baseline {
+            command:
+                BaselineCommand::Set {
+                    reference,
+                    measurement,
+                    selectors,
+                    aggregate_by,
+                },
+        } => {
+            let pinned = baseline::set(&reference, &measurement, &selectors, aggregate_by)?;
+            eprintln!(
+                "Pinned {} measurements of {} ({})",
+                pinned.values.len(),
+                pinned.reference,
+                pinned.commit
+            );
+            Ok(())
+        }
+        Commands::Baseline {
+            command: BaselineCommand::Show {},
+        } => {
+            let pinned =
+                determine_baseline_from_config()?.ok_or_else(|| anyhow!("No baseline pinned"))?;
+            println!("{} {}", pinned.reference, pinned.commit);
+            for (name, val) in &pinned.values {
+                println!("{name} {val}");
+            }
+            Ok(())
+        }
+        Commands::Stats {
+            report_history,
+            measurement,
+            selectors,
+            format,
+        } => Ok(summary(
+            report_history.max_count,
+            &measurement,
+            &selectors,
+            format,
+        )?),
+        Commands::Compare {
+            base,
+            head,
+            measurement,
+            report_history,
+            selectors,
+            aggregate_by,
+            sigma,
+        } => Ok(compare(
+            &base,
+            &head,
+            &measurement,
+            report_history.max_count,
+            &selectors,
+            aggregate_by,
+            sigma,
+        )?),
+        Commands::BumpEpoch {
+            measurement,
+            all_failing,
+            max_count,
+            sigma,
+            reason,
+        } => {
+            let measurements = match measurement {
+                Some(measurement) => vec![measurement],
+                None => audit::failing_measurements("HEAD", max_count, sigma)?,
+            };
+            for measurement in &measurements {
+                bump_epoch(measurement, reason.as_deref())?;
+            }
+            if all_failing {
+                eprintln!(
+                    "Bumped the epochs of {} failing measurements",
+                    measurements.len()
+                );
+                for measurement in &measurements {
+                    println!("{measurement}");
+                }
+            }
+            Ok(())
+        }
+        Commands::Size {
+            by_commit,
+            top,
+            trend,
+        } => Ok(size(by_commit, top, trend)?),
+        Commands::Prune { dry_run } => {
+            let summary = prune(dry_run)?;
+            if dry_run {
+                for object in &summary.objects {
+                    println!("{object}");
+                }
+            }
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            eprintln!(
+                "{verb} the notes of {} objects ({} bytes)",
+                summary.objects.len(),
+                summary.bytes
+            );
+            Ok(())
+        }
+        Commands::Remove {
+            measurement,
+            filter,
+            commit,
+            dry_run,
+        } => {
+            let filter = filter
+                .map(|f| Regex::new(&format!("^(?:{f})$")))
+                .transpose()
+                .context("Invalid filter")?;
+            let commits: Vec<_> = commit
+                .iter()
+                .map(|c| git_interop::resolve_commit(c))
+                .try_collect()?;
+            let all = measurement.is_empty() && filter.is_none();
+            let removed = remove(
+                (!commits.is_empty()).then_some(commits.as_slice()),
+                |m| {
+                    all || measurement.contains(&m.name)
+                        || filter.as_ref().is_some_and(|f| f.is_match(&m.name))
+                },
+                dry_run,
+            )?;
+            if dry_run {
+                for (commit, measurements) in &removed {
+                    for m in measurements {
+                        let key_values = m
+                            .key_values
+                            .iter()
+                            .sorted()
+                            .map(|(k, v)| format!(" {k}={v}"))
+                            .join("");
+                        println!("{commit} {} {}{key_values}", m.name, m.val);
+                    }
+                }
+            }
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            eprintln!(
+                "{verb} {} measurements",
+                removed.iter().map(|(_, ms)| ms.len()).sum::<usize>()
+            );
+            Ok(())
+        }
+        Commands::Gc {
+            dry_run,
+            refs: true,
+        } => {
+            let max_age =
+                determine_temp_ref_max_age_from_config().unwrap_or(DEFAULT_TEMP_REF_MAX_AGE);
+            let refs = if dry_run {
+                git_interop::stale_temp_ref_names(max_age)?
+            } else {
+                gc_temp_refs(None, max_age)?
+            };
+            for refname in &refs {
+                println!("{refname}");
+            }
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            eprintln!("{verb} {} stale temporary refs", refs.len());
+            Ok(())
+        }
+        Commands::Gc {
+            dry_run,
+            refs: false,
+        } => {
+            let summary = gc(&determine_retention_policy_from_config()?, dry_run)?;
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            eprintln!(
+                "{verb} {} measurements from {} commits",
+                summary.removed, summary.commits
+            );
+            Ok(())
+        }
+        Commands::MigrateShards {} => {
+            let migrated = git_interop::migrate_to_shards()?;
+            eprintln!("Moved the measurements of {migrated} commits into shards");
+            Ok(())
+        }
+        Commands::MigrateStorage { to, dry_run } => {
+            let migrated = migrate(to, dry_run)?;
+            let verb = if dry_run {
+                "Would convert"
+            } else {
+                "Converted"
+            };
+            eprintln!("{verb} the measurements of {migrated} commits to {to}");
+            Ok(())
+        }
+        Commands::Digest {
+            since,
+            report_history,
+            sigma,
+            format,
+            output,
+        } => Ok(digest(
+            since,
+            report_history.max_count,
+            sigma,
+            format,
+            &output,
+        )?),
+        Commands::Demo {
+            commits,
+            directory,
+            seed,
+        } => Ok(demo(&directory, commits.into(), seed)?),
+        Commands::Relink { from, to } => {
+            let relinked = relink(&from, &to)?;
+            eprintln!("Copied measurements to {relinked} commits");
+            Ok(())
+        }
+        Commands::Config {
+            command: ConfigCommand::Check {},
+        } => Ok(config_check::check()?),
+        Commands::Config {
+            command: ConfigCommand::Get { key },
+        } => {
+            let value = get_setting(&key)?.ok_or_else(|| anyhow!("{key} is not set"))?;
+            println!("{value}");
+            Ok(())
+        }
+        Commands::Config {
+            command: ConfigCommand::Set { key, value },
+        } => Ok(set_setting(&key, &value)?),
+        Commands::Doctor {} => Ok(doctor()?),
         Commands::Manpage {} => {
             generate_manpage().expect("Man page generation failed");
             Ok(())
@@ -230,6 +1618,14 @@ pub fn handle_calls() -> Result<()> {
     }
 }
 
+fn maybe_gc_temp_refs(temp_gc: &CliTempGc) -> Result<()> {
+    if temp_gc.no_temp_gc {
+        return Ok(());
+    }
+    let max_age = determine_temp_ref_max_age_from_config().unwrap_or(DEFAULT_TEMP_REF_MAX_AGE);
+    gc_temp_refs(None, max_age).map(|_| ())
+}
+
 fn generate_manpage() -> Result<()> {
     let man = clap_mangen::Man::new(Cli::command());
     man.render(&mut std::io::stdout())?;
@@ -254,4 +1650,21 @@ mod test {
     fn verify_cli() {
         Cli::command().debug_assert()
     }
+
+    #[test]
+    fn verify_parse_fraction() {
+        assert_eq!(parse_fraction("0.5").unwrap(), 0.5);
+        assert_eq!(parse_fraction("1").unwrap(), 1.0);
+        assert!(parse_fraction("1.5").is_err());
+        assert!(parse_fraction("half").is_err());
+    }
+
+    #[test]
+    fn verify_parse_outlier_percentage() {
+        assert_eq!(parse_outlier_percentage("0").unwrap(), 0.0);
+        assert_eq!(parse_outlier_percentage("12.5").unwrap(), 12.5);
+        assert!(parse_outlier_percentage("50").is_err());
+        assert!(parse_outlier_percentage("-1").is_err());
+        assert!(parse_outlier_percentage("ten").is_err());
+    }
 }
@@ -0,0 +1,69 @@
+use crate::{
+    data::{MeasurementData, ReductionFunc, Selector},
+    measurement_retrieval::{self, summarize_measurements},
+    stats::{self, Stats},
+};
+use anyhow::Result;
+use itertools::Itertools;
+
+fn collect_summaries(
+    start: &str,
+    max_count: usize,
+    measurement: &str,
+    selectors: &[Selector],
+    summarize_by: ReductionFunc,
+) -> Result<Stats> {
+    let commits = measurement_retrieval::walk_commits_from(start, max_count)?;
+
+    let filter_by = |m: &MeasurementData| m.name == measurement && m.matches_selectors(selectors);
+
+    let vals: Vec<_> = summarize_measurements(commits, &summarize_by, &filter_by)
+        .filter_map_ok(|cs| cs.measurement.map(|m| m.val))
+        .try_collect()?;
+
+    Ok(stats::aggregate_measurements(vals.into_iter()))
+}
+
+/// Compare the measurements of the last `max_count` commits reachable from `base` with those
+/// reachable from `head`. Each commit's measurements are first summarized with `summarize_by`.
+/// A difference is reported as significant if the absolute Welch's t statistic exceeds `sigma`.
+pub fn compare(
+    base: &str,
+    head: &str,
+    measurements: &[String],
+    max_count: usize,
+    selectors: &[Selector],
+    summarize_by: ReductionFunc,
+    sigma: f64,
+) -> Result<()> {
+    for measurement in measurements {
+        let base_summary =
+            collect_summaries(base, max_count, measurement, selectors, summarize_by)?;
+        let head_summary =
+            collect_summaries(head, max_count, measurement, selectors, summarize_by)?;
+
+        println!("{measurement}:");
+        println!("  {base}: {base_summary}");
+        println!("  {head}: {head_summary}");
+
+        if base_summary.len == 0 || head_summary.len == 0 {
+            println!("  Not enough measurements to compare.");
+            continue;
+        }
+
+        let relative = (head_summary.mean - base_summary.mean) / base_summary.mean * 100.0;
+        match base_summary.welch_t(&head_summary) {
+            Some(t) => {
+                let verdict = if t.abs() > sigma {
+                    "significant"
+                } else {
+                    "not significant"
+                };
+                println!("  Δ: {relative:+.2}% (t: {t:.2}, {verdict})");
+            }
+            None => println!("  Δ: {relative:+.2}% (no variance to test significance)"),
+        }
+    }
+
+    Ok(())
+}
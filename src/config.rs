@@ -1,11 +1,21 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use std::{
+    collections::HashMap,
     fs::File,
     io::{Read, Write},
+    time::Duration,
 };
-use toml_edit::{value, Document};
+use toml_edit::{table, value, Array, Document, Entry, Item, Key, Table, TableLike, Value};
 
+use crate::baseline::Baseline;
+use crate::config_check;
+use crate::data::{ReductionFunc, Selector};
+use crate::doctor::Status;
 use crate::git_interop::get_head_revision;
+use crate::reporting::SectionConfig;
+use crate::retention::RetentionPolicy;
+use crate::sparkline;
+use crate::units::parse_period;
 
 // TODO(kaihowl) proper error handling
 pub fn write_config(conf: &str) {
@@ -49,23 +59,1004 @@ fn determine_epoch(measurement: &str, conf_str: &str) -> Option<u32> {
     get_epoch(measurement).or_else(|| get_epoch("*"))
 }
 
-pub fn bump_epoch_in_conf(measurement: &str, conf_str: &mut String) -> Result<()> {
+/// Unit of the measurement's values, configured like the epoch as
+/// `[measurement."name"] unit = "ns"`.
+pub fn determine_unit_from_config(measurement: &str) -> Option<String> {
+    let conf = read_config()?;
+    determine_unit(measurement, &conf)
+}
+
+fn determine_unit(measurement: &str, conf_str: &str) -> Option<String> {
+    let config = conf_str.parse::<Document>().ok()?;
+
+    let get_unit = |section: &str| {
+        let unit = config
+            .get("measurement")?
+            .get(section)?
+            .get("unit")?
+            .as_str()?;
+        Some(unit.to_owned())
+    };
+
+    get_unit(measurement).or_else(|| get_unit("*"))
+}
+
+/// Maximum drift of the measurement's values in percent per commit before an audit fails,
+/// configured like the unit as `[measurement."name"] max_trend_pct_per_commit = 0.5`.
+pub fn determine_max_trend_from_config(measurement: &str) -> Option<f64> {
+    let conf = read_config()?;
+    determine_max_trend(measurement, &conf)
+}
+
+fn determine_max_trend(measurement: &str, conf_str: &str) -> Option<f64> {
+    let config = conf_str.parse::<Document>().ok()?;
+
+    let get_max_trend = |section: &str| {
+        let max_trend = config
+            .get("measurement")?
+            .get(section)?
+            .get("max_trend_pct_per_commit")?;
+        max_trend
+            .as_float()
+            .or_else(|| max_trend.as_integer().map(|i| i as f64))
+            .filter(|m| *m >= 0.0)
+    };
+
+    get_max_trend(measurement).or_else(|| get_max_trend("*"))
+}
+
+/// Maximum relative increase in percent of the measurement over the pinned baseline, configured
+/// as `[measurement."name"] max_regression_pct = 5`.
+pub fn determine_max_regression_from_config(measurement: &str) -> Option<f64> {
+    let conf = read_config()?;
+    determine_max_regression(measurement, &conf)
+}
+
+fn determine_max_regression(measurement: &str, conf_str: &str) -> Option<f64> {
+    let config = conf_str.parse::<Document>().ok()?;
+
+    let get_max_regression = |section: &str| {
+        let max_regression = config
+            .get("measurement")?
+            .get(section)?
+            .get("max_regression_pct")?;
+        max_regression
+            .as_float()
+            .or_else(|| max_regression.as_integer().map(|i| i as f64))
+            .filter(|m| *m >= 0.0)
+    };
+
+    get_max_regression(measurement).or_else(|| get_max_regression("*"))
+}
+
+/// Aggregation of the measurement's values per commit in audits without `--aggregate-by`,
+/// configured as `[measurement."name"] aggregate_by = "trimmed_mean(10)"`.
+pub fn determine_aggregate_by_from_config(measurement: &str) -> Result<Option<ReductionFunc>> {
+    match read_config() {
+        Some(conf) => determine_aggregate_by(measurement, &conf),
+        None => Ok(None),
+    }
+}
+
+fn determine_aggregate_by(measurement: &str, conf_str: &str) -> Result<Option<ReductionFunc>> {
+    let Ok(config) = conf_str.parse::<Document>() else {
+        return Ok(None);
+    };
+
+    let get_aggregate_by = |section: &str| {
+        config
+            .get("measurement")?
+            .get(section)?
+            .get("aggregate_by")?
+            .as_str()
+    };
+
+    get_aggregate_by(measurement)
+        .or_else(|| get_aggregate_by("*"))
+        .map(|name| {
+            name.parse()
+                .with_context(|| format!("Invalid aggregate_by for measurement '{measurement}'"))
+        })
+        .transpose()
+}
+
+/// Sigma of audits of the measurement with the `selectors`, configured per selector as
+/// `[measurement."name".selector."os=mac"] sigma = 6.0`. The first override of the measurement
+/// whose selector is among the `selectors` applies, otherwise the first of `[measurement."*"]`.
+/// Without a matching override, `[measurement."name"] sigma = 5.0` applies, otherwise the sigma
+/// of `[measurement."*"]`.
+pub fn determine_sigma_from_config(
+    measurement: &str,
+    selectors: &[Selector],
+) -> Result<Option<f64>> {
+    match read_config() {
+        Some(conf) => determine_sigma(measurement, selectors, &conf),
+        None => Ok(None),
+    }
+}
+
+fn determine_sigma(
+    measurement: &str,
+    selectors: &[Selector],
+    conf_str: &str,
+) -> Result<Option<f64>> {
+    let Ok(config) = conf_str.parse::<Document>() else {
+        return Ok(None);
+    };
+
+    let positive = |sigma: &Item, of: String| -> Result<f64> {
+        sigma
+            .as_float()
+            .or_else(|| sigma.as_integer().map(|i| i as f64))
+            .filter(|s| *s > 0.0)
+            .with_context(|| format!("The sigma {of} must be a positive number"))
+    };
+
+    let get_override = |section: &str| -> Result<Option<f64>> {
+        let Some(overrides) = config
+            .get("measurement")
+            .and_then(|m| m.get(section))
+            .and_then(|s| s.get("selector"))
+            .and_then(|s| s.as_table_like())
+        else {
+            return Ok(None);
+        };
+        for (selector, settings) in overrides.iter() {
+            let selector = selector.parse::<Selector>().with_context(|| {
+                format!("Invalid selector '{selector}' of measurement '{section}'")
+            })?;
+            if !selectors.contains(&selector) {
+                continue;
+            }
+            let Some(sigma) = settings.get("sigma") else {
+                continue;
+            };
+            let sigma = positive(
+                sigma,
+                format!("for '{selector}' of measurement '{section}'"),
+            )?;
+            return Ok(Some(sigma));
+        }
+        Ok(None)
+    };
+
+    let get_sigma = |section: &str| -> Result<Option<f64>> {
+        config
+            .get("measurement")
+            .and_then(|m| m.get(section))
+            .and_then(|s| s.get("sigma"))
+            .map(|sigma| positive(sigma, format!("of measurement '{section}'")))
+            .transpose()
+    };
+
+    if let Some(sigma) = get_override(measurement)? {
+        return Ok(Some(sigma));
+    }
+    if let Some(sigma) = get_override("*")? {
+        return Ok(Some(sigma));
+    }
+    match get_sigma(measurement)? {
+        Some(sigma) => Ok(Some(sigma)),
+        None => get_sigma("*"),
+    }
+}
+
+/// Command that takes the measurement, e.g. to rerun it when verifying a failed audit.
+/// Configured as `[measurement."name"] command = ["cargo", "run", "--release"]`.
+pub fn determine_command_from_config(measurement: &str) -> Option<Vec<String>> {
+    let conf = read_config()?;
+    determine_command(measurement, &conf)
+}
+
+fn determine_command(measurement: &str, conf_str: &str) -> Option<Vec<String>> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let command = config
+        .get("measurement")?
+        .get(measurement)?
+        .get("command")?
+        .as_array()?
+        .iter()
+        .map(|arg| arg.as_str().map(str::to_owned))
+        .collect::<Option<Vec<_>>>()?;
+    (!command.is_empty()).then_some(command)
+}
+
+/// Regex extracting the value of the measurement from the output of its configured command,
+/// e.g. to rerun it when verifying a failed audit. Configured as
+/// `[measurement."name"] extract_regex = "took (\\d+) ns"`.
+pub fn determine_extract_regex_from_config(measurement: &str) -> Option<String> {
+    let conf = read_config()?;
+    determine_extract_regex(measurement, &conf)
+}
+
+fn determine_extract_regex(measurement: &str, conf_str: &str) -> Option<String> {
+    let config = conf_str.parse::<Document>().ok()?;
+    config
+        .get("measurement")?
+        .get(measurement)?
+        .get("extract_regex")?
+        .as_str()
+        .map(str::to_owned)
+}
+
+/// Maximum age of temporary refs before they are garbage collected.
+/// Configured in seconds as `[gc] temp_ref_max_age = 86400`.
+pub fn determine_temp_ref_max_age_from_config() -> Option<Duration> {
+    let conf = read_config()?;
+    determine_temp_ref_max_age(&conf)
+}
+
+fn determine_temp_ref_max_age(conf_str: &str) -> Option<Duration> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let secs = config.get("gc")?.get("temp_ref_max_age")?.as_integer()?;
+    u64::try_from(secs).ok().map(Duration::from_secs)
+}
+
+/// Name of the storage backend configured as `[storage] backend = "<name>"`.
+pub fn determine_storage_backend_from_config() -> Option<String> {
+    let conf = read_config()?;
+    determine_storage_backend(&conf)
+}
+
+fn determine_storage_backend(conf_str: &str) -> Option<String> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let backend = config.get("storage")?.get("backend")?.as_str()?;
+    Some(backend.to_owned())
+}
+
+/// Scheme for sharding the measurements over several notes refs, configured as
+/// `[storage] shards = "year"`.
+pub fn determine_storage_shards_from_config() -> Option<String> {
+    let conf = read_config()?;
+    determine_storage_shards(&conf)
+}
+
+fn determine_storage_shards(conf_str: &str) -> Option<String> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let shards = config.get("storage")?.get("shards")?.as_str()?;
+    Some(shards.to_owned())
+}
+
+/// Serialization of newly added measurements, configured as `[storage] format = "v4"`.
+pub fn determine_storage_format_from_config() -> Option<String> {
+    let conf = read_config()?;
+    determine_storage_format(&conf)
+}
+
+fn determine_storage_format(conf_str: &str) -> Option<String> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let format = config.get("storage")?.get("format")?.as_str()?;
+    Some(format.to_owned())
+}
+
+/// Notes refs of earlier git-perf versions to additionally read measurements from, configured
+/// as `[storage] legacy_refs = ["refs/notes/perf-v2"]`.
+pub fn determine_legacy_refs_from_config() -> Vec<String> {
+    read_config()
+        .map(|conf| determine_legacy_refs(&conf))
+        .unwrap_or_default()
+}
+
+pub(crate) fn determine_legacy_refs(conf_str: &str) -> Vec<String> {
+    let Ok(config) = conf_str.parse::<Document>() else {
+        return Vec::new();
+    };
+    config
+        .get("storage")
+        .and_then(|s| s.get("legacy_refs"))
+        .and_then(|r| r.as_array())
+        .map(|refs| {
+            refs.iter()
+                .filter_map(|r| r.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Size budget in bytes of a single note of a shard, configured as
+/// `[storage] max_note_size = 1048576`. Measurements beyond it go to the shard's chunk refs.
+pub fn determine_max_note_size_from_config() -> Option<usize> {
+    let conf = read_config()?;
+    determine_max_note_size(&conf)
+}
+
+fn determine_max_note_size(conf_str: &str) -> Option<usize> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let size = config.get("storage")?.get("max_note_size")?.as_integer()?;
+    usize::try_from(size).ok()
+}
+
+/// Whether writing and pushing measurements takes an advisory lock on `.git/git-perf.lock`,
+/// configured as `[storage] local_lock = true`.
+pub fn determine_local_lock_from_config() -> bool {
+    read_config()
+        .map(|conf| determine_local_lock(&conf))
+        .unwrap_or(false)
+}
+
+fn determine_local_lock(conf_str: &str) -> bool {
+    conf_str
+        .parse::<Document>()
+        .ok()
+        .and_then(|config| config.get("storage")?.get("local_lock")?.as_bool())
+        .unwrap_or(false)
+}
+
+/// Whether the deserialized measurements are cached in `.git/git-perf/cache`, configured as
+/// `[storage] cache = true`.
+pub fn determine_storage_cache_from_config() -> bool {
+    read_config()
+        .map(|conf| determine_storage_cache(&conf))
+        .unwrap_or(false)
+}
+
+fn determine_storage_cache(conf_str: &str) -> bool {
+    conf_str
+        .parse::<Document>()
+        .ok()
+        .and_then(|config| config.get("storage")?.get("cache")?.as_bool())
+        .unwrap_or(false)
+}
+
+/// Whether measurements are stamped with the fingerprint of the machine they are taken on, as
+/// with `--capture-env`. Configured as `[measure] capture_env = true`.
+pub fn determine_capture_env_from_config() -> bool {
+    read_config()
+        .map(|conf| determine_capture_env(&conf))
+        .unwrap_or(false)
+}
+
+fn determine_capture_env(conf_str: &str) -> bool {
+    conf_str
+        .parse::<Document>()
+        .ok()
+        .and_then(|config| config.get("measure")?.get("capture_env")?.as_bool())
+        .unwrap_or(false)
+}
+
+/// Whether report hover texts should omit the sample count and spread of aggregated points.
+/// Configured as `[report] compact_hover = true`.
+pub fn determine_compact_hover_from_config() -> bool {
+    read_config()
+        .map(|conf| determine_compact_hover(&conf))
+        .unwrap_or(false)
+}
+
+fn determine_compact_hover(conf_str: &str) -> bool {
+    conf_str
+        .parse::<Document>()
+        .ok()
+        .and_then(|config| config.get("report")?.get("compact_hover")?.as_bool())
+        .unwrap_or(false)
+}
+
+/// Link to a commit in HTML reports, configured as
+/// `[report] commit_url_template = "https://github.com/org/repo/commit/{sha}"`.
+pub fn determine_commit_url_template_from_config() -> Option<String> {
+    let conf = read_config()?;
+    determine_commit_url_template(&conf)
+}
+
+fn determine_commit_url_template(conf_str: &str) -> Option<String> {
+    let config = conf_str.parse::<Document>().ok()?;
+    Some(
+        config
+            .get("report")?
+            .get("commit_url_template")?
+            .as_str()?
+            .to_owned(),
+    )
+}
+
+/// Number of characters of commit hashes shown in HTML reports, configured as
+/// `[report] sha_length = 10`.
+pub fn determine_report_sha_length_from_config() -> Option<usize> {
+    let conf = read_config()?;
+    determine_report_sha_length(&conf)
+}
+
+fn determine_report_sha_length(conf_str: &str) -> Option<usize> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let length = config.get("report")?.get("sha_length")?.as_integer()?;
+    usize::try_from(length).ok().filter(|l| *l > 0)
+}
+
+/// Whether reports scale values of measurements with a configured unit to a readable unit.
+/// Configured as `[report] auto_scale_units = false`, enabled by default.
+pub fn determine_auto_scale_units_from_config() -> bool {
+    read_config()
+        .map(|conf| determine_auto_scale_units(&conf))
+        .unwrap_or(true)
+}
+
+fn determine_auto_scale_units(conf_str: &str) -> bool {
+    conf_str
+        .parse::<Document>()
+        .ok()
+        .and_then(|config| config.get("report")?.get("auto_scale_units")?.as_bool())
+        .unwrap_or(true)
+}
+
+/// Link to a commit in digests, configured as
+/// `[digest] commit_url = "https://github.com/org/repo/commit/{commit}"`.
+pub fn determine_digest_commit_url_from_config() -> Option<String> {
+    let conf = read_config()?;
+    determine_digest_commit_url(&conf)
+}
+
+fn determine_digest_commit_url(conf_str: &str) -> Option<String> {
+    let config = conf_str.parse::<Document>().ok()?;
+    Some(
+        config
+            .get("digest")?
+            .get("commit_url")?
+            .as_str()?
+            .to_owned(),
+    )
+}
+
+/// Remotes to push to by default, configured as `[remote] push = ["origin", "mirror"]`.
+pub fn determine_push_remotes_from_config() -> Vec<String> {
+    read_config()
+        .map(|conf| determine_push_remotes(&conf))
+        .unwrap_or_default()
+}
+
+fn determine_push_remotes(conf_str: &str) -> Vec<String> {
+    let Ok(config) = conf_str.parse::<Document>() else {
+        return Vec::new();
+    };
+    config
+        .get("remote")
+        .and_then(|r| r.get("push"))
+        .and_then(|p| p.as_array())
+        .map(|remotes| {
+            remotes
+                .iter()
+                .filter_map(|r| r.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Number of failed measurements tolerated by an audit, configured as
+/// `[audit] max_failures = 2`.
+pub fn determine_audit_max_failures_from_config() -> Option<usize> {
+    let conf = read_config()?;
+    determine_audit_max_failures(&conf)
+}
+
+fn determine_audit_max_failures(conf_str: &str) -> Option<usize> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let max_failures = config.get("audit")?.get("max_failures")?.as_integer()?;
+    usize::try_from(max_failures).ok()
+}
+
+/// Multiple of the stddev beyond which an audit results in a warning instead of passing,
+/// configured as `[audit] warn_sigma = 2.0`.
+pub fn determine_audit_warn_sigma_from_config() -> Option<f64> {
+    let conf = read_config()?;
+    determine_audit_warn_sigma(&conf)
+}
+
+fn determine_audit_warn_sigma(conf_str: &str) -> Option<f64> {
+    let config = conf_str.parse::<Document>().ok()?;
+    let warn_sigma = config.get("audit")?.get("warn_sigma")?;
+    warn_sigma
+        .as_float()
+        .or_else(|| warn_sigma.as_integer().map(|i| i as f64))
+        .filter(|w| *w > 0.0)
+}
+
+/// Mapping of measurement name patterns (regular expressions) to the path globs of their owning
+/// component, configured as `[audit.paths] "bench::parser::.*" = ["src/parser/**"]`.
+/// `*` matches within a single directory and `**` across directories.
+pub fn determine_audit_paths_from_config() -> Vec<(String, Vec<String>)> {
+    read_config()
+        .map(|conf| determine_audit_paths(&conf))
+        .unwrap_or_default()
+}
+
+fn determine_audit_paths(conf_str: &str) -> Vec<(String, Vec<String>)> {
+    let Ok(config) = conf_str.parse::<Document>() else {
+        return Vec::new();
+    };
+    let Some(paths) = config
+        .get("audit")
+        .and_then(|a| a.get("paths"))
+        .and_then(|p| p.as_table_like())
+    else {
+        return Vec::new();
+    };
+
+    paths
+        .iter()
+        .filter_map(|(pattern, globs)| {
+            let globs = globs
+                .as_array()?
+                .iter()
+                .filter_map(|g| g.as_str().map(str::to_owned))
+                .collect();
+            Some((pattern.to_owned(), globs))
+        })
+        .collect()
+}
+
+/// Sections of reports with `--sections` in the order of their configuration as
+/// `[report.section.<id>]` blocks.
+pub fn determine_report_sections_from_config() -> Result<Vec<SectionConfig>> {
+    read_config()
+        .map(|conf| determine_report_sections(&conf))
+        .unwrap_or_else(|| Ok(Vec::new()))
+}
+
+pub(crate) fn determine_report_sections(conf_str: &str) -> Result<Vec<SectionConfig>> {
+    let config = conf_str
+        .parse::<Document>()
+        .context("Failed to parse .gitperfconfig")?;
+    let Some(sections) = config.get("report").and_then(|r| r.get("section")) else {
+        return Ok(Vec::new());
+    };
+    let sections = sections
+        .as_table_like()
+        .context("report.section must be a table of sections")?;
+    sections
+        .iter()
+        .map(|(id, section)| {
+            let section = section
+                .as_table_like()
+                .with_context(|| format!("report.section.{id} must be a table"))?;
+            let string = |key: &str| {
+                section
+                    .get(key)
+                    .map(|v| {
+                        v.as_str()
+                            .map(str::to_owned)
+                            .with_context(|| format!("report.section.{id}.{key} must be a string"))
+                    })
+                    .transpose()
+            };
+            let separate_by = section
+                .get("separate_by")
+                .map(|keys| {
+                    keys.as_array()
+                        .and_then(|keys| {
+                            keys.iter()
+                                .map(|k| k.as_str().map(str::to_owned))
+                                .collect::<Option<Vec<_>>>()
+                        })
+                        .with_context(|| {
+                            format!("report.section.{id}.separate_by must be an array of strings")
+                        })
+                })
+                .transpose()?
+                .unwrap_or_default();
+            let aggregate_by = string("aggregate_by")?
+                .map(|a| a.parse())
+                .transpose()
+                .with_context(|| format!("Invalid report.section.{id}.aggregate_by"))?;
+            let depth = section
+                .get("depth")
+                .map(|depth| {
+                    depth
+                        .as_integer()
+                        .and_then(|d| usize::try_from(d).ok())
+                        .filter(|d| *d > 0)
+                        .with_context(|| {
+                            format!("report.section.{id}.depth must be a positive integer")
+                        })
+                })
+                .transpose()?;
+            let show_changes = section
+                .get("show_changes")
+                .map(|show| {
+                    show.as_bool().with_context(|| {
+                        format!("report.section.{id}.show_changes must be a boolean")
+                    })
+                })
+                .transpose()?
+                .unwrap_or(false);
+            let chart = string("chart")?
+                .map(|c| c.parse())
+                .transpose()
+                .with_context(|| format!("Invalid report.section.{id}.chart"))?
+                .unwrap_or_default();
+            let start = string("ref")?;
+            let range = string("range")?;
+            if start.is_some() && range.is_some() {
+                bail!("report.section.{id} can only have one of ref and range");
+            }
+            Ok(SectionConfig {
+                id: id.to_owned(),
+                title: string("title")?,
+                filter: string("filter")?,
+                start,
+                range,
+                separate_by,
+                aggregate_by,
+                depth,
+                show_changes,
+                chart,
+            })
+        })
+        .collect()
+}
+
+/// Retention policies applied by `git perf gc`, configured as
+/// `[retention] max_age = "90d"`, `max_points_per_measurement = 500` and
+/// `keep_tagged_commits = true`.
+pub fn determine_retention_policy_from_config() -> Result<RetentionPolicy> {
+    read_config()
+        .map(|conf| determine_retention_policy(&conf))
+        .unwrap_or_else(|| Ok(RetentionPolicy::default()))
+}
+
+pub(crate) fn determine_retention_policy(conf_str: &str) -> Result<RetentionPolicy> {
+    let config = conf_str
+        .parse::<Document>()
+        .context("Failed to parse .gitperfconfig")?;
+    let Some(retention) = config.get("retention") else {
+        return Ok(RetentionPolicy::default());
+    };
+    let max_age = retention
+        .get("max_age")
+        .map(|age| {
+            let age = age
+                .as_str()
+                .context("retention.max_age must be a string such as \"90d\"")?;
+            parse_period(age).context("Invalid retention.max_age")
+        })
+        .transpose()?;
+    let max_points_per_measurement = retention
+        .get("max_points_per_measurement")
+        .map(|points| {
+            points
+                .as_integer()
+                .and_then(|p| usize::try_from(p).ok())
+                .context("retention.max_points_per_measurement must be a non-negative integer")
+        })
+        .transpose()?;
+    let keep_tagged_commits = retention
+        .get("keep_tagged_commits")
+        .map(|keep| {
+            keep.as_bool()
+                .context("retention.keep_tagged_commits must be a boolean")
+        })
+        .transpose()?
+        .unwrap_or(false);
+    Ok(RetentionPolicy {
+        max_age,
+        max_points_per_measurement,
+        keep_tagged_commits,
+    })
+}
+
+/// Rendering of the trend sparklines in audit, digest, and markdown report output, configured as
+/// `[sparkline] width = 30`, `scale = "log"` and `clamp_percentiles = [5, 95]`.
+pub fn determine_sparkline_options_from_config() -> Result<sparkline::Options> {
+    read_config()
+        .map(|conf| determine_sparkline_options(&conf))
+        .unwrap_or_else(|| Ok(sparkline::Options::default()))
+}
+
+pub(crate) fn determine_sparkline_options(conf_str: &str) -> Result<sparkline::Options> {
+    let config = conf_str
+        .parse::<Document>()
+        .context("Failed to parse .gitperfconfig")?;
+    let Some(sparkline) = config.get("sparkline") else {
+        return Ok(sparkline::Options::default());
+    };
+    let width = sparkline
+        .get("width")
+        .map(|width| {
+            width
+                .as_integer()
+                .and_then(|w| usize::try_from(w).ok())
+                .context("sparkline.width must be a positive integer")
+        })
+        .transpose()?;
+    let scale = sparkline
+        .get("scale")
+        .map(|scale| {
+            scale
+                .as_str()
+                .context("sparkline.scale must be a string")?
+                .parse()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let clamp = sparkline
+        .get("clamp_percentiles")
+        .map(|clamp| {
+            let percentiles = clamp.as_array().and_then(|a| {
+                a.iter()
+                    .map(|p| p.as_float().or_else(|| p.as_integer().map(|i| i as f64)))
+                    .collect::<Option<Vec<_>>>()
+            });
+            match percentiles.as_deref() {
+                Some(&[low, high]) => Ok((low, high)),
+                _ => bail!("sparkline.clamp_percentiles must be a pair of numbers such as [5, 95]"),
+            }
+        })
+        .transpose()?;
+    let options = sparkline::Options {
+        width,
+        scale,
+        clamp,
+    };
+    options.validate()?;
+    Ok(options)
+}
+
+/// Baseline pinned with `git perf baseline set`, stored as
+/// `[baseline] ref = "v1.2.0"`, `commit`, `aggregate_by`, `selectors` and one value per
+/// measurement in `[baseline.values]`.
+pub fn determine_baseline_from_config() -> Result<Option<Baseline>> {
+    read_config()
+        .map(|conf| determine_baseline(&conf))
+        .unwrap_or(Ok(None))
+}
+
+pub(crate) fn determine_baseline(conf_str: &str) -> Result<Option<Baseline>> {
+    let config = conf_str
+        .parse::<Document>()
+        .context("Failed to parse .gitperfconfig")?;
+    let Some(baseline) = config.get("baseline") else {
+        return Ok(None);
+    };
+    let string = |key: &str| {
+        baseline
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_owned)
+            .with_context(|| format!("baseline.{key} must be a string"))
+    };
+    let selectors = baseline
+        .get("selectors")
+        .and_then(|s| s.as_array())
+        .context("baseline.selectors must be an array")?
+        .iter()
+        .map(|selector| {
+            selector
+                .as_str()
+                .context("baseline.selectors must contain strings")?
+                .parse()
+                .context("Invalid selector in baseline.selectors")
+        })
+        .collect::<Result<_>>()?;
+    let values = baseline
+        .get("values")
+        .and_then(|v| v.as_table_like())
+        .context("baseline.values must be a table")?
+        .iter()
+        .map(|(name, val)| {
+            val.as_float()
+                .or_else(|| val.as_integer().map(|i| i as f64))
+                .map(|val| (name.to_owned(), val))
+                .with_context(|| format!("baseline.values.{name} must be a number"))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Some(Baseline {
+        reference: string("ref")?,
+        commit: string("commit")?,
+        aggregate_by: string("aggregate_by")?.parse()?,
+        selectors,
+        values,
+    }))
+}
+
+pub fn set_baseline_in_conf(baseline: &Baseline, conf_str: &mut String) -> Result<()> {
+    let mut conf = conf_str
+        .parse::<Document>()
+        .context("Failed to parse .gitperfconfig")?;
+
+    conf["baseline"] = table();
+    conf["baseline"]["ref"] = value(&baseline.reference);
+    conf["baseline"]["commit"] = value(&baseline.commit);
+    conf["baseline"]["aggregate_by"] = value(baseline.aggregate_by.to_string());
+    conf["baseline"]["selectors"] = value(
+        baseline
+            .selectors
+            .iter()
+            .map(Selector::to_string)
+            .collect::<Array>(),
+    );
+    conf["baseline"]["values"] = table();
+    for (name, val) in &baseline.values {
+        conf["baseline"]["values"][name] = value(*val);
+    }
+    *conf_str = conf.to_string();
+
+    Ok(())
+}
+
+/// Replace the pinned baseline in .gitperfconfig.
+pub fn set_baseline(baseline: &Baseline) -> Result<()> {
+    let mut conf_str = read_config().unwrap_or_default();
+    set_baseline_in_conf(baseline, &mut conf_str)?;
+    write_config(&conf_str);
+    Ok(())
+}
+
+/// Start a new epoch for the measurement at HEAD. A `reason` is recorded for the new epoch in
+/// `[measurement."name".epoch_reasons]`.
+pub fn bump_epoch_in_conf(
+    measurement: &str,
+    reason: Option<&str>,
+    conf_str: &mut String,
+) -> Result<()> {
     let mut conf = conf_str
         .parse::<Document>()
         .expect("failed to parse config");
 
     let head_revision = get_head_revision()?;
+    let epoch = &head_revision[0..8];
     // TODO(kaihowl) ensure that always non-inline tables are written in an empty config file
-    conf["measurement"][measurement]["epoch"] = value(&head_revision[0..8]);
+    conf["measurement"][measurement]["epoch"] = value(epoch);
+    if let Some(reason) = reason {
+        conf["measurement"][measurement]["epoch_reasons"][epoch] = value(reason);
+    }
     *conf_str = conf.to_string();
 
     Ok(())
 }
 
 // TODO(kaihowl) proper error handling
-pub fn bump_epoch(measurement: &str) -> Result<()> {
+pub fn bump_epoch(measurement: &str, reason: Option<&str>) -> Result<()> {
     let mut conf_str = read_config().unwrap_or_default();
-    bump_epoch_in_conf(measurement, &mut conf_str)?;
+    bump_epoch_in_conf(measurement, reason, &mut conf_str)?;
+    write_config(&conf_str);
+    Ok(())
+}
+
+/// Reasons recorded for the epochs of the measurement with `git perf bump-epoch --reason`,
+/// configured as `[measurement."name".epoch_reasons] 0a1b2c3d = "switched allocator"`. Reasons
+/// of `[measurement."*"]` apply to all measurements.
+pub fn determine_epoch_reasons_from_config(measurement: &str) -> HashMap<u32, String> {
+    read_config()
+        .map(|conf| determine_epoch_reasons(measurement, &conf))
+        .unwrap_or_default()
+}
+
+fn determine_epoch_reasons(measurement: &str, conf_str: &str) -> HashMap<u32, String> {
+    let Ok(config) = conf_str.parse::<Document>() else {
+        return HashMap::new();
+    };
+    let mut reasons = HashMap::new();
+    // The measurement's own reasons take precedence
+    for section in ["*", measurement] {
+        let Some(section_reasons) = config
+            .get("measurement")
+            .and_then(|m| m.get(section))
+            .and_then(|s| s.get("epoch_reasons"))
+            .and_then(|r| r.as_table_like())
+        else {
+            continue;
+        };
+        for (epoch, reason) in section_reasons.iter() {
+            if let (Ok(epoch), Some(reason)) = (u32::from_str_radix(epoch, 16), reason.as_str()) {
+                reasons.insert(epoch, reason.to_owned());
+            }
+        }
+    }
+    reasons
+}
+
+/// Keys of a setting given as a dotted path quoted like the keys in .gitperfconfig, e.g.
+/// `measurement."build".unit` or `measurement."*".epoch`.
+fn parse_setting_path(path: &str) -> Result<Vec<Key>> {
+    match Key::parse(path) {
+        // Formatted like new keys, not like in the path
+        Ok(keys) if keys.len() >= 2 => Ok(keys
+            .into_iter()
+            .map(|mut key| {
+                key.decor_mut().clear();
+                key
+            })
+            .collect()),
+        _ => bail!(
+            "Invalid setting '{path}', expected a section and a key such as \
+             measurement.\"build\".unit"
+        ),
+    }
+}
+
+/// Value of the setting at `path` in `conf_str`. Strings are returned without quotes, other
+/// values as written in TOML.
+pub fn get_setting_in_conf(path: &str, conf_str: &str) -> Result<Option<String>> {
+    let keys = parse_setting_path(path)?;
+    let conf = conf_str
+        .parse::<Document>()
+        .context("Failed to parse .gitperfconfig")?;
+    let mut item = conf.as_item();
+    for key in &keys {
+        match item.get(key.get()) {
+            Some(next) => item = next,
+            None => return Ok(None),
+        }
+    }
+    let value = item
+        .as_value()
+        .with_context(|| format!("{path} is a section, get one of its settings instead"))?;
+    Ok(Some(match value.as_str() {
+        Some(s) => s.to_owned(),
+        None => {
+            let mut value = value.clone();
+            value.decor_mut().clear();
+            value.to_string()
+        }
+    }))
+}
+
+/// Set the setting at `path` in `conf_str` to `val`, keeping the comments and formatting of all
+/// other settings. `val` is parsed as a TOML value, e.g. `5.0`, `true` or `["make"]`, and taken
+/// as a string otherwise.
+pub fn set_setting_in_conf(path: &str, val: &str, conf_str: &mut String) -> Result<()> {
+    let keys = parse_setting_path(path)?;
+    let (key, sections) = keys.split_last().expect("Settings have a key");
+    let mut conf = conf_str
+        .parse::<Document>()
+        .context("Failed to parse .gitperfconfig")?;
+    let mut table: &mut dyn TableLike = conf.as_table_mut();
+    for section in sections {
+        table = table
+            .entry_format(section)
+            .or_insert_with(|| {
+                let mut section = Table::new();
+                section.set_implicit(true);
+                Item::Table(section)
+            })
+            .as_table_like_mut()
+            .with_context(|| format!("{} in {path} is not a section", section.get()))?;
+    }
+    let val = val
+        .parse::<Value>()
+        .unwrap_or_else(|_| Value::from(val))
+        .decorated(" ", "");
+    match table.entry_format(key) {
+        Entry::Occupied(mut setting) if setting.get().is_value() => {
+            let decor = setting.get().as_value().expect("Checked").decor().clone();
+            *setting.get_mut() = Item::Value(val);
+            *setting
+                .get_mut()
+                .as_value_mut()
+                .expect("Just set")
+                .decor_mut() = decor;
+        }
+        Entry::Occupied(_) => bail!("{path} is a section, set one of its settings instead"),
+        Entry::Vacant(setting) => {
+            setting.insert(Item::Value(val));
+        }
+    }
+    *conf_str = conf.to_string();
+    Ok(())
+}
+
+/// Value of the setting at `path` in .gitperfconfig, see [`get_setting_in_conf`].
+pub fn get_setting(path: &str) -> Result<Option<String>> {
+    get_setting_in_conf(path, &read_config().unwrap_or_default())
+}
+
+/// Set the setting at `path` in .gitperfconfig, see [`set_setting_in_conf`]. Fails without
+/// writing if this makes a setting unknown or invalid that was not before.
+pub fn set_setting(path: &str, val: &str) -> Result<()> {
+    let previous = read_config().unwrap_or_default();
+    let mut conf_str = previous.clone();
+    set_setting_in_conf(path, val, &mut conf_str)?;
+    let known_problems = config_check::lint(&previous);
+    if let Some(problem) = config_check::lint(&conf_str)
+        .into_iter()
+        .find(|check| check.status == Status::Fail && !known_problems.contains(check))
+    {
+        bail!(
+            "Not setting {path}: {}{}",
+            problem.message,
+            problem
+                .fix
+                .map(|fix| format!(". {fix}"))
+                .unwrap_or_default()
+        );
+    }
     write_config(&conf_str);
     Ok(())
 }
@@ -73,6 +1064,8 @@ pub fn bump_epoch(measurement: &str) -> Result<()> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::data::ReductionFunc;
+    use itertools::Itertools;
 
     #[test]
     fn test_read_epochs() {
@@ -99,6 +1092,454 @@ epoch="12344555"
         assert_eq!(epoch, Some(0x12344555));
     }
 
+    #[test]
+    fn test_read_temp_ref_max_age() {
+        let configfile = r#"[gc]
+temp_ref_max_age = 3600
+"#;
+        assert_eq!(
+            determine_temp_ref_max_age(configfile),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(determine_temp_ref_max_age(""), None);
+        assert_eq!(
+            determine_temp_ref_max_age("[gc]\ntemp_ref_max_age = -1"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_read_storage_backend() {
+        let configfile = r#"[storage]
+backend = "git-notes"
+"#;
+        assert_eq!(
+            determine_storage_backend(configfile),
+            Some("git-notes".to_string())
+        );
+        assert_eq!(determine_storage_backend(""), None);
+    }
+
+    #[test]
+    fn test_read_storage_shards() {
+        let configfile = r#"[storage]
+shards = "year"
+"#;
+        assert_eq!(
+            determine_storage_shards(configfile),
+            Some("year".to_string())
+        );
+        assert_eq!(
+            determine_storage_shards("[storage]\nbackend = \"git-notes\"\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_read_storage_format() {
+        assert_eq!(
+            determine_storage_format("[storage]\nformat = \"v4\"\n"),
+            Some("v4".to_string())
+        );
+        assert_eq!(
+            determine_storage_format("[storage]\nshards = \"year\"\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_read_legacy_refs() {
+        let configfile = r#"[storage]
+legacy_refs = ["refs/notes/perf", "refs/notes/perf-v2"]
+"#;
+        assert_eq!(
+            determine_legacy_refs(configfile),
+            vec![
+                "refs/notes/perf".to_string(),
+                "refs/notes/perf-v2".to_string()
+            ]
+        );
+        assert!(determine_legacy_refs("[storage]\nformat = \"v4\"\n").is_empty());
+    }
+
+    #[test]
+    fn test_read_max_note_size() {
+        assert_eq!(
+            determine_max_note_size("[storage]\nmax_note_size = 1048576\n"),
+            Some(1048576)
+        );
+        assert_eq!(
+            determine_max_note_size("[storage]\nmax_note_size = -1\n"),
+            None
+        );
+        assert_eq!(determine_max_note_size(""), None);
+    }
+
+    #[test]
+    fn test_read_local_lock() {
+        assert!(determine_local_lock("[storage]\nlocal_lock = true\n"));
+        assert!(!determine_local_lock("[storage]\nlocal_lock = false\n"));
+        assert!(!determine_local_lock(""));
+    }
+
+    #[test]
+    fn test_read_storage_cache() {
+        assert!(determine_storage_cache("[storage]\ncache = true\n"));
+        assert!(!determine_storage_cache("[storage]\ncache = false\n"));
+        assert!(!determine_storage_cache(""));
+    }
+
+    #[test]
+    fn test_read_capture_env() {
+        assert!(determine_capture_env("[measure]\ncapture_env = true\n"));
+        assert!(!determine_capture_env("[measure]\ncapture_env = false\n"));
+        assert!(!determine_capture_env(""));
+    }
+
+    #[test]
+    fn test_read_compact_hover() {
+        assert!(determine_compact_hover("[report]\ncompact_hover = true\n"));
+        assert!(!determine_compact_hover(
+            "[report]\ncompact_hover = false\n"
+        ));
+        assert!(!determine_compact_hover(""));
+    }
+
+    #[test]
+    fn test_read_command() {
+        let configfile = r#"[measurement."timer"]
+command = ["sleep", "0.1"]
+
+[measurement."empty"]
+command = []
+"#;
+        assert_eq!(
+            determine_command("timer", configfile),
+            Some(vec!["sleep".to_string(), "0.1".to_string()])
+        );
+        assert_eq!(determine_command("empty", configfile), None);
+        assert_eq!(determine_command("other", configfile), None);
+    }
+
+    #[test]
+    fn test_read_extract_regex() {
+        let configfile = r#"[measurement."timer"]
+extract_regex = 'took (\d+) ns'
+"#;
+        assert_eq!(
+            determine_extract_regex("timer", configfile),
+            Some(r"took (\d+) ns".to_string())
+        );
+        assert_eq!(determine_extract_regex("other", configfile), None);
+    }
+
+    #[test]
+    fn test_read_units() {
+        let configfile = r#"[measurement."timer"]
+unit = "ms"
+
+[measurement."*"]
+unit = "ns"
+"#;
+        assert_eq!(determine_unit("timer", configfile), Some("ms".to_string()));
+        assert_eq!(determine_unit("other", configfile), Some("ns".to_string()));
+        assert_eq!(determine_unit("timer", ""), None);
+    }
+
+    #[test]
+    fn test_read_max_trend() {
+        let configfile = r#"[measurement."timer"]
+max_trend_pct_per_commit = 0.5
+
+[measurement."*"]
+max_trend_pct_per_commit = 2
+"#;
+        assert_eq!(determine_max_trend("timer", configfile), Some(0.5));
+        assert_eq!(determine_max_trend("other", configfile), Some(2.0));
+        assert_eq!(
+            determine_max_trend(
+                "timer",
+                "[measurement.timer]\nmax_trend_pct_per_commit = -1\n"
+            ),
+            None
+        );
+        assert_eq!(determine_max_trend("timer", ""), None);
+    }
+
+    #[test]
+    fn test_read_aggregate_by() {
+        let configfile = r#"[measurement."latency"]
+aggregate_by = "trimmed_mean(10)"
+
+[measurement."*"]
+aggregate_by = "geometric_mean"
+"#;
+        assert_eq!(
+            determine_aggregate_by("latency", configfile).unwrap(),
+            Some(ReductionFunc::TrimmedMean(10))
+        );
+        assert_eq!(
+            determine_aggregate_by("other", configfile).unwrap(),
+            Some(ReductionFunc::GeometricMean)
+        );
+        assert_eq!(determine_aggregate_by("latency", "").unwrap(), None);
+        assert!(determine_aggregate_by(
+            "latency",
+            "[measurement.latency]\naggregate_by = \"trimmed_mean(50)\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_read_max_regression() {
+        let configfile = r#"[measurement."timer"]
+max_regression_pct = 2.5
+
+[measurement."*"]
+max_regression_pct = 10
+"#;
+        assert_eq!(determine_max_regression("timer", configfile), Some(2.5));
+        assert_eq!(determine_max_regression("other", configfile), Some(10.0));
+        assert_eq!(determine_max_regression("timer", ""), None);
+    }
+
+    #[test]
+    fn test_write_and_read_baseline() {
+        let baseline = Baseline {
+            reference: "v1.2.0".to_owned(),
+            commit: "0123abcd".to_owned(),
+            aggregate_by: ReductionFunc::Median,
+            selectors: vec!["os=linux".parse().unwrap(), "arch!=arm64".parse().unwrap()],
+            values: vec![("bench::sort".to_owned(), 1.5), ("timer".to_owned(), 2.0)],
+        };
+        let mut conf = "[measurement.\"timer\"]\nepoch = \"00000001\"\n".to_owned();
+        set_baseline_in_conf(&baseline, &mut conf).unwrap();
+        assert!(conf.contains("epoch = \"00000001\""));
+        assert!(conf.contains("[baseline.values]\n\"bench::sort\" = 1.5\ntimer = 2.0\n"));
+        assert_eq!(determine_baseline(&conf).unwrap(), Some(baseline));
+        assert_eq!(determine_baseline("").unwrap(), None);
+        assert!(determine_baseline("[baseline]\nref = \"v1\"\n").is_err());
+    }
+
+    #[test]
+    fn test_read_auto_scale_units() {
+        assert!(!determine_auto_scale_units(
+            "[report]\nauto_scale_units = false\n"
+        ));
+        assert!(determine_auto_scale_units(
+            "[report]\nauto_scale_units = true\n"
+        ));
+        assert!(determine_auto_scale_units(""));
+    }
+
+    #[test]
+    fn test_read_commit_url_template() {
+        let configfile = r#"[report]
+commit_url_template = "https://example.com/commit/{sha}"
+"#;
+        assert_eq!(
+            determine_commit_url_template(configfile),
+            Some("https://example.com/commit/{sha}".to_string())
+        );
+        assert_eq!(determine_commit_url_template(""), None);
+    }
+
+    #[test]
+    fn test_read_report_sha_length() {
+        assert_eq!(
+            determine_report_sha_length("[report]\nsha_length = 12\n"),
+            Some(12)
+        );
+        assert_eq!(
+            determine_report_sha_length("[report]\nsha_length = 0\n"),
+            None
+        );
+        assert_eq!(determine_report_sha_length(""), None);
+    }
+
+    #[test]
+    fn test_read_digest_commit_url() {
+        let configfile = r#"[digest]
+commit_url = "https://example.com/commit/{commit}"
+"#;
+        assert_eq!(
+            determine_digest_commit_url(configfile),
+            Some("https://example.com/commit/{commit}".to_string())
+        );
+        assert_eq!(determine_digest_commit_url(""), None);
+    }
+
+    #[test]
+    fn test_read_push_remotes() {
+        let configfile = r#"[remote]
+push = ["origin", "mirror"]
+"#;
+        assert_eq!(determine_push_remotes(configfile), vec!["origin", "mirror"]);
+        assert!(determine_push_remotes("").is_empty());
+    }
+
+    #[test]
+    fn test_read_audit_max_failures() {
+        assert_eq!(
+            determine_audit_max_failures("[audit]\nmax_failures = 3\n"),
+            Some(3)
+        );
+        assert_eq!(
+            determine_audit_max_failures("[audit]\nmax_failures = -3\n"),
+            None
+        );
+        assert_eq!(determine_audit_max_failures(""), None);
+    }
+
+    #[test]
+    fn test_read_audit_warn_sigma() {
+        assert_eq!(
+            determine_audit_warn_sigma("[audit]\nwarn_sigma = 2.5\n"),
+            Some(2.5)
+        );
+        assert_eq!(
+            determine_audit_warn_sigma("[audit]\nwarn_sigma = 2\n"),
+            Some(2.0)
+        );
+        assert_eq!(
+            determine_audit_warn_sigma("[audit]\nwarn_sigma = -1.0\n"),
+            None
+        );
+        assert_eq!(determine_audit_warn_sigma(""), None);
+    }
+
+    #[test]
+    fn test_read_report_sections() {
+        let configfile = r#"[report.section.parsing]
+title = "Parser benchmarks"
+filter = "bench::parse.*"
+separate_by = ["os", "arch"]
+aggregate_by = "p95"
+depth = 100
+show_changes = true
+
+[report.section.build]
+ref = "release/2.x"
+
+[report.section.release]
+separate_by = ["os"]
+chart = "bar"
+range = "v1.0..v2.0"
+"#;
+        assert_eq!(
+            determine_report_sections(configfile).unwrap(),
+            vec![
+                SectionConfig {
+                    id: "parsing".to_owned(),
+                    title: Some("Parser benchmarks".to_owned()),
+                    filter: Some("bench::parse.*".to_owned()),
+                    start: None,
+                    range: None,
+                    separate_by: vec!["os".to_owned(), "arch".to_owned()],
+                    aggregate_by: Some(crate::data::ReductionFunc::P95),
+                    depth: Some(100),
+                    show_changes: true,
+                    chart: crate::reporting::ChartType::TimeSeries,
+                },
+                SectionConfig {
+                    id: "build".to_owned(),
+                    start: Some("release/2.x".to_owned()),
+                    ..Default::default()
+                },
+                SectionConfig {
+                    id: "release".to_owned(),
+                    separate_by: vec!["os".to_owned()],
+                    chart: crate::reporting::ChartType::BarLatest,
+                    range: Some("v1.0..v2.0".to_owned()),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert!(determine_report_sections("").unwrap().is_empty());
+        assert!(determine_report_sections("[report.section.a]\ndepth = 0\n").is_err());
+        assert!(determine_report_sections("[report.section.a]\naggregate_by = \"sum\"\n").is_err());
+        assert!(determine_report_sections("[report.section.a]\nseparate_by = \"os\"\n").is_err());
+        assert!(determine_report_sections("[report.section.a]\nchart = \"pie\"\n").is_err());
+        assert!(determine_report_sections(
+            "[report.section.a]\nref = \"main\"\nrange = \"a..b\"\n"
+        )
+        .is_err());
+        assert!(determine_report_sections("[report.section.a]\nref = 1\n").is_err());
+    }
+
+    #[test]
+    fn test_read_audit_paths() {
+        let configfile = r#"[audit.paths]
+"bench::parser::.*" = ["src/parser/**", "Cargo.lock"]
+"bench::lexer::.*" = ["src/lexer/**"]
+"#;
+        assert_eq!(
+            determine_audit_paths(configfile),
+            vec![
+                (
+                    "bench::parser::.*".to_string(),
+                    vec!["src/parser/**".to_string(), "Cargo.lock".to_string()]
+                ),
+                (
+                    "bench::lexer::.*".to_string(),
+                    vec!["src/lexer/**".to_string()]
+                ),
+            ]
+        );
+        assert!(determine_audit_paths("").is_empty());
+    }
+
+    #[test]
+    fn test_read_retention_policy() {
+        let configfile = r#"[retention]
+max_age = "90d"
+max_points_per_measurement = 500
+keep_tagged_commits = true
+"#;
+        assert_eq!(
+            determine_retention_policy(configfile).unwrap(),
+            RetentionPolicy {
+                max_age: Some(Duration::from_secs(90 * 86400)),
+                max_points_per_measurement: Some(500),
+                keep_tagged_commits: true,
+            }
+        );
+        assert_eq!(
+            determine_retention_policy("").unwrap(),
+            RetentionPolicy::default()
+        );
+        assert!(determine_retention_policy("[retention]\nmax_age = \"90\"\n").is_err());
+        assert!(determine_retention_policy("[retention]\nmax_age = 90\n").is_err());
+        assert!(
+            determine_retention_policy("[retention]\nmax_points_per_measurement = -1\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_read_sparkline_options() {
+        let configfile = r#"[sparkline]
+width = 30
+scale = "log"
+clamp_percentiles = [5, 95.5]
+"#;
+        assert_eq!(
+            determine_sparkline_options(configfile).unwrap(),
+            sparkline::Options {
+                width: Some(30),
+                scale: sparkline::Scale::Log,
+                clamp: Some((5.0, 95.5)),
+            }
+        );
+        assert_eq!(
+            determine_sparkline_options("").unwrap(),
+            sparkline::Options::default()
+        );
+        assert!(determine_sparkline_options("[sparkline]\nwidth = 1\n").is_err());
+        assert!(determine_sparkline_options("[sparkline]\nscale = \"sqrt\"\n").is_err());
+        assert!(determine_sparkline_options("[sparkline]\nclamp_percentiles = [5]\n").is_err());
+        assert!(determine_sparkline_options("[sparkline]\nclamp_percentiles = [95, 5]\n").is_err());
+    }
+
     #[test]
     fn test_bump_epochs() {
         let configfile = r#"[measurement."something"]
@@ -107,7 +1548,7 @@ epoch = "34567898"
 "#;
 
         let mut actual = String::from(configfile);
-        bump_epoch_in_conf("something", &mut actual).expect("Failed to bump epoch");
+        bump_epoch_in_conf("something", None, &mut actual).expect("Failed to bump epoch");
 
         let expected = format!(
             r#"[measurement."something"]
@@ -120,14 +1561,155 @@ epoch = "{}"
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_bump_epoch_with_reason() {
+        let mut conf = r#"[measurement."*".epoch_reasons]
+00000001 = "new runner"
+"#
+        .to_owned();
+        bump_epoch_in_conf("something", Some("switched allocator"), &mut conf)
+            .expect("Failed to bump epoch");
+        let epoch = determine_epoch("something", &conf).expect("Epoch was bumped");
+        let reasons = determine_epoch_reasons("something", &conf);
+        assert_eq!(reasons.len(), 2);
+        assert_eq!(reasons[&epoch], "switched allocator");
+        assert_eq!(reasons[&1], "new runner");
+        assert_eq!(
+            determine_epoch_reasons("other", &conf),
+            HashMap::from([(1, "new runner".to_owned())])
+        );
+    }
+
     #[test]
     fn test_bump_new_epoch_and_read_it() {
         let mut conf = String::new();
-        bump_epoch_in_conf("mymeasurement", &mut conf).expect("Failed to bump epoch");
+        bump_epoch_in_conf("mymeasurement", None, &mut conf).expect("Failed to bump epoch");
         let epoch = determine_epoch("mymeasurement", &conf);
         assert!(epoch.is_some());
     }
 
+    #[test]
+    fn test_read_sigma_per_selector() {
+        let configfile = r#"[measurement."build".selector."os=mac"]
+sigma = 6.0
+
+[measurement."build".selector."os=linux"]
+sigma = 3
+
+[measurement."*".selector."os=mac"]
+sigma = 5.0
+
+[measurement."*".selector."arch=arm"]
+sigma = 8.0
+"#;
+        let sigma = |measurement, selectors: &[&str]| {
+            let selectors = selectors.iter().map(|s| s.parse().unwrap()).collect_vec();
+            determine_sigma(measurement, &selectors, configfile).unwrap()
+        };
+        assert_eq!(sigma("build", &["os=mac"]), Some(6.0));
+        assert_eq!(sigma("build", &["os=linux", "arch=arm"]), Some(3.0));
+        assert_eq!(sigma("build", &["arch=arm"]), Some(8.0));
+        assert_eq!(sigma("test", &["os=mac"]), Some(5.0));
+        assert_eq!(sigma("build", &["os!=mac"]), None);
+        assert_eq!(sigma("build", &[]), None);
+
+        let invalid = "[measurement.\"build\".selector.\"os=mac\"]\nsigma = -1\n";
+        let mac = ["os=mac".parse().unwrap()];
+        assert!(determine_sigma("build", &mac, invalid).is_err());
+        let invalid = "[measurement.\"build\".selector.\"os\"]\nsigma = 1\n";
+        assert!(determine_sigma("build", &mac, invalid).is_err());
+    }
+
+    #[test]
+    fn test_read_sigma_per_measurement() {
+        let configfile = r#"[measurement."build"]
+sigma = 5.0
+
+[measurement."build".selector."os=mac"]
+sigma = 6.0
+
+[measurement."*"]
+sigma = 2
+
+[measurement."*".selector."arch=arm"]
+sigma = 8.0
+"#;
+        let sigma = |measurement, selectors: &[&str]| {
+            let selectors = selectors.iter().map(|s| s.parse().unwrap()).collect_vec();
+            determine_sigma(measurement, &selectors, configfile).unwrap()
+        };
+        assert_eq!(sigma("build", &[]), Some(5.0));
+        assert_eq!(sigma("build", &["os=linux"]), Some(5.0));
+        assert_eq!(sigma("build", &["os=mac"]), Some(6.0));
+        assert_eq!(sigma("build", &["arch=arm"]), Some(8.0));
+        assert_eq!(sigma("test", &[]), Some(2.0));
+        assert_eq!(sigma("test", &["arch=arm"]), Some(8.0));
+
+        let invalid = "[measurement.\"build\"]\nsigma = 0\n";
+        assert!(determine_sigma("build", &[], invalid).is_err());
+        let invalid = "[measurement.\"build\"]\nsigma = \"high\"\n";
+        assert!(determine_sigma("build", &[], invalid).is_err());
+    }
+
+    #[test]
+    fn test_get_setting() {
+        let conf = r#"[measurement."build.time"]
+unit = "ms"
+max_trend_pct_per_commit = 0.5
+command = ["make", "bench"]
+"#;
+        let get = |path| get_setting_in_conf(path, conf).unwrap();
+        assert_eq!(
+            get(r#"measurement."build.time".unit"#).as_deref(),
+            Some("ms")
+        );
+        assert_eq!(
+            get(r#"measurement."build.time".max_trend_pct_per_commit"#).as_deref(),
+            Some("0.5")
+        );
+        assert_eq!(
+            get(r#"measurement."build.time".command"#).as_deref(),
+            Some(r#"["make", "bench"]"#)
+        );
+        assert_eq!(get("measurement.build.unit"), None);
+        assert!(get_setting_in_conf(r#"measurement."build.time""#, conf).is_err());
+        assert!(get_setting_in_conf("storage", conf).is_err());
+    }
+
+    #[test]
+    fn test_set_setting_keeps_formatting() {
+        let mut conf = r#"# Settings of the build
+[measurement."build"]
+unit = "ms" # milliseconds
+"#
+        .to_owned();
+        set_setting_in_conf(r#"measurement."build".unit"#, "s", &mut conf).unwrap();
+        set_setting_in_conf(
+            r#"measurement."build".max_trend_pct_per_commit"#,
+            "5.0",
+            &mut conf,
+        )
+        .unwrap();
+        set_setting_in_conf(r#"measurement."*".epoch"#, "00000002", &mut conf).unwrap();
+        assert_eq!(
+            conf,
+            r#"# Settings of the build
+[measurement."build"]
+unit = "s" # milliseconds
+max_trend_pct_per_commit = 5.0
+
+[measurement."*"]
+epoch = "00000002"
+"#
+        );
+        assert_eq!(determine_unit("build", &conf).as_deref(), Some("s"));
+        assert_eq!(determine_max_trend("build", &conf), Some(5.0));
+        assert_eq!(determine_epoch("other", &conf), Some(2));
+
+        assert!(set_setting_in_conf(r#"measurement."build""#, "1", &mut conf).is_err());
+        assert!(set_setting_in_conf("measurement", "1", &mut conf).is_err());
+    }
+
     #[test]
     fn test_parsing() {
         let toml_str = r#"
@@ -0,0 +1,651 @@
+//! Lint of .gitperfconfig for `git perf config check`. The config module ignores settings it does
+//! not know and most values it cannot parse, so typos otherwise go unnoticed.
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use toml_edit::{Document, Item, TableLike, Value};
+
+use crate::{
+    config,
+    data::{ReductionFunc, Selector},
+    doctor::{print_checks, Check},
+    serialization::{Format, LegacyFormat},
+};
+
+/// Expected value of a setting
+#[derive(Clone, Copy)]
+enum Kind {
+    String,
+    Bool,
+    /// Non-negative integer
+    Count,
+    /// Non-negative integer or float
+    Number,
+    Strings,
+    /// Hexadecimal string
+    Epoch,
+    /// String accepted by `parse`, one of `values` unless it is parameterized
+    Parsed {
+        parse: fn(&str) -> Result<()>,
+        values: &'static [&'static str],
+    },
+    /// Checked with the whole section by the config module
+    Section,
+    /// Table of strings keyed by hexadecimal epochs
+    EpochReasons,
+}
+
+type Schema = &'static [(&'static str, Kind)];
+
+const AGGREGATE_BY: Kind = Kind::Parsed {
+    parse: |s| s.parse::<ReductionFunc>().map(|_| ()),
+    values: &[
+        "min",
+        "max",
+        "median",
+        "mean",
+        "p90",
+        "p95",
+        "p99",
+        "geometric_mean",
+    ],
+};
+
+const STORAGE: Schema = &[
+    (
+        "backend",
+        Kind::Parsed {
+            parse: |s| match s {
+                "git-notes" => Ok(()),
+                _ => bail!("Unknown storage backend '{s}'"),
+            },
+            values: &["git-notes"],
+        },
+    ),
+    (
+        "shards",
+        Kind::Parsed {
+            parse: |s| match s {
+                "year" => Ok(()),
+                _ => bail!("Unknown shard scheme '{s}'"),
+            },
+            values: &["year"],
+        },
+    ),
+    (
+        "format",
+        Kind::Parsed {
+            parse: |s| s.parse::<Format>().map(|_| ()),
+            values: &["v3", "v4"],
+        },
+    ),
+    ("legacy_refs", Kind::Strings),
+    ("max_note_size", Kind::Count),
+    ("local_lock", Kind::Bool),
+    ("cache", Kind::Bool),
+];
+
+const MEASUREMENT: Schema = &[
+    ("epoch", Kind::Epoch),
+    ("unit", Kind::String),
+    ("max_trend_pct_per_commit", Kind::Number),
+    ("max_regression_pct", Kind::Number),
+    ("aggregate_by", AGGREGATE_BY),
+    ("sigma", Kind::Number),
+    ("command", Kind::Strings),
+    (
+        "extract_regex",
+        Kind::Parsed {
+            parse: |s| Regex::new(s).map(|_| ()).map_err(Into::into),
+            values: &[],
+        },
+    ),
+    ("epoch_reasons", Kind::EpochReasons),
+    ("selector", Kind::Section),
+];
+
+/// Overrides of the settings of a measurement for audits with a selector
+const SELECTOR_OVERRIDE: Schema = &[("sigma", Kind::Number)];
+
+const REPORT: Schema = &[
+    ("compact_hover", Kind::Bool),
+    ("commit_url_template", Kind::String),
+    ("sha_length", Kind::Count),
+    ("auto_scale_units", Kind::Bool),
+    ("section", Kind::Section),
+];
+
+const REPORT_SECTION: Schema = &[
+    ("title", Kind::Section),
+    ("filter", Kind::Section),
+    ("ref", Kind::Section),
+    ("range", Kind::Section),
+    ("separate_by", Kind::Section),
+    ("aggregate_by", Kind::Section),
+    ("depth", Kind::Section),
+    ("show_changes", Kind::Section),
+    ("chart", Kind::Section),
+];
+
+const AUDIT: Schema = &[
+    ("max_failures", Kind::Count),
+    ("warn_sigma", Kind::Number),
+    ("paths", Kind::Section),
+];
+
+const SPARKLINE: Schema = &[
+    ("width", Kind::Section),
+    ("scale", Kind::Section),
+    ("clamp_percentiles", Kind::Section),
+];
+
+const RETENTION: Schema = &[
+    ("max_age", Kind::Section),
+    ("max_points_per_measurement", Kind::Section),
+    ("keep_tagged_commits", Kind::Section),
+];
+
+const BASELINE: Schema = &[
+    ("ref", Kind::Section),
+    ("commit", Kind::Section),
+    ("aggregate_by", Kind::Section),
+    ("selectors", Kind::Section),
+    ("values", Kind::Section),
+];
+
+/// Settings of the top-level sections. Measurements and report sections have their own schema
+/// per entry.
+const SECTIONS: &[(&str, Schema)] = &[
+    ("storage", STORAGE),
+    ("measurement", &[]),
+    ("report", REPORT),
+    ("audit", AUDIT),
+    ("sparkline", SPARKLINE),
+    ("retention", RETENTION),
+    ("remote", &[("push", Kind::Strings)]),
+    ("measure", &[("capture_env", Kind::Bool)]),
+    ("gc", &[("temp_ref_max_age", Kind::Count)]),
+    ("digest", &[("commit_url", Kind::String)]),
+    ("baseline", BASELINE),
+];
+
+/// Number of single-character insertions, deletions, and substitutions turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to `name`, if it is close enough to likely be meant.
+fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(distance, c)| *distance <= 2.max(c.len() / 3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+fn unknown(what: String, name: &str, known: &[&str]) -> Check {
+    let fix = match closest(name, known.iter().copied()) {
+        Some(suggestion) => format!("Did you mean '{suggestion}'?"),
+        None if known.is_empty() => "Remove it".to_owned(),
+        None => format!("Remove it, known are: {}", known.join(", ")),
+    };
+    Check::fail(format!("Unknown {what}"), fix)
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|i| i as f64))
+}
+
+/// Problem with the `value` of the setting at `path` of the `kind`, if any.
+fn check_value(path: &str, value: &Item, kind: Kind) -> Option<Check> {
+    let fix = "Correct the setting";
+    if let Kind::EpochReasons = kind {
+        let valid = value.as_table_like().is_some_and(|reasons| {
+            reasons.iter().all(|(epoch, reason)| {
+                u32::from_str_radix(epoch, 16).is_ok() && reason.as_str().is_some()
+            })
+        });
+        return (!valid).then(|| {
+            Check::fail(
+                format!("{path} must map hexadecimal epochs to strings"),
+                fix,
+            )
+        });
+    }
+    let Some(value) = value.as_value() else {
+        return match kind {
+            Kind::Section => None,
+            _ => Some(Check::fail(format!("{path} must not be a table"), fix)),
+        };
+    };
+    let expected = match kind {
+        Kind::String if value.as_str().is_none() => "a string",
+        Kind::Bool if value.as_bool().is_none() => "a boolean",
+        Kind::Count if value.as_integer().is_none_or(|i| i < 0) => "a non-negative integer",
+        Kind::Number if as_number(value).is_none_or(|n| n < 0.0) => "a non-negative number",
+        Kind::Strings
+            if value
+                .as_array()
+                .is_none_or(|a| a.iter().any(|v| !v.is_str())) =>
+        {
+            "an array of strings"
+        }
+        Kind::Epoch
+            if value
+                .as_str()
+                .is_none_or(|s| u32::from_str_radix(s, 16).is_err()) =>
+        {
+            "a hexadecimal string such as \"0a1b2c3d\""
+        }
+        Kind::Parsed { parse, values } => {
+            let Some(s) = value.as_str() else {
+                return Some(Check::fail(format!("{path} must be a string"), fix));
+            };
+            return parse(s).err().map(|e| {
+                let fix = match closest(s, values.iter().copied()) {
+                    Some(suggestion) => format!("Did you mean '{suggestion}'?"),
+                    None => fix.to_owned(),
+                };
+                Check::fail(format!("Invalid {path}: {e:#}"), fix)
+            });
+        }
+        _ => return None,
+    };
+    Some(Check::fail(format!("{path} must be {expected}"), fix))
+}
+
+/// Problems of the settings in `table` at `path` against the `schema`.
+fn check_table(path: &str, table: &dyn TableLike, schema: Schema) -> Vec<Check> {
+    let known: Vec<_> = schema.iter().map(|(key, _)| *key).collect();
+    table
+        .iter()
+        .filter_map(|(key, value)| {
+            let key_path = format!("{path}.{key}");
+            match schema.iter().find(|(known, _)| *known == key) {
+                Some((_, kind)) => check_value(&key_path, value, *kind),
+                None => Some(unknown(format!("setting {key_path}"), key, &known)),
+            }
+        })
+        .collect()
+}
+
+/// Problems of the `[measurement."<name>"]` settings and their overrides of the defaults in
+/// `[measurement."*"]`.
+fn check_measurements(measurements: &dyn TableLike, has_baseline: bool) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let defaults = measurements.get("*").and_then(Item::as_table_like);
+    for (name, settings) in measurements.iter() {
+        let path = format!("measurement.\"{name}\"");
+        let Some(settings) = settings.as_table_like() else {
+            checks.push(Check::fail(
+                format!("{path} must be a table"),
+                format!("Move the settings of the measurement into [{path}]"),
+            ));
+            continue;
+        };
+        checks.extend(check_table(&path, settings, MEASUREMENT));
+        checks.extend(check_selector_overrides(&path, settings));
+        if name == "*" {
+            if settings.contains_key("command") {
+                checks.push(Check::warn(
+                    format!("{path}.command is never used"),
+                    "Commands are only looked up per measurement, move it to the measurements \
+                     it takes",
+                ));
+            }
+        } else if let Some(defaults) = defaults {
+            for (key, value) in settings.iter() {
+                let default = defaults.get(key).and_then(Item::as_value);
+                if default.is_some_and(|d| same_value(d, value)) {
+                    checks.push(Check::warn(
+                        format!("{path}.{key} repeats the default of measurement.\"*\""),
+                        "Remove the override",
+                    ));
+                }
+            }
+        }
+        if settings.contains_key("max_regression_pct") && !has_baseline {
+            checks.push(Check::warn(
+                format!("{path}.max_regression_pct has no effect without a pinned baseline"),
+                "Pin one with 'git perf baseline set <ref>' or remove the setting",
+            ));
+        }
+    }
+    checks
+}
+
+/// Problems of the `[measurement."<name>".selector."<key=value>"]` overrides of the
+/// measurement's `settings` at `path`.
+fn check_selector_overrides(path: &str, settings: &dyn TableLike) -> Vec<Check> {
+    let Some(overrides) = settings.get("selector") else {
+        return Vec::new();
+    };
+    let Some(overrides) = overrides.as_table_like() else {
+        return vec![Check::fail(
+            format!("{path}.selector must be a table"),
+            format!("Configure the overrides per selector in [{path}.selector.\"key=value\"]"),
+        )];
+    };
+    let mut checks = Vec::new();
+    for (selector, settings) in overrides.iter() {
+        let selector_path = format!("{path}.selector.\"{selector}\"");
+        if let Err(e) = selector.parse::<Selector>() {
+            checks.push(Check::fail(
+                format!("Invalid {selector_path}: {e:#}"),
+                "Correct the selector",
+            ));
+        }
+        match settings.as_table_like() {
+            Some(settings) => {
+                checks.extend(check_table(&selector_path, settings, SELECTOR_OVERRIDE))
+            }
+            None => checks.push(Check::fail(
+                format!("{selector_path} must be a table"),
+                "Correct the setting",
+            )),
+        }
+    }
+    checks
+}
+
+fn same_value(default: &Value, value: &Item) -> bool {
+    let without_decor = |v: &Value| {
+        let mut v = v.clone();
+        v.decor_mut().clear();
+        v.to_string()
+    };
+    value
+        .as_value()
+        .is_some_and(|v| without_decor(v) == without_decor(default))
+}
+
+/// Problems of the settings in the `[report.section.<id>]` blocks.
+fn check_report_sections(report: &dyn TableLike) -> Vec<Check> {
+    let Some(sections) = report.get("section").and_then(Item::as_table_like) else {
+        return Vec::new();
+    };
+    sections
+        .iter()
+        .filter_map(|(id, section)| Some((id, section.as_table_like()?)))
+        .flat_map(|(id, section)| {
+            check_table(&format!("report.section.{id}"), section, REPORT_SECTION)
+        })
+        .collect()
+}
+
+/// Problems of the whole sections, reported as the config module fails to read them.
+fn check_sections(conf_str: &str) -> Vec<Check> {
+    let mut problems = Vec::new();
+    let mut check = |result: Result<()>| {
+        if let Err(e) = result {
+            problems.push(Check::fail(format!("{e:#}"), "Correct the setting"));
+        }
+    };
+    check(config::determine_report_sections(conf_str).map(|_| ()));
+    check(config::determine_retention_policy(conf_str).map(|_| ()));
+    check(config::determine_baseline(conf_str).map(|_| ()));
+    check(config::determine_sparkline_options(conf_str).map(|_| ()));
+    for notes_ref in config::determine_legacy_refs(conf_str) {
+        check(LegacyFormat::of_ref(&notes_ref).map(|_| ()));
+    }
+    problems
+}
+
+/// Problems of the configuration `conf_str`: unknown sections and settings, invalid values, and
+/// per-measurement overrides that conflict with the defaults or have no effect.
+pub fn lint(conf_str: &str) -> Vec<Check> {
+    let config = match conf_str.parse::<Document>() {
+        Ok(config) => config,
+        Err(e) => {
+            return vec![Check::fail(
+                format!(".gitperfconfig is not valid TOML: {e}"),
+                "Fix the syntax of .gitperfconfig",
+            )]
+        }
+    };
+    let known: Vec<_> = SECTIONS.iter().map(|(section, _)| *section).collect();
+    let mut checks = Vec::new();
+    for (name, section) in config.iter() {
+        let Some((_, schema)) = SECTIONS.iter().find(|(known, _)| *known == name) else {
+            checks.push(unknown(format!("section [{name}]"), name, &known));
+            continue;
+        };
+        let Some(table) = section.as_table_like() else {
+            checks.push(Check::fail(
+                format!("{name} must be a table"),
+                format!("Move the setting into a [{name}] section"),
+            ));
+            continue;
+        };
+        match name {
+            "measurement" => {
+                checks.extend(check_measurements(table, config.contains_key("baseline")))
+            }
+            "report" => {
+                checks.extend(check_table(name, table, schema));
+                checks.extend(check_report_sections(table));
+            }
+            _ => checks.extend(check_table(name, table, schema)),
+        }
+    }
+    checks.extend(check_sections(conf_str));
+    checks
+}
+
+/// Print the problems of .gitperfconfig with their fixes. Fails if any setting is unknown or
+/// invalid.
+pub fn check() -> Result<()> {
+    let Some(conf) = config::read_config() else {
+        return print_checks(&[Check::ok("No .gitperfconfig, using the defaults")]);
+    };
+    let checks = lint(&conf);
+    if checks.is_empty() {
+        return print_checks(&[Check::ok(".gitperfconfig is valid")]);
+    }
+    print_checks(&checks)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::doctor::Status;
+
+    use super::*;
+
+    fn messages(conf: &str) -> Vec<(Status, String, String)> {
+        lint(conf)
+            .into_iter()
+            .map(|c| (c.status, c.message, c.fix.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn distance_between_names() {
+        assert_eq!(edit_distance("format", "format"), 0);
+        assert_eq!(edit_distance("formt", "format"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(closest("shard", ["shards", "format"]), Some("shards"));
+        assert_eq!(closest("xyz", ["shards", "format"]), None);
+    }
+
+    #[test]
+    fn valid_config_has_no_problems() {
+        let conf = r#"
+        [storage]
+        format = "v4"
+        cache = true
+        [measurement."*"]
+        epoch = "00000001"
+        aggregate_by = "median"
+        [measurement."build"]
+        unit = "ms"
+        command = ["make"]
+        [measurement."build".epoch_reasons]
+        0a1b2c3d = "switched allocator"
+        [measurement."build".selector."os=mac"]
+        sigma = 6.0
+        [report.section.build]
+        filter = "build"
+        depth = 10
+        [audit.paths]
+        "bench::.*" = ["src/**"]
+        "#;
+        assert!(lint(conf).is_empty(), "{:?}", messages(conf));
+    }
+
+    #[test]
+    fn unknown_sections_and_settings_with_suggestions() {
+        let conf = r#"
+        [storage]
+        formt = "v4"
+        [sparklines]
+        width = 10
+        [measurement."build"]
+        uint = "ms"
+        [report.section.build]
+        filtr = "build"
+        "#;
+        assert_eq!(
+            messages(conf),
+            [
+                (
+                    Status::Fail,
+                    "Unknown setting storage.formt".to_owned(),
+                    "Did you mean 'format'?".to_owned()
+                ),
+                (
+                    Status::Fail,
+                    "Unknown section [sparklines]".to_owned(),
+                    "Did you mean 'sparkline'?".to_owned()
+                ),
+                (
+                    Status::Fail,
+                    "Unknown setting measurement.\"build\".uint".to_owned(),
+                    "Did you mean 'unit'?".to_owned()
+                ),
+                (
+                    Status::Fail,
+                    "Unknown setting report.section.build.filtr".to_owned(),
+                    "Did you mean 'filter'?".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        let conf = r#"
+        [storage]
+        shards = "month"
+        local_lock = "yes"
+        [measurement."build"]
+        aggregate_by = "medain"
+        epoch = "xyz"
+        max_trend_pct_per_commit = -1
+        extract_regex = "took (\\d+"
+        "#;
+        let checks = messages(conf);
+        assert_eq!(checks.len(), 6, "{checks:?}");
+        assert!(checks.iter().all(|(status, _, _)| *status == Status::Fail));
+        assert_eq!(
+            checks[0].1,
+            "Invalid storage.shards: Unknown shard scheme 'month'"
+        );
+        assert_eq!(checks[1].1, "storage.local_lock must be a boolean");
+        assert!(checks[2]
+            .1
+            .starts_with("Invalid measurement.\"build\".aggregate_by"));
+        assert_eq!(checks[2].2, "Did you mean 'median'?");
+        assert!(checks[3].1.contains("epoch must be a hexadecimal string"));
+        assert_eq!(
+            checks[4].1,
+            "measurement.\"build\".max_trend_pct_per_commit must be a non-negative number"
+        );
+        assert!(checks[5]
+            .1
+            .starts_with("Invalid measurement.\"build\".extract_regex: regex parse error"));
+    }
+
+    #[test]
+    fn selector_overrides() {
+        let conf = r#"
+        [measurement."build".selector."os"]
+        sigma = 6.0
+        [measurement."build".selector."os=mac"]
+        sigm = 6.0
+        "#;
+        let checks = messages(conf);
+        assert_eq!(checks.len(), 2, "{checks:?}");
+        assert!(checks[0]
+            .1
+            .starts_with("Invalid measurement.\"build\".selector.\"os\": invalid selector"));
+        assert_eq!(
+            checks[1].1,
+            "Unknown setting measurement.\"build\".selector.\"os=mac\".sigm"
+        );
+        assert_eq!(checks[1].2, "Did you mean 'sigma'?");
+    }
+
+    #[test]
+    fn section_problems_of_config_module() {
+        let checks = messages("[sparkline]\nscale = \"cubic\"\n");
+        assert_eq!(checks.len(), 1, "{checks:?}");
+        assert_eq!(checks[0].0, Status::Fail);
+        assert_eq!(
+            messages("[storage\n")[0].1.lines().next(),
+            Some(".gitperfconfig is not valid TOML: TOML parse error at line 1, column 9")
+        );
+    }
+
+    #[test]
+    fn conflicting_measurement_overrides() {
+        let conf = r#"
+        [measurement."*"]
+        unit = "ms"
+        command = ["make"]
+        [measurement."build"]
+        unit = "ms"
+        max_regression_pct = 5
+        [measurement."test"]
+        unit = "s"
+        "#;
+        assert_eq!(
+            messages(conf),
+            [
+                (
+                    Status::Warn,
+                    "measurement.\"*\".command is never used".to_owned(),
+                    "Commands are only looked up per measurement, move it to the measurements \
+                     it takes"
+                        .to_owned()
+                ),
+                (
+                    Status::Warn,
+                    "measurement.\"build\".unit repeats the default of measurement.\"*\""
+                        .to_owned(),
+                    "Remove the override".to_owned()
+                ),
+                (
+                    Status::Warn,
+                    "measurement.\"build\".max_regression_pct has no effect without a pinned \
+                     baseline"
+                        .to_owned(),
+                    "Pin one with 'git perf baseline set <ref>' or remove the setting".to_owned()
+                ),
+            ]
+        );
+    }
+}
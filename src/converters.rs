@@ -0,0 +1,140 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+
+use crate::{
+    config,
+    data::MeasurementData,
+    parsers::{BenchmarkMeasurement, ParsedMeasurement},
+};
+
+/// Factor to convert a value in `unit` to the stored unit: nanoseconds, the unit of
+/// `git perf measure`, for durations and percent for relative changes.
+fn conversion_factor(unit: &str) -> Option<f64> {
+    match unit {
+        "%" => Some(1.0),
+        "ns" => Some(1.0),
+        "us" | "µs" | "μs" => Some(1e3),
+        "ms" => Some(1e6),
+        "s" => Some(1e9),
+        _ => None,
+    }
+}
+
+fn convert_benchmark(
+    benchmark: BenchmarkMeasurement,
+    timestamp: f64,
+    key_values: &[(String, String)],
+) -> Result<MeasurementData> {
+    let factor = conversion_factor(&benchmark.unit).ok_or_else(|| {
+        anyhow!(
+            "Unsupported unit '{}' for benchmark '{}'",
+            benchmark.unit,
+            benchmark.id
+        )
+    })?;
+
+    // Measurement names cannot contain whitespace, e.g., for benchmarked shell commands.
+    let id = benchmark.id.split_whitespace().join("_");
+    let name = format!("bench::{id}::{}", benchmark.statistic);
+    let mut all_key_values = benchmark.metadata;
+    all_key_values.extend(key_values.iter().cloned());
+
+    Ok(MeasurementData {
+        epoch: config::determine_epoch_from_config(&name).unwrap_or(0),
+        name,
+        timestamp,
+        val: benchmark.value * factor,
+        key_values: all_key_values,
+    })
+}
+
+/// Convert parsed measurements into storable measurements.
+/// Benchmarks are named `bench::<id>::<statistic>` and their durations converted to nanoseconds.
+/// Whitespace in the id is replaced with underscores.
+/// The supplied `key_values` are added to every measurement. Skipped entries are ignored.
+pub fn convert_to_measurements(
+    parsed: impl IntoIterator<Item = ParsedMeasurement>,
+    key_values: &[(String, String)],
+) -> Result<Vec<MeasurementData>> {
+    convert_each(parsed.into_iter().map(Ok), key_values).collect()
+}
+
+/// Convert parsed measurements like [`convert_to_measurements`], one at a time while iterating.
+/// Errors of `parsed` are passed on.
+pub fn convert_each<'a>(
+    parsed: impl Iterator<Item = Result<ParsedMeasurement>> + 'a,
+    key_values: &'a [(String, String)],
+) -> impl Iterator<Item = Result<MeasurementData>> + 'a {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time before unix epoch")
+        .as_secs_f64();
+
+    parsed.filter_map(move |p| match p {
+        Ok(ParsedMeasurement::Benchmark(b)) => Some(convert_benchmark(b, timestamp, key_values)),
+        Ok(ParsedMeasurement::Skipped(_)) => None,
+        Err(e) => Some(Err(e)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn benchmark(unit: &str, value: f64) -> ParsedMeasurement {
+        ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+            id: "sort".to_string(),
+            statistic: "mean".to_string(),
+            value,
+            unit: unit.to_string(),
+            metadata: [("aggregate".to_string(), "mean".to_string())].into(),
+        })
+    }
+
+    #[test]
+    fn convert_units() {
+        let key_values = [("os".to_string(), "linux".to_string())];
+        let converted =
+            convert_to_measurements(vec![benchmark("us", 1.5), benchmark("s", 2.0)], &key_values)
+                .unwrap();
+        assert_eq!(converted[0].name, "bench::sort::mean");
+        assert_eq!(converted[0].val, 1500.0);
+        assert_eq!(converted[1].val, 2e9);
+        assert_eq!(
+            converted[0].key_values,
+            HashMap::from([
+                ("aggregate".to_string(), "mean".to_string()),
+                ("os".to_string(), "linux".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn keep_relative_changes() {
+        let converted = convert_to_measurements(vec![benchmark("%", -2.5)], &[]).unwrap();
+        assert_eq!(converted[0].val, -2.5);
+        assert!(convert_to_measurements(vec![benchmark("cycles", 1.0)], &[]).is_err());
+    }
+
+    #[test]
+    fn whitespace_in_id() {
+        let parsed = ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+            id: "sleep  0.1".to_string(),
+            statistic: "mean".to_string(),
+            value: 0.1,
+            unit: "s".to_string(),
+            metadata: HashMap::new(),
+        });
+        let converted = convert_to_measurements(vec![parsed], &[]).unwrap();
+        assert_eq!(converted[0].name, "bench::sleep_0.1::mean");
+    }
+
+    #[test]
+    fn unsupported_unit() {
+        assert!(convert_to_measurements(vec![benchmark("furlong", 1.0)], &[]).is_err());
+    }
+}
@@ -1,19 +1,98 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 
-use clap::ValueEnum;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ReductionFunc {
     Min,
     Max,
     Median,
     Mean,
+    /// 90th percentile
+    P90,
+    /// 95th percentile
+    P95,
+    /// 99th percentile
+    P99,
+    /// Mean after dropping the given percentage (below 50) of the values at each end, written
+    /// as `trimmed_mean(10)`
+    TrimmedMean(u8),
+    /// Exponential of the mean logarithm. Only defined for positive values.
+    GeometricMean,
+}
+
+impl FromStr for ReductionFunc {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        if let Some(percent) = name
+            .strip_prefix("trimmed_mean(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            return match percent.trim().parse::<u8>() {
+                Ok(percent) if percent < 50 => Ok(ReductionFunc::TrimmedMean(percent)),
+                _ => bail!(
+                    "Invalid trim percentage in '{name}', expected a whole number from 0 to 49"
+                ),
+            };
+        }
+        Ok(match name {
+            "min" => ReductionFunc::Min,
+            "max" => ReductionFunc::Max,
+            "median" => ReductionFunc::Median,
+            "mean" => ReductionFunc::Mean,
+            "p90" => ReductionFunc::P90,
+            "p95" => ReductionFunc::P95,
+            "p99" => ReductionFunc::P99,
+            "geometric_mean" => ReductionFunc::GeometricMean,
+            _ => bail!(
+                "Invalid aggregation '{name}', expected min, max, median, mean, p90, p95, p99, \
+                 trimmed_mean(<percent>), or geometric_mean"
+            ),
+        })
+    }
+}
+
+impl Display for ReductionFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReductionFunc::Min => f.write_str("min"),
+            ReductionFunc::Max => f.write_str("max"),
+            ReductionFunc::Median => f.write_str("median"),
+            ReductionFunc::Mean => f.write_str("mean"),
+            ReductionFunc::P90 => f.write_str("p90"),
+            ReductionFunc::P95 => f.write_str("p95"),
+            ReductionFunc::P99 => f.write_str("p99"),
+            ReductionFunc::TrimmedMean(percent) => write!(f, "trimmed_mean({percent})"),
+            ReductionFunc::GeometricMean => f.write_str("geometric_mean"),
+        }
+    }
+}
+
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// Structured JSON on stdout
+    Json,
+    /// Markdown table with an inline trend image per measurement, e.g. for pull request comments
+    Markdown,
 }
 
 #[derive(Debug)]
 pub struct MeasurementSummary {
     pub epoch: u32,
     pub val: f64,
+    /// Number of reduced measurements
+    pub len: usize,
+    /// Sample standard deviation of the reduced measurements
+    pub stddev: f64,
+    /// Median absolute deviation of the reduced measurements
+    pub mad: f64,
 }
 
 #[derive(Debug)]
@@ -22,7 +101,7 @@ pub struct CommitSummary {
     pub measurement: Option<MeasurementSummary>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct MeasurementData {
     pub epoch: u32,
     pub name: String,
@@ -31,3 +110,198 @@ pub struct MeasurementData {
     pub val: f64,
     pub key_values: HashMap<String, String>,
 }
+
+/// Key recording the names of the build matrix dimensions of a measurement, comma separated,
+/// e.g. `os,rust`.
+pub const MATRIX_KEY: &str = "matrix";
+
+impl MeasurementData {
+    /// Whether the key-values satisfy all of the `selectors`.
+    pub fn matches_selectors(&self, selectors: &[Selector]) -> bool {
+        selectors.iter().all(|s| s.matches(&self.key_values))
+    }
+}
+
+/// Names of all build matrix dimensions recorded with any of the `measurements`, sorted.
+pub fn matrix_dimensions<'a>(
+    measurements: impl IntoIterator<Item = &'a MeasurementData>,
+) -> Vec<String> {
+    let mut dimensions: Vec<String> = measurements
+        .into_iter()
+        .filter_map(|m| m.key_values.get(MATRIX_KEY))
+        .flat_map(|dimensions| dimensions.split(','))
+        .map(str::to_owned)
+        .collect();
+    dimensions.sort();
+    dimensions.dedup();
+    dimensions
+}
+
+/// Condition on one key-value of measurements.
+#[derive(Clone, Debug)]
+pub enum Condition {
+    /// `key=value`: the key has exactly this value
+    Equals(String),
+    /// `key!=value`: the key is missing or has another value
+    NotEquals(String),
+    /// `key=~regex`: the key has a value matching the regex anywhere, unless anchored
+    Matches(Regex),
+}
+
+/// Selection of measurements by a key-value, given as `key=value`, `key!=value`, or
+/// `key=~regex`.
+#[derive(Clone, Debug)]
+pub struct Selector {
+    pub key: String,
+    pub condition: Condition,
+}
+
+impl Selector {
+    pub fn equals(key: &str, value: &str) -> Self {
+        Selector {
+            key: key.to_owned(),
+            condition: Condition::Equals(value.to_owned()),
+        }
+    }
+
+    pub fn matches(&self, key_values: &HashMap<String, String>) -> bool {
+        let value = key_values.get(&self.key);
+        match &self.condition {
+            Condition::Equals(expected) => value == Some(expected),
+            Condition::NotEquals(excluded) => value != Some(excluded),
+            Condition::Matches(regex) => value.is_some_and(|v| regex.is_match(v)),
+        }
+    }
+
+    /// The key-value pair of an exact selector, e.g. to stamp it onto new measurements.
+    pub fn as_key_value(&self) -> Option<(String, String)> {
+        match &self.condition {
+            Condition::Equals(value) => Some((self.key.clone(), value.clone())),
+            Condition::NotEquals(_) | Condition::Matches(_) => None,
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.split_whitespace().count() > 1 {
+            bail!("invalid selector: found space in '{s}'");
+        }
+        let Some(pos) = s.find('=') else {
+            bail!("invalid selector: expected key=value, key!=value, or key=~regex in '{s}'");
+        };
+        let (key, condition) = if let Some(key) = s[..pos].strip_suffix('!') {
+            (key, Condition::NotEquals(s[pos + 1..].to_owned()))
+        } else if let Some(regex) = s[pos + 1..].strip_prefix('~') {
+            let regex =
+                Regex::new(regex).with_context(|| format!("invalid regex in selector '{s}'"))?;
+            (&s[..pos], Condition::Matches(regex))
+        } else {
+            (&s[..pos], Condition::Equals(s[pos + 1..].to_owned()))
+        };
+        if key.is_empty() {
+            bail!("invalid selector: missing key in '{s}'");
+        }
+        Ok(Selector {
+            key: key.to_owned(),
+            condition,
+        })
+    }
+}
+
+impl Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.condition {
+            Condition::Equals(value) => write!(f, "{}={value}", self.key),
+            Condition::NotEquals(value) => write!(f, "{}!={value}", self.key),
+            Condition::Matches(regex) => write!(f, "{}=~{}", self.key, regex.as_str()),
+        }
+    }
+}
+
+impl PartialEq for Selector {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key_values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn union_of_matrix_dimensions() {
+        let measurement = |pairs: &[(&str, &str)]| MeasurementData {
+            epoch: 0,
+            name: "timer".to_string(),
+            timestamp: 0.0,
+            val: 1.0,
+            key_values: key_values(pairs),
+        };
+        let measurements = [
+            measurement(&[(MATRIX_KEY, "rust,os"), ("os", "linux"), ("rust", "stable")]),
+            measurement(&[(MATRIX_KEY, "os"), ("os", "mac")]),
+            measurement(&[("os", "windows")]),
+        ];
+        assert_eq!(matrix_dimensions(&measurements), ["os", "rust"]);
+        assert!(matrix_dimensions(&measurements[2..]).is_empty());
+    }
+
+    #[test]
+    fn selector_forms() {
+        let linux = key_values(&[("os", "linux")]);
+        let windows = key_values(&[("os", "windows")]);
+        let none = key_values(&[]);
+
+        let equals: Selector = "os=linux".parse().unwrap();
+        assert!(equals.matches(&linux));
+        assert!(!equals.matches(&windows));
+        assert!(!equals.matches(&none));
+
+        let not_equals: Selector = "os!=windows".parse().unwrap();
+        assert!(not_equals.matches(&linux));
+        assert!(!not_equals.matches(&windows));
+        assert!(not_equals.matches(&none));
+
+        let regex: Selector = "os=~^(linux|mac)".parse().unwrap();
+        assert!(regex.matches(&linux));
+        assert!(!regex.matches(&windows));
+        assert!(!regex.matches(&none));
+    }
+
+    #[test]
+    fn selector_round_trip() {
+        for selector in ["os=linux", "os!=windows", "os=~^l.*x$", "empty="] {
+            assert_eq!(selector.parse::<Selector>().unwrap().to_string(), selector);
+        }
+        assert_eq!(
+            "os=linux".parse::<Selector>().unwrap().as_key_value(),
+            Some(("os".to_owned(), "linux".to_owned()))
+        );
+        assert_eq!(
+            "os!=linux".parse::<Selector>().unwrap().as_key_value(),
+            None
+        );
+        // Values may contain '=' themselves
+        assert!("flags=a=b"
+            .parse::<Selector>()
+            .unwrap()
+            .matches(&key_values(&[("flags", "a=b")])));
+    }
+
+    #[test]
+    fn invalid_selectors() {
+        for invalid in ["os", "=linux", "!=linux", "os=~(", "os=a b"] {
+            assert!(invalid.parse::<Selector>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    collections::HashMap,
+    env::set_current_dir,
+    f64::consts::PI,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+
+use crate::{config, data::MeasurementData, git_interop, measurement_storage::MeasurementWriter};
+
+const SAMPLES_PER_COMMIT: usize = 5;
+const PLATFORMS: [&str; 2] = ["linux", "mac"];
+/// Pretend that one commit was measured per hour
+const SECONDS_PER_COMMIT: f64 = 3600.0;
+
+/// Deterministic pseudo random numbers (xorshift64*), good enough for synthetic noise.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniformly distributed in `(0, 1]`
+    fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal distribution (Box-Muller)
+    fn next_gaussian(&mut self) -> f64 {
+        let (u1, u2) = (self.next_f64(), self.next_f64());
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+/// Synthetic measurement with a gradual trend and an optional sudden change.
+struct Series {
+    name: &'static str,
+    base: f64,
+    /// Relative standard deviation of the samples
+    noise: f64,
+    /// Relative change per commit
+    trend: f64,
+    /// Relative change from the commit at this fraction of the history on
+    change_point: Option<(f64, f64)>,
+    /// Start a new epoch at the change point as the change is accepted
+    new_epoch: bool,
+}
+
+const SERIES: [Series; 3] = [
+    Series {
+        name: "demo::startup",
+        base: 50_000_000.0,
+        noise: 0.03,
+        trend: 0.001,
+        change_point: None,
+        new_epoch: false,
+    },
+    Series {
+        name: "demo::parse",
+        base: 8_000_000.0,
+        noise: 0.02,
+        trend: 0.0,
+        change_point: Some((0.6, 0.25)),
+        new_epoch: false,
+    },
+    Series {
+        name: "demo::max_rss",
+        base: 40_000_000.0,
+        noise: 0.01,
+        trend: 0.0,
+        change_point: Some((0.75, -0.3)),
+        new_epoch: true,
+    },
+];
+
+impl Series {
+    fn change_commit(&self, commits: usize) -> Option<usize> {
+        self.change_point
+            .map(|(at, _)| (at * commits as f64).round() as usize)
+    }
+
+    /// Expected value at commit `n` (oldest first) before noise
+    fn expected(&self, n: usize, commits: usize) -> f64 {
+        let trend = 1.0 + self.trend * n as f64;
+        let change = match (self.change_commit(commits), self.change_point) {
+            (Some(at), Some((_, change))) if n >= at => 1.0 + change,
+            _ => 1.0,
+        };
+        self.base * trend * change
+    }
+
+    fn sample(&self, n: usize, commits: usize, platform: usize, rng: &mut Rng) -> f64 {
+        // Slower runners for the second platform
+        let platform_factor = 1.0 + 0.2 * platform as f64;
+        let expected = self.expected(n, commits) * platform_factor;
+        (expected * (1.0 + self.noise * rng.next_gaussian())).max(0.0)
+    }
+}
+
+fn generate_commit(n: usize, commits: usize, start: f64, rng: &mut Rng) -> Result<()> {
+    let epoch_bumps = SERIES
+        .iter()
+        .filter(|s| s.new_epoch && s.change_commit(commits) == Some(n))
+        .collect_vec();
+
+    git_interop::commit_all(&format!("Demo commit {n}"))?;
+    if !epoch_bumps.is_empty() {
+        // Like a user accepting the change, measured again on the commit changing the config
+        for series in &epoch_bumps {
+            config::bump_epoch(series.name, Some("accepted performance change"))?;
+        }
+        let names = epoch_bumps.iter().map(|s| s.name).join(", ");
+        git_interop::commit_all(&format!("Accept performance change of {names}"))?;
+    }
+
+    let writer = MeasurementWriter::new();
+    writer.queue_all(
+        SERIES
+            .iter()
+            .flat_map(|series| {
+                let epoch = config::determine_epoch_from_config(series.name).unwrap_or(0);
+                PLATFORMS
+                    .iter()
+                    .enumerate()
+                    .flat_map(move |(platform, os)| {
+                        (0..SAMPLES_PER_COMMIT).map(move |_| (series, epoch, platform, *os))
+                    })
+            })
+            .map(|(series, epoch, platform, os)| MeasurementData {
+                epoch,
+                name: series.name.to_owned(),
+                timestamp: start + n as f64 * SECONDS_PER_COMMIT,
+                val: series.sample(n, commits, platform, rng),
+                key_values: HashMap::from([("os".to_owned(), os.to_owned())]),
+            }),
+    );
+    writer.commit()?;
+    Ok(())
+}
+
+/// Create a scratch repository in `dir` with `commits` commits of synthetic measurement history,
+/// including noise, a gradual trend, a regression and an accepted change starting a new epoch.
+pub fn demo(dir: &Path, commits: usize, seed: u64) -> Result<()> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+        bail!(
+            "Refusing to create demo repository in non-empty '{}'",
+            dir.display()
+        );
+    }
+    fs::create_dir_all(dir)?;
+    git_interop::init_repo(dir)?;
+    set_current_dir(dir).with_context(|| format!("Failed to enter '{}'", dir.display()))?;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+    let start = now - commits as f64 * SECONDS_PER_COMMIT;
+    let mut rng = Rng::new(seed);
+    for n in 0..commits {
+        generate_commit(n, commits, start, &mut rng)?;
+    }
+
+    eprintln!(
+        "Created demo repository with {commits} commits in '{}'. Try:",
+        dir.display()
+    );
+    eprintln!("  cd {}", dir.display());
+    eprintln!("  git perf report -a median -s os");
+    eprintln!("  git perf audit -m demo::parse -m demo::startup -s os=linux");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rng_is_deterministic() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let a = (0..10).map(|_| a.next_u64()).collect_vec();
+        let b = (0..10).map(|_| b.next_u64()).collect_vec();
+        assert_eq!(a, b);
+        assert_ne!(Rng::new(0).next_u64(), Rng::new(1).next_u64());
+    }
+
+    #[test]
+    fn gaussian_noise_is_centered() {
+        let mut rng = Rng::new(7);
+        let samples = (0..10_000).map(|_| rng.next_gaussian()).collect_vec();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.05, "mean {mean}");
+        assert!(samples.iter().all(|s| s.is_finite()));
+    }
+
+    #[test]
+    fn change_point() {
+        let parse = &SERIES[1];
+        assert_eq!(parse.change_commit(100), Some(60));
+        assert_eq!(parse.expected(59, 100), 8_000_000.0);
+        assert_eq!(parse.expected(60, 100), 10_000_000.0);
+    }
+}
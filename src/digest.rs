@@ -0,0 +1,504 @@
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use itertools::Itertools;
+use readable::num::Float;
+
+use crate::{
+    audit::{self, AuditResult, AuditStatus},
+    config,
+    data::{MeasurementSummary, ReductionFunc},
+    git_interop,
+    measurement_retrieval::{self, Commit, ReductionFuncIterator},
+    sparkline,
+};
+
+/// Number of regressions and improvements listed in a digest.
+const TOP_CHANGES: usize = 5;
+
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DigestFormat {
+    /// HTML with inline styles and without scripts, suitable as an email body
+    #[default]
+    EmailHtml,
+}
+
+/// Latest value of a measurement compared to the mean of its earlier values in the same epoch.
+#[derive(Debug, PartialEq)]
+struct Change {
+    name: String,
+    commit: String,
+    previous: f64,
+    latest: f64,
+}
+
+impl Change {
+    fn relative(&self) -> f64 {
+        (self.latest - self.previous) / self.previous
+    }
+}
+
+/// Number of commits with data per measurement.
+#[derive(Debug, PartialEq)]
+struct MeasurementCoverage {
+    name: String,
+    with_data: usize,
+}
+
+struct Digest {
+    period: Duration,
+    head: Option<String>,
+    commits: usize,
+    /// Sorted by relative change, largest increase first
+    changes: Vec<Change>,
+    failed_audits: Vec<AuditResult>,
+    coverage: Vec<MeasurementCoverage>,
+    commit_url: Option<String>,
+    sparkline: sparkline::Options,
+}
+
+/// The commits from the newest one back to the oldest one with measurements taken at or after
+/// `cutoff`. Older measurements are dropped.
+fn recent_commits(commits: Vec<Commit>, cutoff: f64) -> Vec<Commit> {
+    let mut recent = commits
+        .into_iter()
+        .map(|mut c| {
+            c.measurements.retain(|m| m.timestamp >= cutoff);
+            c
+        })
+        .collect_vec();
+    let len = recent
+        .iter()
+        .rposition(|c| !c.measurements.is_empty())
+        .map_or(0, |pos| pos + 1);
+    recent.truncate(len);
+    recent
+}
+
+/// Per measurement, sorted by name, the summarized value of each commit with data, newest first.
+fn series(commits: &[Commit]) -> Vec<(String, Vec<(usize, MeasurementSummary)>)> {
+    commits
+        .iter()
+        .flat_map(|c| c.measurements.iter().map(|m| m.name.clone()))
+        .unique()
+        .sorted()
+        .map(|name| {
+            let summaries = commits
+                .iter()
+                .enumerate()
+                .filter_map(|(i, c)| {
+                    // Same aggregation as audit
+                    c.measurements
+                        .iter()
+                        .filter(|m| m.name == name)
+                        .reduce_by(ReductionFunc::Min)
+                        .map(|s| (i, s))
+                })
+                .collect_vec();
+            (name, summaries)
+        })
+        .collect()
+}
+
+fn changes(
+    commits: &[Commit],
+    series: &[(String, Vec<(usize, MeasurementSummary)>)],
+) -> Vec<Change> {
+    series
+        .iter()
+        .filter_map(|(name, summaries)| {
+            let ((index, latest), older) = summaries.split_first()?;
+            let older = older
+                .iter()
+                .take_while(|(_, s)| s.epoch == latest.epoch)
+                .map(|(_, s)| s.val)
+                .collect_vec();
+            if older.is_empty() {
+                return None;
+            }
+            let previous = older.iter().sum::<f64>() / older.len() as f64;
+            (previous != 0.0).then(|| Change {
+                name: name.clone(),
+                commit: commits[*index].commit.clone(),
+                previous,
+                latest: latest.val,
+            })
+        })
+        .sorted_by(|a, b| b.relative().total_cmp(&a.relative()))
+        .collect()
+}
+
+/// `s` with the characters special to HTML escaped.
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn describe_period(period: Duration) -> String {
+    let secs = period.as_secs();
+    let (count, unit) = match secs {
+        s if s >= 86400 && s % 86400 == 0 => (s / 86400, "day"),
+        s if s >= 3600 && s % 3600 == 0 => (s / 3600, "hour"),
+        s if s >= 60 && s % 60 == 0 => (s / 60, "minute"),
+        s => (s, "second"),
+    };
+    let plural_s = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural_s}")
+}
+
+const BODY_STYLE: &str =
+    "font-family:Arial,Helvetica,sans-serif;font-size:14px;color:#24292e;max-width:720px";
+const HEADING_STYLE: &str = "font-size:16px;margin:20px 0 8px 0";
+const TABLE_STYLE: &str = "border-collapse:collapse;width:100%";
+const CELL_STYLE: &str = "border:1px solid #d0d7de;padding:4px 8px;text-align:left";
+const HEADER_CELL_STYLE: &str =
+    "border:1px solid #d0d7de;padding:4px 8px;text-align:left;background:#f6f8fa";
+const REGRESSION_COLOR: &str = "#cf222e";
+const IMPROVEMENT_COLOR: &str = "#1a7f37";
+
+impl Digest {
+    fn commit_link(&self, commit: &str) -> String {
+        let short = escape(&commit[..commit.len().min(10)]);
+        match &self.commit_url {
+            Some(url) => format!(
+                "<a href=\"{}\" style=\"color:#0969da\">{short}</a>",
+                escape(&url.replace("{commit}", commit))
+            ),
+            None => format!("<code>{short}</code>"),
+        }
+    }
+
+    fn table(header: &[&str], rows: Vec<Vec<String>>) -> String {
+        let header = header
+            .iter()
+            .map(|h| format!("<th style=\"{HEADER_CELL_STYLE}\">{h}</th>"))
+            .join("");
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                let cells = row
+                    .into_iter()
+                    .map(|cell| format!("<td style=\"{CELL_STYLE}\">{cell}</td>"))
+                    .join("");
+                format!("<tr>{cells}</tr>\n")
+            })
+            .join("");
+        format!("<table style=\"{TABLE_STYLE}\">\n<tr>{header}</tr>\n{rows}</table>\n")
+    }
+
+    fn section(title: &str, content: Option<String>) -> String {
+        format!(
+            "<h3 style=\"{HEADING_STYLE}\">{title}</h3>\n{}",
+            content.unwrap_or_else(|| "<p>None</p>\n".to_owned())
+        )
+    }
+
+    fn changes_section<'a>(
+        &self,
+        title: &str,
+        changes: impl Iterator<Item = &'a Change>,
+        color: &str,
+    ) -> String {
+        let rows = changes
+            .take(TOP_CHANGES)
+            .map(|c| {
+                vec![
+                    escape(&c.name),
+                    self.commit_link(&c.commit),
+                    Float::from(c.previous).to_string(),
+                    Float::from(c.latest).to_string(),
+                    format!(
+                        "<span style=\"color:{color}\">{:+.2}%</span>",
+                        c.relative() * 100.0
+                    ),
+                ]
+            })
+            .collect_vec();
+        Self::section(
+            title,
+            (!rows.is_empty()).then(|| {
+                Self::table(
+                    &["Measurement", "Commit", "Previous mean", "Latest", "Change"],
+                    rows,
+                )
+            }),
+        )
+    }
+
+    fn as_email_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str("<title>Performance digest</title>\n</head>\n");
+        out.push_str(&format!("<body style=\"{BODY_STYLE}\">\n"));
+        out.push_str(&format!(
+            "<h2 style=\"{HEADING_STYLE}\">Performance digest</h2>\n"
+        ));
+
+        let period = describe_period(self.period);
+        if self.commits == 0 {
+            out.push_str(&format!("<p>No measurements in the last {period}.</p>\n"));
+            out.push_str("</body>\n</html>\n");
+            return out;
+        }
+        let head = self
+            .head
+            .as_deref()
+            .map(|head| format!(" up to {}", self.commit_link(head)))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "<p>{} commits with measurements of the last {period}{head}.</p>\n",
+            self.commits
+        ));
+
+        let failed = self
+            .failed_audits
+            .iter()
+            .map(|r| {
+                vec![
+                    escape(&r.measurement),
+                    r.head
+                        .map(|h| Float::from(h).to_string())
+                        .unwrap_or_default(),
+                    r.tail
+                        .as_ref()
+                        .map(|t| Float::from(t.mean).to_string())
+                        .unwrap_or_default(),
+                    r.z_score.map(|z| format!("{z:.2}")).unwrap_or_default(),
+                    r.html_sparkline(&self.sparkline),
+                ]
+            })
+            .collect_vec();
+        out.push_str(&Self::section(
+            "Failed audits",
+            (!failed.is_empty()).then(|| {
+                Self::table(
+                    &["Measurement", "HEAD", "Tail mean", "z-score", "Trend"],
+                    failed,
+                )
+            }),
+        ));
+
+        out.push_str(&self.changes_section(
+            "Top regressions",
+            self.changes.iter().filter(|c| c.relative() > 0.0),
+            REGRESSION_COLOR,
+        ));
+        out.push_str(&self.changes_section(
+            "Top improvements",
+            self.changes.iter().rev().filter(|c| c.relative() < 0.0),
+            IMPROVEMENT_COLOR,
+        ));
+
+        let coverage = self
+            .coverage
+            .iter()
+            .map(|c| {
+                vec![
+                    escape(&c.name),
+                    format!("{}/{}", c.with_data, self.commits),
+                    format!("{:.0}%", c.with_data as f64 / self.commits as f64 * 100.0),
+                ]
+            })
+            .collect_vec();
+        out.push_str(&Self::section(
+            "Data coverage",
+            Some(Self::table(
+                &["Measurement", "Commits with data", "Coverage"],
+                coverage,
+            )),
+        ));
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Write a compact summary of the measurements taken within the last `period` in the last
+/// `max_count` commits: the largest regressions and improvements of the latest values, failed
+/// audits of HEAD, and how many commits have data. Regressions are increases of the value.
+pub fn digest(
+    period: Duration,
+    max_count: usize,
+    sigma: f64,
+    format: DigestFormat,
+    output: &Path,
+) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+    let commits: Vec<_> = measurement_retrieval::walk_commits(max_count)?.try_collect()?;
+    let commits = recent_commits(commits, now - period.as_secs_f64());
+    let series = series(&commits);
+
+    let failed_audits = series
+        .iter()
+        .filter_map(|(name, _)| {
+            // Measurements missing on HEAD cannot fail an audit
+            audit::audit_measurement(
+                name,
+                "HEAD",
+                max_count,
+                2,
+                &[],
+                ReductionFunc::Min,
+                sigma,
+                None,
+                0.0,
+                None,
+            )
+            .ok()
+        })
+        .filter(|r| r.status == AuditStatus::Fail)
+        .collect_vec();
+
+    let digest = Digest {
+        period,
+        head: git_interop::get_head_revision().ok(),
+        commits: commits.len(),
+        changes: changes(&commits, &series),
+        failed_audits,
+        coverage: series
+            .iter()
+            .map(|(name, summaries)| MeasurementCoverage {
+                name: name.clone(),
+                with_data: summaries.len(),
+            })
+            .collect(),
+        commit_url: config::determine_digest_commit_url_from_config(),
+        sparkline: config::determine_sparkline_options_from_config()?,
+    };
+
+    let content = match format {
+        DigestFormat::EmailHtml => digest.as_email_html(),
+    };
+
+    if output == Path::new("-") {
+        match io::stdout().write_all(content.as_bytes()) {
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+            res => res,
+        }?;
+    } else {
+        File::create(output)?.write_all(content.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::data::MeasurementData;
+
+    use super::*;
+
+    fn commit(commit: &str, measurements: &[(&str, u32, f64, f64)]) -> Commit {
+        Commit {
+            commit: commit.to_owned(),
+            measurements: measurements
+                .iter()
+                .map(|(name, epoch, timestamp, val)| MeasurementData {
+                    epoch: *epoch,
+                    name: (*name).to_owned(),
+                    timestamp: *timestamp,
+                    val: *val,
+                    key_values: HashMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn recent_commits_stop_at_oldest_recent_measurement() {
+        let commits = vec![
+            commit("c0", &[("timer", 0, 100.0, 1.0)]),
+            commit("c1", &[]),
+            commit("c2", &[("timer", 0, 90.0, 1.0), ("timer", 0, 10.0, 1.0)]),
+            commit("c3", &[("timer", 0, 10.0, 1.0)]),
+        ];
+        let recent = recent_commits(commits, 50.0);
+        assert_eq!(
+            recent.iter().map(|c| c.commit.as_str()).collect_vec(),
+            ["c0", "c1", "c2"]
+        );
+        assert_eq!(recent[2].measurements.len(), 1);
+        assert!(recent_commits(vec![commit("c0", &[("timer", 0, 10.0, 1.0)])], 50.0).is_empty());
+    }
+
+    #[test]
+    fn changes_against_same_epoch() {
+        let commits = vec![
+            commit("c0", &[("timer", 1, 0.0, 12.0), ("other", 0, 0.0, 5.0)]),
+            commit("c1", &[("timer", 1, 0.0, 10.0), ("other", 0, 0.0, 10.0)]),
+            commit("c2", &[("timer", 0, 0.0, 100.0), ("other", 0, 0.0, 10.0)]),
+            commit("c3", &[("new", 0, 0.0, 1.0)]),
+        ];
+        let series = series(&commits);
+        assert_eq!(
+            series.iter().map(|(name, _)| name.as_str()).collect_vec(),
+            ["new", "other", "timer"]
+        );
+        let changes = changes(&commits, &series);
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    name: "timer".to_owned(),
+                    commit: "c0".to_owned(),
+                    previous: 10.0,
+                    latest: 12.0,
+                },
+                Change {
+                    name: "other".to_owned(),
+                    commit: "c0".to_owned(),
+                    previous: 10.0,
+                    latest: 5.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn email_html_is_self_contained() {
+        let digest = Digest {
+            period: Duration::from_secs(7 * 86400),
+            head: Some("0123456789abcdef".to_owned()),
+            commits: 2,
+            changes: vec![Change {
+                name: "<timer>".to_owned(),
+                commit: "0123456789abcdef".to_owned(),
+                previous: 10.0,
+                latest: 12.0,
+            }],
+            failed_audits: vec![],
+            coverage: vec![MeasurementCoverage {
+                name: "<timer>".to_owned(),
+                with_data: 1,
+            }],
+            commit_url: Some("https://example.com/commit/{commit}".to_owned()),
+            sparkline: sparkline::Options::default(),
+        };
+        let html = digest.as_email_html();
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<timer>"));
+        assert!(html.contains("&lt;timer&gt;"));
+        assert!(html.contains("of the last 7 days"));
+        assert!(html.contains("+20.00%"));
+        assert!(html.contains("1/2"));
+        assert!(html.contains("href=\"https://example.com/commit/0123456789abcdef\""));
+    }
+
+    #[test]
+    fn describe_periods() {
+        assert_eq!(describe_period(Duration::from_secs(86400)), "1 day");
+        assert_eq!(describe_period(Duration::from_secs(14 * 86400)), "14 days");
+        assert_eq!(describe_period(Duration::from_secs(7200)), "2 hours");
+        assert_eq!(describe_period(Duration::from_secs(90)), "90 seconds");
+    }
+}
@@ -0,0 +1,292 @@
+//! Checks of the environment git-perf runs in, each with a fix for the problems found. Most of
+//! these problems otherwise only surface as errors of the affected operations.
+
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
+use toml_edit::Document;
+
+use crate::{
+    config,
+    git_interop::{self, DEFAULT_REMOTE, DEFAULT_TEMP_REF_MAX_AGE, MIN_GIT_VERSION},
+    serialization::{Format, LegacyFormat},
+    storage,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Works, but likely not as intended
+    Warn,
+    /// Operations will fail
+    Fail,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Check {
+    pub status: Status,
+    /// What was found
+    pub message: String,
+    /// How to resolve a warning or failure
+    pub fix: Option<String>,
+}
+
+impl Check {
+    pub(crate) fn ok(message: impl Into<String>) -> Self {
+        Check {
+            status: Status::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    pub(crate) fn warn(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            status: Status::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    pub(crate) fn fail(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            status: Status::Fail,
+            ..Self::warn(message, fix)
+        }
+    }
+
+    /// A failure of the check itself, e.g. because git could not be run.
+    fn error(what: &str, error: anyhow::Error) -> Self {
+        Self::fail(
+            format!("Could not check {what}: {error:#}"),
+            "Run git perf doctor from within the repository's work tree",
+        )
+    }
+}
+
+fn check_git_version() -> Check {
+    match git_interop::get_git_version() {
+        Ok(version) if version < MIN_GIT_VERSION => Check::fail(
+            format!(
+                "git {} is older than the required {}",
+                git_interop::concat_version(version),
+                git_interop::concat_version(MIN_GIT_VERSION)
+            ),
+            format!(
+                "Install git {} or newer",
+                git_interop::concat_version(MIN_GIT_VERSION)
+            ),
+        ),
+        Ok(version) => Check::ok(format!("git {}", git_interop::concat_version(version))),
+        Err(e) => Check::fail(format!("git cannot be run: {e:#}"), "Install git"),
+    }
+}
+
+fn check_remotes() -> Vec<Check> {
+    let mut remotes = config::determine_push_remotes_from_config();
+    if remotes.is_empty() {
+        remotes.push(DEFAULT_REMOTE.to_owned());
+    }
+    remotes
+        .into_iter()
+        .map(|remote| match git_interop::remote_url(&remote) {
+            Some(url) => Check::ok(format!("Remote {remote} at {url}")),
+            None => Check::warn(
+                format!("Remote {remote} is not configured, push and pull will fail"),
+                format!(
+                    "Add it with 'git remote add {remote} <url>' or list the remotes to use in \
+                     [remote] push of .gitperfconfig"
+                ),
+            ),
+        })
+        .collect()
+}
+
+fn check_shallow() -> Check {
+    match git_interop::is_shallow_repo() {
+        Ok(true) => Check::warn(
+            "Shallow clone, reports and audits are limited to the fetched history and prune \
+             refuses to run",
+            "Fetch the full history with 'git fetch --unshallow'",
+        ),
+        Ok(false) => Check::ok("Full clone"),
+        Err(e) => Check::error("for a shallow clone", e),
+    }
+}
+
+fn check_head() -> Check {
+    match git_interop::is_detached_head() {
+        Ok(true) => Check::warn(
+            "Detached HEAD, measurements are added to the checked out commit only",
+            "Check out a branch unless this is a CI checkout of a single commit",
+        ),
+        Ok(false) => Check::ok("HEAD is on a branch"),
+        Err(e) => Check::error("HEAD", e),
+    }
+}
+
+fn check_notes_refs() -> Vec<Check> {
+    let mut checks = Vec::new();
+    match git_interop::has_notes_refs() {
+        Ok(true) => checks.push(Check::ok("Measurements available locally")),
+        Ok(false) => checks.push(Check::warn(
+            "No measurements available locally",
+            "Fetch them with 'git perf pull' or add some with 'git perf add'",
+        )),
+        Err(e) => checks.push(Check::error("the notes refs", e)),
+    }
+    match git_interop::notes_ref_problems() {
+        Ok(problems) => checks.extend(problems.into_iter().map(|problem| {
+            Check::fail(
+                problem,
+                "Replace it with a regular ref to the notes commit with 'git update-ref \
+                 --no-deref <ref> <commit>' or remove it with 'git update-ref -d <ref>'",
+            )
+        })),
+        Err(e) => checks.push(Check::error("the notes refs", e)),
+    }
+    let max_age =
+        config::determine_temp_ref_max_age_from_config().unwrap_or(DEFAULT_TEMP_REF_MAX_AGE);
+    match git_interop::stale_temp_ref_names(max_age) {
+        Ok(refs) if refs.is_empty() => {}
+        Ok(refs) => checks.push(Check::warn(
+            format!(
+                "Stale temporary refs of interrupted operations: {}",
+                refs.join(", ")
+            ),
+            "Remove them with 'git perf gc --refs'",
+        )),
+        Err(e) => checks.push(Check::error("temporary refs", e)),
+    }
+    match git_interop::unmerged_shard_refs() {
+        Ok(refs) if refs.is_empty() => {}
+        Ok(refs) => checks.push(Check::warn(
+            format!("Fetched shards not merged yet: {}", refs.join(", ")),
+            "Merge them by running 'git perf pull' again",
+        )),
+        Err(e) => checks.push(Check::error("fetched shards", e)),
+    }
+    checks
+}
+
+/// Problems of the settings read leniently elsewhere, e.g. on first use.
+fn config_problems() -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut check = |result: Result<()>| {
+        if let Err(e) = result {
+            problems.push(format!("{e:#}"));
+        }
+    };
+    check(config::determine_report_sections_from_config().map(|_| ()));
+    check(config::determine_retention_policy_from_config().map(|_| ()));
+    check(config::determine_baseline_from_config().map(|_| ()));
+    check(config::determine_sparkline_options_from_config().map(|_| ()));
+    check(storage::backend().map(|_| ()));
+    check(
+        config::determine_storage_format_from_config()
+            .map(|format| format.parse::<Format>())
+            .transpose()
+            .map(|_| ()),
+    );
+    check(
+        match config::determine_storage_shards_from_config().as_deref() {
+            None | Some("year") => Ok(()),
+            Some(other) => Err(anyhow::anyhow!("Unknown shard scheme '{other}'")),
+        },
+    );
+    for notes_ref in config::determine_legacy_refs_from_config() {
+        check(LegacyFormat::of_ref(&notes_ref).map(|_| ()));
+    }
+    problems
+}
+
+fn check_config() -> Vec<Check> {
+    let Some(conf) = config::read_config() else {
+        return vec![Check::ok("No .gitperfconfig, using the defaults")];
+    };
+    if let Err(e) = conf.parse::<Document>() {
+        return vec![Check::fail(
+            format!(".gitperfconfig is not valid TOML: {e}"),
+            "Fix the syntax of .gitperfconfig",
+        )];
+    }
+    let problems = config_problems();
+    if problems.is_empty() {
+        return vec![Check::ok(".gitperfconfig is valid")];
+    }
+    problems
+        .into_iter()
+        .map(|problem| Check::fail(problem, "Correct the setting in .gitperfconfig"))
+        .collect()
+}
+
+/// Run all checks. Checks of the repository are skipped outside of one.
+pub fn diagnose() -> Vec<Check> {
+    let mut checks = vec![check_git_version()];
+    if !git_interop::is_inside_work_tree() {
+        checks.push(Check::fail(
+            "Not inside the work tree of a git repository",
+            "Run git perf from within the repository to measure",
+        ));
+        return checks;
+    }
+    checks.extend(check_remotes());
+    checks.push(check_shallow());
+    checks.push(check_head());
+    checks.extend(check_notes_refs());
+    checks.extend(check_config());
+    checks
+}
+
+/// Print the result of all checks with the fixes of the problems found. Fails if any check
+/// failed.
+pub fn doctor() -> Result<()> {
+    print_checks(&diagnose())
+}
+
+/// Print the `checks` with the fixes of the problems found. Fails if any check failed.
+pub(crate) fn print_checks(checks: &[Check]) -> Result<()> {
+    for check in checks {
+        println!("{:<4}  {}", check.status, check.message);
+        if let Some(fix) = &check.fix {
+            println!("      fix: {fix}");
+        }
+    }
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count();
+    if failed > 0 {
+        bail!("{failed} of {} checks failed", checks.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn status_is_padded_for_alignment() {
+        assert_eq!(format!("{:<4}|", Status::Ok), "ok  |");
+        assert_eq!(format!("{:<4}|", Status::Fail), "FAIL|");
+    }
+
+    #[test]
+    fn only_problems_have_fixes() {
+        assert_eq!(Check::ok("fine").fix, None);
+        let check = Check::fail("broken", "repair");
+        assert_eq!(check.status, Status::Fail);
+        assert_eq!(check.fix.as_deref(), Some("repair"));
+    }
+}
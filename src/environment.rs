@@ -0,0 +1,162 @@
+//! Fingerprint of the machine the measurements are taken on. Stored as key-values, it lets
+//! reports separate and audits select the measurements of the same kind of hardware.
+
+#[cfg(not(target_os = "macos"))]
+use std::fs;
+#[cfg(target_os = "macos")]
+use std::process;
+use std::{env, thread};
+
+use itertools::Itertools;
+
+/// The key-values describing the current machine, sorted by key. Keys that cannot be detected
+/// are left out.
+pub fn fingerprint() -> Vec<(String, String)> {
+    fingerprint_from(
+        |name| env::var(name).ok().filter(|v| !v.is_empty()),
+        cpu_model(),
+        thread::available_parallelism().ok().map(usize::from),
+    )
+}
+
+fn fingerprint_from(
+    var: impl Fn(&str) -> Option<String>,
+    cpu_model: Option<String>,
+    cores: Option<usize>,
+) -> Vec<(String, String)> {
+    let host_kind = match var("RUNNER_ENVIRONMENT") {
+        // github-hosted or self-hosted on GitHub Actions
+        Some(environment) => environment,
+        None if var("CI").is_some_and(|ci| ci != "false") => "ci".to_owned(),
+        None => "local".to_owned(),
+    };
+    let runner = var("CI_RUNNER_TAGS")
+        .map(|tags| runner_tags(&tags))
+        .filter(|tags| !tags.is_empty())
+        // Image of GitHub-hosted runners, e.g. ubuntu22
+        .or_else(|| var("ImageOS"));
+    [
+        ("arch", Some(env::consts::ARCH.to_owned())),
+        ("cores", cores.map(|c| c.to_string())),
+        ("cpu", cpu_model),
+        ("host_kind", Some(host_kind)),
+        ("os", Some(env::consts::OS.to_owned())),
+        ("runner", runner),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key.to_owned(), sanitize(&value?))))
+    .filter(|(_, value)| !value.is_empty())
+    .collect()
+}
+
+/// GitLab runner tags, given as `["docker", "linux"]` or `docker, linux`, sorted and joined
+/// with '+'.
+fn runner_tags(tags: &str) -> String {
+    tags.trim_matches(|c| c == '[' || c == ']')
+        .split(',')
+        .map(|tag| tag.trim().trim_matches('"'))
+        .filter(|tag| !tag.is_empty())
+        .sorted()
+        .join("+")
+}
+
+/// Key-values must not contain whitespace or '='.
+fn sanitize(value: &str) -> String {
+    value.replace('=', "").split_whitespace().join("_")
+}
+
+#[cfg(target_os = "macos")]
+fn cpu_model() -> Option<String> {
+    let output = process::Command::new("sysctl")
+        .args(["-n", "machdep.cpu.brand_string"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo_model(&cpuinfo)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn cpuinfo_model(cpuinfo: &str) -> Option<String> {
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "model name").then(|| value.trim().to_owned())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn local_fingerprint() {
+        let fingerprint = fingerprint_from(
+            |_| None,
+            Some("Intel(R) Xeon(R) CPU @ 2.20GHz".to_owned()),
+            Some(4),
+        );
+        let keys = fingerprint.iter().map(|(k, _)| k.as_str()).collect_vec();
+        assert_eq!(keys, ["arch", "cores", "cpu", "host_kind", "os"]);
+        assert!(fingerprint.contains(&("cores".to_owned(), "4".to_owned())));
+        assert!(fingerprint.contains(&(
+            "cpu".to_owned(),
+            "Intel(R)_Xeon(R)_CPU_@_2.20GHz".to_owned()
+        )));
+        assert!(fingerprint.contains(&("host_kind".to_owned(), "local".to_owned())));
+    }
+
+    #[test]
+    fn ci_fingerprint() {
+        let github = fingerprint_from(
+            |name| match name {
+                "CI" => Some("true".to_owned()),
+                "RUNNER_ENVIRONMENT" => Some("github-hosted".to_owned()),
+                "ImageOS" => Some("ubuntu22".to_owned()),
+                _ => None,
+            },
+            None,
+            None,
+        );
+        assert!(github.contains(&("host_kind".to_owned(), "github-hosted".to_owned())));
+        assert!(github.contains(&("runner".to_owned(), "ubuntu22".to_owned())));
+        assert!(!github.iter().any(|(k, _)| k == "cpu" || k == "cores"));
+
+        let gitlab = fingerprint_from(
+            |name| match name {
+                "CI" => Some("true".to_owned()),
+                "CI_RUNNER_TAGS" => Some(r#"["linux", "docker"]"#.to_owned()),
+                _ => None,
+            },
+            None,
+            None,
+        );
+        assert!(gitlab.contains(&("host_kind".to_owned(), "ci".to_owned())));
+        assert!(gitlab.contains(&("runner".to_owned(), "docker+linux".to_owned())));
+    }
+
+    #[test]
+    fn runner_tag_formats() {
+        assert_eq!(runner_tags(r#"["b", "a"]"#), "a+b");
+        assert_eq!(runner_tags("b, a"), "a+b");
+        assert_eq!(runner_tags("[]"), "");
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn cpu_model_of_cpuinfo() {
+        let cpuinfo =
+            "processor\t: 0\nvendor_id\t: GenuineIntel\nmodel name\t: Intel(R) Core(TM) i7\n";
+        assert_eq!(
+            cpuinfo_model(cpuinfo).as_deref(),
+            Some("Intel(R) Core(TM) i7")
+        );
+        assert_eq!(cpuinfo_model("processor\t: 0\n"), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Events of operations such as measuring, auditing, and pushing with retries for CI log
+//! aggregation. With `--log-format json`, every event is printed to stderr as one JSON object per
+//! line. As text, only warnings are printed, as their plain message.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Warnings as plain text
+    #[default]
+    Text,
+    /// All events as one JSON object per line
+    Json,
+}
+
+pub fn set_log_format(format: LogFormat) {
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    /// Only printed as JSON
+    Info,
+    Warn,
+}
+
+/// Outcome of a single step of an operation, e.g. a push attempt that is retried.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Event<'a> {
+    pub level: Level,
+    /// E.g. `push`, `pull`, `measure`, or `audit`
+    pub operation: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub measurement: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u128>,
+    /// E.g. `success`, `retry`, `failure`, `skipped`, or the status of an audit
+    pub outcome: &'a str,
+    pub message: String,
+}
+
+impl<'a> Event<'a> {
+    pub fn info(operation: &'a str, outcome: &'a str, message: String) -> Self {
+        Event {
+            level: Level::Info,
+            operation,
+            measurement: None,
+            remote: None,
+            duration_ms: None,
+            outcome,
+            message,
+        }
+    }
+
+    pub fn warn(operation: &'a str, outcome: &'a str, message: String) -> Self {
+        Event {
+            level: Level::Warn,
+            ..Self::info(operation, outcome, message)
+        }
+    }
+
+    /// `success` or, with the error as message, `failure` depending on `result`.
+    pub fn outcome_of<T>(operation: &'a str, result: &anyhow::Result<T>, success: String) -> Self {
+        match result {
+            Ok(_) => Self::info(operation, "success", success),
+            Err(e) => Self::info(operation, "failure", format!("{e:#}")),
+        }
+    }
+
+    pub fn measurement(self, measurement: &'a str) -> Self {
+        Event {
+            measurement: Some(measurement),
+            ..self
+        }
+    }
+
+    pub fn remote(self, remote: &'a str) -> Self {
+        Event {
+            remote: Some(remote),
+            ..self
+        }
+    }
+
+    pub fn duration(self, duration: Duration) -> Self {
+        Event {
+            duration_ms: Some(duration.as_millis()),
+            ..self
+        }
+    }
+
+    pub fn emit(&self) {
+        if JSON.load(Ordering::Relaxed) {
+            eprintln!(
+                "{}",
+                serde_json::to_string(self).expect("Events are serializable")
+            );
+        } else if self.level == Level::Warn {
+            eprintln!("{}", self.message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serialize_event() {
+        let event = Event::info("push", "retry", "Rejected by origin".to_owned())
+            .remote("origin")
+            .duration(Duration::from_millis(1500));
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"level":"info","operation":"push","remote":"origin","duration_ms":1500,"outcome":"retry","message":"Rejected by origin"}"#
+        );
+        let event = Event::warn("audit", "fail", String::new()).measurement("timer");
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"level":"warn","operation":"audit","measurement":"timer","outcome":"fail","message":""}"#
+        );
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, ErrorKind, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::{
+    data::{MeasurementData, Selector},
+    measurement_retrieval,
+    reporting::JsonMeasurement,
+};
+
+/// Formats of `git perf export`.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line
+    Json,
+    /// Comma separated values with a header line
+    Csv,
+    /// Latest value of each measurement as Prometheus gauges with the key-values as labels
+    #[cfg_attr(feature = "cli", value(name = "openmetrics"))]
+    OpenMetrics,
+    /// Apache Parquet file with the columns of the CSV format and the key-values as a map
+    #[cfg(feature = "export-parquet")]
+    Parquet,
+}
+
+/// Prefix of the metric names in OpenMetrics exports.
+const METRIC_PREFIX: &str = "git_perf_";
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Valid OpenMetrics metric or label name with all characters outside of `[a-zA-Z0-9_]` replaced.
+fn openmetrics_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+fn openmetrics_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn openmetrics_value(value: f64) -> String {
+    match value {
+        f64::INFINITY => "+Inf".to_owned(),
+        f64::NEG_INFINITY => "-Inf".to_owned(),
+        value => format!("{value:?}"),
+    }
+}
+
+/// Series of a gauge as its metric name and sorted labels.
+type Series = (String, Vec<(String, String)>);
+
+/// Write the most recent value of each combination of measurement name and key-values as an
+/// OpenMetrics gauge. Only the latest value of each series is kept while reading `measurements`.
+fn write_openmetrics(
+    out: &mut impl Write,
+    measurements: impl Iterator<Item = MeasurementData>,
+) -> Result<()> {
+    let mut latest: BTreeMap<Series, (f64, f64)> = BTreeMap::new();
+    for m in measurements {
+        let labels = m
+            .key_values
+            .iter()
+            .map(|(k, v)| (openmetrics_name(k), v.clone()))
+            .sorted()
+            .collect();
+        let entry = latest
+            .entry((openmetrics_name(&m.name), labels))
+            .or_insert((m.timestamp, m.val));
+        if m.timestamp > entry.0 {
+            *entry = (m.timestamp, m.val);
+        }
+    }
+
+    let families = latest.into_iter().group_by(|((name, _), _)| name.clone());
+    for (name, series) in &families {
+        writeln!(out, "# TYPE {METRIC_PREFIX}{name} gauge")?;
+        for ((_, labels), (_, val)) in series {
+            let labels = if labels.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    "{{{}}}",
+                    labels
+                        .iter()
+                        .map(|(k, v)| format!("{k}=\"{}\"", openmetrics_label_value(v)))
+                        .join(",")
+                )
+            };
+            writeln!(
+                out,
+                "{METRIC_PREFIX}{name}{labels} {}",
+                openmetrics_value(val)
+            )?;
+        }
+    }
+    writeln!(out, "# EOF")?;
+    Ok(())
+}
+
+fn write_row(
+    out: &mut impl Write,
+    format: ExportFormat,
+    commit: &str,
+    measurement: &MeasurementData,
+) -> Result<()> {
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer(
+                &mut *out,
+                &JsonMeasurement {
+                    commit: commit.to_owned(),
+                    measurement,
+                },
+            )?;
+            writeln!(out)?;
+        }
+        ExportFormat::Csv => {
+            let key_values = measurement
+                .key_values
+                .iter()
+                .sorted()
+                .map(|(k, v)| format!("{k}={v}"))
+                .join(";");
+            writeln!(
+                out,
+                "{},{},{},{:?},{:?},{}",
+                commit,
+                measurement.epoch,
+                csv_field(&measurement.name),
+                measurement.timestamp,
+                measurement.val,
+                csv_field(&key_values),
+            )?;
+        }
+        ExportFormat::OpenMetrics => unreachable!("OpenMetrics exports are not written by row"),
+        #[cfg(feature = "export-parquet")]
+        ExportFormat::Parquet => unreachable!("Parquet exports are not written by row"),
+    }
+    Ok(())
+}
+
+fn write_export(
+    out: &mut (impl Write + Send),
+    format: ExportFormat,
+    range: Option<&str>,
+    measurement_names: &[String],
+    key_values: &[Selector],
+) -> Result<()> {
+    let relevant = |m: &MeasurementData| {
+        if !measurement_names.is_empty() && !measurement_names.contains(&m.name) {
+            return false;
+        }
+        m.matches_selectors(key_values)
+    };
+
+    if format == ExportFormat::OpenMetrics {
+        let measurements = measurement_retrieval::walk_all(range)?
+            .map_ok(|commit| commit.measurements)
+            .flatten_ok()
+            .filter_ok(|m| relevant(m));
+        itertools::process_results(measurements, |measurements| {
+            write_openmetrics(out, measurements)
+        })??;
+        out.flush()?;
+        return Ok(());
+    }
+
+    #[cfg(feature = "export-parquet")]
+    if format == ExportFormat::Parquet {
+        let mut parquet = parquet_export::ParquetExport::new(&mut *out)?;
+        for commit in measurement_retrieval::walk_all(range)? {
+            let commit = commit?;
+            for measurement in commit.measurements.iter().filter(|m| relevant(m)) {
+                parquet.push(&commit.commit, measurement)?;
+            }
+        }
+        parquet.finish()?;
+        out.flush()?;
+        return Ok(());
+    }
+
+    if format == ExportFormat::Csv {
+        writeln!(out, "commit,epoch,name,timestamp,val,key_values")?;
+    }
+
+    for commit in measurement_retrieval::walk_all(range)? {
+        let commit = commit?;
+        for measurement in commit.measurements.iter().filter(|m| relevant(m)) {
+            write_row(out, format, &commit.commit, measurement)?;
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Write all stored measurements, optionally restricted to a commit range, without building a
+/// report. Writes to stdout if `output` is `-`.
+pub fn export(
+    output: &Path,
+    format: ExportFormat,
+    range: Option<&str>,
+    measurement_names: &[String],
+    key_values: &[Selector],
+) -> Result<()> {
+    if output == Path::new("-") {
+        let mut out = BufWriter::new(io::stdout());
+        match write_export(&mut out, format, range, measurement_names, key_values) {
+            Err(e)
+                if e.downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.kind() == ErrorKind::BrokenPipe) =>
+            {
+                Ok(())
+            }
+            res => res,
+        }
+    } else {
+        let mut out = BufWriter::new(File::create(output)?);
+        write_export(&mut out, format, range, measurement_names, key_values)
+    }
+}
+
+#[cfg(feature = "export-parquet")]
+mod parquet_export {
+    use std::{io::Write, sync::Arc};
+
+    use anyhow::{Context, Result};
+    use itertools::Itertools;
+    use parquet::{
+        basic::Compression,
+        data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int64Type},
+        file::{
+            properties::WriterProperties,
+            writer::{SerializedFileWriter, SerializedRowGroupWriter},
+        },
+        schema::parser::parse_message_type,
+    };
+
+    use crate::data::MeasurementData;
+
+    const SCHEMA: &str = "
+        message measurement {
+            REQUIRED BYTE_ARRAY commit (UTF8);
+            REQUIRED INT64 epoch;
+            REQUIRED BYTE_ARRAY name (UTF8);
+            REQUIRED DOUBLE timestamp;
+            REQUIRED DOUBLE val;
+            REQUIRED GROUP key_values (MAP) {
+                REPEATED GROUP key_value {
+                    REQUIRED BYTE_ARRAY key (UTF8);
+                    REQUIRED BYTE_ARRAY value (UTF8);
+                }
+            }
+        }
+    ";
+
+    /// Number of measurements buffered before they are written as a row group.
+    const ROW_GROUP_SIZE: usize = 64 * 1024;
+
+    /// Columns of the measurements of the next row group.
+    #[derive(Default)]
+    struct Columns {
+        commit: Vec<ByteArray>,
+        epoch: Vec<i64>,
+        name: Vec<ByteArray>,
+        timestamp: Vec<f64>,
+        val: Vec<f64>,
+        key: Vec<ByteArray>,
+        value: Vec<ByteArray>,
+        /// Definition and repetition levels of the key-values. An empty map is a single undefined
+        /// entry, further entries of a map repeat the first.
+        key_value_def_levels: Vec<i16>,
+        key_value_rep_levels: Vec<i16>,
+    }
+
+    /// Parquet file of measurements, written as a row group whenever enough are buffered.
+    pub(super) struct ParquetExport<W: Write + Send> {
+        writer: SerializedFileWriter<W>,
+        columns: Columns,
+    }
+
+    impl<W: Write + Send> ParquetExport<W> {
+        pub(super) fn new(out: W) -> Result<Self> {
+            let schema = Arc::new(parse_message_type(SCHEMA)?);
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            Ok(ParquetExport {
+                writer: SerializedFileWriter::new(out, schema, Arc::new(properties))?,
+                columns: Columns::default(),
+            })
+        }
+
+        pub(super) fn push(&mut self, commit: &str, measurement: &MeasurementData) -> Result<()> {
+            let columns = &mut self.columns;
+            columns.commit.push(commit.into());
+            columns.epoch.push(measurement.epoch.into());
+            columns.name.push(measurement.name.as_str().into());
+            columns.timestamp.push(measurement.timestamp);
+            columns.val.push(measurement.val);
+            if measurement.key_values.is_empty() {
+                columns.key_value_def_levels.push(0);
+                columns.key_value_rep_levels.push(0);
+            }
+            for (i, (k, v)) in measurement.key_values.iter().sorted().enumerate() {
+                columns.key.push(k.as_str().into());
+                columns.value.push(v.as_str().into());
+                columns.key_value_def_levels.push(1);
+                columns.key_value_rep_levels.push(i16::from(i > 0));
+            }
+            if columns.commit.len() >= ROW_GROUP_SIZE {
+                self.write_row_group()?;
+            }
+            Ok(())
+        }
+
+        fn write_row_group(&mut self) -> Result<()> {
+            let columns = std::mem::take(&mut self.columns);
+            let levels = (
+                columns.key_value_def_levels.as_slice(),
+                columns.key_value_rep_levels.as_slice(),
+            );
+            let mut row_group = self.writer.next_row_group()?;
+            write_column::<ByteArrayType, _>(&mut row_group, &columns.commit, None)?;
+            write_column::<Int64Type, _>(&mut row_group, &columns.epoch, None)?;
+            write_column::<ByteArrayType, _>(&mut row_group, &columns.name, None)?;
+            write_column::<DoubleType, _>(&mut row_group, &columns.timestamp, None)?;
+            write_column::<DoubleType, _>(&mut row_group, &columns.val, None)?;
+            write_column::<ByteArrayType, _>(&mut row_group, &columns.key, Some(levels))?;
+            write_column::<ByteArrayType, _>(&mut row_group, &columns.value, Some(levels))?;
+            row_group.close()?;
+            Ok(())
+        }
+
+        /// Write the remaining measurements and the footer of the file.
+        pub(super) fn finish(mut self) -> Result<()> {
+            if !self.columns.commit.is_empty() {
+                self.write_row_group()?;
+            }
+            self.writer.close()?;
+            Ok(())
+        }
+    }
+
+    fn write_column<T: DataType, W: Write + Send>(
+        row_group: &mut SerializedRowGroupWriter<'_, W>,
+        values: &[T::T],
+        levels: Option<(&[i16], &[i16])>,
+    ) -> Result<()> {
+        let mut column = row_group
+            .next_column()?
+            .context("Parquet schema has fewer columns than written")?;
+        column.typed::<T>().write_batch(
+            values,
+            levels.map(|(def_levels, _)| def_levels),
+            levels.map(|(_, rep_levels)| rep_levels),
+        )?;
+        column.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn measurement() -> MeasurementData {
+        MeasurementData {
+            epoch: 0,
+            name: "timer".to_string(),
+            timestamp: 1234.0,
+            val: 42.0,
+            key_values: [
+                ("os".to_string(), "linux".to_string()),
+                ("arch".to_string(), "x86".to_string()),
+            ]
+            .into(),
+        }
+    }
+
+    #[test]
+    fn csv_row() {
+        let mut out = Vec::new();
+        write_row(&mut out, ExportFormat::Csv, "abc", &measurement()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "abc,0,timer,1234.0,42.0,arch=x86;os=linux\n"
+        );
+    }
+
+    #[test]
+    fn csv_escaping() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn json_row() {
+        let mut out = Vec::new();
+        write_row(&mut out, ExportFormat::Json, "abc", &measurement()).unwrap();
+        let line = String::from_utf8(out).unwrap();
+        assert!(line.starts_with("{\"commit\":\"abc\",\"epoch\":0,\"name\":\"timer\""));
+        assert!(line.ends_with("}\n"));
+    }
+
+    #[test]
+    fn openmetrics_latest_value_per_series() {
+        let older = MeasurementData {
+            timestamp: 1000.0,
+            val: 1.0,
+            ..measurement()
+        };
+        let other = MeasurementData {
+            epoch: 0,
+            name: "build-time".to_string(),
+            timestamp: 1.0,
+            val: f64::INFINITY,
+            key_values: [("path".to_string(), "C:\\\"x\"".to_string())].into(),
+        };
+        let mut out = Vec::new();
+        write_openmetrics(&mut out, [measurement(), older, other].into_iter()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# TYPE git_perf_build_time gauge\n\
+             git_perf_build_time{path=\"C:\\\\\\\"x\\\"\"} +Inf\n\
+             # TYPE git_perf_timer gauge\n\
+             git_perf_timer{arch=\"x86\",os=\"linux\"} 42.0\n\
+             # EOF\n"
+        );
+    }
+
+    #[test]
+    fn openmetrics_names() {
+        assert_eq!(openmetrics_name("timer"), "timer");
+        assert_eq!(openmetrics_name("rss.max-kb"), "rss_max_kb");
+        assert_eq!(openmetrics_name("99th"), "_99th");
+    }
+
+    #[cfg(feature = "export-parquet")]
+    #[test]
+    fn parquet_rows() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let without_key_values = MeasurementData {
+            name: "build-time".to_string(),
+            key_values: Default::default(),
+            ..measurement()
+        };
+        let mut file = tempfile::tempfile().unwrap();
+        let mut parquet = parquet_export::ParquetExport::new(&mut file).unwrap();
+        parquet.push("abc", &measurement()).unwrap();
+        parquet.push("def", &without_key_values).unwrap();
+        parquet.push("abc", &measurement()).unwrap();
+        parquet.finish().unwrap();
+
+        let reader = SerializedFileReader::new(file).unwrap();
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_string())
+            .collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], rows[2]);
+        assert_eq!(
+            rows[0],
+            r#"{commit: "abc", epoch: 0, name: "timer", timestamp: 1234.0, val: 42.0, key_values: {"arch" -> "x86", "os" -> "linux"}}"#
+        );
+        assert_eq!(
+            rows[1],
+            r#"{commit: "def", epoch: 0, name: "build-time", timestamp: 1234.0, val: 42.0, key_values: {}}"#
+        );
+    }
+}
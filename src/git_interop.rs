@@ -1,9 +1,13 @@
 use std::{
+    collections::{HashMap, HashSet},
     env::current_dir,
-    io,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
     path::{Path, PathBuf},
-    process::{self},
-    time::Duration,
+    process::{self, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, bail, Context, Result};
@@ -11,6 +15,13 @@ use backoff::{Error, ExponentialBackoffBuilder};
 use itertools::Itertools;
 use thiserror::Error;
 
+use crate::{
+    config,
+    events::Event,
+    progress::Progress,
+    serialization::{deserialize, lines_within_budget},
+};
+
 #[derive(Debug, Error)]
 enum GitError {
     #[error("Git failed to execute.\n\nstdout:\n{stdout}\nstderr:\n{stderr}")]
@@ -20,16 +31,93 @@ enum GitError {
     IoError(#[from] io::Error),
 }
 
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Guarantee that no network operations are attempted for the rest of the process and that
+/// reading measurements fails if they are not available locally.
+pub fn set_offline() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+static NO_NETWORK: AtomicBool = AtomicBool::new(false);
+
+/// Make every fetch and push fail immediately for the rest of the process. Unlike
+/// [`set_offline`], reading local measurements is unaffected.
+pub fn set_no_network() {
+    NO_NETWORK.store(true, Ordering::Relaxed);
+}
+
+fn ensure_online(operation: &str, remote: &str) -> Result<()> {
+    if NO_NETWORK.load(Ordering::Relaxed) {
+        bail!("Cannot {operation} '{remote}': network disabled by --no-network");
+    }
+    if OFFLINE.load(Ordering::Relaxed) {
+        bail!("Refusing to {operation} '{remote}' in offline mode");
+    }
+    Ok(())
+}
+
+/// In offline mode, fail fast if the measurements have never been fetched or added locally
+/// instead of silently reporting that there are none.
+fn ensure_notes_available() -> Result<()> {
+    if !OFFLINE.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if !existing_notes_refs()?.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "No measurements available locally in offline mode: {REFS_NOTES_BRANCH} is missing. \
+         Run 'git perf pull' while online first."
+    )
+}
+
 fn run_git(args: &[&str], working_dir: &Option<&Path>) -> Result<String, GitError> {
-    let working_dir = working_dir.map(PathBuf::from).unwrap_or(current_dir()?);
+    run_git_with_input(args, working_dir, None)
+}
+
+fn git_command(args: &[&str], working_dir: &Option<&Path>) -> Result<process::Command, GitError> {
+    let working_dir = match working_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => current_dir()?,
+    };
 
-    let output = process::Command::new("git")
+    let mut command = process::Command::new("git");
+    if NO_NETWORK.load(Ordering::Relaxed) {
+        // Objects missing in partial clones must not be fetched on demand either
+        command.env("GIT_NO_LAZY_FETCH", "1");
+    }
+    command
         // TODO(kaihowl) set correct encoding and lang?
         .env("LANG", "")
         .env("LC_ALL", "C")
         .current_dir(working_dir)
-        .args(args)
-        .output()?;
+        .args(args);
+    Ok(command)
+}
+
+fn run_git_with_input(
+    args: &[&str],
+    working_dir: &Option<&Path>,
+    input: Option<&str>,
+) -> Result<String, GitError> {
+    let mut child = git_command(args, working_dir)?
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Write from a separate thread to not deadlock on a full stdout pipe.
+    let output = thread::scope(|s| {
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            s.spawn(move || stdin.write_all(input.as_bytes()));
+        }
+        child.wait_with_output()
+    })?;
 
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
 
@@ -41,50 +129,664 @@ fn run_git(args: &[&str], working_dir: &Option<&Path>) -> Result<String, GitErro
     Ok(stdout)
 }
 
+/// Like [`run_git`] for fetches and pushes, showing the transfer status git reports on stderr,
+/// e.g. `Receiving objects: 42% (21/50), 1.20 MiB | 2.40 MiB/s`, next to `message`.
+fn run_git_with_progress(
+    args: &[&str],
+    working_dir: &Option<&Path>,
+    message: &str,
+) -> Result<String, GitError> {
+    let progress = Progress::spinner(message);
+    if !progress.is_visible() {
+        return run_git(args, working_dir);
+    }
+    // The option belongs to the subcommand and git omits the status if stderr is no terminal
+    let mut args = args.to_vec();
+    args.insert(1, "--progress");
+    let mut child = git_command(&args, working_dir)?
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stderr_pipe = child.stderr.take().expect("stderr is piped");
+
+    let (stderr, output) = thread::scope(|s| {
+        let progress = &progress;
+        let reader = s.spawn(move || -> io::Result<Vec<u8>> {
+            let mut stderr = Vec::new();
+            let mut status = Vec::new();
+            // Status lines are overwritten in place with '\r'
+            for byte in BufReader::new(stderr_pipe).bytes() {
+                let byte = byte?;
+                stderr.push(byte);
+                if byte != b'\r' && byte != b'\n' {
+                    status.push(byte);
+                } else if !status.is_empty() {
+                    progress
+                        .set_message(format!("{message}: {}", String::from_utf8_lossy(&status)));
+                    status.clear();
+                }
+            }
+            Ok(stderr)
+        });
+        let output = child.wait_with_output();
+        (reader.join().expect("stderr reader panicked"), output)
+    });
+    let (stderr, output) = (stderr?, output?);
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&stderr).to_string();
+        return Err(GitError::ExecError { stdout, stderr });
+    }
+
+    Ok(stdout)
+}
+
 const REFS_NOTES_BRANCH: &str = "refs/notes/perf-v3";
 
-pub fn add_note_line_to_head(line: &str) -> Result<()> {
+/// Namespace of the sharded notes refs, e.g. `refs/notes/perf-v3-shards/2024`. A separate namespace
+/// as git cannot store refs below the existing `refs/notes/perf-v3` ref.
+const REFS_NOTES_SHARDS: &str = "refs/notes/perf-v3-shards/";
+
+/// Where sharded notes refs fetched from a remote are kept until merged.
+const REFS_NOTES_MERGE_SHARDS: &str = "refs/notes/perf-v3-merge-shards/";
+
+/// Temporary refs created while writing or merging measurements. Crashed or killed operations
+/// leave them behind.
+const REFS_NOTES_TEMP_PATTERNS: [&str; 2] =
+    ["refs/notes/perf-v3-add-*", "refs/notes/perf-v3-merge-*"];
+
+pub const DEFAULT_TEMP_REF_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Whether new measurements are written to sharded notes refs, configured as
+/// `[storage] shards = "year"`.
+fn sharded() -> Result<bool> {
+    match config::determine_storage_shards_from_config().as_deref() {
+        None => Ok(false),
+        Some("year") => Ok(true),
+        Some(other) => bail!("Unknown shard scheme '{other}'"),
+    }
+}
+
+/// Gregorian year of a unix timestamp in UTC.
+fn year_of(timestamp: f64) -> i64 {
+    // civil_from_days by Howard Hinnant with days counted from 0000-03-01
+    let days = (timestamp / 86400.0).floor() as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let year = year_of_era + era * 400;
+    if month_from_march >= 10 {
+        year + 1
+    } else {
+        year
+    }
+}
+
+/// The shard ref a serialized measurement line belongs to: the year of its timestamp. Lines
+/// that cannot be parsed are stored in the shard of the current year.
+fn shard_ref(line: &str) -> String {
+    let timestamp = deserialize(line).first().map_or_else(
+        || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("System time before unix epoch")
+                .as_secs_f64()
+        },
+        |m| m.timestamp,
+    );
+    format!("{REFS_NOTES_SHARDS}{}", year_of(timestamp))
+}
+
+/// The unsharded and all sharded notes refs that exist locally.
+fn existing_notes_refs() -> Result<Vec<String>> {
+    let output = run_git(
+        &[
+            "for-each-ref",
+            "--format=%(refname)",
+            REFS_NOTES_BRANCH,
+            REFS_NOTES_SHARDS,
+        ],
+        &None,
+    )
+    .context("Failed to list notes refs")?;
+    Ok(output.lines().map(str::to_owned).collect())
+}
+
+/// The existing notes refs with the object ids they point to, one `<ref> <oid>` per line. Changes
+/// whenever measurements are added, fetched, or removed.
+pub fn notes_refs_state() -> Result<String> {
     run_git(
         &[
-            "notes",
-            "--ref",
+            "for-each-ref",
+            "--format=%(refname) %(objectname)",
             REFS_NOTES_BRANCH,
-            "append",
+            REFS_NOTES_SHARDS,
+        ],
+        &None,
+    )
+    .context("Failed to list notes refs")
+}
+
+/// The notes refs to read measurements from. Falls back to the unsharded ref if none exist yet.
+fn notes_refs() -> Result<Vec<String>> {
+    let refs = existing_notes_refs()?;
+    if refs.is_empty() {
+        Ok(vec![REFS_NOTES_BRANCH.to_owned()])
+    } else {
+        Ok(refs)
+    }
+}
+
+/// Advisory lock serializing writes and pushes of concurrent git-perf processes on the same
+/// repository. Released when dropped.
+struct LocalLock {
+    _file: Option<File>,
+}
+
+/// The git dir shared by all worktrees of the repository.
+fn common_dir(work_dir: Option<&Path>) -> Result<PathBuf> {
+    let git_dir = run_git(&["rev-parse", "--git-common-dir"], &work_dir)
+        .context("Failed to determine git directory")?;
+    let mut path = work_dir.map(Path::to_path_buf).unwrap_or_default();
+    path.push(git_dir.trim());
+    Ok(path)
+}
+
+/// Directory for files git-perf can rebuild at any time, e.g. `.git/git-perf/cache`.
+pub fn cache_dir() -> Result<PathBuf> {
+    Ok(common_dir(None)?.join("git-perf").join("cache"))
+}
+
+/// Take the lock on `git-perf.lock` in the common git dir if enabled with
+/// `[storage] local_lock = true`, blocking until it becomes available.
+fn local_lock(work_dir: Option<&Path>) -> Result<LocalLock> {
+    if !config::determine_local_lock_from_config() {
+        return Ok(LocalLock { _file: None });
+    }
+    let path = common_dir(work_dir)?.join("git-perf.lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+    lock_exclusive(&file).with_context(|| format!("Failed to lock {}", path.display()))?;
+    Ok(LocalLock { _file: Some(file) })
+}
+
+#[cfg(unix)]
+fn lock_exclusive(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the descriptor stays open for the duration of the call. The lock is released when
+    // the file is closed.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn lock_exclusive(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Create a new repository in `dir` with a local committer identity.
+pub fn init_repo(dir: &Path) -> Result<()> {
+    let dir = Some(dir);
+    run_git(&["init", "--quiet"], &dir).context("Failed to create repository")?;
+    run_git(&["config", "user.name", "git-perf"], &dir)?;
+    run_git(&["config", "user.email", "git-perf@example.com"], &dir)?;
+    Ok(())
+}
+
+/// Commit all changes of the working tree, even if there are none.
+pub fn commit_all(message: &str) -> Result<()> {
+    run_git(&["add", "--all"], &None)?;
+    run_git(
+        &["commit", "--quiet", "--allow-empty", "-m", message],
+        &None,
+    )
+    .context("Failed to create commit")?;
+    Ok(())
+}
+
+pub fn add_note_line_to_head(line: &str) -> Result<()> {
+    add_note_line(line, "HEAD")
+}
+
+pub fn add_note_line(line: &str, commit: &str) -> Result<()> {
+    let _lock = local_lock(None)?;
+    if !sharded()? {
+        if config::determine_max_note_size_from_config().is_some() {
+            bail!("[storage] max_note_size requires sharding with [storage] shards = \"year\"");
+        }
+        return append_note(REFS_NOTES_BRANCH, line, commit);
+    }
+    let shards = line
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .into_group_map_by(|l| shard_ref(l));
+    for (shard, lines) in shards.into_iter().sorted() {
+        append_to_shard(&shard, &lines, commit)?;
+    }
+    Ok(())
+}
+
+/// The `chunk`-th ref of a shard holding the measurements that did not fit into the notes of
+/// the previous ones: `<shard>`, `<shard>.1`, `<shard>.2`, ...
+fn chunk_ref(shard: &str, chunk: usize) -> String {
+    if chunk == 0 {
+        shard.to_owned()
+    } else {
+        format!("{shard}.{chunk}")
+    }
+}
+
+/// Size in bytes of the note of `commit` in `notes_ref`, 0 if there is none.
+fn note_size(notes_ref: &str, commit: &str) -> Result<usize> {
+    let note = match run_git(&["notes", "--ref", notes_ref, "list", commit], &None) {
+        Ok(note) => note,
+        Err(GitError::ExecError { .. }) => return Ok(0),
+        Err(e) => return Err(e).context("Failed to look up note"),
+    };
+    let size = run_git(&["cat-file", "-s", note.trim()], &None).context("Failed to size note")?;
+    size.trim().parse().context("Failed to parse note size")
+}
+
+/// Append `lines` to the note of `commit` in `shard`. With `[storage] max_note_size`, lines that
+/// would grow the note beyond it are appended to the notes of the shard's chunk refs instead.
+/// Reads reassemble the chunks as they fan out over all shard refs.
+fn append_to_shard(shard: &str, lines: &[&str], commit: &str) -> Result<()> {
+    let Some(budget) = config::determine_max_note_size_from_config() else {
+        return append_note(shard, &lines.iter().join("\n"), commit);
+    };
+    let mut remaining = lines;
+    for chunk in 0.. {
+        if remaining.is_empty() {
+            break;
+        }
+        let notes_ref = chunk_ref(shard, chunk);
+        let used = note_size(&notes_ref, commit)?;
+        let fitting = lines_within_budget(remaining, budget.saturating_sub(used));
+        // A single line larger than the budget still needs a place
+        let fitting = if fitting == 0 && used == 0 {
+            1
+        } else {
+            fitting
+        };
+        if fitting > 0 {
+            append_note(&notes_ref, &remaining[..fitting].iter().join("\n"), commit)?;
+            remaining = &remaining[fitting..];
+        }
+    }
+    Ok(())
+}
+
+fn append_note(notes_ref: &str, line: &str, commit: &str) -> Result<()> {
+    // Passed on stdin as the lines of large imports exceed the limit of command line arguments
+    run_git_with_input(
+        &[
+            "notes", "--ref", notes_ref, "append",
             // TODO(kaihowl) disabled until #96 is solved
             // "--no-separator",
-            "-m",
-            line,
+            "-F", "-", commit,
         ],
         &None,
+        Some(line),
     )
     .context("Failed to add new measurement")?;
 
     Ok(())
 }
 
+/// Read the raw note lines of a single commit. Returns no lines if the commit has no note.
+pub fn read_note_lines(commit: &str) -> Result<Vec<String>> {
+    ensure_notes_available()?;
+    let mut lines = Vec::new();
+    for notes_ref in notes_refs()? {
+        lines.extend(read_note_lines_of(&notes_ref, commit)?);
+    }
+    Ok(lines)
+}
+
+fn read_note_lines_of(notes_ref: &str, commit: &str) -> Result<Vec<String>> {
+    match run_git(&["notes", "--ref", notes_ref, "show", commit], &None) {
+        Ok(note) => Ok(note.lines().map(str::to_owned).collect()),
+        Err(GitError::ExecError { stderr, .. }) if stderr.contains("no note found") => {
+            Ok(Vec::new())
+        }
+        Err(e) => Err(e).context("Failed to read measurements"),
+    }
+}
+
+/// Replace the measurements of a single commit. An empty content removes all of them.
+/// Lines already stored in one of the notes refs stay there, new lines are added as usual.
+pub fn replace_note(content: &str, commit: &str) -> Result<()> {
+    let refs = notes_refs()?;
+    if let [notes_ref] = refs.as_slice() {
+        if !sharded()? {
+            return replace_note_of(notes_ref, content, commit);
+        }
+    }
+
+    let mut remaining = content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .collect_vec();
+    for notes_ref in refs {
+        let existing = read_note_lines_of(&notes_ref, commit)?
+            .into_iter()
+            .filter(|l| !l.trim().is_empty())
+            .collect_vec();
+        let kept = existing
+            .iter()
+            .filter(|line| match remaining.iter().position(|r| r == line) {
+                Some(pos) => {
+                    remaining.remove(pos);
+                    true
+                }
+                None => false,
+            })
+            .join("\n");
+        if kept.lines().count() != existing.len() {
+            replace_note_of(&notes_ref, &kept, commit)?;
+        }
+    }
+    if remaining.is_empty() {
+        Ok(())
+    } else {
+        add_note_line(&remaining.join("\n"), commit)
+    }
+}
+
+fn replace_note_of(notes_ref: &str, content: &str, commit: &str) -> Result<()> {
+    if content.trim().is_empty() {
+        run_git(
+            &[
+                "notes",
+                "--ref",
+                notes_ref,
+                "remove",
+                "--ignore-missing",
+                commit,
+            ],
+            &None,
+        )
+        .context("Failed to remove measurements")?;
+    } else {
+        run_git_with_input(
+            &[
+                "notes", "--ref", notes_ref, "add", "--force", "-F", "-", commit,
+            ],
+            &None,
+            Some(content),
+        )
+        .context("Failed to rewrite measurements")?;
+    }
+
+    Ok(())
+}
+
+/// Paths changed by the given commit with respect to its first parent.
+pub fn changed_paths(commit: &str) -> Result<Vec<String>> {
+    let output = run_git(
+        &[
+            "diff-tree",
+            "--no-commit-id",
+            "--name-only",
+            "-r",
+            "--root",
+            "-m",
+            "--first-parent",
+            commit,
+        ],
+        &None,
+    )
+    .with_context(|| format!("Failed to determine changed paths of {commit}"))?;
+
+    Ok(output.lines().map(str::to_owned).collect())
+}
+
+/// Values of all trailers with the given key in the message of `commit`.
+pub fn commit_trailers(commit: &str, key: &str) -> Result<Vec<String>> {
+    let output = run_git(
+        &[
+            "log",
+            "-1",
+            &format!("--format=%(trailers:key={key},valueonly,unfold)"),
+            commit,
+            "--",
+        ],
+        &None,
+    )
+    .with_context(|| format!("Failed to read trailers of {commit}"))?;
+
+    Ok(output
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+const CHANGE_ID_TRAILER: &str = "Change-Id";
+
+/// Identities of the selected commits (full hash to identity) that survive rewriting the
+/// commits, e.g. by a rebase: the Change-Id trailer if present, otherwise the stable patch id.
+/// Commits without changes and without a Change-Id have no identity.
+fn commit_identities(selection: &[&str], input: Option<&str>) -> Result<HashMap<String, String>> {
+    let trailer_format =
+        format!("--format=%H %(trailers:key={CHANGE_ID_TRAILER},valueonly,separator=%x2C)");
+    let trailers = run_git_with_input(
+        &[
+            &["--no-pager", "log", "--no-color", &trailer_format],
+            selection,
+        ]
+        .concat(),
+        &None,
+        input,
+    )
+    .context("Failed to read Change-Id trailers")?;
+
+    let mut identities: HashMap<String, String> = trailers
+        .lines()
+        .filter_map(|l| {
+            let (commit, change_ids) = l.split_once(' ')?;
+            let change_id = change_ids.split(',').next()?.trim();
+            (!change_id.is_empty()).then(|| (commit.to_owned(), format!("change-id:{change_id}")))
+        })
+        .collect();
+
+    let patches = run_git_with_input(
+        &[
+            &[
+                "--no-pager",
+                "log",
+                "-p",
+                "--no-color",
+                "--no-ext-diff",
+                "--pretty=medium",
+            ],
+            selection,
+        ]
+        .concat(),
+        &None,
+        input,
+    )
+    .context("Failed to read patches")?;
+    let patch_ids = run_git_with_input(&["patch-id", "--stable"], &None, Some(&patches))
+        .context("Failed to compute patch ids")?;
+
+    for line in patch_ids.lines() {
+        if let Some((patch_id, commit)) = line.split_once(' ') {
+            identities
+                .entry(commit.to_owned())
+                .or_insert_with(|| format!("patch-id:{patch_id}"));
+        }
+    }
+
+    Ok(identities)
+}
+
+/// Identities of all commits in `range` (e.g. `old~5..old`), see [`commit_identities`].
+pub fn commit_identities_in_range(range: &str) -> Result<HashMap<String, String>> {
+    commit_identities(&[range, "--"], None)
+        .with_context(|| format!("Failed to identify commits in {range}"))
+}
+
+/// Identities of the given commits, see [`commit_identities`].
+pub fn commit_identities_of(commits: &[&str]) -> Result<HashMap<String, String>> {
+    if commits.is_empty() {
+        return Ok(HashMap::new());
+    }
+    commit_identities(
+        &["--no-walk=unsorted", "--stdin"],
+        Some(&(commits.join("\n") + "\n")),
+    )
+}
+
+/// Full hash of the commit `rev` (e.g. a branch name) points to.
+pub fn resolve_commit(rev: &str) -> Result<String> {
+    if rev.starts_with('-') {
+        bail!("Invalid revision '{rev}'");
+    }
+    let commit = run_git(
+        &[
+            "rev-parse",
+            "--verify",
+            "--end-of-options",
+            &format!("{rev}^{{commit}}"),
+        ],
+        &None,
+    )
+    .with_context(|| format!("Failed to resolve '{rev}' to a commit."))?;
+
+    Ok(commit.trim().to_owned())
+}
+
+/// Metadata of a commit shown in reports.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitInfo {
+    /// Committer time in seconds since the epoch
+    pub time: i64,
+    pub author: String,
+    /// Author date in the local time zone as `YYYY-MM-DD HH:MM`
+    pub date: String,
+    /// First line of the commit message
+    pub subject: String,
+}
+
+fn parse_commit_info(line: &str) -> Result<(&str, CommitInfo)> {
+    let [commit, time, author, date, subject] = line.split('\0').collect_vec()[..] else {
+        bail!("Unexpected commit metadata '{line}'");
+    };
+    Ok((
+        commit,
+        CommitInfo {
+            time: time.parse()?,
+            author: author.to_owned(),
+            date: date.to_owned(),
+            subject: subject.to_owned(),
+        },
+    ))
+}
+
+/// Metadata of each of the `commits`, in the same order.
+pub fn commit_infos(commits: &[&str]) -> Result<Vec<CommitInfo>> {
+    if commits.is_empty() {
+        return Ok(Vec::new());
+    }
+    let output = run_git_with_input(
+        &[
+            "log",
+            "--no-walk=unsorted",
+            "--stdin",
+            "--date=format-local:%Y-%m-%d %H:%M",
+            "--format=%H%x00%ct%x00%an%x00%ad%x00%s",
+        ],
+        &None,
+        Some(&(commits.join("\n") + "\n")),
+    )
+    .context("Failed to retrieve commit metadata")?;
+    let infos: HashMap<_, _> = output.lines().map(parse_commit_info).try_collect()?;
+    commits
+        .iter()
+        .map(|commit| {
+            infos
+                .get(commit)
+                .cloned()
+                .ok_or_else(|| anyhow!("No metadata of commit {commit}"))
+        })
+        .collect()
+}
+
+/// Committer time of each of the `commits` in seconds since the epoch, in the same order.
+pub fn commit_times(commits: &[&str]) -> Result<Vec<i64>> {
+    Ok(commit_infos(commits)?
+        .into_iter()
+        .map(|info| info.time)
+        .collect())
+}
+
+/// Number of first-parent commits starting at `start` that were committed at most `period`
+/// before it, including `start` itself.
+pub fn count_commits_within(start: &str, period: Duration) -> Result<usize> {
+    let commit = resolve_commit(start)?;
+    let [time] = commit_times(&[&commit])?[..] else {
+        bail!("No commit time of {commit}");
+    };
+    let since = format!("--since=@{}", time - period.as_secs() as i64);
+    let count = run_git(
+        &["rev-list", "--first-parent", "--count", &since, &commit],
+        &None,
+    )
+    .with_context(|| format!("Failed to count the commits of {start}"))?;
+    Ok(count.trim().parse::<usize>()?.max(1))
+}
+
 pub fn get_head_revision() -> Result<String> {
     let head = run_git(&["rev-parse", "HEAD"], &None).context("Failed to parse HEAD.")?;
 
     Ok(head.trim().to_owned())
 }
-pub fn fetch(work_dir: Option<&Path>) -> Result<()> {
+/// Remote used if none is given on the command line or in the config.
+pub const DEFAULT_REMOTE: &str = "origin";
+
+pub fn fetch(work_dir: Option<&Path>, remote: &str) -> Result<()> {
+    ensure_online("fetch from", remote)?;
+    // Sharded refs are fetched into a separate namespace as refspec globs need a destination
+    let shards_refspec = format!("+{REFS_NOTES_SHARDS}*:{REFS_NOTES_MERGE_SHARDS}*");
+    let refspec = if sharded()? {
+        shards_refspec.as_str()
+    } else {
+        REFS_NOTES_BRANCH
+    };
     // Use git directly to avoid having to implement ssh-agent and/or extraHeader handling
-    run_git(&["fetch", "origin", REFS_NOTES_BRANCH], &work_dir)
-        .context("Failed to fetch performance measurements.")?;
+    run_git_with_progress(
+        &["fetch", remote, refspec],
+        &work_dir,
+        &format!("Fetching from {remote}"),
+    )
+    .with_context(|| format!("Failed to fetch performance measurements from {remote}."))?;
 
     Ok(())
 }
 
-pub fn reconcile() -> Result<()> {
-    let _ = run_git(
+fn merge_notes(notes_ref: &str, other: &str) -> Result<()> {
+    run_git(
         &[
             "notes",
             "--ref",
-            REFS_NOTES_BRANCH,
+            notes_ref,
             "merge",
             "-s",
             "cat_sort_uniq",
-            "FETCH_HEAD",
+            other,
         ],
         &None,
     )
@@ -92,36 +794,43 @@ pub fn reconcile() -> Result<()> {
     Ok(())
 }
 
+pub fn reconcile() -> Result<()> {
+    if !sharded()? {
+        return merge_notes(REFS_NOTES_BRANCH, "FETCH_HEAD");
+    }
+    for fetched_ref in unmerged_shard_refs()? {
+        let shard = fetched_ref.replacen(REFS_NOTES_MERGE_SHARDS, REFS_NOTES_SHARDS, 1);
+        merge_notes(&shard, &fetched_ref)?;
+        run_git(&["update-ref", "-d", &fetched_ref], &None)
+            .with_context(|| format!("Failed to remove {fetched_ref}"))?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, Error)]
 enum PushError {
     #[error("A ref failed to be pushed:\n{stdout}\n{stderr}")]
     RefFailedToPush { stdout: String, stderr: String },
 }
 
-pub fn raw_push(work_dir: Option<&Path>) -> Result<()> {
-    // TODO(kaihowl) configure remote?
-    // TODO(kaihowl) factor into constants
+pub fn raw_push(work_dir: Option<&Path>, remote: &str) -> Result<()> {
+    ensure_online("push to", remote)?;
+    let _lock = local_lock(work_dir)?;
     // TODO(kaihowl) capture output
-    let output = run_git(
-        &[
-            "push",
-            "--porcelain",
-            "origin",
-            format!("{REFS_NOTES_BRANCH}:{REFS_NOTES_BRANCH}").as_str(),
-        ],
-        &work_dir,
-    );
+    let refspecs = push_refspecs()?;
+    let mut args = vec!["push", "--porcelain", remote];
+    args.extend(refspecs.iter().map(String::as_str));
+    let output = run_git_with_progress(&args, &work_dir, &format!("Pushing to {remote}"));
 
     match output {
         Ok(_) => Ok(()),
         Err(GitError::ExecError { stdout, stderr }) => {
-            for line in stdout.lines() {
-                if !line.contains(format!("{REFS_NOTES_BRANCH}:").as_str()) {
-                    continue;
-                }
-                if !line.starts_with('!') {
-                    return Ok(());
-                }
+            let mut notes_lines = stdout
+                .lines()
+                .filter(|line| refspecs.iter().any(|r| line.contains(r.as_str())))
+                .peekable();
+            if notes_lines.peek().is_some() && notes_lines.all(|line| !line.starts_with('!')) {
+                return Ok(());
             }
             bail!(PushError::RefFailedToPush { stdout, stderr })
         }
@@ -129,19 +838,208 @@ pub fn raw_push(work_dir: Option<&Path>) -> Result<()> {
     }
 }
 
+fn push_refspecs() -> Result<Vec<String>> {
+    Ok(notes_refs()?
+        .into_iter()
+        .map(|notes_ref| format!("{notes_ref}:{notes_ref}"))
+        .collect_vec())
+}
+
+/// The porcelain status lines, e.g. `*\t<src>:<dst>\t[new reference]`, of the notes refs a push
+/// to `remote` would update without pushing anything. Rejected refs (`!`) are merged with the
+/// remote and pushed again by [`push`].
+pub fn push_dry_run(work_dir: Option<&Path>, remote: &str) -> Result<Vec<String>> {
+    ensure_online("push to", remote)?;
+    let refspecs = push_refspecs()?;
+    let mut args = vec!["push", "--dry-run", "--porcelain", remote];
+    args.extend(refspecs.iter().map(String::as_str));
+    let stdout = match run_git(&args, &work_dir) {
+        Ok(stdout) => stdout,
+        // Rejected refs still print their status
+        Err(GitError::ExecError { stdout, .. }) if stdout.lines().any(|l| l.starts_with('!')) => {
+            stdout
+        }
+        Err(e) => bail!(e),
+    };
+    Ok(stdout
+        .lines()
+        .filter(|line| refspecs.iter().any(|r| line.contains(r.as_str())))
+        .filter(|line| !line.starts_with('='))
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Notes of objects that no longer exist, as removed by [`prune`].
+#[derive(Debug, Default)]
+pub struct PruneSummary {
+    /// Objects whose notes were (or would be) removed
+    pub objects: Vec<String>,
+    /// Total size of these notes in bytes
+    pub bytes: u64,
+}
+
 // TODO(kaihowl) what happens with a git dir supplied with -C?
-pub fn prune() -> Result<()> {
+pub fn prune(dry_run: bool) -> Result<PruneSummary> {
     if is_shallow_repo().context("Could not determine if shallow clone.")? {
         // TODO(kaihowl) is this not already checked by git itself?
         bail!("Refusing to prune on a shallow repo")
     }
 
-    run_git(&["notes", "--ref", REFS_NOTES_BRANCH, "prune"], &None).context("Failed to prune.")?;
+    let notes_refs = notes_refs()?;
+    let progress = Progress::items("Pruning notes refs", notes_refs.len());
+    let mut summary = PruneSummary::default();
+    for notes_ref in notes_refs {
+        progress.inc(1);
+        let pruned = run_git(
+            &[
+                "notes",
+                "--ref",
+                &notes_ref,
+                "prune",
+                "--dry-run",
+                "--verbose",
+            ],
+            &None,
+        )
+        .context("Failed to prune.")?;
+        let pruned = pruned.lines().collect_vec();
+        if pruned.is_empty() {
+            continue;
+        }
+        let notes = run_git(&["notes", "--ref", &notes_ref, "list"], &None)
+            .context("Failed to list notes.")?;
+        let blobs = notes
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter(|(_, object)| pruned.contains(object))
+            .map(|(blob, _)| blob)
+            .join("\n");
+        summary.bytes += object_sizes(&blobs)?;
+        if !dry_run {
+            run_git(&["notes", "--ref", &notes_ref, "prune"], &None).context("Failed to prune.")?;
+        }
+        summary
+            .objects
+            .extend(pruned.into_iter().map(str::to_owned));
+    }
+
+    Ok(summary)
+}
 
-    Ok(())
+/// Total size in bytes of the newline-separated `objects`.
+fn object_sizes(objects: &str) -> Result<u64> {
+    Ok(object_size_list(objects)?.into_iter().sum())
+}
+
+/// Size in bytes of each of the newline-separated `objects`, in the same order.
+fn object_size_list(objects: &str) -> Result<Vec<u64>> {
+    run_git_with_input(
+        &["cat-file", "--batch-check=%(objectsize)"],
+        &None,
+        Some(&format!("{objects}\n")),
+    )
+    .context("Failed to determine object sizes.")?
+    .lines()
+    .map(|size| {
+        size.parse::<u64>()
+            .with_context(|| format!("Invalid object size '{size}'"))
+    })
+    .collect()
+}
+
+/// Size in bytes of the measurements of each annotated commit, summed over all notes refs, the
+/// largest first.
+pub fn note_sizes() -> Result<Vec<(String, u64)>> {
+    let mut sizes: HashMap<String, u64> = HashMap::new();
+    for notes_ref in existing_notes_refs()? {
+        let notes = run_git(&["notes", "--ref", &notes_ref, "list"], &None)
+            .context("Failed to list notes.")?;
+        let (blobs, commits): (Vec<_>, Vec<_>) = notes
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .unzip();
+        if blobs.is_empty() {
+            continue;
+        }
+        for (commit, size) in commits
+            .into_iter()
+            .zip(object_size_list(&blobs.join("\n"))?)
+        {
+            *sizes.entry(commit.to_owned()).or_default() += size;
+        }
+    }
+    Ok(sizes
+        .into_iter()
+        .sorted_by(|(a_commit, a), (b_commit, b)| b.cmp(a).then(a_commit.cmp(b_commit)))
+        .collect())
 }
 
-fn is_shallow_repo() -> Result<bool> {
+/// Number of notes and their total size in bytes at the notes commit `notes_commit`.
+fn notes_tree_size(notes_commit: &str) -> Result<(usize, u64)> {
+    let tree = run_git(&["ls-tree", "-r", "-l", notes_commit], &None)
+        .with_context(|| format!("Failed to list the notes of {notes_commit}"))?;
+    tree.lines()
+        .filter_map(|line| line.split_whitespace().nth(3))
+        .try_fold((0, 0), |(count, bytes), size| {
+            let size = size
+                .parse::<u64>()
+                .with_context(|| format!("Invalid object size '{size}'"))?;
+            Ok((count + 1, bytes + size))
+        })
+}
+
+/// Number of annotated commits and the size of their notes in bytes at the end of each month
+/// (`YYYY-MM`, committer time of the notes commits) in which measurements changed, oldest first.
+pub fn notes_size_history() -> Result<Vec<(String, usize, u64)>> {
+    let mut per_ref = Vec::new();
+    for notes_ref in existing_notes_refs()? {
+        let log = run_git(
+            &[
+                "log",
+                "--first-parent",
+                "--date=format:%Y-%m",
+                "--format=%H %cd",
+                &notes_ref,
+            ],
+            &None,
+        )
+        .with_context(|| format!("Failed to walk the history of {notes_ref}"))?;
+        // The log is newest first, so the first commit of a month is its last state
+        let sizes: Vec<(String, (usize, u64))> = log
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .unique_by(|(_, month)| *month)
+            .map(|(commit, month)| Ok((month.to_owned(), notes_tree_size(commit)?)))
+            .collect::<Result<_>>()?;
+        per_ref.push(sizes.into_iter().rev().collect_vec());
+    }
+    let months = per_ref
+        .iter()
+        .flatten()
+        .map(|(month, _)| month.clone())
+        .sorted()
+        .dedup()
+        .collect_vec();
+    // Refs without changes in a month keep their size of the last month they changed in
+    Ok(months
+        .into_iter()
+        .map(|month| {
+            let (count, bytes) = per_ref
+                .iter()
+                .filter_map(|sizes| {
+                    sizes
+                        .iter()
+                        .take_while(|(m, _)| *m <= month)
+                        .last()
+                        .map(|(_, size)| *size)
+                })
+                .fold((0, 0), |(count, bytes), (c, b)| (count + c, bytes + b));
+            (month, count, bytes)
+        })
+        .collect())
+}
+
+pub fn is_shallow_repo() -> Result<bool> {
     let output = run_git(&["rev-parse", "--is-shallow-repository"], &None)
         .context("Failed to determine if repo is a shallow clone.")?;
 
@@ -150,24 +1048,338 @@ fn is_shallow_repo() -> Result<bool> {
 
 // TODO(kaihowl) return a nested iterator / generator instead?
 pub fn walk_commits(num_commits: usize) -> Result<Vec<(String, Vec<String>)>> {
+    walk_commits_from("HEAD", num_commits)
+}
+
+/// `--notes=<ref>` arguments for `git log` to show the notes of all notes refs.
+fn notes_args() -> Result<Vec<String>> {
+    Ok(notes_refs()?
+        .into_iter()
+        .map(|notes_ref| format!("--notes={notes_ref}"))
+        .collect())
+}
+
+pub fn walk_commits_from(start: &str, num_commits: usize) -> Result<Vec<(String, Vec<String>)>> {
+    ensure_notes_available()?;
+    let num_commits_arg = num_commits.to_string();
+    let notes_args = notes_args()?;
+    let mut args = vec![
+        "--no-pager",
+        "log",
+        "--no-color",
+        "--ignore-missing",
+        "-n",
+        num_commits_arg.as_str(),
+        "--first-parent",
+        "--pretty=--,%H,%D%n%N",
+        "--decorate=full",
+    ];
+    args.extend(notes_args.iter().map(String::as_str));
+    args.extend(["--end-of-options", start, "--"]);
+    let progress = Progress::spinner("Walking commits");
+    let output = run_git(&args, &None)
+        .with_context(|| format!("Failed to retrieve commits from {start}"))?;
+    progress.finish();
+
+    let (commits, detected_shallow) = parse_log_with_notes(&output);
+
+    if detected_shallow && commits.len() < num_commits {
+        bail!("Refusing to continue as commit log depth was limited by shallow clone");
+    }
+
+    Ok(commits)
+}
+
+/// The last `num_commits` first-parent commits starting at `start`, newest first, without
+/// reading their notes.
+pub fn first_parent_commits(start: &str, num_commits: usize) -> Result<Vec<String>> {
+    let num_commits_arg = num_commits.to_string();
     let output = run_git(
         &[
-            "--no-pager",
-            "log",
-            "--no-color",
-            "--ignore-missing",
-            "-n",
-            num_commits.to_string().as_str(),
+            "rev-list",
             "--first-parent",
-            "--pretty=--,%H,%D%n%N",
-            "--decorate=full",
-            format!("--notes={REFS_NOTES_BRANCH}").as_str(),
-            "HEAD",
+            "-n",
+            &num_commits_arg,
+            "--end-of-options",
+            start,
+            "--",
+        ],
+        &None,
+    )
+    .with_context(|| format!("Failed to retrieve commits from {start}"))?;
+    let commits = output.lines().map(str::to_owned).collect_vec();
+    if commits.len() < num_commits && is_shallow_repo()? {
+        bail!("Refusing to continue as commit log depth was limited by shallow clone");
+    }
+    Ok(commits)
+}
+
+/// All commits with measurements and their note lines. If a `range` (e.g. `main~10..main`) is
+/// given, only commits within it are considered, otherwise all annotated commits.
+pub fn walk_notes(range: Option<&str>) -> Result<Vec<(String, Vec<String>)>> {
+    stream_notes(range)?.collect()
+}
+
+/// Like [`walk_notes`], but the commits are read one at a time from the output of `git log`
+/// while iterating. The notes must not be changed before the iteration ends.
+pub fn stream_notes(range: Option<&str>) -> Result<NotesLog> {
+    ensure_notes_available()?;
+    stream_notes_of(&notes_refs()?, range)
+}
+
+/// All commits with notes in the legacy `notes_ref` of an earlier git-perf version and their
+/// note lines as stored. No lines if the ref does not exist.
+pub fn walk_legacy_notes(notes_ref: &str) -> Result<Vec<(String, Vec<String>)>> {
+    if run_git(&["rev-parse", "--verify", "--quiet", notes_ref], &None).is_err() {
+        return Ok(Vec::new());
+    }
+    walk_notes_of(&[notes_ref.to_owned()], None)
+        .with_context(|| format!("Failed to read the legacy notes ref {notes_ref}"))
+}
+
+fn walk_notes_of(notes_refs: &[String], range: Option<&str>) -> Result<Vec<(String, Vec<String>)>> {
+    stream_notes_of(notes_refs, range)?.collect()
+}
+
+fn stream_notes_of(notes_refs: &[String], range: Option<&str>) -> Result<NotesLog> {
+    let notes_args = notes_refs
+        .iter()
+        .map(|notes_ref| format!("--notes={notes_ref}"))
+        .collect_vec();
+    let mut args = vec!["--no-pager", "log", "--no-color"];
+    match range {
+        Some(range) => {
+            args.extend(["--pretty=--,%H,%D%n%N", "--decorate=full"]);
+            args.extend(notes_args.iter().map(String::as_str));
+            args.extend([range, "--"]);
+            NotesLog::spawn(
+                &args,
+                None,
+                format!("Failed to retrieve commits for {range}"),
+            )
+        }
+        None => {
+            let mut commits = Vec::new();
+            for notes_ref in notes_refs {
+                let annotated = run_git(&["notes", "--ref", notes_ref, "list"], &None)
+                    .context("Failed to list annotated commits")?;
+                commits.extend(
+                    annotated
+                        .lines()
+                        .filter_map(|l| l.split_whitespace().nth(1).map(str::to_owned)),
+                );
+            }
+            let commits = commits.into_iter().unique().join("\n");
+            if commits.is_empty() {
+                return Ok(NotesLog::empty());
+            }
+            args.extend([
+                "--no-walk=unsorted",
+                "--stdin",
+                "--pretty=--,%H,%D%n%N",
+                "--decorate=full",
+            ]);
+            args.extend(notes_args.iter().map(String::as_str));
+            NotesLog::spawn(
+                &args,
+                Some(commits + "\n"),
+                "Failed to retrieve annotated commits".to_owned(),
+            )
+        }
+    }
+}
+
+/// Commits and their note lines, parsed from the `--,%H,%D%n%N` formatted output of a running
+/// `git log` line by line. Commits without notes are skipped.
+pub struct NotesLog {
+    /// `None` if there is nothing to read
+    child: Option<process::Child>,
+    lines: Option<io::Split<BufReader<process::ChildStdout>>>,
+    stderr: Option<thread::JoinHandle<io::Result<Vec<u8>>>>,
+    /// The commit whose note lines are read and its lines so far
+    current: Option<(String, Vec<String>)>,
+    /// Context of errors of git
+    failure: String,
+}
+
+impl NotesLog {
+    fn empty() -> Self {
+        NotesLog {
+            child: None,
+            lines: None,
+            stderr: None,
+            current: None,
+            failure: String::new(),
+        }
+    }
+
+    fn spawn(args: &[&str], input: Option<String>, failure: String) -> Result<Self> {
+        let mut child = git_command(args, &None)
+            .context(failure.clone())?
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(failure.clone())?;
+        // Write and drain from separate threads to not deadlock on a full pipe. Write errors
+        // surface as a failed git invocation.
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            thread::spawn(move || stdin.write_all(input.as_bytes()));
+        }
+        let mut stderr_pipe = child.stderr.take().expect("stderr is piped");
+        let stderr = thread::spawn(move || {
+            let mut stderr = Vec::new();
+            stderr_pipe.read_to_end(&mut stderr).map(|_| stderr)
+        });
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(NotesLog {
+            child: Some(child),
+            lines: Some(BufReader::new(stdout).split(b'\n')),
+            stderr: Some(stderr),
+            current: None,
+            failure,
+        })
+    }
+
+    /// Wait for git after all of its output is read.
+    fn finish(&mut self) -> Result<()> {
+        self.lines = None;
+        let Some(mut child) = self.child.take() else {
+            return Ok(());
+        };
+        let status = child.wait()?;
+        let stderr = match self.stderr.take().map(|stderr| stderr.join()) {
+            Some(Ok(Ok(stderr))) => String::from_utf8_lossy(&stderr).to_string(),
+            _ => String::new(),
+        };
+        if !status.success() {
+            return Err(anyhow!(GitError::ExecError {
+                stdout: String::new(),
+                stderr,
+            })
+            .context(self.failure.clone()));
+        }
+        Ok(())
+    }
+
+    fn next_commit(&mut self) -> Result<Option<(String, Vec<String>)>> {
+        while let Some(line) = self.lines.as_mut().and_then(Iterator::next) {
+            let line = String::from_utf8_lossy(&line?).into_owned();
+            if line.starts_with("--") {
+                let commit = line
+                    .split(',')
+                    .nth(1)
+                    .context("Could not read commit header.")?
+                    .to_owned();
+                match self.current.replace((commit, Vec::new())) {
+                    Some((commit, lines)) if !lines.is_empty() => return Ok(Some((commit, lines))),
+                    _ => {}
+                }
+            } else {
+                let (_, lines) = self
+                    .current
+                    .as_mut()
+                    .context("Note lines without a commit header")?;
+                lines.push(line);
+            }
+        }
+        self.finish()?;
+        Ok(self.current.take().filter(|(_, lines)| !lines.is_empty()))
+    }
+}
+
+impl Iterator for NotesLog {
+    type Item = Result<(String, Vec<String>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.next_commit();
+        if next.is_err() {
+            // Stop at the first error
+            self.lines = None;
+            self.current = None;
+        }
+        next.transpose()
+    }
+}
+
+impl Drop for NotesLog {
+    /// Stop git if the iteration ended early, e.g. because of a closed pipe while exporting.
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Move all measurements of the unsharded notes ref into the sharded refs and delete it.
+/// Lines already present in their shard, e.g. from an interrupted migration, are not copied again.
+/// Returns the number of migrated commits.
+pub fn migrate_to_shards() -> Result<usize> {
+    if !sharded()? {
+        bail!("Configure sharding with [storage] shards = \"year\" in .gitperfconfig first");
+    }
+    let Ok(old_head) = run_git(
+        &["rev-parse", "--verify", "--quiet", REFS_NOTES_BRANCH],
+        &None,
+    ) else {
+        return Ok(0);
+    };
+
+    let mut migrated = 0;
+    for (commit, lines) in walk_notes_of(&[REFS_NOTES_BRANCH.to_owned()], None)? {
+        let shards = lines
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+            .into_group_map_by(|l| shard_ref(l));
+        for (shard, lines) in shards.into_iter().sorted() {
+            let mut existing = Vec::new();
+            for notes_ref in existing_notes_refs()? {
+                if notes_ref == shard || notes_ref.starts_with(&format!("{shard}.")) {
+                    existing.extend(read_note_lines_of(&notes_ref, &commit)?);
+                }
+            }
+            let missing = lines
+                .into_iter()
+                .filter(|l| !existing.contains(l))
+                .map(String::as_str)
+                .collect_vec();
+            if !missing.is_empty() {
+                append_to_shard(&shard, &missing, &commit)?;
+            }
+        }
+        migrated += 1;
+    }
+
+    run_git(
+        &["update-ref", "-d", REFS_NOTES_BRANCH, old_head.trim()],
+        &None,
+    )
+    .with_context(|| format!("Failed to remove {REFS_NOTES_BRANCH}"))?;
+    Ok(migrated)
+}
+
+/// Commits pointed to by a tag. Annotated tags are peeled to the tagged commit.
+pub fn tagged_commits() -> Result<HashSet<String>> {
+    let output = run_git(
+        &[
+            "for-each-ref",
+            "--format=%(objectname) %(*objectname)",
+            "refs/tags",
         ],
         &None,
     )
-    .context("Failed to retrieve commits")?;
+    .context("Failed to list tags")?;
+    Ok(output.split_whitespace().map(str::to_owned).collect())
+}
 
+/// Parse the output of `git log --pretty=--,%H,%D%n%N` into commits and their note lines.
+/// Additionally returns whether a shallow clone boundary was encountered.
+fn parse_log_with_notes(output: &str) -> (Vec<(String, Vec<String>)>, bool) {
     let mut current_commit = None;
     let mut detected_shallow = false;
 
@@ -182,7 +1394,7 @@ pub fn walk_commits(num_commits: usize) -> Result<Vec<(String, Vec<String>)>> {
                     .to_owned(),
             );
 
-            detected_shallow |= info[2..].iter().any(|s| *s == "grafted");
+            detected_shallow |= info[2..].contains(&"grafted");
 
             None
         } else {
@@ -207,27 +1419,237 @@ pub fn walk_commits(num_commits: usize) -> Result<Vec<(String, Vec<String>)>> {
         })
         .collect();
 
-    if detected_shallow && commits.len() < num_commits {
-        bail!("Refusing to continue as commit log depth was limited by shallow clone");
+    (commits, detected_shallow)
+}
+
+/// Whether the current directory is inside the work tree of a git repository.
+pub fn is_inside_work_tree() -> bool {
+    run_git(&["rev-parse", "--is-inside-work-tree"], &None).is_ok_and(|out| out.trim() == "true")
+}
+
+/// Whether HEAD points to a commit directly instead of a branch, e.g. in CI checkouts.
+pub fn is_detached_head() -> Result<bool> {
+    match run_git(&["symbolic-ref", "--quiet", "HEAD"], &None) {
+        Ok(_) => Ok(false),
+        Err(GitError::ExecError { .. }) => Ok(true),
+        Err(e) => Err(e).context("Failed to resolve HEAD"),
     }
+}
 
-    Ok(commits)
+/// URL of the `remote`, `None` if it is not configured.
+pub fn remote_url(remote: &str) -> Option<String> {
+    run_git(&["remote", "get-url", remote], &None)
+        .ok()
+        .map(|url| url.trim().to_owned())
+}
+
+/// Problems of the local notes refs: symbolic refs, which are rewritten as regular refs by
+/// pushes and merges elsewhere, and refs not pointing to a commit.
+pub fn notes_ref_problems() -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    for notes_ref in existing_notes_refs()? {
+        if let Ok(target) = run_git(&["symbolic-ref", "--quiet", &notes_ref], &None) {
+            problems.push(format!(
+                "{notes_ref} is a symbolic ref to {}",
+                target.trim()
+            ));
+        } else if run_git(
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{notes_ref}^{{commit}}"),
+            ],
+            &None,
+        )
+        .is_err()
+        {
+            problems.push(format!("{notes_ref} does not point to a commit"));
+        }
+    }
+    Ok(problems)
+}
+
+/// Whether any notes refs exist locally, i.e. measurements were added or pulled.
+pub fn has_notes_refs() -> Result<bool> {
+    Ok(!existing_notes_refs()?.is_empty())
+}
+
+/// Temporary refs older than `max_age`, e.g. left behind by crashed operations.
+pub fn stale_temp_ref_names(max_age: Duration) -> Result<Vec<String>> {
+    Ok(list_stale_temp_refs(None, max_age)?
+        .into_iter()
+        .map(|(refname, _)| refname)
+        .collect())
+}
+
+/// Stale temporary refs with the objects they point to.
+fn list_stale_temp_refs(
+    work_dir: Option<&Path>,
+    max_age: Duration,
+) -> Result<Vec<(String, String)>> {
+    let mut args = vec!["for-each-ref", "--format=%(refname) %(objectname)"];
+    args.extend(REFS_NOTES_TEMP_PATTERNS);
+    let output = run_git(&args, &work_dir).context("Failed to list temporary refs")?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time before unix epoch")
+        .as_secs();
+    let mut stale = Vec::new();
+    for (refname, oid) in output
+        .lines()
+        .filter_map(|l| l.split_whitespace().collect_tuple())
+    {
+        let written = temp_ref_written_at(work_dir, refname)
+            .with_context(|| format!("Failed to determine the age of {refname}"))?;
+        if written.is_some_and(|written| is_stale(written, now, max_age)) {
+            stale.push((refname.to_owned(), oid.to_owned()));
+        }
+    }
+    Ok(stale)
+}
+
+/// When `refname` was last written in seconds since the unix epoch: the modification time of its
+/// loose ref file or, if packed, of the packed-refs file, which is at least as recent. Without
+/// either, e.g. with the reftable backend, the oldest entry of its reflog. Not the creator date
+/// of the ref, which is the date of the commit it points to and can be arbitrarily old even for a
+/// ref just created by a live operation.
+fn temp_ref_written_at(work_dir: Option<&Path>, refname: &str) -> Result<Option<u64>> {
+    let dir = match work_dir {
+        Some(dir) => dir.to_owned(),
+        None => current_dir()?,
+    };
+    for file in [refname, "packed-refs"] {
+        let path = run_git(&["rev-parse", "--git-path", file], &work_dir)?;
+        if let Ok(modified) = fs::metadata(dir.join(path.trim())).and_then(|m| m.modified()) {
+            return Ok(Some(
+                modified
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs()),
+            ));
+        }
+    }
+    let reflog = run_git(
+        &["reflog", "show", "--date=unix", "--format=%gd", refname],
+        &work_dir,
+    )?;
+    Ok(reflog.lines().last().and_then(parse_reflog_time))
+}
+
+/// Timestamp of a reflog selector printed with `--date=unix`, e.g. `notes/perf@{1700000000}`.
+fn parse_reflog_time(selector: &str) -> Option<u64> {
+    selector
+        .rsplit_once("@{")?
+        .1
+        .strip_suffix('}')?
+        .parse()
+        .ok()
 }
 
-pub fn pull(work_dir: Option<&Path>) -> Result<()> {
-    fetch(work_dir)?;
-    reconcile()
+/// Whether a temporary ref last written at `written` is at least `max_age` old relative to `now`.
+fn is_stale(written: u64, now: u64, max_age: Duration) -> bool {
+    now.saturating_sub(written) >= max_age.as_secs()
 }
 
-pub fn push(work_dir: Option<&Path>) -> Result<()> {
+/// Fetched shards that were not merged, e.g. after an interrupted pull.
+pub fn unmerged_shard_refs() -> Result<Vec<String>> {
+    let output = run_git(
+        &[
+            "for-each-ref",
+            "--format=%(refname)",
+            REFS_NOTES_MERGE_SHARDS,
+        ],
+        &None,
+    )
+    .context("Failed to list fetched shards")?;
+    Ok(output.lines().map(str::to_owned).collect())
+}
+
+/// Delete temporary perf refs older than `max_age` and return the names of the deleted refs.
+/// Each ref is only deleted if it still points to the object observed during listing. A
+/// concurrently running operation that updated its temporary ref in the meantime is left alone.
+pub fn gc_temp_refs(work_dir: Option<&Path>, max_age: Duration) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    for (refname, oid) in list_stale_temp_refs(work_dir, max_age)? {
+        match run_git(&["update-ref", "-d", &refname, &oid], &work_dir) {
+            Ok(_) => removed.push(refname),
+            // Losing the race against a live operation is fine, do not abort.
+            Err(e) => Event::warn(
+                "gc_temp_refs",
+                "skipped",
+                format!("Skipping removal of temporary ref {refname}: {e}"),
+            )
+            .emit(),
+        }
+    }
+
+    Ok(removed)
+}
+
+pub fn pull(work_dir: Option<&Path>, remote: &str) -> Result<()> {
+    let start = Instant::now();
+    let result = fetch(work_dir, remote).and_then(|_| reconcile());
+    Event::outcome_of("pull", &result, format!("Pulled from {remote}"))
+        .remote(remote)
+        .duration(start.elapsed())
+        .emit();
+    result
+}
+
+/// Pull from each of the remotes in turn, merging all measurements into the local notes ref.
+pub fn pull_from_remotes(work_dir: Option<&Path>, remotes: &[String]) -> Result<()> {
+    for remote in remotes {
+        pull(work_dir, remote)?;
+    }
+    Ok(())
+}
+
+/// Push to each of the remotes with independent retries. A failing remote does not prevent
+/// pushing to the remaining ones, but all failures are reported.
+pub fn push_to_remotes(work_dir: Option<&Path>, remotes: &[String]) -> Result<()> {
+    let failures = remotes
+        .iter()
+        .filter_map(|remote| {
+            push(work_dir, remote)
+                .err()
+                .map(|e| format!("{remote}: {e:#}"))
+        })
+        .collect_vec();
+    if !failures.is_empty() {
+        bail!(
+            "Failed to push to {} of {} remotes:\n{}",
+            failures.len(),
+            remotes.len(),
+            failures.join("\n")
+        );
+    }
+    Ok(())
+}
+
+pub fn push(work_dir: Option<&Path>, remote: &str) -> Result<()> {
     // TODO(kaihowl) check transient/permanent error
     let op = || -> Result<(), backoff::Error<anyhow::Error>> {
-        raw_push(work_dir).map_err(|e| match e.downcast_ref::<PushError>() {
-            Some(PushError::RefFailedToPush { .. }) => match pull(work_dir) {
+        let start = Instant::now();
+        let result = raw_push(work_dir, remote);
+        let rejected = result
+            .as_ref()
+            .is_err_and(|e| matches!(e.downcast_ref(), Some(PushError::RefFailedToPush { .. })));
+        let event = Event::outcome_of("push", &result, format!("Pushed to {remote}"));
+        Event {
+            outcome: if rejected { "retry" } else { event.outcome },
+            ..event
+        }
+        .remote(remote)
+        .duration(start.elapsed())
+        .emit();
+        result.map_err(|e| {
+            if !rejected {
+                return Error::Permanent(e);
+            }
+            match pull(work_dir, remote) {
                 Err(pull_error) => Error::permanent(pull_error),
                 Ok(_) => Error::transient(e),
-            },
-            None => Error::Permanent(e),
+            }
         })
     };
 
@@ -255,26 +1677,28 @@ fn parse_git_version(version: &str) -> Result<(i32, i32, i32)> {
     }
 }
 
-fn get_git_version() -> Result<(i32, i32, i32)> {
+pub fn get_git_version() -> Result<(i32, i32, i32)> {
     let version = run_git(&["--version"], &None).context("Determine git version")?;
     parse_git_version(&version)
 }
 
-fn concat_version(version_tuple: (i32, i32, i32)) -> String {
+pub fn concat_version(version_tuple: (i32, i32, i32)) -> String {
     format!(
         "{}.{}.{}",
         version_tuple.0, version_tuple.1, version_tuple.2
     )
 }
 
+/// Oldest supported git version
+pub const MIN_GIT_VERSION: (i32, i32, i32) = (2, 41, 0);
+
 pub fn check_git_version() -> Result<()> {
     let version_tuple = get_git_version().context("Determining compatible git version")?;
-    let expected_version = (2, 41, 0);
-    if version_tuple < expected_version {
+    if version_tuple < MIN_GIT_VERSION {
         bail!(
             "Version {} is smaller than {}",
             concat_version(version_tuple),
-            concat_version(expected_version)
+            concat_version(MIN_GIT_VERSION)
         )
     }
     Ok(())
@@ -369,7 +1793,7 @@ mod test {
         // We only want to verify that a call on the server with the authorization header was
         // received.
         hermetic_git_env();
-        pull(Some(repo_dir.path()))
+        pull(Some(repo_dir.path()), DEFAULT_REMOTE)
             .expect_err("We have no valid git http server setup -> should fail");
     }
 
@@ -391,7 +1815,7 @@ mod test {
 
         // TODO(kaihowl) duplication, leaks out of this test
         hermetic_git_env();
-        let error = push(Some(repo_dir.path()));
+        let error = push(Some(repo_dir.path()), DEFAULT_REMOTE);
         error
             .as_ref()
             .expect_err("We have no valid git http server setup -> should fail");
@@ -412,6 +1836,164 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_resolve_commit() {
+        let repo_dir = dir_with_repo();
+        set_current_dir(repo_dir.path()).expect("Failed to change dir");
+        assert_eq!(
+            resolve_commit("HEAD").unwrap(),
+            get_head_revision().unwrap()
+        );
+        assert!(resolve_commit("does-not-exist").is_err());
+        let output = repo_dir.path().join("output");
+        assert!(resolve_commit(&format!("--output={}", output.display())).is_err());
+        assert!(!output.exists(), "not taken as an option");
+    }
+
+    #[test]
+    fn test_is_stale() {
+        assert!(is_stale(1000, 2000, Duration::from_secs(60)));
+        assert!(is_stale(1940, 2000, Duration::from_secs(60)));
+        assert!(!is_stale(1950, 2000, Duration::from_secs(60)));
+        assert!(!is_stale(3000, 2000, Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_parse_reflog_time() {
+        assert_eq!(
+            parse_reflog_time("notes/perf-v3-add-1@{1700000000}"),
+            Some(1_700_000_000)
+        );
+        assert_eq!(parse_reflog_time("notes/perf-v3-add-1@{0}"), Some(0));
+        assert_eq!(parse_reflog_time("notes/perf-v3-add-1"), None);
+    }
+
+    #[test]
+    fn test_year_of() {
+        assert_eq!(year_of(0.0), 1970);
+        assert_eq!(year_of(951_782_400.0), 2000, "leap day 2000-02-29");
+        assert_eq!(year_of(1_704_067_199.0), 2023);
+        assert_eq!(year_of(1_704_067_200.0), 2024);
+        assert_eq!(year_of(-1.0), 1969);
+    }
+
+    #[test]
+    fn test_gc_temp_refs() {
+        let repo_dir = dir_with_repo();
+        let temp_ref = "refs/notes/perf-v3-add-deadbeef";
+        run_git_command(&["update-ref", temp_ref, "HEAD"], repo_dir.path());
+
+        let removed = gc_temp_refs(Some(repo_dir.path()), Duration::from_secs(3600)).unwrap();
+        assert!(removed.is_empty());
+        let refs = run_git(&["for-each-ref", temp_ref], &Some(repo_dir.path())).unwrap();
+        assert!(!refs.is_empty(), "Fresh temporary ref must be retained");
+
+        let removed = gc_temp_refs(Some(repo_dir.path()), Duration::ZERO).unwrap();
+        assert_eq!(removed, vec![temp_ref]);
+        let refs = run_git(&["for-each-ref", temp_ref], &Some(repo_dir.path())).unwrap();
+        assert!(refs.is_empty(), "Stale temporary ref must be removed");
+    }
+
+    #[test]
+    fn test_gc_temp_refs_by_time_written() {
+        let repo_dir = dir_with_repo();
+        let git = |args: &[&str]| run_git(args, &Some(repo_dir.path())).unwrap();
+        let exists = |refname: &str| !git(&["for-each-ref", refname]).is_empty();
+
+        // A live operation just created a ref to an old commit
+        let tree = git(&["write-tree"]);
+        let output = process::Command::new("git")
+            .args(["commit-tree", "-m", "Old notes", tree.trim()])
+            .envs([
+                ("GIT_AUTHOR_NAME", "testuser"),
+                ("GIT_AUTHOR_EMAIL", "testuser@example.com"),
+                ("GIT_COMMITTER_NAME", "testuser"),
+                ("GIT_COMMITTER_EMAIL", "testuser@example.com"),
+                ("GIT_COMMITTER_DATE", "2000-01-01T00:00:00Z"),
+            ])
+            .current_dir(repo_dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let old_commit = String::from_utf8(output.stdout).unwrap();
+        let live_ref = "refs/notes/perf-v3-add-live";
+        git(&["update-ref", live_ref, old_commit.trim()]);
+
+        // A crashed operation left a ref behind two hours ago
+        let crashed_ref = "refs/notes/perf-v3-merge-crashed";
+        git(&["update-ref", crashed_ref, "HEAD"]);
+        File::options()
+            .write(true)
+            .open(repo_dir.path().join(".git").join(crashed_ref))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+            .unwrap();
+
+        let removed = gc_temp_refs(Some(repo_dir.path()), Duration::from_secs(3600)).unwrap();
+        assert_eq!(removed, vec![crashed_ref]);
+        assert!(exists(live_ref), "Recently written ref must be retained");
+        assert!(!exists(crashed_ref));
+
+        // Packed refs are at most as old as the packed-refs file
+        git(&["pack-refs", "--all"]);
+        let removed = gc_temp_refs(Some(repo_dir.path()), Duration::from_secs(3600)).unwrap();
+        assert!(removed.is_empty());
+        assert!(exists(live_ref));
+    }
+
+    #[test]
+    fn test_replace_and_read_note() {
+        let repo_dir = dir_with_repo();
+        set_current_dir(repo_dir.path()).expect("Failed to change dir");
+        hermetic_git_env();
+
+        assert!(read_note_lines("HEAD").unwrap().is_empty());
+
+        add_note_line("first", "HEAD").unwrap();
+        assert_eq!(read_note_lines("HEAD").unwrap(), vec!["first"]);
+
+        replace_note("second\nthird", "HEAD").unwrap();
+        assert_eq!(read_note_lines("HEAD").unwrap(), vec!["second", "third"]);
+
+        replace_note("", "HEAD").unwrap();
+        assert!(read_note_lines("HEAD").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stream_notes() {
+        let repo_dir = dir_with_repo();
+        set_current_dir(repo_dir.path()).expect("Failed to change dir");
+        hermetic_git_env();
+
+        add_note_line("first", "HEAD").unwrap();
+        run_git_command(
+            &["commit", "--allow-empty", "-m", "second"],
+            repo_dir.path(),
+        );
+        add_note_line("second\nthird", "HEAD").unwrap();
+        let head = get_head_revision().unwrap();
+
+        // Each note is followed by an empty line
+        let mut notes = stream_notes(Some("HEAD")).unwrap();
+        assert_eq!(
+            notes.next().unwrap().unwrap(),
+            (
+                head,
+                vec!["second".to_owned(), "third".to_owned(), "".to_owned()]
+            )
+        );
+        assert_eq!(notes.next().unwrap().unwrap().1, vec!["first", ""]);
+        assert!(notes.next().is_none());
+
+        assert_eq!(stream_notes(None).unwrap().count(), 2);
+        // Stopping early ends git
+        assert!(stream_notes(None).unwrap().next().is_some());
+
+        let mut missing = stream_notes(Some("no-such-branch")).unwrap();
+        assert!(missing.next().unwrap().is_err());
+        assert!(missing.next().is_none());
+    }
+
     #[test]
     fn test_parse_git_version() {
         let version = parse_git_version("git version 2.52.0");
@@ -420,4 +2002,22 @@ mod test {
         let version = parse_git_version("git version 2.52.0\n");
         assert_eq!(version.unwrap(), (2, 52, 0));
     }
+
+    #[test]
+    fn commit_info_from_log_line() {
+        let (commit, info) =
+            parse_commit_info("abc\x001700000000\x00Jane Doe\x002023-11-14 22:13\x00Fix: a | b")
+                .unwrap();
+        assert_eq!(commit, "abc");
+        assert_eq!(
+            info,
+            CommitInfo {
+                time: 1_700_000_000,
+                author: "Jane Doe".to_owned(),
+                date: "2023-11-14 22:13".to_owned(),
+                subject: "Fix: a | b".to_owned(),
+            }
+        );
+        assert!(parse_commit_info("abc\x001700000000").is_err());
+    }
 }
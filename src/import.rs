@@ -0,0 +1,568 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Cursor},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    cargo_workspace::WorkspaceCrates,
+    config,
+    converters::convert_each,
+    data::MeasurementData,
+    measurement_retrieval,
+    measurement_storage::MeasurementWriter,
+    parsers::{
+        criterion::CriterionParser, google_benchmark::GoogleBenchmarkParser,
+        hyperfine::HyperfineParser, junit_xml::JunitXmlParser,
+        pytest_benchmark::PytestBenchmarkParser, BenchmarkMeasurement, ParsedEntries,
+        ParsedMeasurement, Parser, SkippedEntry,
+    },
+    progress::Progress,
+};
+
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Detect the format of each input from its content
+    Auto,
+    /// Google Benchmark JSON (`--benchmark_format=json`)
+    GoogleBenchmark,
+    /// pytest-benchmark JSON (`--benchmark-json`)
+    PytestBenchmark,
+    /// hyperfine JSON (`--export-json`)
+    Hyperfine,
+    /// cargo-criterion JSON messages (`--message-format=json`)
+    CriterionJson,
+    /// JUnit XML, e.g. of `pytest --junitxml` or `cargo nextest`, read while importing
+    Junit,
+}
+
+impl ImportFormat {
+    /// Name as given on the command line
+    fn name(&self) -> &'static str {
+        match self {
+            ImportFormat::Auto => "auto",
+            ImportFormat::GoogleBenchmark => "google-benchmark",
+            ImportFormat::PytestBenchmark => "pytest-benchmark",
+            ImportFormat::Hyperfine => "hyperfine",
+            ImportFormat::CriterionJson => "criterion-json",
+            ImportFormat::Junit => "junit",
+        }
+    }
+
+    /// Parser of the format. Only criterion supports comparing with a `compare_baseline`.
+    fn parser(&self, compare_baseline: Option<&str>) -> Result<Box<dyn Parser>> {
+        if compare_baseline.is_some()
+            && !matches!(self, ImportFormat::CriterionJson | ImportFormat::Auto)
+        {
+            bail!(
+                "Comparing with a baseline is not supported for {}",
+                self.name()
+            );
+        }
+        Ok(match self {
+            ImportFormat::Auto => Box::new(AutoParser {
+                compare_baseline: compare_baseline.map(str::to_owned),
+            }),
+            ImportFormat::GoogleBenchmark => Box::new(GoogleBenchmarkParser),
+            ImportFormat::PytestBenchmark => Box::new(PytestBenchmarkParser),
+            ImportFormat::Hyperfine => Box::new(HyperfineParser),
+            ImportFormat::CriterionJson => Box::new(CriterionParser {
+                compare_baseline: compare_baseline.map(str::to_owned),
+            }),
+            ImportFormat::Junit => Box::new(JunitXmlParser),
+        })
+    }
+
+    /// The format of `input`, detected by the characteristic keys of each format's JSON.
+    /// XML inputs are taken to be JUnit reports.
+    fn detect(input: &str) -> Result<ImportFormat> {
+        if input.trim_start().starts_with('<') {
+            return Ok(ImportFormat::Junit);
+        }
+        // cargo-criterion writes one JSON message per line
+        let document = serde_json::from_str::<Value>(input)
+            .or_else(|_| {
+                let first_line = input.lines().find(|line| !line.trim().is_empty());
+                serde_json::from_str::<Value>(first_line.unwrap_or_default())
+            })
+            .context("Failed to detect the format: the input is neither JSON nor JSON lines")?;
+        let has = |key: &str| document.get(key).is_some();
+        let first_benchmark = document.get("benchmarks").and_then(|b| b.get(0));
+        let benchmark_has = |key: &str| first_benchmark.is_some_and(|b| b.get(key).is_some());
+
+        let candidates = [
+            (ImportFormat::CriterionJson, has("reason")),
+            (ImportFormat::Hyperfine, has("results")),
+            (
+                ImportFormat::GoogleBenchmark,
+                has("benchmarks") && (has("context") || benchmark_has("real_time")),
+            ),
+            (
+                ImportFormat::PytestBenchmark,
+                has("benchmarks") && (has("machine_info") || benchmark_has("stats")),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(format, matches)| matches.then_some(format))
+        .collect_vec();
+        match candidates.as_slice() {
+            [format] => Ok(*format),
+            [] => bail!(
+                "Failed to detect the format, none of {} matches. Specify the format explicitly.",
+                DETECTED_FORMATS.iter().map(ImportFormat::name).join(", ")
+            ),
+            ambiguous => bail!(
+                "Failed to detect the format, the input matches {}. Specify the format explicitly.",
+                ambiguous.iter().map(ImportFormat::name).join(" and ")
+            ),
+        }
+    }
+}
+
+/// Formats that [`ImportFormat::Auto`] tells apart
+const DETECTED_FORMATS: [ImportFormat; 5] = [
+    ImportFormat::GoogleBenchmark,
+    ImportFormat::PytestBenchmark,
+    ImportFormat::Hyperfine,
+    ImportFormat::CriterionJson,
+    ImportFormat::Junit,
+];
+
+/// Parser of inputs in any of the [`DETECTED_FORMATS`], each parsed by the parser of its
+/// detected format. The `compare_baseline` only applies to criterion inputs.
+/// JUnit reports are read while parsing, all other inputs are read completely for the detection.
+struct AutoParser {
+    compare_baseline: Option<String>,
+}
+
+impl Parser for AutoParser {
+    fn parse<'a>(&self, mut input: Box<dyn BufRead + 'a>) -> Result<ParsedEntries<'a>> {
+        if starts_with_markup(&mut input)? {
+            return JunitXmlParser.parse(input);
+        }
+        let input = io::read_to_string(input).context("Failed to read input")?;
+        let format = ImportFormat::detect(&input)?;
+        let compare_baseline = self
+            .compare_baseline
+            .as_deref()
+            .filter(|_| format == ImportFormat::CriterionJson);
+        format
+            .parser(compare_baseline)?
+            .parse(Box::new(Cursor::new(input)))
+    }
+}
+
+/// Whether `input` continues with `<` after whitespace, which is consumed.
+fn starts_with_markup(input: &mut dyn BufRead) -> Result<bool> {
+    loop {
+        let buf = input.fill_buf().context("Failed to read input")?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        let whitespace = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        if let Some(&next) = buf.get(whitespace) {
+            input.consume(whitespace);
+            return Ok(next == b'<');
+        }
+        input.consume(whitespace);
+    }
+}
+
+/// What to do with imported measurements that have the same name and key-values as one already
+/// stored for HEAD, e.g. when a retried CI job imports the same results again.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dedupe {
+    /// Add them nevertheless
+    Off,
+    /// Do not add them
+    Skip,
+    /// Remove the stored ones in favor of them
+    Replace,
+}
+
+fn read_input(file: &Path) -> Result<Box<dyn BufRead>> {
+    if file == Path::new("-") {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        let file =
+            File::open(file).with_context(|| format!("Failed to read '{}'", file.display()))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Expand glob patterns (e.g. `target/**/results.json`) in `files` to the matching files in
+/// sorted order. Other arguments, including `-` for stdin, are taken as they are.
+/// Files matched by several arguments are only read once.
+fn expand_inputs(files: &[String]) -> Result<Vec<PathBuf>> {
+    if files.is_empty() {
+        return Ok(vec![PathBuf::from("-")]);
+    }
+    let mut inputs = Vec::new();
+    for file in files {
+        if !file.contains(['*', '?', '[']) {
+            inputs.push(PathBuf::from(file));
+            continue;
+        }
+        let matches: Vec<_> = glob::glob(file)
+            .with_context(|| format!("Invalid glob pattern '{file}'"))?
+            .try_collect()?;
+        if matches.is_empty() {
+            bail!("No files match '{file}'");
+        }
+        inputs.extend(matches.into_iter().sorted());
+    }
+    Ok(inputs.into_iter().unique().collect())
+}
+
+/// Bookkeeping of a single import for CI to assert on.
+#[derive(Debug, Serialize)]
+pub struct ImportSummary {
+    pub format: String,
+    /// Files read, `-` for stdin
+    pub files: Vec<String>,
+    /// Number of entries found in the input, including skipped ones
+    pub parsed: usize,
+    /// Number of measurements added to HEAD
+    pub imported: usize,
+    /// Distinct names of the added measurements
+    pub measurements: Vec<String>,
+    pub skipped: Vec<SkippedEntry>,
+    /// Number of measurements dropped for repeating an earlier one of the same file
+    pub duplicates: usize,
+    /// Number of measurements not added since HEAD already has them, see [`Dedupe::Skip`]
+    pub already_stored: usize,
+    /// Number of measurements of HEAD removed in favor of imported ones, see [`Dedupe::Replace`]
+    pub replaced: usize,
+}
+
+impl ImportSummary {
+    fn print(&self) {
+        println!(
+            "Imported {} of {} {} entries from {} files into {} measurements",
+            self.imported,
+            self.parsed,
+            self.format,
+            self.files.len(),
+            self.measurements.len()
+        );
+        for name in &self.measurements {
+            println!("  {name}");
+        }
+        for skipped in &self.skipped {
+            println!("Skipped '{}': {}", skipped.id, skipped.reason);
+        }
+        if self.duplicates > 0 {
+            println!("Dropped {} duplicate measurements", self.duplicates);
+        }
+        if self.already_stored > 0 {
+            println!(
+                "Skipped {} measurements already stored for HEAD",
+                self.already_stored
+            );
+        }
+        if self.replaced > 0 {
+            println!("Replaced {} measurements stored for HEAD", self.replaced);
+        }
+    }
+}
+
+/// Key of the owning crate of measurements imported with `workspace_crates`.
+const CRATE_KEY: &str = "crate";
+
+/// Record the crate owning the benchmark as its `crate` key-value.
+fn tag_owning_crate(benchmark: &mut BenchmarkMeasurement, crates: &WorkspaceCrates) {
+    if let Some(owner) = crates.owner_of(&benchmark.id) {
+        benchmark
+            .metadata
+            .insert(CRATE_KEY.to_owned(), owner.to_owned());
+    }
+}
+
+/// Prefix the name of a measurement with an owning crate with `<crate>::`.
+fn namespace_by_crate(mut m: MeasurementData) -> MeasurementData {
+    if let Some(owner) = m.key_values.get(CRATE_KEY) {
+        m.name = format!("{owner}::{}", m.name);
+        m.epoch = config::determine_epoch_from_config(&m.name).unwrap_or(0);
+    }
+    m
+}
+
+/// Name and sorted key-values, which identify the measurements of a series across imports.
+fn series_key(m: &MeasurementData) -> (String, Vec<(String, String)>) {
+    (
+        m.name.clone(),
+        m.key_values.clone().into_iter().sorted().collect(),
+    )
+}
+
+/// Name, value, and sorted key-values, which are the same for duplicates within a file.
+fn duplicate_key(m: &MeasurementData) -> (String, u64, Vec<(String, String)>) {
+    let (name, key_values) = series_key(m);
+    (name, m.val.to_bits(), key_values)
+}
+
+/// Import the results of a benchmark framework from each of the `files` (or glob patterns) and add
+/// them to HEAD in a single write. Reads from stdin if no file is given. A summary of the import is
+/// printed with `verbose` and written as JSON to `report`. With `compare_baseline`, the relative
+/// changes against that baseline contained in the input are imported as well. With
+/// `workspace_crates`, measurements of benchmarks owned by a crate of the cargo workspace in the
+/// current directory are prefixed with `<crate>::` and get the crate as `crate` key-value.
+/// Measurements already stored for HEAD are treated according to `dedupe`.
+#[allow(clippy::too_many_arguments)]
+pub fn import(
+    format: ImportFormat,
+    files: &[String],
+    key_values: &[(String, String)],
+    compare_baseline: Option<&str>,
+    workspace_crates: bool,
+    dedupe: Dedupe,
+    verbose: bool,
+    report: Option<&Path>,
+) -> Result<()> {
+    let parser = format.parser(compare_baseline)?;
+    let inputs = expand_inputs(files)?;
+    let crates = if workspace_crates {
+        if key_values.iter().any(|(k, _)| k == CRATE_KEY) {
+            bail!("The key '{CRATE_KEY}' is reserved for the owning crate of the workspace");
+        }
+        Some(WorkspaceCrates::detect()?)
+    } else {
+        None
+    };
+
+    let mut num_parsed = 0;
+    let writer = MeasurementWriter::new();
+    let mut names = Vec::new();
+    let mut skipped = Vec::new();
+    let mut duplicates = 0;
+    let stored = match dedupe {
+        Dedupe::Off => Vec::new(),
+        Dedupe::Skip | Dedupe::Replace => measurement_retrieval::measurements_of("HEAD")?
+            .iter()
+            .map(series_key)
+            .collect(),
+    };
+    let stored_series: HashSet<_> = stored.iter().collect();
+    let mut already_stored = 0;
+    let mut imported_series = HashSet::new();
+    let progress = Progress::items("Importing files", inputs.len());
+    for input in &inputs {
+        progress.inc(1);
+        let entries = parser
+            .parse(read_input(input)?)
+            .with_context(|| format!("Failed to parse '{}'", input.display()))?;
+        // Entries are converted and queued while parsing, without collecting them first.
+        let benchmarks = entries.filter_map_ok(|entry| {
+            num_parsed += 1;
+            match entry {
+                ParsedMeasurement::Skipped(s) => {
+                    skipped.push(s);
+                    None
+                }
+                ParsedMeasurement::Benchmark(mut benchmark) => {
+                    if let Some(crates) = &crates {
+                        tag_owning_crate(&mut benchmark, crates);
+                    }
+                    Some(ParsedMeasurement::Benchmark(benchmark))
+                }
+            }
+        });
+        let mut seen = HashSet::new();
+        let measurements = convert_each(benchmarks, key_values)
+            .map_ok(namespace_by_crate)
+            .filter_ok(|m| {
+                let first = seen.insert(duplicate_key(m));
+                duplicates += usize::from(!first);
+                first
+            })
+            .filter_ok(|m| match dedupe {
+                Dedupe::Off => true,
+                Dedupe::Skip => {
+                    let is_stored = stored_series.contains(&series_key(m));
+                    already_stored += usize::from(is_stored);
+                    !is_stored
+                }
+                Dedupe::Replace => {
+                    imported_series.insert(series_key(m));
+                    true
+                }
+            })
+            .map_ok(|m| {
+                names.push(m.name.clone());
+                m
+            });
+        itertools::process_results(measurements, |measurements| writer.queue_all(measurements))
+            .with_context(|| format!("Failed to parse '{}'", input.display()))?;
+    }
+    // Cleared before the verbose summary is printed
+    progress.finish();
+
+    let summary = ImportSummary {
+        format: format.name().to_owned(),
+        files: inputs.iter().map(|f| f.display().to_string()).collect(),
+        parsed: num_parsed,
+        imported: writer.len(),
+        measurements: names.into_iter().unique().collect(),
+        skipped,
+        duplicates,
+        already_stored,
+        replaced: stored
+            .iter()
+            .filter(|series| imported_series.contains(*series))
+            .count(),
+    };
+    if verbose {
+        summary.print();
+    }
+    if let Some(report) = report {
+        fs::write(report, serde_json::to_string_pretty(&summary)?)
+            .with_context(|| format!("Failed to write import report '{}'", report.display()))?;
+    }
+
+    if writer.is_empty() {
+        // A repeated import of the same results has nothing left to add
+        if already_stored > 0 {
+            return Ok(());
+        }
+        bail!("No measurements found in input");
+    }
+    if dedupe == Dedupe::Replace {
+        writer.commit_replacing(|m| imported_series.contains(&series_key(m)))?;
+    } else {
+        writer.commit()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn measurement(name: &str, val: f64, os: &str) -> MeasurementData {
+        MeasurementData {
+            epoch: 0,
+            name: name.to_string(),
+            timestamp: 0.0,
+            val,
+            key_values: HashMap::from([
+                ("os".to_string(), os.to_string()),
+                ("arch".to_string(), "x86_64".to_string()),
+            ]),
+        }
+    }
+
+    #[test]
+    fn expand_glob_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        for shard in ["b", "a"] {
+            fs::create_dir(dir.path().join(shard)).unwrap();
+            fs::write(dir.path().join(shard).join("results.json"), "{}").unwrap();
+        }
+        let pattern = format!("{}/*/results.json", dir.path().display());
+        let explicit = dir.path().join("a/results.json");
+        let inputs = expand_inputs(&[pattern, explicit.display().to_string()]).unwrap();
+        assert_eq!(
+            inputs,
+            vec![explicit, dir.path().join("b/results.json")],
+            "sorted and without repetitions"
+        );
+
+        let no_match = format!("{}/*.xml", dir.path().display());
+        assert!(expand_inputs(&[no_match]).is_err());
+        assert_eq!(expand_inputs(&[]).unwrap(), vec![PathBuf::from("-")]);
+    }
+
+    #[test]
+    fn prefix_measurements_with_owning_crate() {
+        let mut owned = measurement("bench::sort::mean", 1.0, "linux");
+        owned
+            .key_values
+            .insert(CRATE_KEY.to_string(), "crate-a".to_string());
+        assert_eq!(namespace_by_crate(owned).name, "crate-a::bench::sort::mean");
+        let unowned = measurement("bench::other::mean", 1.0, "linux");
+        assert_eq!(namespace_by_crate(unowned).name, "bench::other::mean");
+    }
+
+    #[test]
+    fn deduplicate_identical_measurements() {
+        let unique = [
+            measurement("a", 1.0, "linux"),
+            measurement("a", 1.0, "linux"),
+            measurement("a", 1.0, "mac"),
+            measurement("a", 2.0, "linux"),
+            measurement("b", 1.0, "linux"),
+        ]
+        .iter()
+        .map(duplicate_key)
+        .unique()
+        .count();
+        assert_eq!(unique, 4);
+    }
+
+    #[test]
+    fn detect_markup_after_whitespace() {
+        let mut input: &[u8] = b"  \n<testsuites/>";
+        assert!(starts_with_markup(&mut input).unwrap());
+        assert_eq!(input, b"<testsuites/>", "only whitespace is consumed");
+        assert!(!starts_with_markup(&mut &b"\n{}"[..]).unwrap());
+        assert!(!starts_with_markup(&mut &b"  "[..]).unwrap());
+    }
+
+    #[test]
+    fn detect_formats() {
+        let gbench = r#"{"context": {}, "benchmarks": [{"name": "BM_A", "real_time": 1}]}"#;
+        let pytest = r#"{"machine_info": {}, "benchmarks": [{"name": "a", "stats": {}}]}"#;
+        let hyperfine = r#"{"results": [{"command": "sleep 1"}]}"#;
+        let criterion = concat!(
+            r#"{"reason":"benchmark-complete","id":"sort"}"#,
+            "\n",
+            r#"{"reason":"group-complete","group_name":"sort"}"#,
+            "\n"
+        );
+        assert_eq!(
+            ImportFormat::detect(gbench).unwrap(),
+            ImportFormat::GoogleBenchmark
+        );
+        assert_eq!(
+            ImportFormat::detect(pytest).unwrap(),
+            ImportFormat::PytestBenchmark
+        );
+        assert_eq!(
+            ImportFormat::detect(hyperfine).unwrap(),
+            ImportFormat::Hyperfine
+        );
+        assert_eq!(
+            ImportFormat::detect(criterion).unwrap(),
+            ImportFormat::CriterionJson
+        );
+
+        let ambiguous = r#"{"benchmarks": [{"real_time": 1, "stats": {}}]}"#;
+        let error = ImportFormat::detect(ambiguous).unwrap_err().to_string();
+        assert!(error.contains("google-benchmark and pytest-benchmark"));
+        assert!(ImportFormat::detect(r#"{"benchmarks": []}"#).is_err());
+        assert_eq!(
+            ImportFormat::detect("\n<testsuites></testsuites>").unwrap(),
+            ImportFormat::Junit
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn format_names_match_cli() {
+        use clap::ValueEnum;
+
+        for format in ImportFormat::value_variants() {
+            let value = format.to_possible_value().unwrap();
+            assert_eq!(format.name(), value.get_name());
+        }
+    }
+}
@@ -1,11 +1,47 @@
+//! Track, plot, and statistically validate simple measurements using git-notes for storage.
+//!
+//! Programs embedding git-perf should use the [`api`] module, the only part of the crate with
+//! semver guarantees. All other modules are implementation details of the `git-perf` binary.
+
+pub mod api;
 pub mod audit;
+pub mod baseline;
 pub mod basic_measure;
+#[cfg(feature = "import")]
+pub mod cargo_workspace;
+#[cfg(feature = "cli")]
 pub mod cli;
+pub mod compare;
 pub mod config;
+pub mod config_check;
+#[cfg(feature = "import")]
+pub mod converters;
 pub mod data;
+pub mod demo;
+pub mod digest;
+pub mod doctor;
+pub mod environment;
+pub mod events;
+pub mod export;
 pub mod git_interop;
+#[cfg(feature = "import")]
+pub mod import;
 pub mod measurement_retrieval;
 pub mod measurement_storage;
+pub mod notes_cache;
+#[cfg(feature = "import")]
+pub mod parsers;
+pub mod progress;
+pub mod relink;
+pub mod report_diff;
 pub mod reporting;
+pub mod retention;
 pub mod serialization;
+pub mod serve;
+pub mod size;
+pub mod sparkline;
 pub mod stats;
+pub mod storage;
+pub mod summary;
+pub mod trailers;
+pub mod units;
@@ -1,6 +1,13 @@
-use anyhow::Result;
+use std::process::ExitCode;
+
 use git_perf::cli;
 
-fn main() -> Result<()> {
-    cli::handle_calls()
+fn main() -> ExitCode {
+    match cli::handle_calls() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(cli::exit_code(&e))
+        }
+    }
 }
@@ -1,10 +1,18 @@
+use std::{collections::HashMap, iter, mem};
+
 use crate::{
+    config,
     data::{CommitSummary, MeasurementData, MeasurementSummary, ReductionFunc},
-    git_interop::{self},
-    stats::NumericReductionFunc,
+    git_interop, notes_cache,
+    progress::Progress,
+    serialization::{deserialize_legacy, LegacyFormat},
+    stats::{self, NumericReductionFunc, VecAggregation},
+    storage,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use rayon::prelude::*;
 
 // TODO(kaihowl) oh god naming
 pub trait ReductionFuncIterator<'a>: Iterator<Item = &'a MeasurementData> {
@@ -57,16 +65,22 @@ where
     fn reduce_by(self, fun: ReductionFunc) -> Option<MeasurementSummary> {
         let mut peekable = self.peekable();
         let expected_epoch = peekable.peek().map(|m| m.epoch);
-        let mut vals = peekable.map(|m| {
-            debug_assert_eq!(Some(m.epoch), expected_epoch);
-            m.val
-        });
+        let mut vals = peekable
+            .map(|m| {
+                debug_assert_eq!(Some(m.epoch), expected_epoch);
+                m.val
+            })
+            .collect_vec();
 
-        let aggregate_val = vals.aggregate_by(fun);
+        let aggregate_val = vals.iter().copied().aggregate_by(fun);
+        let stats = stats::aggregate_measurements(vals.iter().copied());
 
         Some(MeasurementSummary {
             epoch: expected_epoch?,
             val: aggregate_val?,
+            len: stats.len,
+            stddev: stats.stddev,
+            mad: vals.median_absolute_deviation()?,
         })
     }
 }
@@ -77,22 +91,204 @@ pub struct Commit {
     pub measurements: Vec<MeasurementData>,
 }
 
+/// Measurements of a walked commit, still serialized unless read from the cache.
+enum Notes {
+    Lines(Vec<String>),
+    Cached(Vec<MeasurementData>),
+}
+
+/// The last `num_commits` first-parent commits starting at `start` with their notes, through the
+/// cache if enabled.
+fn walk_notes(start: &str, num_commits: usize) -> Result<Vec<(String, Notes)>> {
+    if notes_cache::enabled() {
+        return Ok(notes_cache::walk(start, num_commits)?
+            .into_iter()
+            .map(|(commit, measurements)| (commit, Notes::Cached(measurements)))
+            .collect());
+    }
+    Ok(storage::backend()?
+        .walk(start, num_commits)?
+        .into_iter()
+        .map(|(commit, lines)| (commit, Notes::Lines(lines)))
+        .collect())
+}
+
 // TODO(hoewelmk) copies all measurements, expensive...
 // TODO(kaihowl) missing check for shallow clone marker!
 pub fn walk_commits(num_commits: usize) -> Result<impl Iterator<Item = Result<Commit>>> {
-    let vec = git_interop::walk_commits(num_commits)?;
-    Ok(vec
-        .into_iter()
-        .take(num_commits)
-        .map(|(commit_id, lines)| -> Result<Commit> {
-            let measurements = crate::serialization::deserialize(&lines.join("\n"));
-            Ok(Commit {
-                commit: commit_id,
-                measurements,
-            })
-        }))
+    walk_commits_from("HEAD", num_commits)
+}
+
+pub fn walk_commits_from(
+    start: &str,
+    num_commits: usize,
+) -> Result<impl Iterator<Item = Result<Commit>>> {
+    let mut vec = walk_notes(start, num_commits)?;
+    vec.truncate(num_commits);
+    Ok(deserialize_commits(vec, legacy_measurements()?))
     // When this fails it is due to a shallow clone.
     // TODO(kaihowl) proper shallow clone support
     // https://github.com/libgit2/libgit2/issues/3058 tracks that we fail to revwalk the
     // last commit because the parent cannot be loooked up.
 }
+
+/// The commit `head` followed by the first-parent history of `baseline`, e.g. to compare a pull
+/// request against its target branch. Like [`walk_commits_from`], at most `num_commits` commits
+/// are returned. `head` itself is never part of the baseline history.
+pub fn walk_commits_against_baseline(
+    head: &str,
+    baseline: &str,
+    num_commits: usize,
+) -> Result<impl Iterator<Item = Result<Commit>>> {
+    let head = walk_notes(head, 1)?;
+    let head_id = head.first().map(|(commit_id, _)| commit_id.clone());
+    let tail = walk_notes(baseline, num_commits)?
+        .into_iter()
+        .filter(move |(commit_id, _)| Some(commit_id) != head_id.as_ref());
+    Ok(deserialize_commits(
+        head.into_iter().chain(tail).take(num_commits).collect(),
+        legacy_measurements()?,
+    ))
+}
+
+/// All commits with measurements, not limited to the first-parent history of HEAD.
+/// Optionally restricted to a commit range such as `main~10..main`. The notes are read and
+/// deserialized one commit at a time while iterating, so that memory does not grow with the
+/// history.
+pub fn walk_all(range: Option<&str>) -> Result<impl Iterator<Item = Result<Commit>>> {
+    let mut notes = storage::backend()?.stream_all(range)?;
+    let mut legacy = legacy_measurements()?;
+    // A range walks all of its commits, otherwise add those only annotated in the legacy refs
+    let add_legacy_only = range.is_none();
+    let mut legacy_only = None;
+    Ok(iter::from_fn(move || {
+        if legacy_only.is_none() {
+            match notes.next() {
+                Some(Ok((commit_id, lines))) => {
+                    let mut commit = deserialize_commit(commit_id, Notes::Lines(lines));
+                    if let Some(measurements) = legacy.remove(&commit.commit) {
+                        commit.measurements.extend(measurements);
+                    }
+                    return Some(Ok(commit));
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None if add_legacy_only => {
+                    legacy_only = Some(
+                        mem::take(&mut legacy)
+                            .into_iter()
+                            .sorted_by(|(a, _), (b, _)| a.cmp(b)),
+                    )
+                }
+                None => return None,
+            }
+        }
+        legacy_only.as_mut()?.next().map(|(commit, measurements)| {
+            Ok(Commit {
+                commit,
+                measurements,
+            })
+        })
+    }))
+}
+
+/// The measurements stored for the single commit `commit`, e.g. to find already added ones.
+pub fn measurements_of(commit: &str) -> Result<Vec<MeasurementData>> {
+    let lines = storage::backend()?.list(commit)?;
+    let mut measurements = crate::serialization::deserialize(&lines.join("\n"));
+    let mut legacy = legacy_measurements()?;
+    if !legacy.is_empty() {
+        if let Some(legacy) = legacy.remove(&git_interop::resolve_commit(commit)?) {
+            measurements.extend(legacy);
+        }
+    }
+    Ok(measurements)
+}
+
+/// Measurements per commit of the legacy notes refs of earlier git-perf versions configured as
+/// `[storage] legacy_refs`. They are only read, never rewritten, e.g. by `remove`.
+fn legacy_measurements() -> Result<HashMap<String, Vec<MeasurementData>>> {
+    let mut measurements: HashMap<String, Vec<MeasurementData>> = HashMap::new();
+    for notes_ref in config::determine_legacy_refs_from_config() {
+        let format = LegacyFormat::of_ref(&notes_ref)?;
+        for (commit, lines) in git_interop::walk_legacy_notes(&notes_ref)? {
+            measurements
+                .entry(commit)
+                .or_default()
+                .extend(deserialize_legacy(&lines.join("\n"), format));
+        }
+    }
+    Ok(measurements)
+}
+
+/// Bound the number of threads deserializing notes, e.g. for `--jobs`. Defaults to the number of
+/// CPUs. Has to be called before any measurements are read.
+pub fn set_jobs(jobs: usize) -> Result<()> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global()
+        .context("Failed to set the number of jobs")
+}
+
+/// Deserialize the notes of the walked `commits` in parallel, counting the processed notes. The
+/// commits keep their order. The `legacy` measurements of the commits are added to theirs.
+fn deserialize_commits(
+    commits: Vec<(String, Notes)>,
+    mut legacy: HashMap<String, Vec<MeasurementData>>,
+) -> impl Iterator<Item = Result<Commit>> {
+    let progress = Progress::items("Processing notes", commits.len());
+    let commits: Vec<_> = commits
+        .into_par_iter()
+        .map(|(commit_id, notes)| {
+            progress.inc(1);
+            deserialize_commit(commit_id, notes)
+        })
+        .collect();
+    progress.finish();
+    commits.into_iter().map(move |mut commit| {
+        if let Some(measurements) = legacy.remove(&commit.commit) {
+            commit.measurements.extend(measurements);
+        }
+        Ok(commit)
+    })
+}
+
+fn deserialize_commit(commit: String, notes: Notes) -> Commit {
+    let measurements = match notes {
+        Notes::Lines(lines) => crate::serialization::deserialize(&lines.join("\n")),
+        Notes::Cached(measurements) => measurements,
+    };
+    Commit {
+        commit,
+        measurements,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serialization::{serialize_single, DELIMITER};
+
+    #[test]
+    fn parallel_deserialization_keeps_commit_order() {
+        let measurement = |name: &str, val: f64| MeasurementData {
+            epoch: 0,
+            name: name.to_owned(),
+            timestamp: 0.0,
+            val,
+            key_values: HashMap::new(),
+        };
+        let commits = (0..1000)
+            .map(|i| {
+                let line = serialize_single(&measurement("timer", i as f64 + 0.5), DELIMITER);
+                (format!("{i:040}"), Notes::Lines(vec![line]))
+            })
+            .collect_vec();
+        let legacy = HashMap::from([(format!("{:040}", 1), vec![measurement("legacy", 1.0)])]);
+        let deserialized: Vec<_> = deserialize_commits(commits, legacy).try_collect().unwrap();
+        for (i, commit) in deserialized.iter().enumerate() {
+            assert_eq!(commit.commit, format!("{i:040}"));
+            assert_eq!(commit.measurements[0].val, i as f64 + 0.5);
+        }
+        assert_eq!(deserialized[1].measurements[1].name, "legacy");
+    }
+}
@@ -1,71 +1,384 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use itertools::Itertools;
+use serde::Deserialize;
 use std::{
     collections::HashMap,
+    sync::Mutex,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
     config,
     data::MeasurementData,
-    git_interop::add_note_line_to_head,
-    serialization::{serialize_multiple, serialize_single, DELIMITER},
+    serialization::{self, deserialize, serialize_multiple, Format},
+    storage,
 };
 
-pub fn add_multiple(
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("TODO(kaihowl)")
+        .as_secs_f64()
+}
+
+/// Measurements of `measurement` with the given values, taken now in the configured epoch.
+pub fn new_measurements(
     measurement: &str,
     values: &[f64],
     key_values: &[(String, String)],
-) -> Result<()> {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("TODO(kaihowl)");
-
-    let timestamp = timestamp.as_secs_f64();
+) -> Vec<MeasurementData> {
+    let timestamp = now();
     let key_values: HashMap<_, _> = key_values.iter().cloned().collect();
+    // TODO(hoewelmk)
+    let epoch = config::determine_epoch_from_config(measurement).unwrap_or(0);
 
-    // TODO(kaihowl) inefficient recopying
-    let mds = values
+    values
         .iter()
         .map(|v| MeasurementData {
-            // TODO(hoewelmk)
-            epoch: config::determine_epoch_from_config(measurement).unwrap_or(0),
+            epoch,
             name: measurement.to_owned(),
             timestamp,
             val: *v,
             key_values: key_values.clone(),
         })
-        .collect_vec();
+        .collect_vec()
+}
 
-    let serialized = serialize_multiple(&mds);
+/// Format of newly added measurements, configured as `[storage] format = "v4"`.
+fn storage_format() -> Result<Format> {
+    config::determine_storage_format_from_config()
+        .map(|format| format.parse())
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// Add already fully populated measurements, possibly of different names, to HEAD in a single
+/// write. Nothing is written without measurements.
+pub fn add_multiple(measurements: &[MeasurementData]) -> Result<()> {
+    if measurements.is_empty() {
+        return Ok(());
+    }
+
+    let serialized = serialize_multiple(measurements, storage_format()?);
 
-    add_note_line_to_head(&serialized)?;
+    storage::backend()?.append("HEAD", &serialized)?;
 
     Ok(())
 }
 
+/// Collects measurements, possibly of different names and from several threads, to add them to
+/// HEAD in a single write on [`commit`](MeasurementWriter::commit).
+#[derive(Debug, Default)]
+pub struct MeasurementWriter {
+    queued: Mutex<Vec<MeasurementData>>,
+}
+
+impl MeasurementWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn queue(&self, measurement: MeasurementData) {
+        self.queued
+            .lock()
+            .expect("Measurement queue poisoned")
+            .push(measurement);
+    }
+
+    pub fn queue_all(&self, measurements: impl IntoIterator<Item = MeasurementData>) {
+        self.queued
+            .lock()
+            .expect("Measurement queue poisoned")
+            .extend(measurements);
+    }
+
+    pub fn len(&self) -> usize {
+        self.queued
+            .lock()
+            .expect("Measurement queue poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add all queued measurements in a single write. Returns the number of added measurements.
+    pub fn commit(self) -> Result<usize> {
+        let queued = self
+            .queued
+            .into_inner()
+            .expect("Measurement queue poisoned");
+        add_multiple(&queued)?;
+        Ok(queued.len())
+    }
+
+    /// Like [`commit`](MeasurementWriter::commit), but also remove the measurements of HEAD
+    /// matching `predicate` in the same write. The stored measurements are thus not lost if
+    /// adding the queued ones fails.
+    pub fn commit_replacing<F>(self, predicate: F) -> Result<usize>
+    where
+        F: Fn(&MeasurementData) -> bool,
+    {
+        let queued = self
+            .queued
+            .into_inner()
+            .expect("Measurement queue poisoned");
+        let backend = storage::backend()?;
+        let (mut lines, _) = split_off_matching(backend.list("HEAD")?, &predicate);
+        if !queued.is_empty() {
+            let serialized = serialize_multiple(&queued, storage_format()?);
+            lines.push(serialized.trim_end().to_owned());
+        }
+        backend.rewrite("HEAD", &lines.join("\n"))?;
+        Ok(queued.len())
+    }
+}
+
 pub fn add(measurement: &str, value: f64, key_values: &[(String, String)]) -> Result<()> {
-    // TODO(kaihowl) configure path
-    // TODO(kaihowl) configure
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("TODO(kaihowl)");
+    add_multiple(&new_measurements(measurement, &[value], key_values))
+}
 
-    let timestamp = timestamp.as_secs_f64();
-    let key_values: HashMap<_, _> = key_values.iter().cloned().collect();
+/// A measurement given as a JSON line to [`add_batch`].
+#[derive(Debug, Deserialize)]
+struct BatchEntry {
+    name: String,
+    value: f64,
+    #[serde(default)]
+    key_values: HashMap<String, String>,
+}
 
-    let md = MeasurementData {
-        // TODO(hoewelmk)
-        epoch: config::determine_epoch_from_config(measurement).unwrap_or(0),
-        name: measurement.to_owned(),
-        timestamp,
-        val: value,
+/// Parse a line of either `name value key=value...` or a JSON object with the fields `name`,
+/// `value` and optionally `key_values`.
+fn parse_batch_line(line: &str) -> Result<BatchEntry> {
+    if line.starts_with('{') {
+        return Ok(serde_json::from_str(line)?);
+    }
+    let mut fields = line.split_whitespace();
+    let name = fields.next().ok_or_else(|| anyhow!("missing name"))?;
+    let value = fields.next().ok_or_else(|| anyhow!("missing value"))?;
+    let value = value
+        .parse()
+        .with_context(|| format!("invalid value '{value}'"))?;
+    let key_values = fields
+        .map(|kv| {
+            kv.split_once('=')
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .ok_or_else(|| anyhow!("invalid key=value: no '=' found in '{kv}'"))
+        })
+        .try_collect()?;
+    Ok(BatchEntry {
+        name: name.to_owned(),
+        value,
         key_values,
+    })
+}
+
+/// Add all measurements of `input`, one per line, to HEAD in a single write. Each line is either
+/// `name value key=value...` or a JSON object such as
+/// `{"name": "timer", "value": 1.5, "key_values": {"os": "linux"}}`. Empty lines and lines
+/// starting with `#` are ignored. The `key_values` are added to every measurement, the `defaults`
+/// only to the ones without a value for their key.
+/// Returns the number of added measurements.
+pub fn add_batch(
+    input: &str,
+    key_values: &[(String, String)],
+    defaults: &[(String, String)],
+) -> Result<usize> {
+    let timestamp = now();
+    let writer = MeasurementWriter::new();
+
+    for (n, line) in input
+        .lines()
+        .enumerate()
+        .map(|(n, line)| (n + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+    {
+        let mut entry = parse_batch_line(line).with_context(|| format!("Line {n}"))?;
+        for (k, v) in key_values {
+            if entry.key_values.insert(k.clone(), v.clone()).is_some() {
+                bail!("Line {n}: the key '{k}' was specified more than once");
+            }
+        }
+        for (k, v) in defaults {
+            entry
+                .key_values
+                .entry(k.clone())
+                .or_insert_with(|| v.clone());
+        }
+        writer.queue(MeasurementData {
+            epoch: config::determine_epoch_from_config(&entry.name).unwrap_or(0),
+            name: entry.name,
+            timestamp,
+            val: entry.value,
+            key_values: entry.key_values,
+        });
+    }
+
+    if writer.is_empty() {
+        bail!("No measurements found in input");
+    }
+    writer.commit()
+}
+
+/// Remove all measurements matching `predicate` from the `commits`, or from every commit if
+/// `None`. With `dry_run`, nothing is removed. Returns the (would be) removed measurements of each
+/// commit they are removed from.
+pub fn remove<F>(
+    commits: Option<&[String]>,
+    predicate: F,
+    dry_run: bool,
+) -> Result<Vec<(String, Vec<MeasurementData>)>>
+where
+    F: Fn(&MeasurementData) -> bool,
+{
+    let backend = storage::backend()?;
+    let notes = match commits {
+        Some(commits) => commits
+            .iter()
+            .map(|commit| Ok((commit.clone(), backend.list(commit)?)))
+            .collect::<Result<Vec<_>>>()?,
+        None => backend.walk_all(None)?,
     };
+    let mut removed = Vec::new();
+    for (commit, lines) in notes {
+        let (kept, matching) = split_off_matching(lines, &predicate);
+        if matching.is_empty() {
+            continue;
+        }
+        if !dry_run {
+            backend.rewrite(&commit, &kept.join("\n"))?;
+        }
+        removed.push((commit, matching));
+    }
+    Ok(removed)
+}
 
-    let serialized = serialize_single(&md, DELIMITER);
+/// The note `lines` without the measurements matching `predicate`, and the matching measurements.
+fn split_off_matching<F>(lines: Vec<String>, predicate: &F) -> (Vec<String>, Vec<MeasurementData>)
+where
+    F: Fn(&MeasurementData) -> bool,
+{
+    let mut matching = Vec::new();
+    let mut kept = Vec::new();
+    for line in lines {
+        let (line_matching, line_kept): (Vec<_>, Vec<_>) =
+            deserialize(&line).into_iter().partition(predicate);
+        if line_matching.is_empty() {
+            kept.push(line);
+        } else if !line_kept.is_empty() {
+            // Keep the rest of a line with several measurements in its format
+            kept.push(
+                serialize_multiple(&line_kept, serialization::format_of(&line))
+                    .trim_end()
+                    .to_owned(),
+            );
+        }
+        matching.extend(line_matching);
+    }
+    (kept, matching)
+}
 
-    add_note_line_to_head(&serialized)?;
+/// Rewrite the measurements of all commits with any line not in `format`, e.g. to convert the
+/// history after changing `[storage] format`. With `dry_run`, nothing is rewritten. Returns the
+/// number of (would be) rewritten commits.
+pub fn migrate(format: Format, dry_run: bool) -> Result<usize> {
+    let backend = storage::backend()?;
+    let mut migrated = 0;
+    for (commit, lines) in backend.walk_all(None)? {
+        if lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .all(|line| serialization::format_of(line) == format)
+        {
+            continue;
+        }
+        if !dry_run {
+            let measurements = deserialize(&lines.join("\n"));
+            backend.rewrite(&commit, &serialize_multiple(&measurements, format))?;
+        }
+        migrated += 1;
+    }
+    Ok(migrated)
+}
 
-    Ok(())
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn writer_queues_from_several_threads() {
+        let writer = MeasurementWriter::new();
+        thread::scope(|s| {
+            for name in ["a", "b", "c"] {
+                let writer = &writer;
+                s.spawn(move || writer.queue_all(new_measurements(name, &[1.0, 2.0], &[])));
+            }
+        });
+        writer.queue(new_measurements("d", &[3.0], &[]).remove(0));
+        assert_eq!(writer.len(), 7);
+    }
+
+    #[test]
+    fn split_off_matching_measurements() {
+        let mut measurements = new_measurements("a", &[1.0, 2.0], &[]);
+        measurements.extend(new_measurements("b", &[3.0], &[]));
+        let v4_line = serialize_multiple(&measurements, Format::V4)
+            .lines()
+            .find(|l| l.contains("\x1ca\x1c"))
+            .unwrap()
+            .to_owned();
+        let b_line = serialize_multiple(&measurements[2..], Format::V3)
+            .trim_end()
+            .to_owned();
+        let (kept, matching) =
+            split_off_matching(vec![v4_line, b_line.clone()], &|m: &MeasurementData| {
+                m.val == 1.0
+            });
+        assert_eq!(matching.len(), 1);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(
+            deserialize(&kept[0]).iter().map(|m| m.val).collect_vec(),
+            [2.0]
+        );
+        assert_eq!(kept[1], b_line);
+    }
+
+    #[test]
+    fn parse_plain_line() {
+        let entry = parse_batch_line("timer 1.5 os=linux arch=x86").unwrap();
+        assert_eq!(entry.name, "timer");
+        assert_eq!(entry.value, 1.5);
+        assert_eq!(
+            entry.key_values,
+            HashMap::from([
+                ("os".to_string(), "linux".to_string()),
+                ("arch".to_string(), "x86".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_json_line() {
+        let entry =
+            parse_batch_line(r#"{"name": "timer", "value": 2, "key_values": {"os": "mac"}}"#)
+                .unwrap();
+        assert_eq!(entry.name, "timer");
+        assert_eq!(entry.value, 2.0);
+        assert_eq!(entry.key_values["os"], "mac");
+        assert!(parse_batch_line(r#"{"name": "timer", "value": 2}"#)
+            .unwrap()
+            .key_values
+            .is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_lines() {
+        assert!(parse_batch_line("timer").is_err());
+        assert!(parse_batch_line("timer fast").is_err());
+        assert!(parse_batch_line("timer 1 os").is_err());
+        assert!(parse_batch_line(r#"{"name": "timer"}"#).is_err());
+    }
 }
@@ -0,0 +1,118 @@
+//! Optional on-disk cache of the deserialized measurements of all annotated commits, enabled with
+//! `[storage] cache = true`. The cache is keyed by the notes refs and the objects they point to,
+//! so that it is rebuilt on the first read after measurements were added, pulled, or removed.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, data::MeasurementData, git_interop, serialization};
+
+const CACHE_FILE: &str = "measurements.json";
+
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    /// Version of git-perf and state of the notes refs the measurements were read at
+    key: String,
+    measurements: HashMap<String, Vec<MeasurementData>>,
+}
+
+/// Whether measurements are read through the cache. Other storage backends than git notes are
+/// never cached.
+pub fn enabled() -> bool {
+    config::determine_storage_cache_from_config()
+        && matches!(
+            config::determine_storage_backend_from_config().as_deref(),
+            None | Some("git-notes")
+        )
+}
+
+fn cache_key() -> Result<String> {
+    Ok(format!(
+        "{}\n{}",
+        env!("CARGO_PKG_VERSION"),
+        git_interop::notes_refs_state()?
+    ))
+}
+
+fn read(path: &Path) -> Option<Cache> {
+    let content = fs::read(path).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+fn write(path: &Path, cache: &Cache) -> Result<()> {
+    let dir = path.parent().expect("Cache file has a parent directory");
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create cache directory {}", dir.display()))?;
+    // Concurrent readers must never see a partially written cache
+    let temp = path.with_extension(format!("tmp-{}", std::process::id()));
+    fs::write(&temp, serde_json::to_vec(cache)?)
+        .with_context(|| format!("Failed to write {}", temp.display()))?;
+    fs::rename(&temp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+fn cache_path() -> Result<PathBuf> {
+    Ok(git_interop::cache_dir()?.join(CACHE_FILE))
+}
+
+/// Measurements per annotated commit, read from the cache while it matches the notes refs and
+/// rebuilt otherwise. Failing to store the rebuilt cache only prints a warning.
+fn measurements_by_commit() -> Result<HashMap<String, Vec<MeasurementData>>> {
+    let key = cache_key()?;
+    let path = cache_path()?;
+    if let Some(cache) = read(&path).filter(|cache| cache.key == key) {
+        return Ok(cache.measurements);
+    }
+    let measurements = git_interop::walk_notes(None)?
+        .into_par_iter()
+        .map(|(commit, lines)| (commit, serialization::deserialize(&lines.join("\n"))))
+        .collect();
+    let cache = Cache { key, measurements };
+    if let Err(e) = write(&path, &cache) {
+        eprintln!("Failed to update the measurement cache: {e:#}");
+    }
+    Ok(cache.measurements)
+}
+
+/// The last `num_commits` first-parent commits starting at `start`, newest first, with their
+/// cached measurements.
+pub fn walk(start: &str, num_commits: usize) -> Result<Vec<(String, Vec<MeasurementData>)>> {
+    let commits = git_interop::first_parent_commits(start, num_commits)?;
+    let mut measurements = measurements_by_commit()?;
+    Ok(commits
+        .into_iter()
+        .map(|commit| {
+            let measurements = measurements.remove(&commit).unwrap_or_default();
+            (commit, measurements)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cached_values_round_trip_exactly() {
+        let measurement = || MeasurementData {
+            epoch: 0,
+            name: "timer".to_owned(),
+            timestamp: 1792293993.5286539,
+            val: 0.1 + 0.2,
+            key_values: HashMap::new(),
+        };
+        let cache = Cache {
+            key: "key".to_owned(),
+            measurements: HashMap::from([("abc".to_owned(), vec![measurement()])]),
+        };
+        let read: Cache = serde_json::from_slice(&serde_json::to_vec(&cache).unwrap()).unwrap();
+        assert_eq!(read.measurements["abc"], [measurement()]);
+    }
+}
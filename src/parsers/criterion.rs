@@ -0,0 +1,177 @@
+use std::{collections::HashMap, io::BufRead};
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use serde::Deserialize;
+
+use super::{BenchmarkMeasurement, ParsedEntries, ParsedMeasurement, Parser, SkippedEntry};
+
+/// Parser for the JSON message stream of cargo-criterion with `--message-format=json`.
+/// With a `compare_baseline`, the relative changes criterion computed against that baseline are
+/// additionally parsed as `mean_change` and `median_change` in percent.
+pub struct CriterionParser {
+    pub compare_baseline: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "reason")]
+enum Message {
+    #[serde(rename = "benchmark-complete")]
+    BenchmarkComplete(Benchmark),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct Benchmark {
+    id: String,
+    mean: Estimate,
+    median: Estimate,
+    slope: Option<Estimate>,
+    change: Option<Change>,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    estimate: f64,
+    unit: String,
+}
+
+#[derive(Deserialize)]
+struct Change {
+    mean: Estimate,
+    median: Estimate,
+}
+
+impl Parser for CriterionParser {
+    fn parse<'a>(&self, input: Box<dyn BufRead + 'a>) -> Result<ParsedEntries<'a>> {
+        let compare_baseline = self.compare_baseline.clone();
+        let messages = input.lines().enumerate().map(move |(number, line)| {
+            let line = line.context("Failed to read criterion JSON messages")?;
+            parse_message(&line, compare_baseline.as_deref()).with_context(|| {
+                format!(
+                    "Failed to parse criterion JSON message on line {}",
+                    number + 1
+                )
+            })
+        });
+        Ok(Box::new(messages.flatten_ok()))
+    }
+}
+
+/// Parses a single line of the message stream, one message per line.
+fn parse_message(line: &str, compare_baseline: Option<&str>) -> Result<Vec<ParsedMeasurement>> {
+    let mut measurements = Vec::new();
+    if line.trim().is_empty() {
+        return Ok(measurements);
+    }
+    let Message::BenchmarkComplete(benchmark) = serde_json::from_str(line)? else {
+        return Ok(measurements);
+    };
+
+    let statistics = [("mean", benchmark.mean), ("median", benchmark.median)]
+        .into_iter()
+        .chain(benchmark.slope.map(|slope| ("slope", slope)));
+    for (statistic, estimate) in statistics {
+        measurements.push(ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+            id: benchmark.id.clone(),
+            statistic: statistic.to_string(),
+            value: estimate.estimate,
+            unit: estimate.unit,
+            metadata: HashMap::new(),
+        }));
+    }
+
+    let Some(baseline) = compare_baseline else {
+        return Ok(measurements);
+    };
+    let Some(change) = benchmark.change else {
+        measurements.push(ParsedMeasurement::Skipped(SkippedEntry {
+            id: benchmark.id,
+            reason: format!("no comparison with baseline '{baseline}'"),
+        }));
+        return Ok(measurements);
+    };
+    for (statistic, estimate) in [
+        ("mean_change", change.mean),
+        ("median_change", change.median),
+    ] {
+        measurements.push(ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+            id: benchmark.id.clone(),
+            statistic: statistic.to_string(),
+            // criterion reports relative changes as fractions
+            value: estimate.estimate * 100.0,
+            unit: "%".to_string(),
+            metadata: [("baseline".to_string(), baseline.to_string())].into(),
+        }));
+    }
+    Ok(measurements)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INPUT: &str = r#"{"reason":"benchmark-complete","id":"sort/1000","report_directory":"target/criterion/reports/sort/1000","iteration_count":[10,20],"measured_values":[1000.0,2000.0],"unit":"ns","throughput":[],"typical":{"estimate":100.0,"lower_bound":99.0,"upper_bound":101.0,"unit":"ns"},"mean":{"estimate":100.5,"lower_bound":99.0,"upper_bound":102.0,"unit":"ns"},"median":{"estimate":100.0,"lower_bound":99.0,"upper_bound":101.0,"unit":"ns"},"median_abs_dev":{"estimate":1.0,"lower_bound":0.5,"upper_bound":1.5,"unit":"ns"},"slope":{"estimate":100.0,"lower_bound":99.0,"upper_bound":101.0,"unit":"ns"},"change":{"mean":{"estimate":0.05,"lower_bound":0.04,"upper_bound":0.06,"unit":"%"},"median":{"estimate":-0.02,"lower_bound":-0.03,"upper_bound":-0.01,"unit":"%"},"change":"Regressed"}}
+{"reason":"benchmark-complete","id":"parse","report_directory":"target/criterion/reports/parse","iteration_count":[10],"measured_values":[50.0],"unit":"ns","throughput":[],"typical":{"estimate":5.0,"lower_bound":4.0,"upper_bound":6.0,"unit":"ns"},"mean":{"estimate":5.0,"lower_bound":4.0,"upper_bound":6.0,"unit":"ns"},"median":{"estimate":5.0,"lower_bound":4.0,"upper_bound":6.0,"unit":"ns"},"median_abs_dev":{"estimate":0.1,"lower_bound":0.0,"upper_bound":0.2,"unit":"ns"},"slope":null,"change":null}
+{"reason":"group-complete","group_name":"sort","benchmarks":["sort/1000"],"report_directory":"target/criterion/reports/sort"}
+"#;
+
+    #[test]
+    fn parse_absolute_statistics() {
+        let parsed = CriterionParser {
+            compare_baseline: None,
+        }
+        .parse_str(INPUT)
+        .unwrap();
+        assert_eq!(parsed.len(), 5, "no slope for 'parse'");
+        assert_eq!(
+            parsed[0],
+            ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                id: "sort/1000".to_string(),
+                statistic: "mean".to_string(),
+                value: 100.5,
+                unit: "ns".to_string(),
+                metadata: HashMap::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_changes_against_baseline() {
+        let parsed = CriterionParser {
+            compare_baseline: Some("main".to_string()),
+        }
+        .parse_str(INPUT)
+        .unwrap();
+        assert_eq!(parsed.len(), 8);
+        assert_eq!(
+            parsed[4],
+            ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                id: "sort/1000".to_string(),
+                statistic: "median_change".to_string(),
+                value: -2.0,
+                unit: "%".to_string(),
+                metadata: [("baseline".to_string(), "main".to_string())].into(),
+            })
+        );
+        assert_eq!(
+            parsed[7],
+            ParsedMeasurement::Skipped(SkippedEntry {
+                id: "parse".to_string(),
+                reason: "no comparison with baseline 'main'".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn reject_invalid_messages() {
+        let parser = CriterionParser {
+            compare_baseline: None,
+        };
+        assert!(parser.parse_str("{").is_err());
+        assert!(parser
+            .parse_str(r#"{"reason":"benchmark-complete"}"#)
+            .is_err());
+    }
+}
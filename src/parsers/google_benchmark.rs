@@ -0,0 +1,161 @@
+use std::{collections::HashMap, io::BufRead};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{BenchmarkMeasurement, ParsedEntries, ParsedMeasurement, Parser, SkippedEntry};
+
+/// Parser for the output of Google Benchmark with `--benchmark_format=json`.
+pub struct GoogleBenchmarkParser;
+
+#[derive(Deserialize)]
+struct Output {
+    benchmarks: Vec<Benchmark>,
+}
+
+#[derive(Deserialize)]
+struct Benchmark {
+    name: String,
+    run_name: Option<String>,
+    run_type: Option<String>,
+    aggregate_name: Option<String>,
+    aggregate_unit: Option<String>,
+    #[serde(default)]
+    error_occurred: bool,
+    real_time: f64,
+    cpu_time: f64,
+    time_unit: String,
+}
+
+impl Parser for GoogleBenchmarkParser {
+    fn parse<'a>(&self, input: Box<dyn BufRead + 'a>) -> Result<ParsedEntries<'a>> {
+        let output: Output =
+            serde_json::from_reader(input).context("Failed to parse Google Benchmark JSON")?;
+
+        let mut measurements = Vec::new();
+        for benchmark in output.benchmarks {
+            if benchmark.error_occurred {
+                measurements.push(ParsedMeasurement::Skipped(SkippedEntry {
+                    id: benchmark.name,
+                    reason: "benchmark reported an error".to_string(),
+                }));
+                continue;
+            }
+
+            let mut metadata = HashMap::new();
+            if benchmark.run_type.as_deref() == Some("aggregate") {
+                // Coefficients of variation are percentages, not times.
+                if benchmark.aggregate_unit.as_deref() == Some("percentage") {
+                    measurements.push(ParsedMeasurement::Skipped(SkippedEntry {
+                        id: benchmark.name,
+                        reason: "percentage aggregates are not times".to_string(),
+                    }));
+                    continue;
+                }
+                if let Some(aggregate) = benchmark.aggregate_name {
+                    metadata.insert("aggregate".to_string(), aggregate);
+                }
+            }
+
+            let id = benchmark.run_name.unwrap_or(benchmark.name);
+            for (statistic, value) in [
+                ("real_time", benchmark.real_time),
+                ("cpu_time", benchmark.cpu_time),
+            ] {
+                measurements.push(ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                    id: id.clone(),
+                    statistic: statistic.to_string(),
+                    value,
+                    unit: benchmark.time_unit.clone(),
+                    metadata: metadata.clone(),
+                }));
+            }
+        }
+
+        Ok(Box::new(measurements.into_iter().map(Ok)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_iterations_and_aggregates() {
+        let input = r#"{
+  "context": { "library_build_type": "release" },
+  "benchmarks": [
+    {
+      "name": "BM_Sort/8",
+      "run_name": "BM_Sort/8",
+      "run_type": "iteration",
+      "iterations": 1000,
+      "real_time": 12.5,
+      "cpu_time": 12.0,
+      "time_unit": "us"
+    },
+    {
+      "name": "BM_Sort/8_mean",
+      "run_name": "BM_Sort/8",
+      "run_type": "aggregate",
+      "aggregate_name": "mean",
+      "aggregate_unit": "time",
+      "real_time": 13.0,
+      "cpu_time": 12.5,
+      "time_unit": "us"
+    },
+    {
+      "name": "BM_Sort/8_cv",
+      "run_name": "BM_Sort/8",
+      "run_type": "aggregate",
+      "aggregate_name": "cv",
+      "aggregate_unit": "percentage",
+      "real_time": 0.01,
+      "cpu_time": 0.01,
+      "time_unit": "us"
+    },
+    {
+      "name": "BM_Broken",
+      "error_occurred": true,
+      "real_time": 0,
+      "cpu_time": 0,
+      "time_unit": "ns"
+    }
+  ]
+}"#;
+        let parsed = GoogleBenchmarkParser.parse_str(input).unwrap();
+        assert_eq!(parsed.len(), 6);
+        assert_eq!(
+            parsed[0],
+            ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                id: "BM_Sort/8".to_string(),
+                statistic: "real_time".to_string(),
+                value: 12.5,
+                unit: "us".to_string(),
+                metadata: HashMap::new(),
+            })
+        );
+        assert_eq!(
+            parsed[3],
+            ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                id: "BM_Sort/8".to_string(),
+                statistic: "cpu_time".to_string(),
+                value: 12.5,
+                unit: "us".to_string(),
+                metadata: [("aggregate".to_string(), "mean".to_string())].into(),
+            })
+        );
+        assert_eq!(
+            parsed[5],
+            ParsedMeasurement::Skipped(SkippedEntry {
+                id: "BM_Broken".to_string(),
+                reason: "benchmark reported an error".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_json() {
+        assert!(GoogleBenchmarkParser.parse_str("{").is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use std::{collections::HashMap, io::BufRead};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{BenchmarkMeasurement, ParsedEntries, ParsedMeasurement, Parser};
+
+/// Parser for the output of hyperfine with `--export-json`.
+pub struct HyperfineParser;
+
+#[derive(Deserialize)]
+struct Output {
+    results: Vec<Benchmark>,
+}
+
+#[derive(Deserialize)]
+struct Benchmark {
+    command: String,
+    mean: f64,
+    median: f64,
+    min: f64,
+    max: f64,
+    #[serde(default)]
+    parameters: HashMap<String, String>,
+}
+
+impl Parser for HyperfineParser {
+    fn parse<'a>(&self, input: Box<dyn BufRead + 'a>) -> Result<ParsedEntries<'a>> {
+        let output: Output =
+            serde_json::from_reader(input).context("Failed to parse hyperfine JSON")?;
+
+        let mut measurements = Vec::new();
+        for benchmark in output.results {
+            for (statistic, value) in [
+                ("mean", benchmark.mean),
+                ("median", benchmark.median),
+                ("min", benchmark.min),
+                ("max", benchmark.max),
+            ] {
+                measurements.push(ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                    id: benchmark.command.clone(),
+                    statistic: statistic.to_string(),
+                    value,
+                    // hyperfine always exports seconds
+                    unit: "s".to_string(),
+                    metadata: benchmark.parameters.clone(),
+                }));
+            }
+        }
+
+        Ok(Box::new(measurements.into_iter().map(Ok)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_statistics() {
+        let input = r#"{
+  "results": [
+    {
+      "command": "sleep 0.1",
+      "mean": 0.105,
+      "stddev": 0.002,
+      "median": 0.104,
+      "user": 0.001,
+      "system": 0.002,
+      "min": 0.101,
+      "max": 0.11,
+      "times": [0.101, 0.104, 0.11],
+      "exit_codes": [0, 0, 0],
+      "parameters": { "delay": "0.1" }
+    }
+  ]
+}"#;
+        let parsed = HyperfineParser.parse_str(input).unwrap();
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(
+            parsed[1],
+            ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                id: "sleep 0.1".to_string(),
+                statistic: "median".to_string(),
+                value: 0.104,
+                unit: "s".to_string(),
+                metadata: [("delay".to_string(), "0.1".to_string())].into(),
+            })
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::{collections::HashMap, io::BufRead};
+
+use anyhow::{anyhow, bail, Context, Result};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use super::{BenchmarkMeasurement, ParsedEntries, ParsedMeasurement, Parser, SkippedEntry};
+
+/// Parser for JUnit XML test reports, e.g. of `pytest --junitxml` or `cargo nextest`.
+/// The duration of each passed testcase is parsed as its `time` statistic, named
+/// `<classname>.<name>`. Skipped, failed, and errored testcases are skipped.
+///
+/// The report is parsed while iterating over the entries. Only the current element is held in
+/// memory, so that reports of hundreds of megabytes can be imported.
+pub struct JunitXmlParser;
+
+impl Parser for JunitXmlParser {
+    fn parse<'a>(&self, input: Box<dyn BufRead + 'a>) -> Result<ParsedEntries<'a>> {
+        let mut reader = Reader::from_reader(input);
+        // Testcases without children are usually written as `<testcase ... />`
+        reader.config_mut().expand_empty_elements = true;
+        Ok(Box::new(Testcases {
+            reader,
+            buf: Vec::new(),
+            has_root: false,
+            testcase: None,
+            done: false,
+        }))
+    }
+}
+
+/// Iterator over the testcases of a report, read one event at a time.
+struct Testcases<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    /// Whether the `<testsuites>` or `<testsuite>` root element was seen
+    has_root: bool,
+    /// The testcase whose end is not read yet
+    testcase: Option<Testcase>,
+    /// Set at the end of the report or after an error
+    done: bool,
+}
+
+struct Testcase {
+    id: String,
+    time: Option<String>,
+    /// Why the testcase has no measurement, e.g. `failed`
+    not_passed: Option<&'static str>,
+}
+
+impl Testcase {
+    fn from_element(element: &BytesStart) -> Result<Self> {
+        let attribute = |name: &str| -> Result<Option<String>> {
+            match element.try_get_attribute(name)? {
+                Some(attribute) => Ok(Some(attribute.unescape_value()?.into_owned())),
+                None => Ok(None),
+            }
+        };
+        let name = attribute("name")?.ok_or_else(|| anyhow!("Testcase without a name"))?;
+        let id = match attribute("classname")? {
+            Some(classname) if !classname.is_empty() => format!("{classname}.{name}"),
+            _ => name,
+        };
+        Ok(Testcase {
+            id,
+            time: attribute("time")?,
+            not_passed: None,
+        })
+    }
+
+    fn into_entry(self) -> Result<ParsedMeasurement> {
+        let reason = match (self.not_passed, self.time) {
+            (Some(reason), _) => reason,
+            (None, None) => "no time",
+            (None, Some(time)) => {
+                let value = time
+                    .parse()
+                    .with_context(|| format!("Invalid time '{time}' of testcase '{}'", self.id))?;
+                return Ok(ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                    id: self.id,
+                    statistic: "time".to_string(),
+                    value,
+                    unit: "s".to_string(),
+                    metadata: HashMap::new(),
+                }));
+            }
+        };
+        Ok(ParsedMeasurement::Skipped(SkippedEntry {
+            id: self.id,
+            reason: reason.to_string(),
+        }))
+    }
+}
+
+impl<R: BufRead> Testcases<R> {
+    fn next_entry(&mut self) -> Result<Option<ParsedMeasurement>> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf)? {
+                Event::Start(element) if !self.has_root => {
+                    if !matches!(element.name().as_ref(), b"testsuites" | b"testsuite") {
+                        bail!("Expected a <testsuites> or <testsuite> element");
+                    }
+                    self.has_root = true;
+                }
+                Event::Start(element) => match (element.name().as_ref(), &mut self.testcase) {
+                    (b"testcase", _) => self.testcase = Some(Testcase::from_element(&element)?),
+                    (b"skipped", Some(testcase)) => {
+                        testcase.not_passed.get_or_insert("skipped");
+                    }
+                    (b"failure", Some(testcase)) => {
+                        testcase.not_passed.get_or_insert("failed");
+                    }
+                    (b"error", Some(testcase)) => {
+                        testcase.not_passed.get_or_insert("errored");
+                    }
+                    _ => {}
+                },
+                Event::End(element) if element.name().as_ref() == b"testcase" => {
+                    if let Some(testcase) = self.testcase.take() {
+                        return testcase.into_entry().map(Some);
+                    }
+                }
+                Event::Eof if !self.has_root => bail!("No <testsuites> or <testsuite> element"),
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Testcases<R> {
+    type Item = Result<ParsedMeasurement>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let entry = self.next_entry().with_context(|| {
+            format!(
+                "Failed to parse JUnit XML at byte {}",
+                self.reader.buffer_position()
+            )
+        });
+        self.done = !matches!(entry, Ok(Some(_)));
+        entry.transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::Cell,
+        io::{BufReader, Read},
+        rc::Rc,
+    };
+
+    use super::*;
+
+    const INPUT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<testsuites>
+  <testsuite name="pytest" tests="4">
+    <properties><property name="os" value="linux"/></properties>
+    <testcase classname="tests.test_sort" name="test_small" time="0.5"/>
+    <testcase classname="tests.test_sort" name="test_large" time="1.25">
+      <system-out>sorting &lt;1000&gt; items</system-out>
+    </testcase>
+    <testcase classname="tests.test_sort" name="test_skip" time="0.0">
+      <skipped message="not on linux"/>
+    </testcase>
+    <testcase classname="" name="test_fail" time="0.1"><failure message="boom"/></testcase>
+  </testsuite>
+</testsuites>
+"#;
+
+    #[test]
+    fn parse_testcases() {
+        let parsed = JunitXmlParser.parse_str(INPUT).unwrap();
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(
+            parsed[1],
+            ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                id: "tests.test_sort.test_large".to_string(),
+                statistic: "time".to_string(),
+                value: 1.25,
+                unit: "s".to_string(),
+                metadata: HashMap::new(),
+            })
+        );
+        assert_eq!(
+            parsed[2],
+            ParsedMeasurement::Skipped(SkippedEntry {
+                id: "tests.test_sort.test_skip".to_string(),
+                reason: "skipped".to_string(),
+            })
+        );
+        assert_eq!(
+            parsed[3],
+            ParsedMeasurement::Skipped(SkippedEntry {
+                id: "test_fail".to_string(),
+                reason: "failed".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn reject_invalid_reports() {
+        assert!(JunitXmlParser.parse_str("").is_err());
+        assert!(JunitXmlParser.parse_str(r#"{"results": []}"#).is_err());
+        assert!(JunitXmlParser.parse_str("<html></html>").is_err());
+        assert!(JunitXmlParser
+            .parse_str(r#"<testsuite><testcase name="a" time="fast"/></testsuite>"#)
+            .is_err());
+        assert!(JunitXmlParser
+            .parse_str(r#"<testsuite><testcase name="a"></testsuite>"#)
+            .is_err());
+    }
+
+    /// A report with `testcases` testcases, generated while being read.
+    struct GeneratedReport {
+        testcases: usize,
+        next: usize,
+        pending: Vec<u8>,
+        bytes_read: Rc<Cell<usize>>,
+    }
+
+    impl Read for GeneratedReport {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.pending.is_empty() {
+                self.pending = match self.next {
+                    0 => b"<testsuites><testsuite name=\"generated\">".to_vec(),
+                    n if n <= self.testcases => format!(
+                        "<testcase classname=\"generated\" name=\"test_{n}\" time=\"0.{n}\">\
+                         <system-out>{}</system-out></testcase>\n",
+                        "x".repeat(100)
+                    )
+                    .into_bytes(),
+                    n if n == self.testcases + 1 => b"</testsuite></testsuites>".to_vec(),
+                    _ => return Ok(0),
+                };
+                self.next += 1;
+            }
+            let len = buf.len().min(self.pending.len());
+            buf[..len].copy_from_slice(&self.pending[..len]);
+            self.pending.drain(..len);
+            self.bytes_read.set(self.bytes_read.get() + len);
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn parse_while_reading() {
+        let testcases = 100_000;
+        let bytes_read = Rc::new(Cell::new(0));
+        let report = GeneratedReport {
+            testcases,
+            next: 0,
+            pending: Vec::new(),
+            bytes_read: bytes_read.clone(),
+        };
+        let mut entries = JunitXmlParser
+            .parse(Box::new(BufReader::new(report)))
+            .unwrap();
+
+        assert!(matches!(
+            entries.next(),
+            Some(Ok(ParsedMeasurement::Benchmark(_)))
+        ));
+        assert!(
+            bytes_read.get() <= 2 * 8192,
+            "only the start of the report is read for the first entry"
+        );
+        assert_eq!(entries.count(), testcases - 1);
+        assert!(bytes_read.get() > testcases * 100);
+    }
+}
@@ -0,0 +1,50 @@
+use std::{collections::HashMap, io::BufRead};
+
+use anyhow::Result;
+use serde::Serialize;
+
+pub mod criterion;
+pub mod google_benchmark;
+pub mod hyperfine;
+pub mod junit_xml;
+pub mod pytest_benchmark;
+
+/// A single statistic reported by a benchmark framework, not yet converted to a measurement.
+#[derive(Debug, PartialEq)]
+pub struct BenchmarkMeasurement {
+    /// Name of the benchmark as reported by the framework
+    pub id: String,
+    /// Which statistic of the benchmark this is, e.g. `real_time` or `mean`
+    pub statistic: String,
+    pub value: f64,
+    /// Unit of `value` as reported by the framework, e.g. `ns`
+    pub unit: String,
+    /// Additional key-value pairs to be stored with the measurement
+    pub metadata: HashMap<String, String>,
+}
+
+/// An entry of the input that was deliberately not turned into a measurement.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SkippedEntry {
+    /// Name of the entry as reported by the framework
+    pub id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParsedMeasurement {
+    Benchmark(BenchmarkMeasurement),
+    Skipped(SkippedEntry),
+}
+
+/// Entries of an input, parsed while iterating as far as the format allows
+pub type ParsedEntries<'a> = Box<dyn Iterator<Item = Result<ParsedMeasurement>> + 'a>;
+
+pub trait Parser {
+    fn parse<'a>(&self, input: Box<dyn BufRead + 'a>) -> Result<ParsedEntries<'a>>;
+
+    /// All entries of an input held in memory.
+    fn parse_str(&self, input: &str) -> Result<Vec<ParsedMeasurement>> {
+        self.parse(Box::new(input.as_bytes()))?.collect()
+    }
+}
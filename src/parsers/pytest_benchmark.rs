@@ -0,0 +1,116 @@
+use std::{collections::HashMap, io::BufRead};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{BenchmarkMeasurement, ParsedEntries, ParsedMeasurement, Parser};
+
+/// Parser for the JSON written by pytest-benchmark with `--benchmark-json`.
+pub struct PytestBenchmarkParser;
+
+#[derive(Deserialize)]
+struct Output {
+    benchmarks: Vec<Benchmark>,
+}
+
+#[derive(Deserialize)]
+struct Benchmark {
+    name: String,
+    group: Option<String>,
+    stats: Stats,
+}
+
+#[derive(Deserialize)]
+struct Stats {
+    min: f64,
+    max: f64,
+    mean: f64,
+    stddev: f64,
+    median: f64,
+}
+
+impl Parser for PytestBenchmarkParser {
+    fn parse<'a>(&self, input: Box<dyn BufRead + 'a>) -> Result<ParsedEntries<'a>> {
+        let output: Output =
+            serde_json::from_reader(input).context("Failed to parse pytest-benchmark JSON")?;
+
+        let mut measurements = Vec::new();
+        for benchmark in output.benchmarks {
+            let mut metadata = HashMap::new();
+            if let Some(group) = benchmark.group {
+                metadata.insert("group".to_string(), group);
+            }
+
+            let stats = benchmark.stats;
+            for (statistic, value) in [
+                ("min", stats.min),
+                ("max", stats.max),
+                ("mean", stats.mean),
+                ("stddev", stats.stddev),
+                ("median", stats.median),
+            ] {
+                measurements.push(ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                    id: benchmark.name.clone(),
+                    statistic: statistic.to_string(),
+                    value,
+                    // pytest-benchmark always reports seconds
+                    unit: "s".to_string(),
+                    metadata: metadata.clone(),
+                }));
+            }
+        }
+
+        Ok(Box::new(measurements.into_iter().map(Ok)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_statistics() {
+        let input = r#"{
+  "machine_info": { "python_implementation": "CPython" },
+  "benchmarks": [
+    {
+      "group": "sorting",
+      "name": "test_sort[8]",
+      "fullname": "tests/test_sort.py::test_sort[8]",
+      "params": { "n": 8 },
+      "stats": {
+        "min": 0.001,
+        "max": 0.004,
+        "mean": 0.002,
+        "stddev": 0.0005,
+        "rounds": 100,
+        "median": 0.0019,
+        "iqr": 0.0002,
+        "ops": 500.0
+      }
+    }
+  ],
+  "datetime": "2024-01-01T00:00:00",
+  "version": "4.0.0"
+}"#;
+        let parsed = PytestBenchmarkParser.parse_str(input).unwrap();
+        let statistics = parsed
+            .iter()
+            .filter_map(|p| match p {
+                ParsedMeasurement::Benchmark(b) => Some(b.statistic.as_str()),
+                ParsedMeasurement::Skipped(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(statistics, ["min", "max", "mean", "stddev", "median"]);
+        assert_eq!(
+            parsed[2],
+            ParsedMeasurement::Benchmark(BenchmarkMeasurement {
+                id: "test_sort[8]".to_string(),
+                statistic: "mean".to_string(),
+                value: 0.002,
+                unit: "s".to_string(),
+                metadata: [("group".to_string(), "sorting".to_string())].into(),
+            })
+        );
+    }
+}
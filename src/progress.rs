@@ -0,0 +1,190 @@
+//! Progress on stderr for operations that can run for minutes, such as pulling, pushing,
+//! pruning, and large imports. Nothing is drawn if stderr is not a terminal, after [`disable`],
+//! or without the `progress` feature.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Never show progress, e.g. for `--no-progress`.
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Progress of a single operation, cleared from the terminal when dropped.
+pub struct Progress {
+    #[cfg(feature = "progress")]
+    bar: indicatif::ProgressBar,
+}
+
+#[cfg(feature = "progress")]
+impl Progress {
+    fn new(len: Option<usize>, template: &str, message: &str) -> Self {
+        Self::with_target(
+            len,
+            template,
+            message,
+            indicatif::ProgressDrawTarget::stderr(),
+        )
+    }
+
+    fn with_target(
+        len: Option<usize>,
+        template: &str,
+        message: &str,
+        target: indicatif::ProgressDrawTarget,
+    ) -> Self {
+        use indicatif::{ProgressBar, ProgressStyle};
+
+        if DISABLED.load(Ordering::Relaxed) {
+            return Progress {
+                bar: ProgressBar::hidden(),
+            };
+        }
+        let bar = ProgressBar::with_draw_target(len.map(|len| len as u64), target);
+        bar.set_style(ProgressStyle::with_template(template).expect("Invalid progress template"));
+        bar.set_message(message.to_owned());
+        bar.enable_steady_tick(std::time::Duration::from_millis(100));
+        Progress { bar }
+    }
+
+    /// Count `len` items, such as commits or notes, described by `message`.
+    pub fn items(message: &str, len: usize) -> Self {
+        Self::new(Some(len), "{msg} [{bar:30}] {pos}/{len}", message)
+    }
+
+    /// A spinner for an operation of unknown length, such as a single git command.
+    pub fn spinner(message: &str) -> Self {
+        Self::new(None, "{spinner} {msg}", message)
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+    }
+
+    pub fn set_message(&self, message: String) {
+        self.bar.set_message(message);
+    }
+
+    /// Whether anything is drawn, e.g. to only ask git for progress output when shown.
+    pub fn is_visible(&self) -> bool {
+        !self.bar.is_hidden()
+    }
+
+    /// Clear the progress before the operation ends, e.g. to print its results.
+    pub fn finish(self) {}
+}
+
+#[cfg(feature = "progress")]
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+#[cfg(not(feature = "progress"))]
+impl Progress {
+    pub fn items(_message: &str, _len: usize) -> Self {
+        Progress {}
+    }
+
+    pub fn spinner(_message: &str) -> Self {
+        Progress {}
+    }
+
+    pub fn inc(&self, _delta: u64) {}
+
+    pub fn set_message(&self, _message: String) {}
+
+    pub fn is_visible(&self) -> bool {
+        false
+    }
+
+    pub fn finish(self) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Terminal recording everything drawn to it.
+    #[cfg(feature = "progress")]
+    #[derive(Debug, Clone, Default)]
+    struct RecordingTerm(std::sync::Arc<std::sync::Mutex<String>>);
+
+    #[cfg(feature = "progress")]
+    impl indicatif::TermLike for RecordingTerm {
+        fn width(&self) -> u16 {
+            80
+        }
+
+        fn move_cursor_up(&self, _n: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn move_cursor_down(&self, _n: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn move_cursor_right(&self, _n: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn move_cursor_left(&self, _n: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn write_line(&self, s: &str) -> std::io::Result<()> {
+            self.write_str(&format!("{s}\n"))
+        }
+
+        fn write_str(&self, s: &str) -> std::io::Result<()> {
+            self.0.lock().unwrap().push_str(s);
+            Ok(())
+        }
+
+        fn clear_line(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Both cases in one test as disabling is global and irreversible
+    #[cfg(feature = "progress")]
+    #[test]
+    fn disable_stops_drawing_on_terminal() {
+        let draw = |term: &RecordingTerm| {
+            let progress = Progress::with_target(
+                Some(2),
+                "{msg} {pos}/{len}",
+                "Testing",
+                indicatif::ProgressDrawTarget::term_like(Box::new(term.clone())),
+            );
+            progress.bar.tick();
+            progress.is_visible()
+        };
+
+        let term = RecordingTerm::default();
+        assert!(draw(&term));
+        assert!(term.0.lock().unwrap().contains("Testing 0/2"));
+
+        disable();
+        let term = RecordingTerm::default();
+        assert!(!draw(&term));
+        assert_eq!(*term.0.lock().unwrap(), "");
+        assert!(!Progress::items("Testing", 2).is_visible());
+        assert!(!Progress::spinner("Testing").is_visible());
+    }
+
+    #[cfg(not(feature = "progress"))]
+    #[test]
+    fn progress_is_invisible_without_feature() {
+        let progress = Progress::items("Testing", 2);
+        progress.inc(1);
+        assert!(!progress.is_visible());
+        assert!(!Progress::spinner("Testing").is_visible());
+    }
+}
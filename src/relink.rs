@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::{git_interop, measurement_retrieval::Commit, storage};
+
+/// Copy the measurements of the commits in `from` to their rewritten counterparts in `to`, e.g.
+/// after a rebase. Commits are matched by their Change-Id trailer or their patch id.
+/// Measurements already present on the new commit are not copied again.
+/// Returns the number of commits that received measurements.
+pub fn relink(from: &str, to: &str) -> Result<usize> {
+    let backend = storage::backend()?;
+    let old_identities = git_interop::commit_identities_in_range(from)?;
+    let mut new_commits = HashMap::new();
+    for (commit, identity) in git_interop::commit_identities_in_range(to)? {
+        new_commits.entry(identity).or_insert(commit);
+    }
+
+    let mut relinked = 0;
+    for (old_commit, lines) in backend.walk_all(Some(from))? {
+        let Some(new_commit) = old_identities
+            .get(&old_commit)
+            .and_then(|identity| new_commits.get(identity))
+        else {
+            continue;
+        };
+        if *new_commit == old_commit {
+            continue;
+        }
+        let existing = backend.list(new_commit)?;
+        let missing = lines
+            .iter()
+            .filter(|l| !l.trim().is_empty() && !existing.contains(l))
+            .join("\n");
+        if missing.is_empty() {
+            continue;
+        }
+        backend.append(new_commit, &missing)?;
+        relinked += 1;
+    }
+
+    Ok(relinked)
+}
+
+/// Number of `commits` without measurements whose rewritten versions (e.g. before a rebase) have
+/// measurements. Only checked if at least half of the commits lack measurements.
+fn orphaned_commits(commits: &[Commit]) -> Result<usize> {
+    let without_data = commits
+        .iter()
+        .filter(|c| c.measurements.is_empty())
+        .map(|c| c.commit.as_str())
+        .collect_vec();
+    if without_data.len() < 2 || without_data.len() * 2 < commits.len() {
+        return Ok(0);
+    }
+
+    let walked: HashSet<_> = commits.iter().map(|c| c.commit.as_str()).collect();
+    let annotated = storage::backend()?
+        .walk_all(None)?
+        .into_iter()
+        .filter(|(commit, lines)| !lines.is_empty() && !walked.contains(commit.as_str()))
+        .map(|(commit, _)| commit)
+        .collect_vec();
+    if annotated.is_empty() {
+        return Ok(0);
+    }
+
+    let annotated_identities: HashSet<_> =
+        git_interop::commit_identities_of(&annotated.iter().map(String::as_str).collect_vec())?
+            .into_values()
+            .collect();
+    let orphaned = git_interop::commit_identities_of(&without_data)?
+        .into_values()
+        .filter(|identity| annotated_identities.contains(identity))
+        .count();
+    Ok(orphaned)
+}
+
+/// Warn if many of the `commits` lack measurements that rewritten versions of them have.
+/// This is only a hint, so failures to check are ignored.
+pub fn warn_about_orphaned_measurements(commits: &[Commit]) {
+    if let Ok(orphaned @ 1..) = orphaned_commits(commits) {
+        eprintln!(
+            "Warning: {orphaned} of the last {} commits have no measurements, but rewritten versions of them (e.g. before a rebase) do. \
+             Copy them with 'git perf relink --from <old-range> --to <new-range>'.",
+            commits.len()
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::{collections::BTreeMap, fmt::Write, fs::File, io::BufReader, path::Path};
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+
+use crate::{
+    data::MeasurementData,
+    reporting::JsonReport,
+    stats::{self, Stats},
+};
+
+fn read_report(path: &Path) -> Result<JsonReport<MeasurementData>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open report '{}'", path.display()))?;
+    serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("Failed to parse report '{}'", path.display()))
+}
+
+/// Group all values of a report by their series: The measurement name together with either its
+/// key-value pairs (raw measurements) or its trace group (summarized measurements).
+fn series(report: JsonReport<MeasurementData>) -> BTreeMap<String, Stats> {
+    let mut series: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+
+    for m in report.measurements {
+        let md = m.measurement;
+        let label = if md.key_values.is_empty() {
+            md.name
+        } else {
+            let key_values = md
+                .key_values
+                .iter()
+                .sorted()
+                .map(|(k, v)| format!("{k}={v}"))
+                .join(", ");
+            format!("{} ({key_values})", md.name)
+        };
+        series.entry(label).or_default().push(md.val);
+    }
+
+    for s in report.summaries {
+        let label = match s.group {
+            Some(group) => format!("{} [{group}]", s.name),
+            None => s.name,
+        };
+        series.entry(label).or_default().push(s.val);
+    }
+
+    series
+        .into_iter()
+        .map(|(label, vals)| (label, stats::aggregate_measurements(vals.into_iter())))
+        .collect()
+}
+
+fn diff_as_markdown(
+    old: &BTreeMap<String, Stats>,
+    new: &BTreeMap<String, Stats>,
+    sigma: f64,
+) -> String {
+    let mut out = String::from("## Performance report changes\n");
+
+    let added = new.keys().filter(|k| !old.contains_key(*k)).collect_vec();
+    let removed = old.keys().filter(|k| !new.contains_key(*k)).collect_vec();
+    let shifted = old
+        .iter()
+        .filter_map(|(label, old_stats)| {
+            let new_stats = new.get(label)?;
+            let shifted = match old_stats.welch_t(new_stats) {
+                Some(t) => t.abs() > sigma,
+                // Without any variance, e.g. for single values, every change is a shift
+                None => old_stats.mean != new_stats.mean,
+            };
+            shifted.then_some((label, old_stats, new_stats))
+        })
+        .collect_vec();
+
+    if added.is_empty() && removed.is_empty() && shifted.is_empty() {
+        out.push_str("\nNo changes.\n");
+        return out;
+    }
+
+    if !added.is_empty() {
+        out.push_str("\n### New measurements\n\n");
+        for label in added {
+            writeln!(out, "- `{label}`: {}", new[label]).unwrap();
+        }
+    }
+
+    if !removed.is_empty() {
+        out.push_str("\n### Disappeared measurements\n\n");
+        for label in removed {
+            writeln!(out, "- `{label}`: {}", old[label]).unwrap();
+        }
+    }
+
+    if !shifted.is_empty() {
+        out.push_str("\n### Significant shifts\n\n");
+        out.push_str("| Measurement | Old | New | Change |\n");
+        out.push_str("|---|---|---|---|\n");
+        for (label, old_stats, new_stats) in shifted {
+            let change = (new_stats.mean - old_stats.mean) / old_stats.mean * 100.0;
+            writeln!(
+                out,
+                "| `{label}` | {old_stats} | {new_stats} | {change:+.2}% |"
+            )
+            .unwrap();
+        }
+    }
+
+    out
+}
+
+/// Print a markdown summary of the differences between two JSON reports.
+pub fn report_diff(old: &Path, new: &Path, sigma: f64) -> Result<()> {
+    let old = series(read_report(old)?);
+    let new = series(read_report(new)?);
+    print!("{}", diff_as_markdown(&old, &new, sigma));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stats_of(vals: &[f64]) -> Stats {
+        stats::aggregate_measurements(vals.iter().copied())
+    }
+
+    #[test]
+    fn no_changes() {
+        let old = BTreeMap::from([("timer".to_string(), stats_of(&[1.0, 2.0]))]);
+        let new = BTreeMap::from([("timer".to_string(), stats_of(&[1.0, 2.0]))]);
+        assert!(diff_as_markdown(&old, &new, 4.0).contains("No changes."));
+    }
+
+    #[test]
+    fn added_removed_and_shifted() {
+        let old = BTreeMap::from([
+            ("timer".to_string(), stats_of(&[1.0, 1.1])),
+            ("gone".to_string(), stats_of(&[1.0])),
+        ]);
+        let new = BTreeMap::from([
+            ("timer".to_string(), stats_of(&[10.0, 10.1])),
+            ("fresh".to_string(), stats_of(&[1.0])),
+        ]);
+        let diff = diff_as_markdown(&old, &new, 4.0);
+        assert!(diff.contains("### New measurements\n\n- `fresh`"));
+        assert!(diff.contains("### Disappeared measurements\n\n- `gone`"));
+        assert!(diff.contains("| `timer` |"));
+    }
+
+    #[test]
+    fn shifts_without_variance() {
+        let old = BTreeMap::from([
+            ("size".to_string(), stats_of(&[100.0, 100.0])),
+            ("single".to_string(), stats_of(&[1.0])),
+            ("same".to_string(), stats_of(&[5.0])),
+        ]);
+        let new = BTreeMap::from([
+            ("size".to_string(), stats_of(&[120.0, 120.0])),
+            ("single".to_string(), stats_of(&[2.0])),
+            ("same".to_string(), stats_of(&[5.0])),
+        ]);
+        let diff = diff_as_markdown(&old, &new, 4.0);
+        assert!(diff.contains("| `size` |"));
+        assert!(diff.contains("| `single` |"));
+        assert!(!diff.contains("`same`"));
+        assert!(!diff.contains("inf"));
+    }
+}
@@ -0,0 +1,59 @@
+// Keeps the hidden traces and the zoom range of the report in the URL hash so that a link
+// to the report restores the same view.
+const graphDiv = document.getElementById("plotly-html-element");
+const axes = ["xaxis", "yaxis"];
+
+function encodeState() {
+  const params = new URLSearchParams();
+  const hidden = graphDiv.data.flatMap((trace, i) =>
+    trace.visible === "legendonly" ? [i] : [],
+  );
+  if (hidden.length > 0) {
+    params.set("hidden", hidden.join(","));
+  }
+  for (const axis of axes) {
+    const layout = graphDiv.layout[axis];
+    if (layout && layout.autorange === false && layout.range) {
+      params.set(axis, layout.range.join(","));
+    }
+  }
+  const hash = params.toString();
+  history.replaceState(null, "", hash ? "#" + hash : location.pathname + location.search);
+}
+
+async function restoreState() {
+  const params = new URLSearchParams(location.hash.slice(1));
+  const hidden = (params.get("hidden") || "")
+    .split(",")
+    .filter((i) => i !== "")
+    .map(Number)
+    .filter((i) => Number.isInteger(i) && i < graphDiv.data.length);
+  if (hidden.length > 0) {
+    await Plotly.restyle(graphDiv, { visible: "legendonly" }, hidden);
+  }
+  const ranges = {};
+  for (const axis of axes) {
+    const range = (params.get(axis) || "").split(",").map(Number);
+    if (range.length === 2 && range.every(Number.isFinite)) {
+      ranges[axis + ".range"] = range;
+    }
+  }
+  if (Object.keys(ranges).length > 0) {
+    await Plotly.relayout(graphDiv, ranges);
+  }
+}
+
+// The plot is created asynchronously by the preceding script.
+function whenPlotted(callback) {
+  if (graphDiv.data) {
+    callback();
+  } else {
+    setTimeout(() => whenPlotted(callback), 50);
+  }
+}
+
+whenPlotted(async () => {
+  await restoreState();
+  graphDiv.on("plotly_relayout", encodeState);
+  graphDiv.on("plotly_restyle", encodeState);
+});
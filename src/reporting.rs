@@ -1,24 +1,52 @@
+#[cfg(any(feature = "report-html", feature = "report-image"))]
+use std::collections::HashMap;
 use std::{
     fs::File,
     io::{self, ErrorKind, Write},
+    iter,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 use anyhow::anyhow;
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+#[cfg(feature = "report-image")]
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
 use itertools::Itertools;
+#[cfg(feature = "report-html")]
 use plotly::{
-    common::{Font, LegendGroupTitle, Title},
-    layout::{Axis, Legend},
+    color::Rgba,
+    common::{
+        ErrorData, ErrorType, Fill, Font, LegendGroupTitle, Line, Marker, MarkerSymbol, Mode, Title,
+    },
+    layout::{Axis, AxisType, BarMode, Legend},
     Configuration, Layout, Plot,
 };
+#[cfg(feature = "report-image")]
+use plotters::{
+    coord::Shift,
+    prelude::{
+        BitMapBackend, ChartBuilder, Circle, Cross, DrawingArea, DrawingBackend, IntoDrawingArea,
+        IntoFont, LineSeries, Palette, Palette99, PathElement, Polygon, SVGBackend,
+        SeriesLabelPosition, BLACK, WHITE,
+    },
+    style::{Color, RGBColor},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 // TODO(kaihowl) find central place for the data structures
+#[cfg(any(feature = "report-html", feature = "report-image"))]
+use crate::units::{self, Scaling};
 use crate::{
-    data::{MeasurementData, MeasurementSummary, ReductionFunc},
+    audit, config,
+    data::{self, MeasurementData, MeasurementSummary, ReductionFunc, Selector},
+    git_interop::{self, CommitInfo},
     measurement_retrieval::{self, Commit, ReductionFuncIterator},
-    serialization::{serialize_single, DELIMITER},
+    relink, sparkline,
+    stats::{self, ChangePoint, NumericReductionFunc},
 };
+use readable::num::Float;
 
 trait Reporter<'a> {
     fn add_commits(&mut self, hashes: &'a [Commit]);
@@ -34,38 +62,338 @@ trait Reporter<'a> {
         measurement_name: &str,
         group_value: Option<&String>,
     );
+    /// Acceptable range `(index, lower, upper)` per commit. Ignored by non-graphical reporters.
+    fn add_threshold_band(
+        &mut self,
+        _indexed_bands: Vec<(usize, f64, f64)>,
+        _measurement_name: &str,
+        _group_value: Option<&String>,
+    ) {
+    }
+    /// Step change of a trace, first seen at the commit `index`. Ignored by non-graphical
+    /// reporters.
+    fn add_change_point(
+        &mut self,
+        _index: usize,
+        _change_point: &ChangePoint,
+        _measurement_name: &str,
+        _group_value: Option<&String>,
+    ) {
+    }
+    /// Title of the report, set before adding the commits
+    fn set_title(&mut self, _title: &str) {}
+    /// Committer time of each commit in seconds since the epoch, newest first, to place the
+    /// commits on a time axis. Set before adding the commits. Ignored by non-graphical reporters.
+    fn set_commit_times(&mut self, _times: Vec<i64>) {}
+    /// Author, date, and subject of each commit, newest first, shown in hover texts. Set before
+    /// adding the commits. Ignored by all but HTML reporters.
+    fn set_commit_infos(&mut self, _infos: Vec<CommitInfo>) {}
     fn as_bytes(&self) -> Vec<u8>;
+    /// The report followed by further sections, each rendered by [`Reporter::as_section_bytes`].
+    fn as_bytes_with_sections(&self, sections: &[Vec<u8>]) -> Vec<u8> {
+        iter::once(self.as_bytes())
+            .chain(sections.iter().cloned())
+            .concat()
+    }
+    /// The report as a section of another report, see [`Reporter::as_bytes_with_sections`].
+    fn as_section_bytes(&self, _id: &str) -> Vec<u8> {
+        self.as_bytes()
+    }
 }
 
+#[cfg(feature = "report-html")]
+const PERMALINK_SCRIPT: &str = include_str!("report_permalink.js");
+
+/// Opens the commit of a clicked point. Expects the links per x-axis position as `commitUrls`
+/// and the id of the plot's element as `plotId`.
+#[cfg(feature = "report-html")]
+const COMMIT_LINK_SCRIPT: &str = r#"document
+  .getElementById(plotId)
+  .on("plotly_click", (event) => {
+    const url = commitUrls[event.points[0].x];
+    if (url) {
+      window.open(url, "_blank");
+    }
+  });
+"#;
+
+/// Id of the plot's element in the page generated by plotly.
+#[cfg(feature = "report-html")]
+const PLOT_ID: &str = "plotly-html-element";
+
+/// Number of characters of commit hashes on the x-axis of graphical reports without
+/// configuration.
+#[cfg(any(feature = "report-html", feature = "report-image"))]
+const DEFAULT_SHA_LENGTH: usize = 6;
+
+#[cfg(feature = "report-html")]
 struct PlotlyReporter {
     plot: Plot,
+    title: String,
     // TODO(kaihowl) hack until we can auto_range 'reverse' the axis in plotly directly
     size: usize,
+    compact_hover: bool,
+    auto_scale_units: bool,
+    /// Display unit per measurement, shared by all of its traces
+    scalings: HashMap<String, Option<Scaling>>,
+    /// Reasons recorded for the epochs per measurement, shown in the hover texts of their values
+    epoch_reasons: HashMap<String, HashMap<u32, String>>,
+    commit_url_template: Option<String>,
+    /// Link per x-axis position, empty without a commit URL template
+    commit_urls: Vec<String>,
+    /// Number of characters of the commit hashes on the x-axis
+    sha_length: usize,
+    /// Full commit hashes, newest first, shown in hover texts
+    hashes: Vec<String>,
+    /// Committer times in seconds since the epoch, newest first, for a time x-axis. Empty for
+    /// equally spaced commits.
+    times: Vec<i64>,
+    /// Metadata of the commits, newest first, shown in hover texts. Empty if unknown.
+    infos: Vec<CommitInfo>,
+    chart: ChartType,
+    /// Bars of the newest commit with [`ChartType::BarLatest`], added as one trace per group
+    /// when rendering
+    bars: Vec<LatestBar>,
+}
+
+/// Value of a measurement at the newest commit of a bar chart, with the standard deviation of
+/// its repetitions as error bar.
+#[cfg(feature = "report-html")]
+#[derive(Debug, PartialEq)]
+struct LatestBar {
+    /// Display name of the measurement, the category on the x-axis
+    measurement: String,
+    group_value: Option<String>,
+    val: f64,
+    stddev: f64,
+    hover_text: String,
+}
+
+#[cfg(feature = "report-html")]
+fn summary_hover_text(summary: &MeasurementSummary) -> String {
+    format!(
+        "n: {}<br>σ: {}<br>MAD: {}",
+        summary.len,
+        Float::from(summary.stddev),
+        Float::from(summary.mad),
+    )
 }
 
+#[cfg(feature = "report-html")]
 impl PlotlyReporter {
-    fn new() -> PlotlyReporter {
+    fn new(auto_scale_units: bool, sha_length: Option<usize>, chart: ChartType) -> PlotlyReporter {
         let config = Configuration::default().responsive(true).fill_frame(true);
         let mut plot = Plot::new();
         plot.set_configuration(config);
-        PlotlyReporter { plot, size: 0 }
+        PlotlyReporter {
+            plot,
+            title: "Performance Measurements".to_owned(),
+            size: 0,
+            compact_hover: config::determine_compact_hover_from_config(),
+            auto_scale_units: auto_scale_units && config::determine_auto_scale_units_from_config(),
+            scalings: HashMap::new(),
+            epoch_reasons: HashMap::new(),
+            commit_url_template: config::determine_commit_url_template_from_config(),
+            commit_urls: Vec::new(),
+            sha_length: sha_length
+                .or_else(config::determine_report_sha_length_from_config)
+                .unwrap_or(DEFAULT_SHA_LENGTH),
+            hashes: Vec::new(),
+            times: Vec::new(),
+            infos: Vec::new(),
+            chart,
+            bars: Vec::new(),
+        }
     }
 
-    fn convert_to_x_y(&self, indexed_measurements: Vec<(usize, f64)>) -> (Vec<usize>, Vec<f64>) {
+    /// Add the bar of a trace for its value at the newest commit, if any, scaled like its other
+    /// traces.
+    fn add_latest_bar(
+        &mut self,
+        latest: Option<MeasurementSummary>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        let Some(latest) = latest else {
+            return;
+        };
+        let scaling = self.scaling(measurement_name, &[latest.val]);
+        let (val, stddev) = match &scaling {
+            Some(s) => (s.apply(latest.val), s.apply(latest.stddev)),
+            None => (latest.val, latest.stddev),
+        };
+        let hover_text = iter::once(self.commit_hover_text(0))
+            .chain(scaling.as_ref().map(|s| s.format_original(latest.val)))
+            .chain((!self.compact_hover).then(|| summary_hover_text(&latest)))
+            .join("<br>");
+        self.bars.push(LatestBar {
+            measurement: Self::display_name(measurement_name, &scaling),
+            group_value: group_value.cloned(),
+            val,
+            stddev,
+            hover_text,
+        });
+    }
+
+    /// The plot with the bars of [`ChartType::BarLatest`] grouped by their group values, each
+    /// group in one trace for a shared color and legend entry.
+    fn rendered_plot(&self) -> Plot {
+        let mut plot = self.plot.clone();
+        let groups = self
+            .bars
+            .iter()
+            .into_group_map_by(|bar| bar.group_value.as_deref());
+        for (group_value, bars) in groups.into_iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+            let x = bars.iter().map(|bar| bar.measurement.clone()).collect_vec();
+            let y = bars.iter().map(|bar| bar.val).collect_vec();
+            let errors = bars.iter().map(|bar| bar.stddev).collect_vec();
+            let hover_texts = bars.iter().map(|bar| bar.hover_text.clone()).collect_vec();
+            let trace = plotly::Bar::new(x, y)
+                .error_y(ErrorData::new(ErrorType::Data).array(errors))
+                .hover_text_array(hover_texts);
+            let trace = match group_value {
+                Some(group_value) => trace.name(group_value),
+                None => trace.show_legend(false),
+            };
+            plot.add_trace(trace);
+        }
+        plot
+    }
+
+    fn commit_hover_text(&self, index: usize) -> String {
+        let commit = format!("commit: {}", self.hashes[index]);
+        match self.infos.get(index) {
+            Some(info) => format!(
+                "{commit}<br>{}, {}<br>{}",
+                crate::digest::escape(&info.author),
+                info.date,
+                crate::digest::escape(&info.subject)
+            ),
+            None => commit,
+        }
+    }
+
+    /// Hover text of the reason recorded for the epoch of the measurement, if any.
+    fn epoch_hover_text(&mut self, measurement_name: &str, epoch: u32) -> Option<String> {
+        let reason = self
+            .epoch_reasons
+            .entry(measurement_name.to_owned())
+            .or_insert_with(|| config::determine_epoch_reasons_from_config(measurement_name))
+            .get(&epoch)?;
+        Some(format!("epoch {epoch:08x}: {reason}"))
+    }
+
+    /// The scaling of the measurement, determined by the values of its first trace.
+    fn scaling(&mut self, measurement_name: &str, values: &[f64]) -> Option<Scaling> {
+        if !self.auto_scale_units {
+            return None;
+        }
+        self.scalings
+            .entry(measurement_name.to_owned())
+            .or_insert_with(|| {
+                let unit = config::determine_unit_from_config(measurement_name)?;
+                units::auto_scale(&unit, values)
+            })
+            .clone()
+    }
+
+    fn scaled_values(scaling: &Option<Scaling>, values: Vec<f64>) -> Vec<f64> {
+        match scaling {
+            Some(scaling) => values.into_iter().map(|v| scaling.apply(v)).collect(),
+            None => values,
+        }
+    }
+
+    fn display_name(measurement_name: &str, scaling: &Option<Scaling>) -> String {
+        match scaling {
+            Some(scaling) => format!("{measurement_name} [{}]", scaling.unit),
+            None => measurement_name.to_owned(),
+        }
+    }
+
+    /// Script opening the commits of the points clicked in the plot with the id `plot_id`.
+    fn commit_link_script(&self, plot_id: &str) -> String {
+        if self.commit_urls.is_empty() {
+            return String::new();
+        }
+        format!(
+            "<script>\n{{\nconst plotId = {};\nconst commitUrls = {};\n{COMMIT_LINK_SCRIPT}}}\n</script>\n",
+            serde_json::to_string(plot_id).expect("Serializing plot id failed"),
+            serde_json::to_string(&self.commit_urls).expect("Serializing commit URLs failed")
+        )
+    }
+
+    /// Position of the commit `index` on the x-axis: its time in milliseconds for a time axis,
+    /// otherwise its position counted from the oldest commit.
+    fn x_position(&self, index: usize) -> i64 {
+        match self.times.get(index) {
+            Some(time) => time * 1000,
+            None => (self.size - index - 1) as i64,
+        }
+    }
+
+    fn convert_to_x_y(&self, indexed_measurements: Vec<(usize, f64)>) -> (Vec<i64>, Vec<f64>) {
         indexed_measurements
             .iter()
-            .map(|(i, m)| (self.size - i - 1, m))
+            .map(|(i, m)| (self.x_position(*i), m))
             .unzip()
     }
 }
 
+#[cfg(feature = "report-html")]
 impl<'a> Reporter<'a> for PlotlyReporter {
     fn add_commits(&mut self, commits: &'a [Commit]) {
         let enumerated_commits = commits.iter().rev().enumerate();
         self.size = commits.len();
+        self.hashes = commits.iter().map(|c| c.commit.clone()).collect();
+
+        if self.chart == ChartType::BarLatest {
+            let title = match commits.first() {
+                Some(latest) => format!(
+                    "{} ({})",
+                    self.title,
+                    &latest.commit[..self.sha_length.min(latest.commit.len())]
+                ),
+                None => self.title.clone(),
+            };
+            let layout = Layout::new()
+                .title(Title::new(&title))
+                .bar_mode(BarMode::Group)
+                .legend(Legend::new().orientation(plotly::common::Orientation::Horizontal));
+            self.plot.set_layout(layout);
+            return;
+        }
+
+        let legend = Legend::new()
+            .group_click(plotly::layout::GroupClick::ToggleItem)
+            .orientation(plotly::common::Orientation::Horizontal);
+        if !self.times.is_empty() {
+            // Commits are only identified by their hover texts, without links
+            let layout = Layout::new()
+                .title(Title::new(&self.title))
+                .x_axis(Axis::new().type_(AxisType::Date))
+                .legend(legend);
+            self.plot.set_layout(layout);
+            return;
+        }
+
+        if let Some(template) = &self.commit_url_template {
+            self.commit_urls = commits
+                .iter()
+                .rev()
+                .map(|c| template.replace("{sha}", &c.commit))
+                .collect();
+        }
 
         let (commit_nrs, short_hashes): (Vec<_>, Vec<_>) = enumerated_commits
-            .map(|(n, c)| (n as f64, c.commit[..6].to_owned()))
+            .map(|(n, c)| {
+                let short_hash = c.commit[..self.sha_length.min(c.commit.len())].to_owned();
+                let label = match self.commit_urls.get(n) {
+                    Some(url) => format!("<a href=\"{url}\">{short_hash}</a>"),
+                    None => short_hash,
+                };
+                (n as f64, label)
+            })
             .unzip();
         let x_axis = Axis::new()
             .tick_values(commit_nrs)
@@ -73,13 +401,9 @@ impl<'a> Reporter<'a> for PlotlyReporter {
             .tick_angle(45.0)
             .tick_font(Font::new().family("monospace"));
         let layout = Layout::new()
-            .title(Title::new("Performance Measurements"))
+            .title(Title::new(&self.title))
             .x_axis(x_axis)
-            .legend(
-                Legend::new()
-                    .group_click(plotly::layout::GroupClick::ToggleItem)
-                    .orientation(plotly::common::Orientation::Horizontal),
-            );
+            .legend(legend);
 
         self.plot.set_layout(layout);
     }
@@ -90,22 +414,51 @@ impl<'a> Reporter<'a> for PlotlyReporter {
         measurement_name: &str,
         group_value: Option<&String>,
     ) {
+        if self.chart == ChartType::BarLatest {
+            // Raw repetitions are shown as their mean
+            let summary = indexed_measurements
+                .iter()
+                .filter(|(i, _)| *i == 0)
+                .map(|(_, m)| *m)
+                .reduce_by(ReductionFunc::Mean);
+            self.add_latest_bar(summary, measurement_name, group_value);
+            return;
+        }
+        let commits = indexed_measurements
+            .iter()
+            .map(|(i, m)| {
+                iter::once(self.commit_hover_text(*i))
+                    .chain(self.epoch_hover_text(measurement_name, m.epoch))
+                    .join("<br>")
+            })
+            .collect_vec();
         let (x, y) = self.convert_to_x_y(
             indexed_measurements
                 .into_iter()
                 .map(|(i, m)| (i, m.val))
                 .collect_vec(),
         );
+        let scaling = self.scaling(measurement_name, &y);
+        let hover_texts = commits
+            .into_iter()
+            .zip(&y)
+            .map(|(commit, v)| match &scaling {
+                Some(s) => format!("{commit}<br>{}", s.format_original(*v)),
+                None => commit,
+            })
+            .collect_vec();
+        let y = Self::scaled_values(&scaling, y);
+        let display_name = Self::display_name(measurement_name, &scaling);
 
-        let trace = plotly::BoxPlot::new_xy(x, y);
+        let trace = plotly::BoxPlot::new_xy(x, y).hover_text_array(hover_texts);
 
         let trace = if let Some(group_value) = group_value {
             trace
                 .name(group_value)
                 .legend_group(measurement_name)
-                .legend_group_title(LegendGroupTitle::new(measurement_name))
+                .legend_group_title(LegendGroupTitle::new(&display_name))
         } else {
-            trace.name(measurement_name)
+            trace.name(&display_name)
         };
 
         self.plot.add_trace(trace);
@@ -117,35 +470,500 @@ impl<'a> Reporter<'a> for PlotlyReporter {
         measurement_name: &str,
         group_value: Option<&String>,
     ) {
+        if self.chart == ChartType::BarLatest {
+            let latest = indexed_measurements
+                .into_iter()
+                .find_map(|(i, m)| (i == 0).then_some(m));
+            self.add_latest_bar(latest, measurement_name, group_value);
+            return;
+        }
+        let values = indexed_measurements
+            .iter()
+            .map(|(_, m)| m.val)
+            .collect_vec();
+        let scaling = self.scaling(measurement_name, &values);
+        let hover_texts = indexed_measurements
+            .iter()
+            .map(|(i, m)| {
+                let original = scaling.as_ref().map(|s| s.format_original(m.val));
+                let summary = (!self.compact_hover).then(|| summary_hover_text(m));
+                iter::once(self.commit_hover_text(*i))
+                    .chain(self.epoch_hover_text(measurement_name, m.epoch))
+                    .chain(original)
+                    .chain(summary)
+                    .join("<br>")
+            })
+            .collect_vec();
         let (x, y) = self.convert_to_x_y(
             indexed_measurements
                 .into_iter()
                 .map(|(i, m)| (i, m.val))
                 .collect_vec(),
         );
+        let y = Self::scaled_values(&scaling, y);
+        let display_name = Self::display_name(measurement_name, &scaling);
 
-        let trace = plotly::Scatter::new(x, y).name(measurement_name);
+        let trace = plotly::Scatter::new(x, y).hover_text_array(hover_texts);
 
         let trace = if let Some(group_value) = group_value {
             trace
                 .name(group_value)
                 .legend_group(measurement_name)
-                .legend_group_title(LegendGroupTitle::new(measurement_name))
+                .legend_group_title(LegendGroupTitle::new(&display_name))
         } else {
-            trace.name(measurement_name)
+            trace.name(&display_name)
         };
 
         self.plot.add_trace(trace);
     }
 
+    fn add_threshold_band(
+        &mut self,
+        indexed_bands: Vec<(usize, f64, f64)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        if self.chart == ChartType::BarLatest {
+            return;
+        }
+        let (lower, upper): (Vec<_>, Vec<_>) = indexed_bands
+            .into_iter()
+            .map(|(i, lower, upper)| ((i, lower), (i, upper)))
+            .unzip();
+        let (x_lower, y_lower) = self.convert_to_x_y(lower);
+        let (x_upper, y_upper) = self.convert_to_x_y(upper);
+        let scaling = self.scaling(measurement_name, &y_upper);
+        let y_lower = Self::scaled_values(&scaling, y_lower);
+        let y_upper = Self::scaled_values(&scaling, y_upper);
+
+        let legend_group = group_value.map_or(measurement_name, |g| g.as_str());
+
+        // The upper bound fills the area towards the previously added lower bound.
+        let lower = plotly::Scatter::new(x_lower, y_lower)
+            .mode(Mode::Lines)
+            .line(Line::new().width(0.0))
+            .legend_group(legend_group)
+            .show_legend(false);
+        let upper = plotly::Scatter::new(x_upper, y_upper)
+            .mode(Mode::Lines)
+            .line(Line::new().width(0.0))
+            .fill(Fill::ToNextY)
+            .fill_color(Rgba::new(128, 128, 128, 0.2))
+            .legend_group(legend_group)
+            .show_legend(false);
+
+        self.plot.add_trace(lower);
+        self.plot.add_trace(upper);
+    }
+
+    fn add_change_point(
+        &mut self,
+        index: usize,
+        change_point: &ChangePoint,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        if self.chart == ChartType::BarLatest {
+            return;
+        }
+        let (x, y) = self.convert_to_x_y(vec![(index, change_point.mean_after)]);
+        let scaling = self.scaling(measurement_name, &y);
+        let format = |value: f64| match &scaling {
+            Some(s) => format!("{} {}", Float::from(s.apply(value)), s.unit),
+            None => Float::from(value).to_string(),
+        };
+        let hover_text = format!(
+            "{}<br>step change: {} → {}",
+            self.commit_hover_text(index),
+            format(change_point.mean_before),
+            format(change_point.mean_after)
+        );
+        let y = Self::scaled_values(&scaling, y);
+        let legend_group = group_value.map_or(measurement_name, |g| g.as_str());
+
+        let marker = plotly::Scatter::new(x, y)
+            .mode(Mode::Markers)
+            .marker(Marker::new().symbol(MarkerSymbol::Star).size(14))
+            .hover_text_array(vec![hover_text])
+            .legend_group(legend_group)
+            .show_legend(false);
+        self.plot.add_trace(marker);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_owned();
+    }
+
+    fn set_commit_times(&mut self, times: Vec<i64>) {
+        self.times = times;
+    }
+
+    fn set_commit_infos(&mut self, infos: Vec<CommitInfo>) {
+        self.infos = infos;
+    }
+
     fn as_bytes(&self) -> Vec<u8> {
-        self.plot.to_html().as_bytes().to_vec()
+        self.as_bytes_with_sections(&[])
+    }
+
+    fn as_bytes_with_sections(&self, sections: &[Vec<u8>]) -> Vec<u8> {
+        let mut html = self.rendered_plot().to_html();
+        // The inlined plotly.js may contain the closing body tag itself.
+        let body_end = html.rfind("</body>").unwrap_or(html.len());
+        let mut appendix = sections
+            .iter()
+            .map(|section| String::from_utf8_lossy(section))
+            .join("");
+        // Restore and track the view through the URL hash for sharing permalinks.
+        appendix.push_str(&format!("<script>\n{PERMALINK_SCRIPT}</script>\n"));
+        appendix.push_str(&self.commit_link_script(PLOT_ID));
+        html.insert_str(body_end, &appendix);
+        html.into_bytes()
+    }
+
+    fn as_section_bytes(&self, id: &str) -> Vec<u8> {
+        let plot_id = format!("section-{id}");
+        let mut html = self.rendered_plot().to_inline_html(Some(&plot_id));
+        html.push_str(&self.commit_link_script(&plot_id));
+        html.into_bytes()
     }
 }
 
+/// Size in pixels of image reports
+#[cfg(feature = "report-image")]
+const IMAGE_SIZE: (u32, u32) = (1280, 720);
+
+#[cfg(feature = "report-image")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ImageFormat {
+    Svg,
+    Png,
+}
+
+#[cfg(feature = "report-image")]
+struct ImageTrace {
+    /// Legend entry
+    name: String,
+    /// `(x, value)` with the oldest commit at x = 0
+    points: Vec<(i32, f64)>,
+    /// Connect the points, which are a single value per commit
+    connected: bool,
+}
+
+/// Static chart of a report, e.g. to attach to the summary of a CI build. Unlike HTML reports,
+/// it has neither hover texts nor commit links.
+#[cfg(feature = "report-image")]
+struct ImageReporter {
+    format: ImageFormat,
+    title: String,
+    auto_scale_units: bool,
+    /// Display unit per measurement, shared by all of its traces
+    scalings: HashMap<String, Option<Scaling>>,
+    /// Number of characters of the commit hashes on the x-axis
+    sha_length: usize,
+    /// Short commit hashes, oldest first
+    labels: Vec<String>,
+    traces: Vec<ImageTrace>,
+    /// Acceptable ranges `(x, lower, upper)` per trace
+    bands: Vec<Vec<(i32, f64, f64)>>,
+    /// Step changes `(x, mean after the change)`
+    change_points: Vec<(i32, f64)>,
+}
+
+#[cfg(feature = "report-image")]
+impl ImageReporter {
+    fn new(
+        format: ImageFormat,
+        auto_scale_units: bool,
+        sha_length: Option<usize>,
+    ) -> Result<ImageReporter> {
+        // Text is laid out with the fonts of the system, even in SVG images.
+        ("sans-serif", 12)
+            .into_font()
+            .box_size("0")
+            .map_err(|e| anyhow!("No font found to draw image reports: {e}"))?;
+        Ok(ImageReporter {
+            format,
+            title: "Performance Measurements".to_owned(),
+            auto_scale_units: auto_scale_units && config::determine_auto_scale_units_from_config(),
+            scalings: HashMap::new(),
+            sha_length: sha_length
+                .or_else(config::determine_report_sha_length_from_config)
+                .unwrap_or(DEFAULT_SHA_LENGTH),
+            labels: Vec::new(),
+            traces: Vec::new(),
+            bands: Vec::new(),
+            change_points: Vec::new(),
+        })
+    }
+
+    fn x(&self, index: usize) -> i32 {
+        (self.labels.len() - index - 1) as i32
+    }
+
+    /// The scaling of the measurement, determined by the values of its first trace.
+    fn scaling(&mut self, measurement_name: &str, values: &[f64]) -> Option<Scaling> {
+        if !self.auto_scale_units {
+            return None;
+        }
+        self.scalings
+            .entry(measurement_name.to_owned())
+            .or_insert_with(|| {
+                let unit = config::determine_unit_from_config(measurement_name)?;
+                units::auto_scale(&unit, values)
+            })
+            .clone()
+    }
+
+    fn add_values(
+        &mut self,
+        indexed_values: Vec<(usize, f64)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+        connected: bool,
+    ) {
+        let values = indexed_values.iter().map(|(_, v)| *v).collect_vec();
+        let scaling = self.scaling(measurement_name, &values);
+        let display_name = match &scaling {
+            Some(scaling) => format!("{measurement_name} [{}]", scaling.unit),
+            None => measurement_name.to_owned(),
+        };
+        let mut points = indexed_values
+            .into_iter()
+            .map(|(i, v)| (self.x(i), scaling.as_ref().map_or(v, |s| s.apply(v))))
+            .collect_vec();
+        points.sort_by_key(|(x, _)| *x);
+        self.traces.push(ImageTrace {
+            name: match group_value {
+                Some(group_value) => format!("{display_name} {group_value}"),
+                None => display_name,
+            },
+            points,
+            connected,
+        });
+    }
+
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>) -> Result<()> {
+        let error = |e| anyhow!("Failed to draw image report: {e}");
+        root.fill(&WHITE).map_err(error)?;
+
+        let values = self
+            .traces
+            .iter()
+            .flat_map(|t| t.points.iter().map(|(_, v)| *v))
+            .chain(self.bands.iter().flatten().flat_map(|(_, l, u)| [*l, *u]))
+            .chain(self.change_points.iter().map(|(_, v)| *v));
+        let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        let (min, max) = if min <= max { (min, max) } else { (0.0, 1.0) };
+        let padding = if max > min {
+            (max - min) * 0.05
+        } else {
+            min.abs().max(1.0) * 0.1
+        };
+
+        let mut chart = ChartBuilder::on(root)
+            .caption(&self.title, ("sans-serif", 24))
+            .margin(20)
+            .x_label_area_size(40)
+            .y_label_area_size(80)
+            .build_cartesian_2d(
+                -1..self.labels.len() as i32,
+                (min - padding)..(max + padding),
+            )
+            .map_err(error)?;
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(20)
+            .x_label_formatter(&|x| {
+                usize::try_from(*x)
+                    .ok()
+                    .and_then(|x| self.labels.get(x))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .x_label_style(("monospace", 12))
+            .draw()
+            .map_err(error)?;
+
+        for band in &self.bands {
+            let outline = band
+                .iter()
+                .map(|(x, _, upper)| (*x, *upper))
+                .chain(band.iter().rev().map(|(x, lower, _)| (*x, *lower)))
+                .collect_vec();
+            chart
+                .draw_series(iter::once(Polygon::new(
+                    outline,
+                    RGBColor(128, 128, 128).mix(0.2),
+                )))
+                .map_err(error)?;
+        }
+
+        for (n, trace) in self.traces.iter().enumerate() {
+            let color = Palette99::pick(n).to_rgba();
+            let series = if trace.connected {
+                chart.draw_series(
+                    LineSeries::new(trace.points.iter().copied(), color.stroke_width(2))
+                        .point_size(3),
+                )
+            } else {
+                chart.draw_series(
+                    trace
+                        .points
+                        .iter()
+                        .map(|point| Circle::new(*point, 3, color.filled())),
+                )
+            }
+            .map_err(error)?;
+            series.label(&trace.name).legend(move |(x, y)| {
+                PathElement::new([(x, y), (x + 20, y)], color.stroke_width(2))
+            });
+        }
+
+        chart
+            .draw_series(
+                self.change_points
+                    .iter()
+                    .map(|point| Cross::new(*point, 8, BLACK.stroke_width(2))),
+            )
+            .map_err(error)?;
+
+        if !self.traces.is_empty() {
+            chart
+                .configure_series_labels()
+                .position(SeriesLabelPosition::UpperLeft)
+                .background_style(WHITE.mix(0.8))
+                .border_style(BLACK)
+                .draw()
+                .map_err(error)?;
+        }
+        root.present().map_err(error)?;
+        Ok(())
+    }
+
+    fn render(&self) -> Result<Vec<u8>> {
+        match self.format {
+            ImageFormat::Svg => {
+                let mut svg = String::new();
+                self.draw(&SVGBackend::with_string(&mut svg, IMAGE_SIZE).into_drawing_area())?;
+                Ok(svg.into_bytes())
+            }
+            ImageFormat::Png => {
+                let (width, height) = IMAGE_SIZE;
+                let mut pixels = vec![0; (width * height * 3) as usize];
+                self.draw(
+                    &BitMapBackend::with_buffer(&mut pixels, IMAGE_SIZE).into_drawing_area(),
+                )?;
+                let mut png = Vec::new();
+                PngEncoder::new(&mut png).write_image(&pixels, width, height, ColorType::Rgb8)?;
+                Ok(png)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "report-image")]
+impl<'a> Reporter<'a> for ImageReporter {
+    fn add_commits(&mut self, commits: &'a [Commit]) {
+        self.labels = commits
+            .iter()
+            .rev()
+            .map(|c| c.commit[..self.sha_length.min(c.commit.len())].to_owned())
+            .collect();
+    }
+
+    fn add_trace(
+        &mut self,
+        indexed_measurements: Vec<(usize, &'a MeasurementData)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        let indexed_values = indexed_measurements
+            .into_iter()
+            .map(|(i, m)| (i, m.val))
+            .collect();
+        self.add_values(indexed_values, measurement_name, group_value, false);
+    }
+
+    fn add_summarized_trace(
+        &mut self,
+        indexed_measurements: Vec<(usize, MeasurementSummary)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        let indexed_values = indexed_measurements
+            .into_iter()
+            .map(|(i, m)| (i, m.val))
+            .collect();
+        self.add_values(indexed_values, measurement_name, group_value, true);
+    }
+
+    fn add_threshold_band(
+        &mut self,
+        indexed_bands: Vec<(usize, f64, f64)>,
+        measurement_name: &str,
+        _group_value: Option<&String>,
+    ) {
+        let upper = indexed_bands.iter().map(|(_, _, u)| *u).collect_vec();
+        let scaling = self.scaling(measurement_name, &upper);
+        let scale = |v: f64| scaling.as_ref().map_or(v, |s| s.apply(v));
+        let mut band = indexed_bands
+            .into_iter()
+            .map(|(i, lower, upper)| (self.x(i), scale(lower), scale(upper)))
+            .collect_vec();
+        band.sort_by_key(|(x, _, _)| *x);
+        self.bands.push(band);
+    }
+
+    fn add_change_point(
+        &mut self,
+        index: usize,
+        change_point: &ChangePoint,
+        measurement_name: &str,
+        _group_value: Option<&String>,
+    ) {
+        let scaling = self.scaling(measurement_name, &[change_point.mean_after]);
+        let value = scaling.as_ref().map_or(change_point.mean_after, |s| {
+            s.apply(change_point.mean_after)
+        });
+        self.change_points.push((self.x(index), value));
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_owned();
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        self.render().expect("Drawing image report failed")
+    }
+}
+
+/// Which rows a CSV report contains.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CsvMode {
+    /// One row per sample with one column per metadata key
+    Raw,
+    /// One row per commit with the aggregated value of each measurement
+    Summary,
+}
+
+struct CsvSummaryRow {
+    index: usize,
+    name: String,
+    group: Option<String>,
+    summary: MeasurementSummary,
+}
+
+/// Tab separated report with a header line. Rows are ordered by commit (newest first), raw
+/// samples additionally by timestamp.
 struct CsvReporter<'a> {
     hashes: Vec<String>,
     indexed_measurements: Vec<(usize, &'a MeasurementData)>,
+    summaries: Vec<CsvSummaryRow>,
 }
 
 impl CsvReporter<'_> {
@@ -153,8 +971,80 @@ impl CsvReporter<'_> {
         CsvReporter {
             hashes: Vec::new(),
             indexed_measurements: Vec::new(),
+            summaries: Vec::new(),
         }
     }
+
+    fn raw_rows(&self) -> String {
+        let keys = self
+            .indexed_measurements
+            .iter()
+            .flat_map(|(_, m)| m.key_values.keys())
+            .unique()
+            .sorted()
+            .collect_vec();
+
+        let header = ["commit", "epoch", "name", "timestamp", "val"]
+            .into_iter()
+            .chain(keys.iter().map(|k| k.as_str()))
+            .join("\t");
+
+        let rows = self
+            .indexed_measurements
+            .iter()
+            .sorted_by(|(a_index, a), (b_index, b)| {
+                a_index
+                    .cmp(b_index)
+                    .then(a.timestamp.total_cmp(&b.timestamp))
+            })
+            .map(|(index, m)| {
+                let metadata = keys
+                    .iter()
+                    .map(|k| m.key_values.get(*k).map(String::as_str).unwrap_or_default());
+                [
+                    self.hashes[*index].clone(),
+                    m.epoch.to_string(),
+                    m.name.clone(),
+                    format!("{:?}", m.timestamp),
+                    format!("{:?}", m.val),
+                ]
+                .into_iter()
+                .chain(metadata.map(str::to_owned))
+                .join("\t")
+            });
+
+        iter::once(header)
+            .chain(rows)
+            .map(|row| row + "\n")
+            .collect()
+    }
+
+    fn summary_rows(&self) -> String {
+        let header = "commit\tepoch\tname\tgroup\tval\tn\tstddev\tmad".to_owned();
+
+        let rows = self
+            .summaries
+            .iter()
+            .sorted_by_key(|row| row.index)
+            .map(|row| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{:?}\t{}\t{:?}\t{:?}",
+                    self.hashes[row.index],
+                    row.summary.epoch,
+                    row.name,
+                    row.group.as_deref().unwrap_or_default(),
+                    row.summary.val,
+                    row.summary.len,
+                    row.summary.stddev,
+                    row.summary.mad,
+                )
+            });
+
+        iter::once(header)
+            .chain(rows)
+            .map(|row| row + "\n")
+            .collect()
+    }
 }
 
 impl<'a> Reporter<'a> for CsvReporter<'a> {
@@ -174,77 +1064,727 @@ impl<'a> Reporter<'a> for CsvReporter<'a> {
 
     fn as_bytes(&self) -> Vec<u8> {
         // TODO(kaihowl) write to path directly instead?
+        if self.summaries.is_empty() {
+            self.raw_rows().into_bytes()
+        } else {
+            self.summary_rows().into_bytes()
+        }
+    }
+
+    fn add_summarized_trace(
+        &mut self,
+        indexed_measurements: Vec<(usize, MeasurementSummary)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        self.summaries
+            .extend(
+                indexed_measurements
+                    .into_iter()
+                    .map(|(index, summary)| CsvSummaryRow {
+                        index,
+                        name: measurement_name.to_owned(),
+                        group: group_value.cloned(),
+                        summary,
+                    }),
+            );
+    }
+}
+
+/// Machine readable report. `commits` are ordered from newest to oldest.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonReport<M> {
+    pub commits: Vec<String>,
+    pub measurements: Vec<JsonMeasurement<M>>,
+    pub summaries: Vec<JsonSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonMeasurement<M> {
+    pub commit: String,
+    #[serde(flatten)]
+    pub measurement: M,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSummary {
+    pub commit: String,
+    pub name: String,
+    pub group: Option<String>,
+    pub epoch: u32,
+    pub val: f64,
+    /// Number of reduced measurements, missing in reports of older versions
+    #[serde(default)]
+    pub n: usize,
+    /// Sample standard deviation of the reduced measurements
+    #[serde(default)]
+    pub stddev: f64,
+    /// Median absolute deviation of the reduced measurements
+    #[serde(default)]
+    pub mad: f64,
+}
+
+struct JsonReporter<'a> {
+    hashes: Vec<String>,
+    indexed_measurements: Vec<(usize, &'a MeasurementData)>,
+    summaries: Vec<JsonSummary>,
+}
+
+impl JsonReporter<'_> {
+    fn new() -> Self {
+        JsonReporter {
+            hashes: Vec::new(),
+            indexed_measurements: Vec::new(),
+            summaries: Vec::new(),
+        }
+    }
+}
 
+impl<'a> Reporter<'a> for JsonReporter<'a> {
+    fn add_commits(&mut self, hashes: &'a [Commit]) {
+        self.hashes = hashes.iter().map(|c| c.commit.to_owned()).collect();
+    }
+
+    fn add_trace(
+        &mut self,
+        indexed_measurements: Vec<(usize, &'a MeasurementData)>,
+        _measurement_name: &str,
+        _group_value: Option<&String>,
+    ) {
         self.indexed_measurements
-            .iter()
-            .map(|(index, measurement_data)| {
-                let ser_measurement = serialize_single(measurement_data, "\t");
-                let commit = &self.hashes[*index];
-                format!("{commit}{DELIMITER}{ser_measurement}")
+            .extend_from_slice(indexed_measurements.as_slice());
+    }
+
+    fn add_summarized_trace(
+        &mut self,
+        indexed_measurements: Vec<(usize, MeasurementSummary)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        self.summaries
+            .extend(indexed_measurements.into_iter().map(|(i, m)| JsonSummary {
+                commit: self.hashes[i].clone(),
+                name: measurement_name.to_owned(),
+                group: group_value.cloned(),
+                epoch: m.epoch,
+                val: m.val,
+                n: m.len,
+                stddev: m.stddev,
+                mad: m.mad,
+            }));
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        let report = JsonReport {
+            commits: self.hashes.clone(),
+            measurements: self
+                .indexed_measurements
+                .iter()
+                .map(|(index, measurement)| JsonMeasurement {
+                    commit: self.hashes[*index].clone(),
+                    measurement: *measurement,
+                })
+                .collect(),
+            summaries: self.summaries.clone(),
+        };
+        serde_json::to_vec_pretty(&report).expect("Serializing report failed")
+    }
+}
+
+/// The acceptable range `mean ± sigma * stddev` of each commit's summarized value with respect
+/// to all of its older commits, as evaluated by `audit`. Older commits from a different epoch are
+/// not considered unless `ignore_epochs` is set. Commits with fewer than two older values have no
+/// band.
+///
+/// The band deliberately uses the tail's mean and standard deviation rather than its median and
+/// a robust dispersion: the audit compares the z-score against exactly these, so only values
+/// within the band pass it.
+fn audit_bands(
+    indexed_summaries: &[(usize, MeasurementSummary)],
+    sigma: f64,
+    ignore_epochs: bool,
+) -> Vec<(usize, f64, f64)> {
+    indexed_summaries
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, (i, summary))| {
+            let tail = indexed_summaries[pos + 1..]
+                .iter()
+                .take_while(|(_, older)| ignore_epochs || older.epoch == summary.epoch)
+                .map(|(_, older)| older.val);
+            let tail_stats = stats::aggregate_measurements(tail);
+            (tail_stats.len >= 2).then_some((
+                *i,
+                tail_stats.mean - sigma * tail_stats.stddev,
+                tail_stats.mean + sigma * tail_stats.stddev,
+            ))
+        })
+        .collect()
+}
+
+struct MarkdownTrace {
+    measurement_name: String,
+    group_value: Option<String>,
+    /// Value per commit index, newest commit first
+    indexed_values: Vec<(usize, f64)>,
+}
+
+/// Table per measurement with the latest value, the change to the previous commit, and a trend.
+struct MarkdownReporter {
+    /// Heading above the tables, e.g. of a report section
+    title: Option<String>,
+    traces: Vec<MarkdownTrace>,
+    sparkline: sparkline::Options,
+}
+
+impl MarkdownReporter {
+    fn new(sparkline: sparkline::Options) -> Self {
+        MarkdownReporter {
+            title: None,
+            traces: Vec::new(),
+            sparkline,
+        }
+    }
+
+    fn add_values(
+        &mut self,
+        indexed_values: Vec<(usize, f64)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        self.traces.push(MarkdownTrace {
+            measurement_name: measurement_name.to_owned(),
+            group_value: group_value.cloned(),
+            indexed_values,
+        });
+    }
+}
+
+impl<'a> Reporter<'a> for MarkdownReporter {
+    fn add_commits(&mut self, _hashes: &'a [Commit]) {}
+
+    fn add_trace(
+        &mut self,
+        indexed_measurements: Vec<(usize, &'a MeasurementData)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
+    ) {
+        // A single value per commit is needed for the table
+        let indexed_values = indexed_measurements
+            .into_iter()
+            .group_by(|(i, _)| *i)
+            .into_iter()
+            .filter_map(|(i, ms)| {
+                ms.map(|(_, m)| m.val)
+                    .aggregate_by(ReductionFunc::Median)
+                    .map(|v| (i, v))
             })
-            .join("")
-            .into_bytes()
+            .collect();
+        self.add_values(indexed_values, measurement_name, group_value);
     }
 
     fn add_summarized_trace(
         &mut self,
-        _indexed_measurements: Vec<(usize, MeasurementSummary)>,
-        _measurement_name: &str,
-        _group_value: Option<&String>,
+        indexed_measurements: Vec<(usize, MeasurementSummary)>,
+        measurement_name: &str,
+        group_value: Option<&String>,
     ) {
-        todo!()
+        let indexed_values = indexed_measurements
+            .into_iter()
+            .map(|(i, m)| (i, m.val))
+            .collect();
+        self.add_values(indexed_values, measurement_name, group_value);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = Some(title.to_owned());
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+        if let Some(title) = &self.title {
+            out.push_str(&format!("## {title}\n\n"));
+        }
+        for (measurement_name, traces) in
+            &self.traces.iter().group_by(|t| t.measurement_name.as_str())
+        {
+            out.push_str(&format!("### {measurement_name}\n\n"));
+            out.push_str("| Group | Latest | Δ previous | Trend |\n");
+            out.push_str("|---|---|---|---|\n");
+            for trace in traces {
+                let mut values = trace.indexed_values.clone();
+                values.sort_by_key(|(i, _)| *i);
+                let latest = values.first().map(|(_, v)| *v);
+                let previous = values.get(1).map(|(_, v)| *v);
+                let delta = match (latest, previous) {
+                    (Some(latest), Some(previous)) => {
+                        format!("{:+.2}%", (latest - previous) / previous * 100.0)
+                    }
+                    _ => "-".to_string(),
+                };
+                let trend = sparkline::markdown_image(
+                    &values.iter().rev().map(|(_, v)| *v).collect_vec(),
+                    None,
+                    sparkline::NEUTRAL_COLOR,
+                    &self.sparkline,
+                );
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    trace.group_value.as_deref().unwrap_or("-"),
+                    latest.map_or("-".to_string(), |l| Float::from(l).to_string()),
+                    delta,
+                    trend,
+                ));
+            }
+            out.push('\n');
+        }
+        out.into_bytes()
+    }
+}
+
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+    Csv,
+    Json,
+    Markdown,
+    Svg,
+    Png,
+}
+
+impl ReportFormat {
+    fn from_file_name(path: &Path) -> Option<ReportFormat> {
+        if path == Path::new("-") {
+            return Some(ReportFormat::Csv);
+        }
+        let extension = path.extension()?.to_ascii_lowercase().into_string().ok()?;
+        ReportFormat::from_extension(&extension)
+    }
+
+    /// Format of files with the (lowercase) `extension`.
+    pub fn from_extension(extension: &str) -> Option<ReportFormat> {
+        match extension {
+            "html" => Some(ReportFormat::Html),
+            "csv" => Some(ReportFormat::Csv),
+            "json" => Some(ReportFormat::Json),
+            "md" => Some(ReportFormat::Markdown),
+            "svg" => Some(ReportFormat::Svg),
+            "png" => Some(ReportFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+/// How HTML reports chart the measurements.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ChartType {
+    /// The values of each commit along the history
+    #[default]
+    TimeSeries,
+    /// Bars of the values of the newest commit only, grouped by the `separate_by` values, with
+    /// the standard deviation of the repetitions as error bars
+    BarLatest,
+}
+
+/// What the x-axis of HTML time series spans.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum XAxis {
+    /// The commits, equally spaced
+    #[default]
+    Commit,
+    /// The committer times of the commits, so that gaps in the history become visible
+    Time,
+}
+
+impl FromStr for ChartType {
+    type Err = anyhow::Error;
+
+    /// The `chart` of a report section in .gitperfconfig
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "time-series" => Ok(ChartType::TimeSeries),
+            "bar" | "bar-latest" => Ok(ChartType::BarLatest),
+            _ => bail!("Unknown chart type '{name}', expected 'time-series' or 'bar'"),
+        }
     }
 }
 
 struct ReporterFactory {}
 
 impl ReporterFactory {
-    fn from_file_name<'a, 'b: 'a>(path: &'b Path) -> Option<Box<dyn Reporter + 'a>> {
-        if path == Path::new("-") {
-            return Some(Box::new(CsvReporter::new()) as Box<dyn Reporter + 'a>);
-        }
-        let mut res = None;
-        if let Some(ext) = path.extension() {
-            let extension = ext.to_ascii_lowercase().into_string().unwrap();
-            res = match extension.as_str() {
-                "html" => Some(Box::new(PlotlyReporter::new()) as Box<dyn Reporter>),
-                "csv" => Some(Box::new(CsvReporter::new()) as Box<dyn Reporter + 'a>),
-                _ => None,
-            }
+    #[cfg_attr(
+        not(any(feature = "report-html", feature = "report-image")),
+        allow(unused_variables)
+    )]
+    fn from_format<'a>(
+        format: ReportFormat,
+        auto_scale_units: bool,
+        sha_length: Option<usize>,
+        chart: ChartType,
+        x_axis: XAxis,
+    ) -> Result<Box<dyn Reporter<'a> + 'a>> {
+        if chart != ChartType::TimeSeries && format != ReportFormat::Html {
+            bail!("Bar charts are only supported in HTML reports.");
         }
-        res
+        if x_axis == XAxis::Time && (chart != ChartType::TimeSeries || format != ReportFormat::Html)
+        {
+            bail!("A time x-axis is only supported for time series in HTML reports.");
+        }
+        Ok(match format {
+            #[cfg(feature = "report-html")]
+            ReportFormat::Html => {
+                Box::new(PlotlyReporter::new(auto_scale_units, sha_length, chart))
+            }
+            #[cfg(not(feature = "report-html"))]
+            ReportFormat::Html => {
+                bail!("HTML reports are not supported without the 'report-html' feature")
+            }
+            ReportFormat::Csv => Box::new(CsvReporter::new()),
+            ReportFormat::Json => Box::new(JsonReporter::new()),
+            ReportFormat::Markdown => Box::new(MarkdownReporter::new(
+                config::determine_sparkline_options_from_config()?,
+            )),
+            #[cfg(feature = "report-image")]
+            ReportFormat::Svg => Box::new(ImageReporter::new(
+                ImageFormat::Svg,
+                auto_scale_units,
+                sha_length,
+            )?),
+            #[cfg(feature = "report-image")]
+            ReportFormat::Png => Box::new(ImageReporter::new(
+                ImageFormat::Png,
+                auto_scale_units,
+                sha_length,
+            )?),
+            #[cfg(not(feature = "report-image"))]
+            ReportFormat::Svg | ReportFormat::Png => {
+                bail!("Image reports are not supported without the 'report-image' feature")
+            }
+        })
     }
 }
 // TODO(kaihowl) needs more fine grained output e2e tests
+#[allow(clippy::too_many_arguments)]
 pub fn report(
     output: PathBuf,
-    separate_by: Option<String>,
+    separate_by: &[String],
+    separate_by_matrix: bool,
+    start: Option<&str>,
     num_commits: usize,
     measurement_names: &[String],
-    key_values: &[(String, String)],
+    key_values: &[Selector],
     aggregate_by: Option<ReductionFunc>,
+    threshold_bands: bool,
+    format: Option<ReportFormat>,
+    csv_mode: Option<CsvMode>,
+    auto_scale_units: bool,
+    ignore_epochs: bool,
+    sha_length: Option<usize>,
+    chart: ChartType,
+    x_axis: XAxis,
+    sections: &[SectionConfig],
+) -> Result<()> {
+    let format = format
+        .or_else(|| ReportFormat::from_file_name(&output))
+        .ok_or(anyhow!("Could not infer output format"))?;
+    let report = if !sections.is_empty() {
+        render_sections(
+            sections,
+            start,
+            num_commits,
+            key_values,
+            threshold_bands,
+            format,
+            auto_scale_units,
+            ignore_epochs,
+            sha_length,
+            x_axis,
+        )?
+    } else {
+        render_report(
+            separate_by,
+            separate_by_matrix,
+            start,
+            num_commits,
+            measurement_names,
+            key_values,
+            aggregate_by,
+            threshold_bands,
+            format,
+            csv_mode,
+            auto_scale_units,
+            ignore_epochs,
+            sha_length,
+            chart,
+            x_axis,
+        )?
+    };
+
+    if output == Path::new("-") {
+        match io::stdout().write_all(&report) {
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+            res => res,
+        }?;
+    } else {
+        File::create(&output)?.write_all(&report)?;
+    }
+
+    Ok(())
+}
+
+/// Which measurements a section of a report shows and how, configured as
+/// `[report.section.<id>]` in .gitperfconfig.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SectionConfig {
+    pub id: String,
+    /// Heading of the section, defaults to the id
+    pub title: Option<String>,
+    /// Regular expression the full names of the shown measurements have to match
+    pub filter: Option<String>,
+    /// Ref whose first-parent history the section shows, defaults to that of the report
+    pub start: Option<String>,
+    /// Revision range whose first-parent history the section shows instead of a ref, e.g.
+    /// `v1.0..release/2.x`
+    pub range: Option<String>,
+    pub separate_by: Vec<String>,
+    pub aggregate_by: Option<ReductionFunc>,
+    /// Number of commits, defaults to that of the report
+    pub depth: Option<usize>,
+    /// Mark the most likely step change of each trace
+    pub show_changes: bool,
+    pub chart: ChartType,
+}
+
+/// Multiple of the pooled standard deviation of a step change marked in a report section,
+/// matching the default sigma of audits.
+const CHANGE_POINT_SIGMA: f64 = 4.0;
+
+/// The `num_commits` newest commits of the first-parent history of `start`, a ref or a revision
+/// range such as `v1.0..main`, or of HEAD.
+fn walk_commits(start: Option<&str>, num_commits: usize) -> Result<Vec<Commit>> {
+    let commits: Vec<Commit> = match start {
+        Some(start) => {
+            // git log ignores missing revisions
+            for rev in start
+                .split("..")
+                .map(|rev| rev.trim_start_matches('.'))
+                .filter(|rev| !rev.is_empty())
+            {
+                git_interop::resolve_commit(rev)?;
+            }
+            measurement_retrieval::walk_commits_from(start, num_commits)?.try_collect()?
+        }
+        None => measurement_retrieval::walk_commits(num_commits)?.try_collect()?,
+    };
+    relink::warn_about_orphaned_measurements(&commits);
+    Ok(commits)
+}
+
+/// Let `plot` show the metadata of the `commits` in HTML reports and place them at their
+/// committer times for [`XAxis::Time`].
+fn set_commit_infos<'a>(
+    plot: &mut (dyn Reporter<'a> + 'a),
+    commits: &[Commit],
+    format: ReportFormat,
+    x_axis: XAxis,
 ) -> Result<()> {
-    let commits: Vec<Commit> = measurement_retrieval::walk_commits(num_commits)?.try_collect()?;
+    if format != ReportFormat::Html {
+        return Ok(());
+    }
+    let hashes = commits.iter().map(|c| c.commit.as_str()).collect_vec();
+    let infos = git_interop::commit_infos(&hashes)?;
+    if x_axis == XAxis::Time {
+        plot.set_commit_times(infos.iter().map(|info| info.time).collect());
+    }
+    plot.set_commit_infos(infos);
+    Ok(())
+}
 
+/// The report of the measurements of the `num_commits` commits starting at `start` (or HEAD)
+/// in `format`. With `separate_by_matrix`, the measurements are separated by all of their build
+/// matrix dimensions instead of `separate_by`.
+#[allow(clippy::too_many_arguments)]
+pub fn render_report(
+    separate_by: &[String],
+    separate_by_matrix: bool,
+    start: Option<&str>,
+    num_commits: usize,
+    measurement_names: &[String],
+    key_values: &[Selector],
+    aggregate_by: Option<ReductionFunc>,
+    threshold_bands: bool,
+    format: ReportFormat,
+    csv_mode: Option<CsvMode>,
+    auto_scale_units: bool,
+    ignore_epochs: bool,
+    sha_length: Option<usize>,
+    chart: ChartType,
+    x_axis: XAxis,
+) -> Result<Vec<u8>> {
+    let commits = walk_commits(start, num_commits)?;
+
+    // Without an explicit mode, CSV reports are summarized if an aggregation is requested
+    let aggregate_by = match (format, csv_mode) {
+        (ReportFormat::Csv, Some(CsvMode::Raw)) => None,
+        (ReportFormat::Csv, Some(CsvMode::Summary)) => {
+            Some(aggregate_by.unwrap_or(ReductionFunc::Min))
+        }
+        (_, Some(_)) => bail!("A CSV mode can only be selected for CSV reports."),
+        (_, None) => aggregate_by,
+    };
     let mut plot =
-        ReporterFactory::from_file_name(&output).ok_or(anyhow!("Could not infer output format"))?;
+        ReporterFactory::from_format(format, auto_scale_units, sha_length, chart, x_axis)?;
 
+    set_commit_infos(plot.as_mut(), &commits, format, x_axis)?;
     plot.add_commits(&commits);
 
     let relevant = |m: &MeasurementData| {
         if !measurement_names.is_empty() && !measurement_names.contains(&m.name) {
             return false;
         }
-        // TODO(kaihowl) express this and the audit-fn equivalent as subset relations
-        key_values
-            .iter()
-            .all(|(k, v)| m.key_values.get(k).map(|mv| v == mv).unwrap_or(false))
+        m.matches_selectors(key_values)
+    };
+    let matrix_dimensions = if separate_by_matrix {
+        let dimensions = data::matrix_dimensions(
+            commits
+                .iter()
+                .flat_map(|c| &c.measurements)
+                .filter(|m| relevant(m)),
+        );
+        if dimensions.is_empty() {
+            bail!("No build matrix dimensions recorded for the reported measurements.");
+        }
+        dimensions
+    } else {
+        Vec::new()
     };
+    add_measurements(
+        plot.as_mut(),
+        &commits,
+        relevant,
+        key_values,
+        if separate_by_matrix {
+            &matrix_dimensions
+        } else {
+            separate_by
+        },
+        aggregate_by,
+        threshold_bands,
+        ignore_epochs,
+        false,
+    )?;
+
+    // TODO(kaihowl) fewer than the -n specified measurements appear in plot (old problem, even in
+    // python)
+
+    Ok(plot.as_bytes())
+}
+
+/// A report with one section per entry of `sections` in `format`, which has to be HTML or
+/// markdown. Each section shows the measurements matching its filter and the `key_values` in
+/// its own plot or table, with the commits of its own ref or range, by default of `start`.
+#[allow(clippy::too_many_arguments)]
+pub fn render_sections(
+    sections: &[SectionConfig],
+    start: Option<&str>,
+    num_commits: usize,
+    key_values: &[Selector],
+    threshold_bands: bool,
+    format: ReportFormat,
+    auto_scale_units: bool,
+    ignore_epochs: bool,
+    sha_length: Option<usize>,
+    x_axis: XAxis,
+) -> Result<Vec<u8>> {
+    if !matches!(format, ReportFormat::Html | ReportFormat::Markdown) {
+        bail!("Sections are only supported in HTML and markdown reports.");
+    }
+    let section_commits: Vec<_> = sections
+        .iter()
+        .map(|section| {
+            let start = section
+                .range
+                .as_deref()
+                .or(section.start.as_deref())
+                .or(start);
+            walk_commits(start, section.depth.unwrap_or(num_commits))
+                .with_context(|| format!("Failed to report section '{}'", section.id))
+        })
+        .try_collect()?;
 
+    let mut plots = Vec::new();
+    for (section, commits) in sections.iter().zip(&section_commits) {
+        let filter = section
+            .filter
+            .as_deref()
+            .map(|filter| {
+                Regex::new(&format!("^(?:{filter})$"))
+                    .with_context(|| format!("Invalid filter '{filter}'"))
+            })
+            .transpose()?;
+        let mut plot = ReporterFactory::from_format(
+            format,
+            auto_scale_units,
+            sha_length,
+            section.chart,
+            x_axis,
+        )
+        .with_context(|| format!("Failed to report section '{}'", section.id))?;
+        plot.set_title(section.title.as_deref().unwrap_or(&section.id));
+        set_commit_infos(plot.as_mut(), commits, format, x_axis)?;
+        plot.add_commits(commits);
+
+        let relevant = |m: &MeasurementData| {
+            filter.as_ref().is_none_or(|f| f.is_match(&m.name)) && m.matches_selectors(key_values)
+        };
+        add_measurements(
+            plot.as_mut(),
+            commits,
+            relevant,
+            key_values,
+            &section.separate_by,
+            section.aggregate_by,
+            threshold_bands,
+            ignore_epochs,
+            section.show_changes,
+        )
+        .with_context(|| format!("Failed to report section '{}'", section.id))?;
+        plots.push(plot);
+    }
+
+    let Some((first, rest)) = plots.split_first() else {
+        bail!("No report sections configured.");
+    };
+    let rest = rest
+        .iter()
+        .zip(&sections[1..])
+        .map(|(plot, section)| plot.as_section_bytes(&section.id))
+        .collect_vec();
+    Ok(first.as_bytes_with_sections(&rest))
+}
+
+/// Add the traces of all measurements of the `commits` that are `relevant` to `plot`, one per
+/// group of values of the `separate_by` keys. Threshold bands and change points are those of the
+/// audits of the measurements with the `selectors`.
+#[allow(clippy::too_many_arguments)]
+fn add_measurements<'a>(
+    plot: &mut (dyn Reporter<'a> + 'a),
+    commits: &'a [Commit],
+    relevant: impl Fn(&MeasurementData) -> bool,
+    selectors: &[Selector],
+    separate_by: &[String],
+    aggregate_by: Option<ReductionFunc>,
+    threshold_bands: bool,
+    ignore_epochs: bool,
+    show_changes: bool,
+) -> Result<()> {
+    let relevant = &relevant;
     let relevant_measurements = commits
         .iter()
         .map(|commit| commit.measurements.iter().filter(|m| relevant(m)));
-
     let unique_measurement_names: Vec<_> = relevant_measurements
         .clone()
         .flat_map(|m| m.map(|m| &m.name))
@@ -260,39 +1800,47 @@ pub fn report(
             .clone()
             .map(|ms| ms.filter(|m| m.name == *measurement_name));
 
-        let group_values = if let Some(separate_by) = &separate_by {
+        let group_values = if separate_by.is_empty() {
+            vec![None]
+        } else {
             filtered_measurements
                 .clone()
                 .flat_map(|ms| {
-                    ms.flat_map(|m| {
-                        m.key_values
+                    ms.filter_map(|m| {
+                        separate_by
                             .iter()
-                            .filter(|(k, _v)| *k == separate_by)
-                            .map(|(_k, v)| v)
+                            .map(|k| m.key_values.get(k))
+                            .collect::<Option<Vec<_>>>()
                     })
                 })
                 .unique()
-                .map(|val| (Some(separate_by), Some(val)))
+                .map(Some)
                 .collect_vec()
-        } else {
-            vec![(None, None)]
         };
 
         if group_values.is_empty() {
             bail!("Invalid separator supplied, no measurements.")
         }
 
-        for (group_key, group_value) in group_values {
+        for group_value in &group_values {
             let group_measurements = filtered_measurements.clone().map(|ms| {
                 ms.filter(|m| {
-                    group_key
-                        .map(|gk| m.key_values.get(gk) == group_value)
+                    group_value
+                        .as_ref()
+                        .map(|gv| {
+                            separate_by
+                                .iter()
+                                .zip(gv)
+                                .all(|(k, v)| m.key_values.get(k) == Some(v))
+                        })
                         .unwrap_or(true)
                 })
             });
 
-            if let Some(reduction_func) = aggregate_by {
-                let trace_measurements = group_measurements
+            let group_label = group_value.as_ref().map(|gv| gv.iter().join("/"));
+
+            let summarize = |reduction_func: ReductionFunc| {
+                group_measurements
                     .clone()
                     .enumerate()
                     .flat_map(move |(i, ms)| {
@@ -300,30 +1848,544 @@ pub fn report(
                             .into_iter()
                             .map(move |m| (i, m))
                     })
-                    .collect_vec();
-                plot.add_summarized_trace(trace_measurements, measurement_name, group_value);
+                    .collect_vec()
+            };
+            // Summarized like the audit would with `--summarize-by <aggregate_by>`
+            let (audited_by, sigma) =
+                audit::resolve_audit_params(measurement_name, selectors, aggregate_by, None, None)?;
+
+            if threshold_bands {
+                plot.add_threshold_band(
+                    audit_bands(&summarize(audited_by), sigma, ignore_epochs),
+                    measurement_name,
+                    group_label.as_ref(),
+                );
+            }
+
+            if show_changes {
+                let summaries = summarize(audited_by);
+                let chronological = summaries.iter().rev().map(|(_, m)| m.val).collect_vec();
+                if let Some(change_point) = stats::detect_change_point(
+                    &chronological,
+                    CHANGE_POINT_SIGMA,
+                    audit::CHANGE_POINT_MIN_SEGMENT,
+                ) {
+                    let (index, _) = summaries[summaries.len() - 1 - change_point.index];
+                    plot.add_change_point(
+                        index,
+                        &change_point,
+                        measurement_name,
+                        group_label.as_ref(),
+                    );
+                }
+            }
+
+            if let Some(reduction_func) = aggregate_by {
+                plot.add_summarized_trace(
+                    summarize(reduction_func),
+                    measurement_name,
+                    group_label.as_ref(),
+                );
             } else {
                 let trace_measurements: Vec<_> = group_measurements
                     .clone()
                     .enumerate()
                     .flat_map(|(i, ms)| ms.map(move |m| (i, m)))
                     .collect();
-                plot.add_trace(trace_measurements, measurement_name, group_value);
+                plot.add_trace(trace_measurements, measurement_name, group_label.as_ref());
             }
         }
     }
 
-    // TODO(kaihowl) fewer than the -n specified measurements appear in plot (old problem, even in
-    // python)
+    Ok(())
+}
 
-    if output == Path::new("-") {
-        match io::stdout().write_all(&plot.as_bytes()) {
-            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
-            res => res,
-        }?;
-    } else {
-        File::create(&output)?.write_all(&plot.as_bytes())?;
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn summary(epoch: u32, val: f64) -> MeasurementSummary {
+        MeasurementSummary {
+            epoch,
+            val,
+            len: 1,
+            stddev: 0.0,
+            mad: 0.0,
+        }
     }
 
-    Ok(())
+    #[test]
+    #[cfg(feature = "report-html")]
+    fn html_with_permalink_script() {
+        let reporter = PlotlyReporter {
+            plot: Plot::new(),
+            title: "Performance Measurements".to_owned(),
+            size: 0,
+            compact_hover: false,
+            auto_scale_units: false,
+            scalings: HashMap::new(),
+            epoch_reasons: HashMap::new(),
+            commit_url_template: None,
+            commit_urls: Vec::new(),
+            sha_length: DEFAULT_SHA_LENGTH,
+            hashes: Vec::new(),
+            times: Vec::new(),
+            infos: Vec::new(),
+            chart: ChartType::TimeSeries,
+            bars: Vec::new(),
+        };
+        let html = String::from_utf8(reporter.as_bytes()).unwrap();
+        let script = html
+            .find("location.hash")
+            .expect("Missing permalink script");
+        assert!(script > html.find("Plotly.newPlot").unwrap());
+        assert!(script < html.find("</body>").unwrap());
+        assert!(!html.contains("commitUrls"));
+    }
+
+    #[test]
+    #[cfg(feature = "report-html")]
+    fn html_with_sections() {
+        let reporter = |title: &str| {
+            let mut reporter = PlotlyReporter::new(false, None, ChartType::TimeSeries);
+            reporter.set_title(title);
+            reporter.commit_urls = vec!["https://example.com/commit/abc".to_owned()];
+            reporter
+        };
+        let section = reporter("Second").as_section_bytes("second");
+        let html = String::from_utf8(reporter("First").as_bytes_with_sections(&[section])).unwrap();
+        assert_eq!(html.matches("<html").count(), 1);
+        let second = html
+            .find(r#"<div id="section-second""#)
+            .expect("Missing section");
+        assert!(second > html.find("plotly-html-element").unwrap());
+        assert!(second < html.find("location.hash").unwrap());
+        assert!(html.contains(r#"const plotId = "section-second";"#));
+        assert!(html.contains(r#"const plotId = "plotly-html-element";"#));
+    }
+
+    #[test]
+    #[cfg(feature = "report-html")]
+    fn html_with_commit_links() {
+        let mut reporter = PlotlyReporter {
+            plot: Plot::new(),
+            title: "Performance Measurements".to_owned(),
+            size: 0,
+            compact_hover: false,
+            auto_scale_units: false,
+            scalings: HashMap::new(),
+            epoch_reasons: HashMap::new(),
+            commit_url_template: Some("https://example.com/commit/{sha}".to_owned()),
+            commit_urls: Vec::new(),
+            sha_length: DEFAULT_SHA_LENGTH,
+            hashes: Vec::new(),
+            times: Vec::new(),
+            infos: Vec::new(),
+            chart: ChartType::TimeSeries,
+            bars: Vec::new(),
+        };
+        let commits = ["0123456789", "abcdef0123"].map(|commit| Commit {
+            commit: commit.to_owned(),
+            measurements: Vec::new(),
+        });
+        reporter.add_commits(&commits);
+        // Oldest commit first on the x-axis
+        assert_eq!(
+            reporter.commit_urls,
+            [
+                "https://example.com/commit/abcdef0123",
+                "https://example.com/commit/0123456789"
+            ]
+        );
+        let html = String::from_utf8(reporter.as_bytes()).unwrap();
+        // The layout is embedded as JSON with escaped angle brackets
+        assert!(html.contains(
+            r#"\u003ca href=\"https://example.com/commit/abcdef0123\"\u003eabcdef\u003c/a\u003e"#
+        ));
+        assert!(html.contains(r#"const commitUrls = ["https://example.com/commit/abcdef0123","#));
+        assert!(html.contains("plotly_click"));
+    }
+
+    #[test]
+    #[cfg(feature = "report-html")]
+    fn html_with_sha_length_and_full_hash_on_hover() {
+        let mut reporter = PlotlyReporter {
+            plot: Plot::new(),
+            title: "Performance Measurements".to_owned(),
+            size: 0,
+            compact_hover: true,
+            auto_scale_units: false,
+            scalings: HashMap::new(),
+            epoch_reasons: HashMap::new(),
+            commit_url_template: None,
+            commit_urls: Vec::new(),
+            sha_length: 8,
+            hashes: Vec::new(),
+            times: Vec::new(),
+            infos: Vec::new(),
+            chart: ChartType::TimeSeries,
+            bars: Vec::new(),
+        };
+        let commits = ["0123456789", "abcdef0123"].map(|commit| Commit {
+            commit: commit.to_owned(),
+            measurements: Vec::new(),
+        });
+        reporter.add_commits(&commits);
+        reporter.add_summarized_trace(vec![(1, summary(0, 1.0))], "timer", None);
+        let html = String::from_utf8(reporter.as_bytes()).unwrap();
+        assert!(html.contains(r#""abcdef01","#));
+        assert!(!html.contains(r#""abcdef012""#));
+        assert!(html.contains(r#""commit: abcdef0123""#));
+    }
+
+    #[test]
+    #[cfg(feature = "report-html")]
+    fn html_with_commit_infos_on_hover() {
+        let mut reporter = PlotlyReporter::new(false, None, ChartType::TimeSeries);
+        reporter.set_commit_infos(vec![CommitInfo {
+            time: 1_700_000_000,
+            author: "Jane Doe".to_owned(),
+            date: "2023-11-14 22:13".to_owned(),
+            subject: "Speed up <parser>".to_owned(),
+        }]);
+        let commits = [Commit {
+            commit: "0123456789".to_owned(),
+            measurements: Vec::new(),
+        }];
+        reporter.add_commits(&commits);
+        reporter.add_summarized_trace(vec![(0, summary(0, 1.0))], "timer", None);
+        let html = String::from_utf8(reporter.as_bytes()).unwrap();
+        assert!(html.contains(
+            r#""commit: 0123456789\u003cbr\u003eJane Doe, 2023-11-14 22:13\u003cbr\u003eSpeed up \u0026lt;parser\u0026gt;\u003cbr\u003en: "#
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "report-html")]
+    fn html_with_epoch_reason_on_hover() {
+        let mut reporter = PlotlyReporter::new(false, None, ChartType::TimeSeries);
+        reporter.epoch_reasons.insert(
+            "timer".to_owned(),
+            HashMap::from([(2, "switched allocator".to_owned())]),
+        );
+        let commits = ["0123456789", "abcdef0123"].map(|commit| Commit {
+            commit: commit.to_owned(),
+            measurements: Vec::new(),
+        });
+        reporter.add_commits(&commits);
+        reporter.add_summarized_trace(
+            vec![(0, summary(2, 1.0)), (1, summary(1, 1.0))],
+            "timer",
+            None,
+        );
+        let html = String::from_utf8(reporter.as_bytes()).unwrap();
+        assert!(html.contains(
+            r#""commit: 0123456789\u003cbr\u003eepoch 00000002: switched allocator\u003cbr\u003en: "#
+        ));
+        assert!(html.contains(r#""commit: abcdef0123\u003cbr\u003en: "#));
+    }
+
+    #[test]
+    #[cfg(feature = "report-html")]
+    fn html_bar_chart_of_latest_commit() {
+        let mut reporter = PlotlyReporter::new(false, None, ChartType::BarLatest);
+        let commits = ["0123456789", "abcdef0123"].map(|commit| Commit {
+            commit: commit.to_owned(),
+            measurements: Vec::new(),
+        });
+        reporter.add_commits(&commits);
+        let linux = "linux".to_owned();
+        let mac = "mac".to_owned();
+        let latest = MeasurementSummary {
+            stddev: 0.5,
+            ..summary(0, 2.0)
+        };
+        reporter.add_summarized_trace(
+            vec![(0, latest), (1, summary(0, 1.0))],
+            "timer",
+            Some(&linux),
+        );
+        reporter.add_summarized_trace(vec![(1, summary(0, 3.0))], "timer", Some(&mac));
+        reporter.add_summarized_trace(vec![(0, summary(0, 4.0))], "build", Some(&linux));
+        reporter.add_threshold_band(vec![(0, 1.0, 2.0)], "timer", Some(&linux));
+
+        // Only the newest commit, one trace per group, without the older commits and bands
+        assert_eq!(
+            reporter
+                .bars
+                .iter()
+                .map(|bar| (bar.measurement.as_str(), bar.val, bar.stddev))
+                .collect_vec(),
+            [("timer", 2.0, 0.5), ("build", 4.0, 0.0)]
+        );
+        let plot = reporter.rendered_plot();
+        let traces = serde_json::to_value(plot.data()).unwrap();
+        assert_eq!(traces.as_array().unwrap().len(), 1);
+        assert_eq!(traces[0]["type"], "bar");
+        assert_eq!(traces[0]["name"], "linux");
+        assert_eq!(traces[0]["x"], serde_json::json!(["timer", "build"]));
+        assert_eq!(traces[0]["error_y"]["array"], serde_json::json!([0.5, 0.0]));
+        let html = String::from_utf8(reporter.as_bytes()).unwrap();
+        assert!(html.contains(r#""barmode": "group""#));
+        assert!(html.contains("Performance Measurements (012345)"));
+    }
+
+    #[test]
+    fn bar_charts_only_in_html() {
+        assert!(ReporterFactory::from_format(
+            ReportFormat::Csv,
+            false,
+            None,
+            ChartType::BarLatest,
+            XAxis::Commit
+        )
+        .is_err());
+        assert_eq!("bar".parse::<ChartType>().unwrap(), ChartType::BarLatest);
+        assert!("pie".parse::<ChartType>().is_err());
+    }
+
+    #[test]
+    fn time_axis_only_for_html_time_series() {
+        let reporter = |format, chart| {
+            ReporterFactory::from_format(format, false, None, chart, XAxis::Time).is_ok()
+        };
+        assert!(!reporter(ReportFormat::Csv, ChartType::TimeSeries));
+        assert!(!reporter(ReportFormat::Markdown, ChartType::TimeSeries));
+        #[cfg(feature = "report-html")]
+        {
+            assert!(reporter(ReportFormat::Html, ChartType::TimeSeries));
+            assert!(!reporter(ReportFormat::Html, ChartType::BarLatest));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "report-html")]
+    fn html_with_time_axis() {
+        let mut reporter = PlotlyReporter::new(false, None, ChartType::TimeSeries);
+        reporter.commit_url_template = Some("https://example.com/commit/{sha}".to_owned());
+        let commits = ["0123456789", "abcdef0123"].map(|commit| Commit {
+            commit: commit.to_owned(),
+            measurements: Vec::new(),
+        });
+        reporter.set_commit_times(vec![1_700_086_400, 1_700_000_000]);
+        reporter.add_commits(&commits);
+        reporter.add_summarized_trace(
+            vec![(0, summary(0, 2.0)), (1, summary(0, 1.0))],
+            "timer",
+            None,
+        );
+        let plot: serde_json::Value = serde_json::from_str(&reporter.plot.to_json()).unwrap();
+        assert_eq!(plot["layout"]["xaxis"]["type"], "date");
+        assert_eq!(
+            plot["data"][0]["x"],
+            serde_json::json!([1_700_086_400_000_i64, 1_700_000_000_000_i64])
+        );
+        assert!(reporter.commit_urls.is_empty());
+    }
+
+    #[test]
+    fn markdown_sections() {
+        let mut first = MarkdownReporter::new(sparkline::Options::default());
+        first.set_title("First");
+        let mut second = MarkdownReporter::new(sparkline::Options::default());
+        second.set_title("Second");
+        let markdown = first.as_bytes_with_sections(&[second.as_section_bytes("second")]);
+        assert_eq!(
+            String::from_utf8(markdown).unwrap(),
+            "## First\n\n## Second\n\n"
+        );
+    }
+
+    #[test]
+    fn markdown_table() {
+        let mut reporter = MarkdownReporter::new(sparkline::Options::default());
+        reporter.add_values(
+            vec![(0, 12.0), (1, 10.0), (2, 11.0)],
+            "timer",
+            Some(&"linux".to_string()),
+        );
+        reporter.add_values(vec![(2, 5.0)], "timer", Some(&"mac".to_string()));
+        let markdown = String::from_utf8(reporter.as_bytes()).unwrap();
+        let expected = format!(
+            "### timer\n\n\
+             | Group | Latest | Δ previous | Trend |\n\
+             |---|---|---|---|\n\
+             | linux | 12.000 | +20.00% | {} |\n\
+             | mac | 5.000 | - | {} |\n\n",
+            sparkline::markdown_image(
+                &[11.0, 10.0, 12.0],
+                None,
+                sparkline::NEUTRAL_COLOR,
+                &sparkline::Options::default()
+            ),
+            sparkline::markdown_image(
+                &[5.0],
+                None,
+                sparkline::NEUTRAL_COLOR,
+                &sparkline::Options::default()
+            ),
+        );
+        assert_eq!(markdown, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "report-image")]
+    fn image_reports() {
+        let commits = ["0123456789", "abcdef0123"].map(|commit| Commit {
+            commit: commit.to_owned(),
+            measurements: Vec::new(),
+        });
+        let reporter = |format| {
+            let mut reporter = ImageReporter::new(format, false, None).unwrap();
+            reporter.add_commits(&commits);
+            reporter.add_summarized_trace(
+                vec![(0, summary(0, 2.0)), (1, summary(0, 1.0))],
+                "timer",
+                Some(&"linux".to_owned()),
+            );
+            reporter
+        };
+
+        let svg = reporter(ImageFormat::Svg);
+        // Oldest commit first on the x-axis
+        assert_eq!(svg.labels, ["abcdef", "012345"]);
+        assert_eq!(svg.traces[0].points, [(0, 1.0), (1, 2.0)]);
+        let svg = String::from_utf8(svg.as_bytes()).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Performance Measurements"));
+        assert!(svg.contains("timer linux"));
+        assert!(svg.contains("abcdef"));
+
+        let png = reporter(ImageFormat::Png).as_bytes();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn json_summaries() {
+        let commits = ["0123456789", "abcdef0123"].map(|commit| Commit {
+            commit: commit.to_owned(),
+            measurements: Vec::new(),
+        });
+        let mut reporter = JsonReporter::new();
+        reporter.add_commits(&commits);
+        reporter.add_summarized_trace(
+            vec![(
+                1,
+                MeasurementSummary {
+                    epoch: 2,
+                    val: 1.5,
+                    len: 3,
+                    stddev: 0.5,
+                    mad: 0.25,
+                },
+            )],
+            "timer",
+            Some(&"linux".to_owned()),
+        );
+        let report: serde_json::Value = serde_json::from_slice(&reporter.as_bytes()).unwrap();
+        assert_eq!(
+            report["summaries"],
+            serde_json::json!([{
+                "commit": "abcdef0123",
+                "name": "timer",
+                "group": "linux",
+                "epoch": 2,
+                "val": 1.5,
+                "n": 3,
+                "stddev": 0.5,
+                "mad": 0.25,
+            }])
+        );
+
+        // Reports of older versions lack the metadata of summaries
+        let old: JsonSummary = serde_json::from_str(
+            r#"{"commit": "abc", "name": "timer", "group": null, "epoch": 0, "val": 1.0}"#,
+        )
+        .unwrap();
+        assert_eq!(old.n, 0);
+    }
+
+    fn measurement(name: &str, timestamp: f64, key_values: &[(&str, &str)]) -> MeasurementData {
+        MeasurementData {
+            epoch: 0,
+            name: name.to_string(),
+            timestamp,
+            val: 1.0,
+            key_values: key_values
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn csv_raw_sorted_columns_and_rows() {
+        let late = measurement("timer", 20.0, &[("os", "linux"), ("arch", "x86")]);
+        let early = measurement("timer", 10.0, &[("os", "mac")]);
+        let older = measurement("other", 5.0, &[]);
+        let mut reporter = CsvReporter::new();
+        reporter.hashes = vec!["c0".to_string(), "c1".to_string()];
+        reporter.add_trace(vec![(1, &older)], "other", None);
+        reporter.add_trace(vec![(0, &late), (0, &early)], "timer", None);
+        let csv = String::from_utf8(reporter.as_bytes()).unwrap();
+        assert_eq!(
+            csv,
+            "commit\tepoch\tname\ttimestamp\tval\tarch\tos\n\
+             c0\t0\ttimer\t10.0\t1.0\t\tmac\n\
+             c0\t0\ttimer\t20.0\t1.0\tx86\tlinux\n\
+             c1\t0\tother\t5.0\t1.0\t\t\n"
+        );
+    }
+
+    #[test]
+    fn csv_summary() {
+        let mut reporter = CsvReporter::new();
+        reporter.hashes = vec!["c0".to_string(), "c1".to_string()];
+        reporter.add_summarized_trace(
+            vec![(1, summary(0, 3.0))],
+            "timer",
+            Some(&"linux".to_string()),
+        );
+        reporter.add_summarized_trace(vec![(0, summary(0, 2.0))], "other", None);
+        let csv = String::from_utf8(reporter.as_bytes()).unwrap();
+        assert_eq!(
+            csv,
+            "commit\tepoch\tname\tgroup\tval\tn\tstddev\tmad\n\
+             c0\t0\tother\t\t2.0\t1\t0.0\t0.0\n\
+             c1\t0\ttimer\tlinux\t3.0\t1\t0.0\t0.0\n"
+        );
+    }
+
+    #[test]
+    fn bands_from_older_commits() {
+        let summaries = vec![
+            (0, summary(0, 10.0)),
+            (1, summary(0, 3.0)),
+            (3, summary(0, 1.0)),
+            (4, summary(0, 2.0)),
+        ];
+        let bands = audit_bands(&summaries, 2.0, false);
+        // HEAD: mean 2, stddev 1 of [3, 1, 2]
+        assert_eq!(bands[0], (0, 0.0, 4.0));
+        // Second commit: mean 1.5, stddev sqrt(0.5) of [1, 2]
+        assert_eq!(bands[1].0, 1);
+        assert!((bands[1].2 - (1.5 + 2.0 * 0.5f64.sqrt())).abs() < 1e-12);
+        // Too few older commits for the rest
+        assert_eq!(bands.len(), 2);
+    }
+
+    #[test]
+    fn bands_stop_at_epoch_boundary() {
+        let summaries = vec![
+            (0, summary(1, 10.0)),
+            (1, summary(1, 3.0)),
+            (2, summary(0, 1.0)),
+            (3, summary(0, 2.0)),
+        ];
+        let bands = audit_bands(&summaries, 2.0, false);
+        assert!(bands.is_empty());
+
+        let bands = audit_bands(&summaries, 2.0, true);
+        assert_eq!(bands.iter().map(|b| b.0).collect_vec(), vec![0, 1]);
+    }
 }
@@ -0,0 +1,233 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+
+use crate::{
+    git_interop,
+    serialization::{self, deserialize, serialize_multiple},
+    storage,
+};
+
+/// Retention policies configured in the `[retention]` section of .gitperfconfig.
+#[derive(Debug, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Remove measurements taken longer ago than this
+    pub max_age: Option<Duration>,
+    /// Keep only the newest measurements of each name
+    pub max_points_per_measurement: Option<usize>,
+    /// Never remove measurements of commits pointed to by a tag
+    pub keep_tagged_commits: bool,
+}
+
+impl RetentionPolicy {
+    fn removes_anything(&self) -> bool {
+        self.max_age.is_some() || self.max_points_per_measurement.is_some()
+    }
+}
+
+/// Outcome of a garbage collection pass.
+#[derive(Debug, Default, PartialEq)]
+pub struct GcSummary {
+    /// Number of removed measurements
+    pub removed: usize,
+    /// Number of commits whose measurements were rewritten
+    pub commits: usize,
+}
+
+/// Position of a measurement as `(commit index, line index, index within the line)`. Lines of
+/// the v4 format hold several measurements.
+type Position = (usize, usize, usize);
+
+/// Positions of the measurements in `notes` to remove under `policy` at unix time `now`.
+/// Measurements of the `protected` commits are neither removed nor count towards the points of
+/// their name.
+fn select_expired(
+    notes: &[(String, Vec<String>)],
+    policy: &RetentionPolicy,
+    protected: &HashSet<String>,
+    now: f64,
+) -> HashSet<Position> {
+    let mut expired = HashSet::new();
+    let mut points: HashMap<String, Vec<(f64, Position)>> = HashMap::new();
+    for (c, (commit, lines)) in notes.iter().enumerate() {
+        if protected.contains(commit) {
+            continue;
+        }
+        for (l, line) in lines.iter().enumerate() {
+            for (i, m) in deserialize(line).into_iter().enumerate() {
+                if policy
+                    .max_age
+                    .is_some_and(|age| m.timestamp < now - age.as_secs_f64())
+                {
+                    expired.insert((c, l, i));
+                }
+                points
+                    .entry(m.name)
+                    .or_default()
+                    .push((m.timestamp, (c, l, i)));
+            }
+        }
+    }
+
+    if let Some(max_points) = policy.max_points_per_measurement {
+        for series in points.into_values() {
+            expired.extend(
+                series
+                    .into_iter()
+                    .sorted_by(|a, b| b.0.total_cmp(&a.0))
+                    .skip(max_points)
+                    .map(|(_, pos)| pos),
+            );
+        }
+    }
+    expired
+}
+
+/// Apply all retention `policy` settings in a single pass over the stored measurements.
+/// With `dry_run`, only counts what would be removed.
+pub fn gc(policy: &RetentionPolicy, dry_run: bool) -> Result<GcSummary> {
+    if !policy.removes_anything() {
+        bail!("No retention policy configured in the [retention] section of .gitperfconfig");
+    }
+
+    let backend = storage::backend()?;
+    let notes = backend.walk_all(None)?;
+    let protected = if policy.keep_tagged_commits {
+        git_interop::tagged_commits()?
+    } else {
+        HashSet::new()
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time before unix epoch")
+        .as_secs_f64();
+    let expired = select_expired(&notes, policy, &protected, now);
+
+    let mut summary = GcSummary::default();
+    for (c, (commit, lines)) in notes.into_iter().enumerate() {
+        let mut removed = 0;
+        let mut kept = Vec::new();
+        for (l, line) in lines.into_iter().enumerate() {
+            let (expired_measurements, kept_measurements): (Vec<_>, Vec<_>) = deserialize(&line)
+                .into_iter()
+                .enumerate()
+                .partition(|(i, _)| expired.contains(&(c, l, *i)));
+            if expired_measurements.is_empty() {
+                kept.push(line);
+                continue;
+            }
+            removed += expired_measurements.len();
+            if !kept_measurements.is_empty() {
+                let kept_measurements = kept_measurements.into_iter().map(|(_, m)| m).collect_vec();
+                kept.push(
+                    serialize_multiple(&kept_measurements, serialization::format_of(&line))
+                        .trim_end()
+                        .to_owned(),
+                );
+            }
+        }
+        if removed == 0 {
+            continue;
+        }
+        summary.removed += removed;
+        summary.commits += 1;
+        if !dry_run {
+            backend.rewrite(&commit, &kept.join("\n"))?;
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        data::MeasurementData,
+        serialization::{serialize_single, Format, DELIMITER},
+    };
+
+    use super::*;
+
+    const DAY: f64 = 86400.0;
+
+    fn line(name: &str, timestamp: f64) -> String {
+        serialize_single(
+            &MeasurementData {
+                epoch: 0,
+                name: name.to_owned(),
+                timestamp,
+                val: 1.0,
+                key_values: HashMap::new(),
+            },
+            DELIMITER,
+        )
+    }
+
+    fn notes() -> Vec<(String, Vec<String>)> {
+        vec![
+            ("old".to_owned(), vec![line("a", 0.0), line("b", 0.0)]),
+            ("mid".to_owned(), vec![line("a", 50.0 * DAY)]),
+            ("new".to_owned(), vec![line("a", 99.0 * DAY)]),
+        ]
+    }
+
+    #[test]
+    fn expire_by_age() {
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_secs(90 * 86400)),
+            ..Default::default()
+        };
+        let expired = select_expired(&notes(), &policy, &HashSet::new(), 100.0 * DAY);
+        assert_eq!(expired, HashSet::from([(0, 0, 0), (0, 1, 0)]));
+    }
+
+    #[test]
+    fn expire_beyond_max_points() {
+        let policy = RetentionPolicy {
+            max_points_per_measurement: Some(1),
+            ..Default::default()
+        };
+        let expired = select_expired(&notes(), &policy, &HashSet::new(), 100.0 * DAY);
+        assert_eq!(
+            expired,
+            HashSet::from([(0, 0, 0), (1, 0, 0)]),
+            "b has a single point"
+        );
+    }
+
+    #[test]
+    fn expire_within_v4_lines() {
+        let measurement = |timestamp| MeasurementData {
+            epoch: 0,
+            name: "a".to_owned(),
+            timestamp,
+            val: 1.0,
+            key_values: HashMap::new(),
+        };
+        let line = serialize_multiple(&[measurement(0.0), measurement(99.0 * DAY)], Format::V4);
+        let notes = vec![("mixed".to_owned(), vec![line])];
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_secs(90 * 86400)),
+            ..Default::default()
+        };
+        let expired = select_expired(&notes, &policy, &HashSet::new(), 100.0 * DAY);
+        assert_eq!(expired, HashSet::from([(0, 0, 0)]));
+    }
+
+    #[test]
+    fn keep_protected_commits() {
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_secs(10 * 86400)),
+            max_points_per_measurement: Some(1),
+            keep_tagged_commits: true,
+        };
+        let protected = HashSet::from(["old".to_owned()]);
+        let expired = select_expired(&notes(), &policy, &protected, 100.0 * DAY);
+        assert_eq!(expired, HashSet::from([(1, 0, 0)]));
+    }
+}
@@ -4,22 +4,17 @@ use std::{
         hash_map::Entry::{Occupied, Vacant},
         HashMap,
     },
+    fmt::Display,
+    iter,
+    str::FromStr,
 };
 
+use anyhow::{bail, Result};
+
 use itertools::Itertools;
 
 use crate::data::MeasurementData;
 
-// TODO(kaihowl) serialization with flatten and custom function does not work
-#[derive(Debug, PartialEq)]
-struct SerializeMeasurementData<'a> {
-    epoch: u32,
-    name: &'a str,
-    timestamp: f64,
-    val: f64,
-    key_values: &'a HashMap<String, String>,
-}
-
 pub const DELIMITER: &str = "";
 
 pub fn serialize_single<M>(measurement_data: &M, custom_delimiter: &str) -> String
@@ -40,11 +35,107 @@ where
     m.join(custom_delimiter) + "\n"
 }
 
-pub fn serialize_multiple<M: Borrow<MeasurementData>>(measurement_data: &[M]) -> String {
-    measurement_data
+/// Layout of the serialized measurements in a note. Either way, each line is self-contained so
+/// that notes added concurrently can be merged line by line. Both are read transparently.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// One line per measurement
+    #[default]
+    V3,
+    /// One line per epoch, name, and key-values with the timestamps and values of all of their
+    /// measurements as columns. Not readable by git-perf before v4 support.
+    V4,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "v3" => Ok(Format::V3),
+            "v4" => Ok(Format::V4),
+            _ => bail!("Unknown storage format '{name}', expected 'v3' or 'v4'"),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Format::V3 => "v3",
+            Format::V4 => "v4",
+        })
+    }
+}
+
+/// First field of v4 lines. Never a valid epoch, so earlier versions skip these lines.
+const V4_MARKER: &str = "v4";
+
+/// Format of a single serialized line.
+pub fn format_of(line: &str) -> Format {
+    match line.strip_prefix(V4_MARKER) {
+        Some(rest) if rest.starts_with(DELIMITER) => Format::V4,
+        _ => Format::V3,
+    }
+}
+
+pub fn serialize_multiple<M: Borrow<MeasurementData>>(
+    measurement_data: &[M],
+    format: Format,
+) -> String {
+    match format {
+        Format::V3 => measurement_data
+            .iter()
+            .map(|md| serialize_single(md, DELIMITER))
+            .join(""),
+        Format::V4 => measurement_data
+            .iter()
+            .map(Borrow::borrow)
+            .into_group_map_by(|md| {
+                let key_values = md
+                    .key_values
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+                    .sorted()
+                    .collect_vec();
+                (md.name.as_str(), md.epoch, key_values)
+            })
+            .into_iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|((name, epoch, key_values), mds)| {
+                serialize_columns(name, epoch, &key_values, &mds)
+            })
+            .join(""),
+    }
+}
+
+/// The v4 line `v4, epoch, name, key=value..., timestamps, values` of measurements sharing their
+/// name, epoch, and key-values. Runs of equal timestamps, e.g. of repetitions, are written once
+/// as `timestamp*count`.
+fn serialize_columns(
+    name: &str,
+    epoch: u32,
+    key_values: &[(&str, &str)],
+    mds: &[&MeasurementData],
+) -> String {
+    let timestamps = mds
         .iter()
-        .map(|md| serialize_single(md, DELIMITER))
-        .join("")
+        .map(|md| md.timestamp)
+        .dedup_with_count()
+        .map(|(count, timestamp)| match count {
+            1 => format!("{timestamp:?}"),
+            _ => format!("{timestamp:?}*{count}"),
+        })
+        .join(",");
+    let values = mds.iter().map(|md| format!("{:?}", md.val)).join(",");
+
+    iter::once(V4_MARKER.to_owned())
+        .chain([epoch.to_string(), name.to_owned()])
+        .chain(key_values.iter().map(|(k, v)| format!("{k}={v}")))
+        .chain([timestamps, values])
+        .join(DELIMITER)
+        + "\n"
 }
 
 fn deserialize_single(line: &str) -> Option<MeasurementData> {
@@ -123,18 +214,163 @@ fn deserialize_single(line: &str) -> Option<MeasurementData> {
     })
 }
 
+fn parse_field<T>(what: &str, field: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match field.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(err) => {
+            eprintln!("Cannot parse {what} '{field}': {err}, skipping record");
+            None
+        }
+    }
+}
+
+fn deserialize_columns(line: &str) -> Option<Vec<MeasurementData>> {
+    let components = line.split(DELIMITER).collect_vec();
+    let [_, epoch, name, key_values @ .., timestamps, values] = components.as_slice() else {
+        eprintln!("Too few items with {}, skipping record", components.len());
+        return None;
+    };
+
+    let epoch = parse_field::<u32>("epoch", epoch)?;
+    let key_values = key_values
+        .iter()
+        .map(|kv| match kv.split_once('=') {
+            Some((key, value)) => Some((key.to_owned(), value.to_owned())),
+            None => {
+                eprintln!("No equals sign in key value pair, skipping record");
+                None
+            }
+        })
+        .collect::<Option<HashMap<_, _>>>()?;
+    let timestamp_runs = timestamps
+        .split(',')
+        .map(|run| match run.split_once('*') {
+            Some((timestamp, count)) => Some((
+                parse_field::<f64>("timestamp", timestamp)?,
+                parse_field::<usize>("count", count)?,
+            )),
+            None => Some((parse_field::<f64>("timestamp", run)?, 1)),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let values = values
+        .split(',')
+        .map(|val| parse_field::<f64>("value", val))
+        .collect::<Option<Vec<_>>>()?;
+    // Checked before expanding the runs, which could otherwise exhaust the memory
+    let num_timestamps = timestamp_runs
+        .iter()
+        .try_fold(0usize, |total, (_, count)| total.checked_add(*count));
+    if num_timestamps != Some(values.len()) {
+        eprintln!(
+            "{} timestamps for {} values, skipping record",
+            num_timestamps.map_or("Too many".to_owned(), |n| n.to_string()),
+            values.len()
+        );
+        return None;
+    }
+    let timestamps = timestamp_runs
+        .into_iter()
+        .flat_map(|(timestamp, count)| iter::repeat_n(timestamp, count));
+
+    Some(
+        timestamps
+            .zip(values)
+            .map(|(timestamp, val)| MeasurementData {
+                epoch,
+                name: name.to_string(),
+                timestamp,
+                val,
+                key_values: key_values.clone(),
+            })
+            .collect(),
+    )
+}
+
+/// Measurements of `lines` in either [`Format`].
 pub fn deserialize(lines: &str) -> Vec<MeasurementData> {
     lines
         .lines()
         .filter(|l| !l.trim().is_empty())
-        .filter_map(deserialize_single)
+        .flat_map(|l| match format_of(l) {
+            Format::V3 => deserialize_single(l).into_iter().collect_vec(),
+            Format::V4 => deserialize_columns(l).unwrap_or_default(),
+        })
+        .collect_vec()
+}
+
+/// Formats of the notes refs of earlier git-perf versions, which are only read.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LegacyFormat {
+    /// `refs/notes/perf`: `name timestamp value key=value...` separated by spaces
+    V1,
+    /// `refs/notes/perf-v2`: like v1, but starting with the epoch
+    V2,
+}
+
+impl LegacyFormat {
+    /// The format of a legacy notes ref by its name, e.g. `refs/notes/perf-v2` or a copy
+    /// fetched to `refs/notes/old/perf-v2`.
+    pub fn of_ref(notes_ref: &str) -> Result<Self> {
+        if notes_ref.ends_with("perf-v2") {
+            Ok(LegacyFormat::V2)
+        } else if notes_ref.ends_with("perf") {
+            Ok(LegacyFormat::V1)
+        } else {
+            bail!(
+                "Cannot tell the format of the legacy notes ref '{notes_ref}', expected a name \
+                 ending in 'perf' (v1) or 'perf-v2' (v2)"
+            )
+        }
+    }
+}
+
+/// Measurements of `lines` of a legacy notes ref in `format`. Converted like the scripts in
+/// `migration/`: v1 lines start in epoch 0 and spaces separate the items.
+pub fn deserialize_legacy(lines: &str, format: LegacyFormat) -> Vec<MeasurementData> {
+    lines
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| {
+            let line = match format {
+                LegacyFormat::V1 => format!("0 {l}"),
+                LegacyFormat::V2 => l.to_owned(),
+            };
+            deserialize_single(&line.replace(' ', DELIMITER))
+        })
         .collect_vec()
 }
 
+/// Number of leading serialized `lines` that can be appended to a note without it growing by
+/// more than `budget` bytes, accounting for the line breaks and the separator of the append.
+pub fn lines_within_budget<S: AsRef<str>>(lines: &[S], budget: usize) -> usize {
+    let mut size = 1;
+    lines
+        .iter()
+        .take_while(|line| {
+            size += line.as_ref().len() + 1;
+            size <= budget
+        })
+        .count()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn split_lines_by_budget() {
+        let lines = ["a".repeat(9), "b".repeat(9), "c".repeat(9)];
+        assert_eq!(lines_within_budget(&lines, 0), 0);
+        assert_eq!(lines_within_budget(&lines, 10), 0);
+        assert_eq!(lines_within_budget(&lines, 11), 1);
+        assert_eq!(lines_within_budget(&lines, 30), 2);
+        assert_eq!(lines_within_budget(&lines, 1000), 3);
+    }
+
     #[test]
     fn key_value_deserialization() {
         let lines = "0test1234123key1=value1key2=value2";
@@ -190,4 +426,87 @@ mod test {
         let serialized = serialize_single(&md, DELIMITER);
         assert_eq!(serialized, "3Mymeasurement1234567.042.0mykey=myvalue\n");
     }
+
+    #[test]
+    fn v4_columns_per_name_epoch_and_key_values() {
+        let md = |name: &str, timestamp: f64, val: f64, os: &str| MeasurementData {
+            epoch: 1,
+            name: name.to_owned(),
+            timestamp,
+            val,
+            key_values: [
+                ("os".to_owned(), os.to_owned()),
+                ("arch".to_owned(), "x64".to_owned()),
+            ]
+            .into(),
+        };
+        let measurements = [
+            md("timer", 10.0, 1.5, "linux"),
+            md("timer", 10.0, 2.5, "linux"),
+            md("timer", 10.0, 3.5, "mac"),
+            md("timer", 11.0, 4.5, "linux"),
+            md("build", 12.0, 5.0, "linux"),
+        ];
+        let serialized = serialize_multiple(&measurements, Format::V4);
+        assert_eq!(
+            serialized,
+            "v4\x1c1\x1cbuild\x1carch=x64\x1cos=linux\x1c12.0\x1c5.0\n\
+             v4\x1c1\x1ctimer\x1carch=x64\x1cos=linux\x1c10.0*2,11.0\x1c1.5,2.5,4.5\n\
+             v4\x1c1\x1ctimer\x1carch=x64\x1cos=mac\x1c10.0\x1c3.5\n"
+        );
+        assert!(serialized.lines().all(|l| format_of(l) == Format::V4));
+
+        let roundtrip = deserialize(&serialized);
+        assert_eq!(roundtrip.len(), measurements.len());
+        assert!(measurements.iter().all(|m| roundtrip.contains(m)));
+    }
+
+    #[test]
+    fn read_v3_and_v4_lines() {
+        let lines = "0\x1ctest\x1c1234\x1c123\n\
+                     v4\x1c0\x1ctest\x1c1235*2\x1c1,2\n\
+                     v4\x1c0\x1ctest\x1c1236\x1c1,2\n\
+                     v4\x1c0\x1ctest\x1c1238*18446744073709551615\x1c1\n\
+                     v4\x1c0\x1ctest\x1c1239*18446744073709551615,1240*2\x1c1\n\
+                     v4\x1c0\x1ctest\x1c1237";
+        assert_eq!(format_of("0\x1ctest\x1c1234\x1c123"), Format::V3);
+        assert_eq!(format_of("v4test"), Format::V3);
+        // Mismatched, overflowing, and missing columns are skipped
+        assert_eq!(
+            deserialize(lines)
+                .iter()
+                .map(|m| (m.timestamp, m.val))
+                .collect_vec(),
+            [(1234.0, 123.0), (1235.0, 1.0), (1235.0, 2.0)]
+        );
+    }
+
+    #[test]
+    fn legacy_formats() {
+        assert_eq!(
+            LegacyFormat::of_ref("refs/notes/perf").unwrap(),
+            LegacyFormat::V1
+        );
+        assert_eq!(
+            LegacyFormat::of_ref("refs/notes/old/perf-v2").unwrap(),
+            LegacyFormat::V2
+        );
+        assert!(LegacyFormat::of_ref("refs/notes/perf-v3").is_err());
+
+        let expected = |epoch| MeasurementData {
+            epoch,
+            name: "timer".to_string(),
+            timestamp: 1234.0,
+            val: 1.5,
+            key_values: [("os".to_string(), "linux".to_string())].into(),
+        };
+        assert_eq!(
+            deserialize_legacy("timer 1234 1.5 os=linux\n", LegacyFormat::V1),
+            [expected(0)]
+        );
+        assert_eq!(
+            deserialize_legacy("\n2 timer 1234 1.5 os=linux", LegacyFormat::V2),
+            [expected(2)]
+        );
+    }
 }
@@ -0,0 +1,307 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::{
+    data::{ReductionFunc, Selector},
+    git_interop,
+    reporting::{render_report, ChartType, ReportFormat, XAxis},
+};
+
+/// Report requested by the query string of a URL, e.g. `?m=timer&k=os=linux&n=100&format=csv`.
+#[derive(Debug, PartialEq)]
+struct ReportQuery {
+    /// `format`: html, csv, json, md, svg, or png
+    format: ReportFormat,
+    /// `n`: number of commits
+    num_commits: usize,
+    /// `start`: first commit instead of HEAD
+    start: Option<String>,
+    /// `m`: measurement, repeatable
+    measurements: Vec<String>,
+    /// `k`: key=value, key!=value, or key=~regex, repeatable
+    key_values: Vec<Selector>,
+    /// `s`: key to separate by, repeatable
+    separate_by: Vec<String>,
+    /// `a`: min, max, median, mean, p90, p95, or p99
+    aggregate_by: Option<ReductionFunc>,
+    /// `threshold_bands`: shade the range of each commit that passes an audit
+    threshold_bands: bool,
+}
+
+fn percent_decode(encoded: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut input = encoded.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [input.next(), input.next()]
+                    .into_iter()
+                    .collect::<Option<Vec<_>>>()
+                    .and_then(|hex| u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok())
+                    .ok_or_else(|| anyhow!("Invalid percent-encoding in '{encoded}'"))?;
+                bytes.push(hex);
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+impl ReportQuery {
+    fn parse(query: &str, num_commits: usize) -> Result<Self> {
+        let mut report = ReportQuery {
+            format: ReportFormat::Html,
+            num_commits,
+            start: None,
+            measurements: Vec::new(),
+            key_values: Vec::new(),
+            separate_by: Vec::new(),
+            aggregate_by: None,
+            threshold_bands: false,
+        };
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            match percent_decode(key)?.as_str() {
+                "format" => {
+                    report.format = ReportFormat::from_extension(&value)
+                        .ok_or_else(|| anyhow!("Invalid format '{value}'"))?
+                }
+                "n" => {
+                    report.num_commits = value
+                        .parse()
+                        .with_context(|| format!("Invalid number of commits '{value}'"))?
+                }
+                "start" => {
+                    // Would be taken as an option by git
+                    if value.starts_with('-') {
+                        bail!("Invalid start '{value}'");
+                    }
+                    report.start = Some(value)
+                }
+                "m" => report.measurements.push(value),
+                "k" => report.key_values.push(value.parse()?),
+                "s" => report.separate_by.push(value),
+                "a" => report.aggregate_by = Some(value.parse()?),
+                "threshold_bands" => {
+                    // Also given without a value
+                    report.threshold_bands = value.is_empty()
+                        || value
+                            .parse()
+                            .with_context(|| format!("Invalid threshold_bands '{value}'"))?
+                }
+                key => bail!("Unknown query parameter '{key}'"),
+            }
+        }
+        Ok(report)
+    }
+
+    fn render(&self) -> Result<Vec<u8>> {
+        let start = self
+            .start
+            .as_deref()
+            .map(git_interop::resolve_commit)
+            .transpose()?;
+        render_report(
+            &self.separate_by,
+            false,
+            start.as_deref(),
+            self.num_commits,
+            &self.measurements,
+            &self.key_values,
+            self.aggregate_by,
+            self.threshold_bands,
+            self.format,
+            None,
+            true,
+            false,
+            None,
+            ChartType::TimeSeries,
+            XAxis::Commit,
+        )
+    }
+}
+
+fn content_type(format: ReportFormat) -> &'static str {
+    match format {
+        ReportFormat::Html => "text/html; charset=utf-8",
+        ReportFormat::Csv => "text/tab-separated-values; charset=utf-8",
+        ReportFormat::Json => "application/json",
+        ReportFormat::Markdown => "text/markdown; charset=utf-8",
+        ReportFormat::Svg => "image/svg+xml",
+        ReportFormat::Png => "image/png",
+    }
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn respond_error(stream: &TcpStream, status: &str, error: &anyhow::Error) -> Result<()> {
+    respond(
+        stream,
+        status,
+        "text/plain; charset=utf-8",
+        format!("{error:#}\n").as_bytes(),
+    )
+}
+
+/// Whether `host`, the Host header of a request, names the server at `address`. Other hosts are
+/// refused, so that web pages cannot make the browser request reports via DNS rebinding.
+fn is_own_host(host: &str, address: SocketAddr) -> bool {
+    host == address.to_string()
+        || (address.ip().is_loopback() && host == format!("localhost:{}", address.port()))
+}
+
+fn handle(stream: TcpStream, address: SocketAddr, num_commits: usize) -> Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut host = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_owned());
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond_error(&stream, "400 Bad Request", &anyhow!("Malformed request"));
+    };
+    if method != "GET" {
+        return respond_error(
+            &stream,
+            "405 Method Not Allowed",
+            &anyhow!("Only GET requests are supported"),
+        );
+    }
+    if !host.is_some_and(|host| is_own_host(&host, address)) {
+        return respond_error(
+            &stream,
+            "403 Forbidden",
+            &anyhow!("Only requests for http://{address}/ are served"),
+        );
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/" {
+        return respond_error(&stream, "404 Not Found", &anyhow!("No such page '{path}'"));
+    }
+
+    let query = match ReportQuery::parse(query, num_commits) {
+        Ok(query) => query,
+        Err(e) => return respond_error(&stream, "400 Bad Request", &e),
+    };
+    match query.render() {
+        Ok(report) => respond(&stream, "200 OK", content_type(query.format), &report),
+        Err(e) => respond_error(&stream, "500 Internal Server Error", &e),
+    }
+}
+
+/// Serve reports generated on demand at `address` until interrupted. The query string of a
+/// request selects the measurements and the format as described in `git perf serve --help`.
+/// Reports span `num_commits` commits unless requested otherwise.
+pub fn serve(address: &str, num_commits: usize) -> Result<()> {
+    let listener =
+        TcpListener::bind(address).with_context(|| format!("Failed to listen on '{address}'"))?;
+    let address = listener.local_addr()?;
+    println!("Serving reports on http://{address}/");
+    io::stdout().flush()?;
+
+    for stream in listener.incoming() {
+        let result = stream
+            .context("Failed to accept connection")
+            .and_then(|stream| handle(stream, address, num_commits));
+        if let Err(e) = result {
+            eprintln!("Failed to handle request: {e:#}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_query() {
+        let query = ReportQuery::parse(
+            "m=timer&m=bench%3A%3Asort&k=os%3Dlinux&k=arch!%3Darm64&s=arch&a=median&n=10&format=csv",
+            40,
+        )
+        .unwrap();
+        assert_eq!(
+            query,
+            ReportQuery {
+                format: ReportFormat::Csv,
+                num_commits: 10,
+                start: None,
+                measurements: vec!["timer".to_owned(), "bench::sort".to_owned()],
+                key_values: vec![
+                    Selector::equals("os", "linux"),
+                    "arch!=arm64".parse().unwrap()
+                ],
+                separate_by: vec!["arch".to_owned()],
+                aggregate_by: Some(ReductionFunc::Median),
+                threshold_bands: false,
+            }
+        );
+        assert_eq!(ReportQuery::parse("", 40).unwrap().num_commits, 40);
+        assert!(
+            ReportQuery::parse("threshold_bands", 40)
+                .unwrap()
+                .threshold_bands
+        );
+        assert!(
+            !ReportQuery::parse("threshold_bands=false", 40)
+                .unwrap()
+                .threshold_bands
+        );
+    }
+
+    #[test]
+    fn reject_invalid_queries() {
+        assert!(ReportQuery::parse("n=many", 40).is_err());
+        assert!(ReportQuery::parse("a=sum", 40).is_err());
+        assert!(ReportQuery::parse("k=os", 40).is_err());
+        assert!(ReportQuery::parse("format=pdf", 40).is_err());
+        assert!(ReportQuery::parse("depth=10", 40).is_err());
+        assert!(ReportQuery::parse("m=%zz", 40).is_err());
+        assert!(ReportQuery::parse("start=--output%3D%2Ftmp%2Fx", 40).is_err());
+    }
+
+    #[test]
+    fn accept_only_own_host() {
+        let address: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        assert!(is_own_host("127.0.0.1:8080", address));
+        assert!(is_own_host("localhost:8080", address));
+        assert!(!is_own_host("localhost:9090", address));
+        assert!(!is_own_host("evil.example:8080", address));
+        let address: SocketAddr = "0.0.0.0:8080".parse().unwrap();
+        assert!(!is_own_host("localhost:8080", address));
+    }
+
+    #[test]
+    fn decode_percent_encoding() {
+        assert_eq!(percent_decode("a+b%2Fc").unwrap(), "a b/c");
+        assert!(percent_decode("%4").is_err());
+    }
+}
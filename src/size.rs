@@ -0,0 +1,98 @@
+//! How much space the measurements take up in the notes refs, e.g. to plan retention policies.
+
+use anyhow::Result;
+
+use crate::git_interop;
+
+/// Share of `part` in `total` in percent.
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64 * 100.0
+    }
+}
+
+/// Lines of the `top` commits with the most measurement bytes out of the `sizes` per commit.
+fn by_commit_lines(sizes: &[(String, u64)], total: u64, top: usize) -> Vec<String> {
+    sizes
+        .iter()
+        .take(top)
+        .map(|(commit, bytes)| format!("{commit} {bytes} bytes ({:.1}%)", percent(*bytes, total)))
+        .collect()
+}
+
+/// Lines of the size per month with the growth since the previous month.
+fn trend_lines(history: &[(String, usize, u64)]) -> Vec<String> {
+    let mut previous = 0;
+    history
+        .iter()
+        .map(|(month, count, bytes)| {
+            let growth = *bytes as i64 - previous as i64;
+            previous = *bytes;
+            format!("{month} {count} commits {bytes} bytes ({growth:+})")
+        })
+        .collect()
+}
+
+/// Print the total size of the measurements. With `by_commit`, additionally the `top` commits
+/// with the most measurement bytes. With `trend`, the size at the end of each month in which
+/// measurements changed.
+pub fn size(by_commit: bool, top: usize, trend: bool) -> Result<()> {
+    let sizes = git_interop::note_sizes()?;
+    let total = sizes.iter().map(|(_, bytes)| bytes).sum();
+    println!(
+        "{} annotated commits, {total} bytes of measurements",
+        sizes.len()
+    );
+    if by_commit {
+        println!();
+        for line in by_commit_lines(&sizes, total, top) {
+            println!("{line}");
+        }
+    }
+    if trend {
+        println!();
+        for line in trend_lines(&git_interop::notes_size_history()?) {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+
+    use super::*;
+
+    #[test]
+    fn largest_commits_with_their_share() {
+        let sizes = [
+            ("a".to_owned(), 300),
+            ("b".to_owned(), 100),
+            ("c".to_owned(), 100),
+        ];
+        assert_eq!(
+            by_commit_lines(&sizes, 500, 2),
+            ["a 300 bytes (60.0%)", "b 100 bytes (20.0%)"]
+        );
+        assert_eq!(by_commit_lines(&[], 0, 2).len(), 0);
+        assert_eq!(percent(1, 0), 0.0);
+    }
+
+    #[test]
+    fn growth_per_month() {
+        let history = [
+            ("2024-01".to_owned(), 2, 100),
+            ("2024-02".to_owned(), 5, 250),
+            ("2024-04".to_owned(), 4, 200),
+        ];
+        assert_eq!(
+            trend_lines(&history).iter().join("\n"),
+            "2024-01 2 commits 100 bytes (+100)\n\
+             2024-02 5 commits 250 bytes (+150)\n\
+             2024-04 4 commits 200 bytes (-50)"
+        );
+    }
+}
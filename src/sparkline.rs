@@ -0,0 +1,339 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+
+use crate::stats::VecAggregation;
+
+const WIDTH: f64 = 100.0;
+const HEIGHT: f64 = 20.0;
+const PADDING: f64 = 2.0;
+
+const LINE_COLOR: &str = "#4a6fa5";
+const BAND_COLOR: &str = "#dbe6f3";
+
+/// Color of the last point of a trend without any verdict.
+pub const NEUTRAL_COLOR: &str = LINE_COLOR;
+/// Color of the last point of a passing audit.
+pub const PASS_COLOR: &str = "#2e7d32";
+/// Color of the last point of an audit with a warning.
+pub const WARN_COLOR: &str = "#f9a825";
+/// Color of the last point of a failing audit.
+pub const FAIL_COLOR: &str = "#c62828";
+/// Color of the last point of a skipped audit.
+pub const SKIP_COLOR: &str = "#9e9e9e";
+
+/// How values map to the height of a sparkline.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Scale {
+    #[default]
+    Linear,
+    /// Logarithmic, for values spanning orders of magnitude. Values that are not positive are
+    /// drawn as the smallest positive value.
+    Log,
+}
+
+impl FromStr for Scale {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Ok(match name {
+            "linear" => Scale::Linear,
+            "log" => Scale::Log,
+            _ => bail!("Invalid sparkline scale '{name}', expected linear or log"),
+        })
+    }
+}
+
+/// How the values are prepared before drawing. By default, all values are drawn scaled linearly
+/// between their minimum and maximum, so a single outlier flattens everything else.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    /// Maximum number of points. Older values are averaged into buckets of consecutive values
+    /// to fit, the last value is always drawn as is.
+    pub width: Option<usize>,
+    pub scale: Scale,
+    /// Lower and upper percentile of the values to clamp them and the band to
+    pub clamp: Option<(f64, f64)>,
+}
+
+impl Options {
+    /// Check the options, e.g. after reading them from the configuration.
+    pub fn validate(&self) -> Result<()> {
+        if self.width.is_some_and(|w| w < 2) {
+            bail!("The sparkline width must be at least 2");
+        }
+        if let Some((low, high)) = self.clamp {
+            if !(0.0..high).contains(&low) || high > 100.0 {
+                bail!(
+                    "Invalid sparkline clamp range {low}-{high}, expected two percentiles from 0 \
+                     to 100 in increasing order"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Clamped, bucketed, and scaled values and band, ready to be drawn.
+    fn apply(&self, values: &[f64], band: Option<(f64, f64)>) -> (Vec<f64>, Option<(f64, f64)>) {
+        let mut values = values.to_vec();
+        let mut band = band;
+        if let Some((low, high)) = self.clamp {
+            let low = values.clone().percentile(low);
+            let high = values.clone().percentile(high);
+            if let (Some(low), Some(high)) = (low, high) {
+                values.iter_mut().for_each(|v| *v = v.clamp(low, high));
+                band = band.map(|(l, h)| (l.clamp(low, high), h.clamp(low, high)));
+            }
+        }
+        if let Some(width) = self.width.filter(|w| values.len() > *w) {
+            values = bucketed(&values, width);
+        }
+        if self.scale == Scale::Log {
+            let smallest = values
+                .iter()
+                .copied()
+                .filter(|v| *v > 0.0)
+                .fold(f64::INFINITY, f64::min);
+            let log = |v: f64| if v > 0.0 { v.ln() } else { smallest.ln() };
+            values.iter_mut().for_each(|v| *v = log(*v));
+            band = band.map(|(low, high)| (log(low), log(high)));
+        }
+        (values, band)
+    }
+}
+
+/// All but the last value averaged into `width - 1` buckets of consecutive values, followed by
+/// the last value.
+fn bucketed(values: &[f64], width: usize) -> Vec<f64> {
+    let (last, older) = values
+        .split_last()
+        .expect("Bucketing of more values than width");
+    let buckets = width - 1;
+    (0..buckets)
+        .map(|b| {
+            let bucket = &older[b * older.len() / buckets..(b + 1) * older.len() / buckets];
+            bucket.iter().sum::<f64>() / bucket.len() as f64
+        })
+        .chain([*last])
+        .collect()
+}
+
+/// Trend of the values (oldest first) as unicode block characters scaled between their minimum
+/// and maximum.
+pub fn unicode(values: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|v| {
+            if max > min {
+                let scaled = (v - min) / (max - min) * (BARS.len() - 1) as f64;
+                BARS[scaled.round() as usize]
+            } else {
+                BARS[0]
+            }
+        })
+        .collect()
+}
+
+/// Tiny SVG line chart of the values (oldest first) with the last value marked in `last_color`.
+/// An optional `band` of expected values, e.g. an audit's acceptance range, is shaded behind it.
+pub fn svg(values: &[f64], band: Option<(f64, f64)>, last_color: &str) -> String {
+    let bounds = values
+        .iter()
+        .copied()
+        .chain(band.into_iter().flat_map(|(low, high)| [low, high]))
+        .filter(|v| v.is_finite());
+    let min = bounds.clone().fold(f64::INFINITY, f64::min);
+    let max = bounds.fold(f64::NEG_INFINITY, f64::max);
+    let y = |v: f64| {
+        if max > min {
+            HEIGHT - PADDING - (v - min) / (max - min) * (HEIGHT - 2.0 * PADDING)
+        } else {
+            HEIGHT / 2.0
+        }
+    };
+    let x = |i: usize| {
+        if values.len() > 1 {
+            PADDING + i as f64 / (values.len() - 1) as f64 * (WIDTH - 2.0 * PADDING)
+        } else {
+            WIDTH / 2.0
+        }
+    };
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+         viewBox=\"0 0 {WIDTH} {HEIGHT}\">"
+    );
+    if let Some((low, high)) = band.filter(|(low, high)| low.is_finite() && high.is_finite()) {
+        out.push_str(&format!(
+            "<rect x=\"0\" y=\"{:.1}\" width=\"{WIDTH}\" height=\"{:.1}\" fill=\"{BAND_COLOR}\"/>",
+            y(high),
+            y(low) - y(high)
+        ));
+    }
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("{:.1},{:.1}", x(i), y(*v)))
+        .join(" ");
+    out.push_str(&format!(
+        "<polyline points=\"{points}\" fill=\"none\" stroke=\"{LINE_COLOR}\" stroke-width=\"1.5\"/>"
+    ));
+    if let Some(last) = values.last() {
+        out.push_str(&format!(
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2.5\" fill=\"{last_color}\"/>",
+            x(values.len() - 1),
+            y(*last)
+        ));
+    }
+    out.push_str("</svg>");
+    out
+}
+
+/// Inline `data:` URI of an SVG image. Percent-encoded to be usable both in markdown images and
+/// HTML attributes.
+pub fn data_uri(svg: &str) -> String {
+    let mut uri = String::from("data:image/svg+xml,");
+    for byte in svg.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~=:/,;".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+/// Markdown image of the SVG sparkline with the unicode sparkline as alternative text for
+/// renderers without support for inline images.
+pub fn markdown_image(
+    values: &[f64],
+    band: Option<(f64, f64)>,
+    last_color: &str,
+    options: &Options,
+) -> String {
+    let (values, band) = options.apply(values, band);
+    format!(
+        "![{}]({})",
+        unicode(&values),
+        data_uri(&svg(&values, band, last_color))
+    )
+}
+
+/// HTML image of the SVG sparkline.
+pub fn html_image(
+    values: &[f64],
+    band: Option<(f64, f64)>,
+    last_color: &str,
+    options: &Options,
+) -> String {
+    let (values, band) = options.apply(values, band);
+    format!(
+        "<img src=\"{}\" width=\"{WIDTH}\" height=\"{HEIGHT}\" alt=\"{}\">",
+        data_uri(&svg(&values, band, last_color)),
+        unicode(&values)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unicode_scaling() {
+        assert_eq!(unicode(&[1.0, 2.0, 8.0]), "▁▂█");
+        assert_eq!(unicode(&[3.0, 3.0]), "▁▁");
+        assert_eq!(unicode(&[]), "");
+    }
+
+    #[test]
+    fn svg_scaling() {
+        let svg = svg(&[1.0, 3.0], None, FAIL_COLOR);
+        assert!(svg.contains("points=\"2.0,18.0 98.0,2.0\""));
+        assert!(svg.contains("<circle cx=\"98.0\" cy=\"2.0\" r=\"2.5\" fill=\"#c62828\"/>"));
+        assert!(!svg.contains("<rect"));
+    }
+
+    #[test]
+    fn svg_band_extends_scale() {
+        let svg = svg(&[2.0], Some((0.0, 4.0)), PASS_COLOR);
+        assert!(svg.contains("<rect x=\"0\" y=\"2.0\" width=\"100\" height=\"16.0\""));
+        assert!(svg.contains("points=\"50.0,10.0\""));
+    }
+
+    #[test]
+    fn data_uri_escaping() {
+        assert_eq!(
+            data_uri("<svg a=\"#1 (2)\"/>"),
+            "data:image/svg+xml,%3Csvg%20a=%22%231%20%282%29%22/%3E"
+        );
+    }
+
+    #[test]
+    fn markdown_image_with_alt_text() {
+        let image = markdown_image(&[1.0, 8.0], None, NEUTRAL_COLOR, &Options::default());
+        let uri = image
+            .strip_prefix("![▁█](")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .unwrap();
+        assert!(uri.starts_with("data:image/svg+xml,%3Csvg"));
+        assert!(!uri.contains(['(', ')', ' ']));
+    }
+
+    #[test]
+    fn buckets_keep_the_last_value() {
+        assert_eq!(
+            bucketed(&[1.0, 3.0, 5.0, 7.0, 100.0], 3),
+            vec![2.0, 6.0, 100.0]
+        );
+        assert_eq!(bucketed(&[1.0, 2.0, 3.0, 4.0], 3), vec![1.0, 2.5, 4.0]);
+        let options = Options {
+            width: Some(3),
+            ..Options::default()
+        };
+        assert_eq!(options.apply(&[1.0, 2.0], None).0, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn clamping_reveals_small_changes() {
+        let values = [
+            10.0, 11.0, 10.0, 11.0, 10.0, 11.0, 10.0, 11.0, 10.0, 1000.0, 12.0,
+        ];
+        assert_eq!(unicode(&values), "▁▁▁▁▁▁▁▁▁█▁");
+        let options = Options {
+            clamp: Some((0.0, 90.0)),
+            ..Options::default()
+        };
+        let (clamped, band) = options.apply(&values, Some((5.0, 2000.0)));
+        assert_eq!(unicode(&clamped), "▁▅▁▅▁▅▁▅▁██");
+        assert_eq!(band, Some((10.0, 12.0)));
+    }
+
+    #[test]
+    fn log_scale() {
+        let options = Options {
+            scale: Scale::Log,
+            ..Options::default()
+        };
+        let (values, band) = options.apply(&[1.0, 10.0, 100.0, 0.0], Some((-1.0, 1000.0)));
+        assert_eq!(unicode(&values), "▁▅█▁");
+        assert_eq!(band, Some((0.0, 1000.0f64.ln())));
+    }
+
+    #[test]
+    fn invalid_options() {
+        let invalid = |width, clamp| Options {
+            width,
+            clamp,
+            ..Options::default()
+        };
+        assert!(invalid(Some(1), None).validate().is_err());
+        assert!(invalid(None, Some((90.0, 10.0))).validate().is_err());
+        assert!(invalid(None, Some((5.0, 101.0))).validate().is_err());
+        assert!(invalid(Some(2), Some((0.0, 100.0))).validate().is_ok());
+    }
+}
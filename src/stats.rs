@@ -6,9 +6,17 @@ use itertools::Itertools;
 use crate::data::ReductionFunc;
 
 use readable::num::*;
+use serde::Serialize;
 
 pub trait VecAggregation {
     fn median(&mut self) -> Option<f64>;
+    fn median_absolute_deviation(&mut self) -> Option<f64>;
+    /// Value below which `percent` of the values fall, interpolated linearly between the
+    /// closest ranks.
+    fn percentile(&mut self, percent: f64) -> Option<f64>;
+    /// Mean of the values left after dropping `percent` of them at both the low and the high
+    /// end. The number of dropped values is rounded down.
+    fn trimmed_mean(&mut self, percent: u8) -> Option<f64>;
 }
 
 concatenate!(AggStats, [Mean, mean], [Variance, sample_variance]);
@@ -22,13 +30,43 @@ pub fn aggregate_measurements(measurements: impl Iterator<Item = f64>) -> Stats
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Stats {
     pub mean: f64,
     pub stddev: f64,
     pub len: usize,
 }
 
+/// Mean and spread of values added one at a time.
+#[derive(Debug, Default)]
+pub struct RunningStats {
+    variance: Variance,
+}
+
+impl RunningStats {
+    pub fn add(&mut self, value: f64) {
+        self.variance.add(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.variance.len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sample standard deviation relative to the magnitude of the mean. Undefined for fewer than
+    /// two values or a mean of zero.
+    pub fn coefficient_of_variation(&self) -> Option<f64> {
+        let mean = self.variance.mean();
+        if self.len() < 2 || mean == 0.0 {
+            return None;
+        }
+        Some(self.variance.sample_variance().sqrt() / mean.abs())
+    }
+}
+
 impl Display for Stats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -42,10 +80,24 @@ impl Display for Stats {
 }
 
 impl Stats {
-    pub fn significantly_different_from(&self, other: &Stats, sigma: f64) -> bool {
+    pub fn z_score(&self, other: &Stats) -> f64 {
         assert!(self.len == 1);
         assert!(other.len >= 1);
-        (self.mean - other.mean).abs() / other.stddev > sigma
+        (self.mean - other.mean).abs() / other.stddev
+    }
+
+    pub fn significantly_different_from(&self, other: &Stats, sigma: f64) -> bool {
+        self.z_score(other) > sigma
+    }
+
+    /// Welch's t statistic of the difference between the means of two samples.
+    /// Positive if `other` has the larger mean. None if the standard error is zero or undefined,
+    /// e.g. if neither sample varies or both are single values.
+    pub fn welch_t(&self, other: &Stats) -> Option<f64> {
+        let standard_error = (self.stddev.powi(2) / self.len as f64
+            + other.stddev.powi(2) / other.len as f64)
+            .sqrt();
+        (standard_error > 0.0).then(|| (other.mean - self.mean) / standard_error)
     }
 }
 
@@ -62,6 +114,43 @@ impl VecAggregation for Vec<f64> {
             odd => Some(self[odd / 2]),
         }
     }
+
+    fn median_absolute_deviation(&mut self) -> Option<f64> {
+        let median = self.median()?;
+        self.iter()
+            .map(|v| (v - median).abs())
+            .collect_vec()
+            .median()
+    }
+
+    fn percentile(&mut self, percent: f64) -> Option<f64> {
+        self.sort_by(f64::total_cmp);
+        let last = self.len().checked_sub(1)?;
+        let rank = percent / 100.0 * last as f64;
+        let (lower, upper) = (self[rank.floor() as usize], self[rank.ceil() as usize]);
+        Some(lower + (upper - lower) * rank.fract())
+    }
+
+    fn trimmed_mean(&mut self, percent: u8) -> Option<f64> {
+        self.sort_by(f64::total_cmp);
+        let trim = self.len() * usize::from(percent.min(49)) / 100;
+        self[trim..self.len() - trim]
+            .iter()
+            .copied()
+            .aggregate_by(ReductionFunc::Mean)
+    }
+}
+
+/// Geometric mean, undefined for empty input and any value that is not positive.
+fn geometric_mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let mut log_mean = Mean::new();
+    for value in values {
+        if value <= 0.0 {
+            return None;
+        }
+        log_mean.add(value.ln());
+    }
+    (!log_mean.is_empty()).then(|| log_mean.mean().exp())
 }
 
 pub trait NumericReductionFunc: Iterator<Item = f64> {
@@ -78,16 +167,115 @@ pub trait NumericReductionFunc: Iterator<Item = f64> {
                     Some(stats.mean())
                 }
             }
+            ReductionFunc::P90 => self.collect_vec().percentile(90.0),
+            ReductionFunc::P95 => self.collect_vec().percentile(95.0),
+            ReductionFunc::P99 => self.collect_vec().percentile(99.0),
+            ReductionFunc::TrimmedMean(percent) => self.collect_vec().trimmed_mean(percent),
+            ReductionFunc::GeometricMean => geometric_mean(self),
         }
     }
 }
 
 impl<T> NumericReductionFunc for T where T: Iterator<Item = f64> {}
 
+/// A step change in a series of values.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ChangePoint {
+    /// Number of values before the change
+    pub index: usize,
+    pub mean_before: f64,
+    pub mean_after: f64,
+    /// Difference of the means in pooled standard deviations
+    pub score: f64,
+}
+
+/// The most likely single step change in `values` (oldest first) if the means before and after
+/// it differ by more than `sigma` pooled standard deviations. Both sides of the change need at
+/// least `min_segment` values.
+pub fn detect_change_point(values: &[f64], sigma: f64, min_segment: usize) -> Option<ChangePoint> {
+    let min_segment = min_segment.max(1);
+    if values.len() < 2 * min_segment {
+        return None;
+    }
+
+    (min_segment..=values.len() - min_segment)
+        .map(|index| {
+            let before = aggregate_measurements(values[..index].iter().copied());
+            let after = aggregate_measurements(values[index..].iter().copied());
+            let squares = |s: &Stats| s.stddev.powi(2) * s.len.saturating_sub(1) as f64;
+            let degrees = (values.len() - 2).max(1) as f64;
+            let pooled_stddev = ((squares(&before) + squares(&after)) / degrees).sqrt();
+            let difference = (after.mean - before.mean).abs();
+            let score = if difference == 0.0 {
+                0.0
+            } else {
+                difference / pooled_stddev
+            };
+            ChangePoint {
+                index,
+                mean_before: before.mean,
+                mean_after: after.mean,
+                score,
+            }
+        })
+        .max_by(|a, b| a.score.total_cmp(&b.score))
+        .filter(|cp| cp.score > sigma)
+}
+
+/// Slope of the least-squares line through `values` (oldest first, one per commit) relative to
+/// their mean, in percent per commit. None for less than two values or a mean of zero.
+pub fn trend_pct_per_commit(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let n = values.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f64>() / n;
+    if mean_y == 0.0 {
+        return None;
+    }
+    let (covariance, variance) =
+        values
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(cov, var), (x, y)| {
+                let dx = x as f64 - mean_x;
+                (cov + dx * (y - mean_y), var + dx * dx)
+            });
+    Some(covariance / variance / mean_y.abs() * 100.0)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn running_coefficient_of_variation() {
+        let mut stats = RunningStats::default();
+        stats.add(10.0);
+        assert_eq!(stats.coefficient_of_variation(), None);
+        stats.add(12.0);
+        stats.add(14.0);
+        assert_eq!(stats.len(), 3);
+        assert!((stats.coefficient_of_variation().unwrap() - 2.0 / 12.0).abs() < 1e-12);
+
+        let mut zero = RunningStats::default();
+        zero.add(-1.0);
+        zero.add(1.0);
+        assert_eq!(zero.coefficient_of_variation(), None);
+    }
+
+    #[test]
+    fn linear_trend() {
+        let drift = [100.0, 101.0, 102.0, 103.0, 104.0];
+        assert!((trend_pct_per_commit(&drift).unwrap() - 1.0 / 102.0 * 100.0).abs() < 1e-12);
+        let falling = drift.iter().rev().copied().collect_vec();
+        assert!(trend_pct_per_commit(&falling).unwrap() < 0.0);
+        assert_eq!(trend_pct_per_commit(&[5.0, 5.0, 5.0]), Some(0.0));
+        assert_eq!(trend_pct_per_commit(&[5.0]), None);
+        assert_eq!(trend_pct_per_commit(&[-1.0, 1.0]), None);
+    }
+
     #[test]
     fn no_floating_error() {
         let measurements = (0..100).map(|_| 0.1).collect_vec();
@@ -119,16 +307,128 @@ mod test {
 
     #[test]
     fn z_score_with_zero_stddev() {
-        let stddev = 0.0;
+        let stddev: f64 = 0.0;
         let mean = 30.0;
         let higher_val = 50.0;
         let lower_val = 10.0;
-        let z_high = ((higher_val - mean) / stddev as f64).abs();
-        let z_low = ((lower_val - mean) / stddev as f64).abs();
+        let z_high = ((higher_val - mean) / stddev).abs();
+        let z_low = ((lower_val - mean) / stddev).abs();
         assert_eq!(z_high, f64::INFINITY);
         assert_eq!(z_low, f64::INFINITY);
     }
 
+    #[test]
+    fn welch_t() {
+        let a = aggregate_measurements([1.0, 2.0, 3.0].into_iter());
+        let b = aggregate_measurements([2.0, 3.0, 4.0].into_iter());
+        let t = a.welch_t(&b).unwrap();
+        // Standard error: sqrt(1/3 + 1/3)
+        assert!((t - 1.0 / (2.0f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert_eq!(b.welch_t(&a), Some(-t));
+    }
+
+    #[test]
+    fn welch_t_without_variance() {
+        let constant = aggregate_measurements([2.0, 2.0, 2.0].into_iter());
+        let other_constant = aggregate_measurements([3.0, 3.0].into_iter());
+        assert_eq!(constant.welch_t(&other_constant), None);
+        assert_eq!(constant.welch_t(&constant), None);
+
+        let single = aggregate_measurements([1.0].into_iter());
+        let other_single = aggregate_measurements([5.0].into_iter());
+        assert_eq!(single.welch_t(&other_single), None);
+
+        let empty = aggregate_measurements(std::iter::empty());
+        assert_eq!(empty.welch_t(&single), None);
+
+        let varying = aggregate_measurements([1.0, 3.0].into_iter());
+        assert_eq!(constant.welch_t(&varying), Some(0.0));
+    }
+
+    #[test]
+    fn percentiles() {
+        let values = (1..=11).map(f64::from).collect_vec();
+        assert_eq!(values.clone().percentile(50.0), Some(6.0));
+        assert_eq!(values.clone().percentile(90.0), Some(10.0));
+        assert!((values.clone().percentile(95.0).unwrap() - 10.5).abs() < 1e-12);
+        assert_eq!(vec![3.0].percentile(99.0), Some(3.0));
+        assert_eq!(Vec::<f64>::new().percentile(90.0), None);
+        let p99 = vec![4.0, 1.0, 3.0, 2.0]
+            .into_iter()
+            .aggregate_by(ReductionFunc::P99);
+        assert!((p99.unwrap() - 3.97).abs() < 1e-12);
+    }
+
+    #[test]
+    fn trimmed_mean() {
+        // One outlier at each end of ten values is dropped by trimming 10%
+        let mut values = (1..=8).map(f64::from).collect_vec();
+        values.extend([-100.0, 1000.0]);
+        assert_eq!(values.clone().trimmed_mean(10), Some(4.5));
+        assert_eq!(
+            values.clone().trimmed_mean(9),
+            values.clone().trimmed_mean(0)
+        );
+        assert_eq!(vec![7.0].trimmed_mean(49), Some(7.0));
+        assert_eq!(Vec::<f64>::new().trimmed_mean(10), None);
+    }
+
+    #[test]
+    fn geometric_mean() {
+        let mean = vec![1.0, 10.0, 100.0]
+            .into_iter()
+            .aggregate_by(ReductionFunc::GeometricMean);
+        assert!((mean.unwrap() - 10.0).abs() < 1e-12);
+        assert_eq!(
+            vec![1.0, 0.0]
+                .into_iter()
+                .aggregate_by(ReductionFunc::GeometricMean),
+            None
+        );
+        assert_eq!(
+            Vec::<f64>::new()
+                .into_iter()
+                .aggregate_by(ReductionFunc::GeometricMean),
+            None
+        );
+    }
+
+    #[test]
+    fn reduction_func_names() {
+        for name in [
+            "min",
+            "p95",
+            "trimmed_mean(10)",
+            "trimmed_mean(0)",
+            "geometric_mean",
+        ] {
+            assert_eq!(name.parse::<ReductionFunc>().unwrap().to_string(), name);
+        }
+        assert_eq!(
+            "trimmed_mean( 5 )".parse::<ReductionFunc>().unwrap(),
+            ReductionFunc::TrimmedMean(5)
+        );
+        for invalid in [
+            "trimmed_mean(50)",
+            "trimmed_mean(-1)",
+            "trimmed_mean",
+            "geomean",
+        ] {
+            assert!(invalid.parse::<ReductionFunc>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn median_absolute_deviation() {
+        assert_eq!(Vec::<f64>::new().median_absolute_deviation(), None);
+        assert_eq!(vec![5.0].median_absolute_deviation(), Some(0.0));
+        // Median 2, absolute deviations [1, 1, 0, 0, 2, 4, 7]
+        assert_eq!(
+            vec![1.0, 1.0, 2.0, 2.0, 4.0, 6.0, 9.0].median_absolute_deviation(),
+            Some(1.0)
+        );
+    }
+
     #[test]
     fn verify_stats() {
         let empty_vec = [];
@@ -199,4 +499,26 @@ mod test {
             three_el_vec.into_iter().aggregate_by(ReductionFunc::Mean)
         );
     }
+
+    #[test]
+    fn step_change_detected() {
+        let values = [10.0, 11.0, 10.0, 9.0, 10.0, 20.0, 21.0, 19.0, 20.0];
+        let cp = detect_change_point(&values, 4.0, 3).expect("Missing change point");
+        assert_eq!(cp.index, 5);
+        assert_eq!(cp.mean_before, 10.0);
+        assert_eq!(cp.mean_after, 20.0);
+    }
+
+    #[test]
+    fn no_change_in_noise() {
+        let values = [10.0, 11.0, 9.0, 10.5, 9.5, 10.0, 11.0, 9.0];
+        assert_eq!(detect_change_point(&values, 4.0, 3), None);
+        assert_eq!(detect_change_point(&[1.0, 1.0, 1.0, 1.0], 4.0, 2), None);
+    }
+
+    #[test]
+    fn change_point_needs_enough_values() {
+        assert_eq!(detect_change_point(&[1.0, 1.0, 5.0, 5.0], 4.0, 3), None);
+        assert!(detect_change_point(&[1.0, 1.0, 1.0, 5.0, 5.0, 5.0], 4.0, 3).is_some());
+    }
 }
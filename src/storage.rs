@@ -0,0 +1,68 @@
+use anyhow::{bail, Result};
+
+use crate::{config, git_interop};
+
+/// Commits and their serialized measurement lines, read while iterating.
+pub type CommitLines = Box<dyn Iterator<Item = Result<(String, Vec<String>)>>>;
+
+/// Where serialized measurements are stored. Each commit owns a list of serialized lines.
+pub trait StorageBackend {
+    /// Append serialized measurement lines to the given commit.
+    fn append(&self, commit: &str, lines: &str) -> Result<()>;
+
+    /// Serialized measurement lines of a single commit.
+    fn list(&self, commit: &str) -> Result<Vec<String>>;
+
+    /// Serialized measurement lines of the last `num_commits` first-parent commits starting at
+    /// `start`, newest first.
+    fn walk(&self, start: &str, num_commits: usize) -> Result<Vec<(String, Vec<String>)>>;
+
+    /// Serialized measurement lines of all commits with measurements, optionally restricted to a
+    /// commit range such as `main~10..main`. No particular order.
+    fn walk_all(&self, range: Option<&str>) -> Result<Vec<(String, Vec<String>)>>;
+
+    /// Like [`StorageBackend::walk_all`], but reading one commit at a time while iterating, e.g.
+    /// to export the whole history. No lines may be appended or rewritten before the end.
+    fn stream_all(&self, range: Option<&str>) -> Result<CommitLines>;
+
+    /// Replace all serialized measurement lines of the given commit.
+    fn rewrite(&self, commit: &str, lines: &str) -> Result<()>;
+}
+
+/// The default backend storing measurements in git notes.
+pub struct GitNotesBackend;
+
+impl StorageBackend for GitNotesBackend {
+    fn append(&self, commit: &str, lines: &str) -> Result<()> {
+        git_interop::add_note_line(lines, commit)
+    }
+
+    fn list(&self, commit: &str) -> Result<Vec<String>> {
+        git_interop::read_note_lines(commit)
+    }
+
+    fn walk(&self, start: &str, num_commits: usize) -> Result<Vec<(String, Vec<String>)>> {
+        git_interop::walk_commits_from(start, num_commits)
+    }
+
+    fn walk_all(&self, range: Option<&str>) -> Result<Vec<(String, Vec<String>)>> {
+        git_interop::walk_notes(range)
+    }
+
+    fn stream_all(&self, range: Option<&str>) -> Result<CommitLines> {
+        Ok(Box::new(git_interop::stream_notes(range)?))
+    }
+
+    fn rewrite(&self, commit: &str, lines: &str) -> Result<()> {
+        git_interop::replace_note(lines, commit)
+    }
+}
+
+/// The storage backend selected with `[storage] backend = "<name>"` in .gitperfconfig.
+/// Defaults to git notes.
+pub fn backend() -> Result<Box<dyn StorageBackend>> {
+    match config::determine_storage_backend_from_config().as_deref() {
+        None | Some("git-notes") => Ok(Box::new(GitNotesBackend)),
+        Some(other) => bail!("Unknown storage backend '{other}'"),
+    }
+}
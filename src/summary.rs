@@ -0,0 +1,163 @@
+use std::iter;
+
+use anyhow::Result;
+use itertools::Itertools;
+use readable::num::{Float, Unsigned};
+use serde::Serialize;
+
+use crate::{
+    data::{MeasurementData, Selector},
+    measurement_retrieval,
+    stats::{self, VecAggregation},
+};
+
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SummaryFormat {
+    /// Aligned table for the terminal
+    #[default]
+    Table,
+    /// JSON array on stdout
+    Json,
+}
+
+/// Summary statistics of all raw values of a single measurement.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct MeasurementStats {
+    pub name: String,
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+    pub mad: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+fn summarize(name: &str, mut vals: Vec<f64>) -> Option<MeasurementStats> {
+    let aggregate = stats::aggregate_measurements(vals.iter().copied());
+    let (min, max) = vals.iter().copied().minmax().into_option()?;
+    Some(MeasurementStats {
+        name: name.to_owned(),
+        count: aggregate.len,
+        mean: aggregate.mean,
+        stddev: aggregate.stddev,
+        mad: vals.median_absolute_deviation()?,
+        median: vals.median()?,
+        min,
+        max,
+    })
+}
+
+fn as_table(summaries: &[MeasurementStats]) -> String {
+    let header = [
+        "measurement",
+        "n",
+        "mean",
+        "median",
+        "stddev",
+        "MAD",
+        "min",
+        "max",
+    ]
+    .map(str::to_owned)
+    .to_vec();
+    let rows = summaries.iter().map(|s| {
+        let mut row = vec![s.name.clone(), Unsigned::from(s.count).to_string()];
+        row.extend(
+            [s.mean, s.median, s.stddev, s.mad, s.min, s.max].map(|v| Float::from(v).to_string()),
+        );
+        row
+    });
+    let rows = iter::once(header).chain(rows).collect_vec();
+    let widths = (0..rows[0].len())
+        .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+        .collect_vec();
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(i, (cell, width))| {
+                    // Names are left aligned, numbers right aligned
+                    if i == 0 {
+                        format!("{cell:<width$}")
+                    } else {
+                        format!("{cell:>width$}")
+                    }
+                })
+                .join("  ")
+                .trim_end()
+                .to_owned()
+        })
+        .join("\n")
+}
+
+/// Print count, mean, median, stddev, MAD, min, and max of the raw values of each measurement
+/// in the last `max_count` commits. Optionally limited to the given measurement names and to
+/// measurements with all of the `selectors`.
+pub fn summary(
+    max_count: usize,
+    measurements: &[String],
+    selectors: &[Selector],
+    format: SummaryFormat,
+) -> Result<()> {
+    let filter_by = |m: &MeasurementData| {
+        (measurements.is_empty() || measurements.contains(&m.name))
+            && m.matches_selectors(selectors)
+    };
+
+    let commits: Vec<_> = measurement_retrieval::walk_commits(max_count)?.try_collect()?;
+    let summaries = commits
+        .into_iter()
+        .flat_map(|c| c.measurements)
+        .filter(filter_by)
+        .map(|m| (m.name, m.val))
+        .into_group_map()
+        .into_iter()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .filter_map(|(name, vals)| summarize(&name, vals))
+        .collect_vec();
+
+    match format {
+        SummaryFormat::Table => {
+            if summaries.is_empty() {
+                eprintln!("No measurements found");
+            } else {
+                println!("{}", as_table(&summaries));
+            }
+        }
+        SummaryFormat::Json => println!("{}", serde_json::to_string_pretty(&summaries)?),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn summarize_values() {
+        let summary = summarize("timer", vec![4.0, 1.0, 2.0, 3.0, 10.0]).unwrap();
+        assert_eq!(summary.count, 5);
+        assert_eq!(summary.mean, 4.0);
+        assert_eq!(summary.median, 3.0);
+        assert_eq!(summary.mad, 1.0);
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 10.0);
+        assert!(summarize("timer", vec![]).is_none());
+    }
+
+    #[test]
+    fn aligned_table() {
+        let summaries = vec![
+            summarize("timer", vec![1.0, 3.0]).unwrap(),
+            summarize("t", vec![100.0]).unwrap(),
+        ];
+        let table = as_table(&summaries);
+        let lines = table.lines().collect_vec();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("measurement  n"));
+        assert!(lines[2].starts_with("t            1  100.000"));
+    }
+}
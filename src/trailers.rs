@@ -0,0 +1,133 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// Trailer key with which commits declare an expected change of a measurement.
+pub const PERF_EXPECT_TRAILER: &str = "Perf-Expect";
+
+/// Expected change of a measurement declared in a commit message, e.g.
+/// `Perf-Expect: build_time +10%`.
+#[derive(Debug, PartialEq)]
+pub struct PerfExpectation {
+    pub measurement: String,
+    /// Maximum accepted relative change in percent. The sign gives the direction.
+    pub change_percent: f64,
+}
+
+impl PerfExpectation {
+    pub fn parse(value: &str) -> Result<PerfExpectation> {
+        let invalid = || anyhow!("Invalid {PERF_EXPECT_TRAILER} trailer '{value}'");
+        let (measurement, change) = value
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| invalid().context("Expected '<measurement> <+/-N>%'"))?;
+        let change = change.trim();
+        if !change.starts_with(['+', '-']) {
+            return Err(invalid().context("The change needs an explicit sign"));
+        }
+        let change_percent = change
+            .strip_suffix('%')
+            .ok_or_else(|| invalid().context("The change must be given in percent"))?
+            .parse::<f64>()
+            .with_context(invalid)?;
+        if !change_percent.is_finite() || change_percent == 0.0 {
+            bail!(invalid().context("The change must be a finite, non-zero percentage"));
+        }
+        Ok(PerfExpectation {
+            measurement: measurement.to_owned(),
+            change_percent,
+        })
+    }
+
+    /// Whether the change from `tail` to `head` is in the declared direction and within the
+    /// declared bound.
+    pub fn allows(&self, tail: f64, head: f64) -> bool {
+        let change_percent = (head - tail) / tail.abs() * 100.0;
+        change_percent.signum() == self.change_percent.signum()
+            && change_percent.abs() <= self.change_percent.abs()
+    }
+}
+
+impl Display for PerfExpectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{PERF_EXPECT_TRAILER}: {} {:+}%",
+            self.measurement, self.change_percent
+        )
+    }
+}
+
+/// The expectation for `measurement` among the trailer values. Declaring more than one
+/// expectation for the same measurement is an error.
+pub fn expectation_for(measurement: &str, values: &[String]) -> Result<Option<PerfExpectation>> {
+    let mut found = None;
+    for value in values {
+        let expectation = PerfExpectation::parse(value)?;
+        if expectation.measurement != measurement {
+            continue;
+        }
+        if found.is_some() {
+            bail!("Multiple {PERF_EXPECT_TRAILER} trailers for measurement '{measurement}'");
+        }
+        found = Some(expectation);
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_expectations() {
+        assert_eq!(
+            PerfExpectation::parse("build_time +10%").unwrap(),
+            PerfExpectation {
+                measurement: "build_time".to_string(),
+                change_percent: 10.0
+            }
+        );
+        assert_eq!(
+            PerfExpectation::parse(" bench::sort::mean   -2.5% ")
+                .unwrap()
+                .change_percent,
+            -2.5
+        );
+        assert!(PerfExpectation::parse("build_time").is_err());
+        assert!(PerfExpectation::parse("build_time 10%").is_err());
+        assert!(PerfExpectation::parse("build_time +10").is_err());
+        assert!(PerfExpectation::parse("build_time +ten%").is_err());
+        assert!(PerfExpectation::parse("build_time +0%").is_err());
+    }
+
+    #[test]
+    fn allowed_changes() {
+        let slower = PerfExpectation::parse("timer +10%").unwrap();
+        assert!(slower.allows(100.0, 109.0));
+        assert!(!slower.allows(100.0, 111.0));
+        assert!(!slower.allows(100.0, 95.0));
+
+        let faster = PerfExpectation::parse("timer -50%").unwrap();
+        assert!(faster.allows(100.0, 60.0));
+        assert!(!faster.allows(100.0, 40.0));
+        assert!(!faster.allows(100.0, 101.0));
+    }
+
+    #[test]
+    fn find_expectation() {
+        let values = vec!["timer +10%".to_string(), "other -5%".to_string()];
+        assert_eq!(
+            expectation_for("other", &values)
+                .unwrap()
+                .unwrap()
+                .change_percent,
+            -5.0
+        );
+        assert!(expectation_for("missing", &values).unwrap().is_none());
+
+        let duplicated = vec!["timer +10%".to_string(), "timer +20%".to_string()];
+        assert!(expectation_for("timer", &duplicated).is_err());
+        assert!(expectation_for("timer", &["garbage".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use readable::num::Float;
+
+/// A unit and its size in multiples of the smallest unit of its family.
+struct Unit {
+    symbol: &'static str,
+    size: f64,
+}
+
+const TIME: [Unit; 4] = [
+    Unit {
+        symbol: "ns",
+        size: 1.0,
+    },
+    Unit {
+        symbol: "µs",
+        size: 1e3,
+    },
+    Unit {
+        symbol: "ms",
+        size: 1e6,
+    },
+    Unit {
+        symbol: "s",
+        size: 1e9,
+    },
+];
+
+const BYTES: [Unit; 4] = [
+    Unit {
+        symbol: "B",
+        size: 1.0,
+    },
+    Unit {
+        symbol: "KiB",
+        size: 1024.0,
+    },
+    Unit {
+        symbol: "MiB",
+        size: 1024.0 * 1024.0,
+    },
+    Unit {
+        symbol: "GiB",
+        size: 1024.0 * 1024.0 * 1024.0,
+    },
+];
+
+const FAMILIES: [&[Unit]; 2] = [&TIME, &BYTES];
+
+fn lookup(symbol: &str) -> Option<(&'static [Unit], &'static Unit)> {
+    // Accept the common ASCII spelling of microseconds
+    let symbol = if symbol == "us" { "µs" } else { symbol };
+    FAMILIES.iter().find_map(|family| {
+        family
+            .iter()
+            .find(|u| u.symbol == symbol)
+            .map(|u| (*family, u))
+    })
+}
+
+/// Size of the time `unit`, such as `ms`, in nanoseconds. `None` for units of other families.
+pub fn nanoseconds_per(unit: &str) -> Option<f64> {
+    match lookup(unit)? {
+        (family, unit) if family[0].symbol == TIME[0].symbol => Some(unit.size),
+        _ => None,
+    }
+}
+
+/// Display values in `unit` instead of their original unit `from` by dividing them by `divisor`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scaling {
+    pub from: &'static str,
+    pub unit: &'static str,
+    pub divisor: f64,
+}
+
+impl Scaling {
+    pub fn apply(&self, value: f64) -> f64 {
+        value / self.divisor
+    }
+
+    /// An unscaled value in its original unit for display, e.g. in hover texts.
+    pub fn format_original(&self, value: f64) -> String {
+        format!("{} {}", Float::from(value), self.from)
+    }
+}
+
+/// The largest unit of the family of `unit` in which the median magnitude of `values` is still at
+/// least one. `None` for unknown units or without values.
+pub fn auto_scale(unit: &str, values: &[f64]) -> Option<Scaling> {
+    let (family, original) = lookup(unit)?;
+    let mut magnitudes: Vec<f64> = values.iter().map(|v| v.abs()).collect();
+    if magnitudes.is_empty() {
+        return None;
+    }
+    magnitudes.sort_by(f64::total_cmp);
+    let median = magnitudes[magnitudes.len() / 2] * original.size;
+    let target = family
+        .iter()
+        .rev()
+        .find(|u| median >= u.size)
+        .unwrap_or(&family[0]);
+    Some(Scaling {
+        from: original.symbol,
+        unit: target.symbol,
+        divisor: target.size / original.size,
+    })
+}
+
+/// Parse a duration such as `500ms`, `30s`, `1.5m` or `2h`.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let pos = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .ok_or_else(|| anyhow!("invalid duration '{}': missing unit (ms, s, m, h)", s))?;
+    let (value, unit) = s.split_at(pos);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| anyhow!("invalid duration '{}': cannot parse number", s))?;
+    let factor = match unit {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(anyhow!("invalid duration '{}': unknown unit '{}'", s, unit)),
+    };
+    seconds(value * factor, s)
+}
+
+/// `secs` as a duration, which fails for negative, NaN, infinite, and too large `secs` of the
+/// duration or period `s`.
+fn seconds(secs: f64, s: &str) -> Result<Duration> {
+    Duration::try_from_secs_f64(secs).map_err(|e| anyhow!("invalid duration '{}': {}", s, e))
+}
+
+/// Like [`parse_duration`] but also in days and weeks, e.g. `90d` or `2w`.
+pub fn parse_period(s: &str) -> Result<Duration> {
+    let days = |suffix: char, factor: f64| -> Option<Result<Duration>> {
+        let value = s.strip_suffix(suffix)?;
+        Some(
+            value
+                .parse::<f64>()
+                .map_err(|_| anyhow!("invalid period '{}': cannot parse number", s))
+                .and_then(|v| seconds(v * factor * 86400.0, s)),
+        )
+    };
+    days('d', 1.0)
+        .or_else(|| days('w', 7.0))
+        .unwrap_or_else(|| parse_duration(s))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scale_time() {
+        let scaling = auto_scale("ns", &[1.5e6, 2e6, 3e9]).unwrap();
+        assert_eq!(scaling.unit, "ms");
+        assert_eq!(scaling.apply(2e6), 2.0);
+        assert_eq!(scaling.format_original(2e6), "2,000,000.000 ns");
+
+        let scaling = auto_scale("ms", &[3000.0]).unwrap();
+        assert_eq!(scaling.unit, "s");
+        assert_eq!(scaling.apply(3000.0), 3.0);
+
+        assert_eq!(auto_scale("us", &[12.0]).unwrap().unit, "µs");
+        assert_eq!(auto_scale("s", &[0.001]).unwrap().unit, "ms");
+    }
+
+    #[test]
+    fn nanoseconds_per_time_unit() {
+        assert_eq!(nanoseconds_per("ns"), Some(1.0));
+        assert_eq!(nanoseconds_per("us"), Some(1e3));
+        assert_eq!(nanoseconds_per("s"), Some(1e9));
+        assert_eq!(nanoseconds_per("MiB"), None);
+        assert_eq!(nanoseconds_per("furlong"), None);
+    }
+
+    #[test]
+    fn scale_bytes() {
+        let scaling = auto_scale("B", &[40.0 * 1024.0 * 1024.0]).unwrap();
+        assert_eq!(scaling.unit, "MiB");
+        assert_eq!(scaling.apply(40.0 * 1024.0 * 1024.0), 40.0);
+    }
+
+    #[test]
+    fn smallest_unit_for_tiny_values() {
+        assert_eq!(auto_scale("ns", &[0.5]).unwrap().unit, "ns");
+    }
+
+    #[test]
+    fn unknown_unit_or_no_values() {
+        assert_eq!(auto_scale("furlong", &[1.0]), None);
+        assert_eq!(auto_scale("ns", &[]), None);
+    }
+
+    #[test]
+    fn verify_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("30d").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("99999999999999999999h").is_err());
+    }
+
+    #[test]
+    fn verify_parse_period() {
+        assert_eq!(parse_period("7d").unwrap(), Duration::from_secs(7 * 86400));
+        assert_eq!(parse_period("2w").unwrap(), Duration::from_secs(14 * 86400));
+        assert_eq!(parse_period("12h").unwrap(), Duration::from_secs(12 * 3600));
+        assert!(parse_period("d").is_err());
+        assert!(parse_period("7").is_err());
+        for invalid in ["-5d", "infd", "NaNd", "-infw", "1e300w"] {
+            assert!(parse_period(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,37 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Add a batch of measurements from stdin
+cd_temp_repo
+git perf add --stdin -k runner=ci <<'EOF_INPUT'
+# Plain lines
+timer 1.5 os=linux
+timer 2
+
+{"name": "memory", "value": 1024, "key_values": {"os": "mac"}}
+EOF_INPUT
+output=$(git perf report -o -)
+[[ $(echo "$output" | tail -n +2 | wc -l) -eq 3 ]] || exit 1
+[[ ${output} == *$'\ttimer\t'*$'\t1.5\tlinux\tci'* ]] || exit 1
+[[ ${output} == *$'\tmemory\t'*$'\t1024.0\tmac\tci'* ]] || exit 1
+# All measurements in a single notes write
+[[ $(git log --format=%s refs/notes/perf-v3 | wc -l) -eq 1 ]] || exit 1
+
+echo Reject invalid input
+cd_temp_repo
+output=$(echo 'timer fast' | git perf add --stdin 2>&1) && exit 1
+[[ ${output} == *'Line 1'* ]] || exit 1
+echo 'timer 1 runner=local' | git perf add --stdin -k runner=ci && exit 1
+echo '' | git perf add --stdin && exit 1
+git perf add --stdin -m timer < /dev/null && exit 1
+git perf add --stdin 1 < /dev/null && exit 1
+# Nothing was added
+git perf report -o - && exit 1
+
+exit 0
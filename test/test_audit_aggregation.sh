@@ -0,0 +1,51 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+add_latencies() {
+  local base=$1
+  for i in $(seq 0 8); do
+    git perf add -m latency $((base + i))
+  done
+}
+
+cd_temp_repo
+for base in 10 11 12 10; do
+  create_commit
+  add_latencies "$base"
+  git perf add -m latency $((base + 9))
+done
+create_commit
+add_latencies 10
+git perf add -m latency 10000
+
+echo A single outlier breaks the mean
+git perf audit -m latency -a mean && exit 1
+
+echo Trimmed mean ignores the outlier
+git perf audit -m latency -a 'trimmed_mean(10)'
+git perf audit -m latency -a 'trimmed_mean(5)' && exit 1
+
+echo Aggregation configured per measurement
+echo '[measurement."latency"]
+aggregate_by = "trimmed_mean(10)"' > .gitperfconfig
+git perf audit -m latency
+git perf audit -m latency -a mean && exit 1
+echo '[measurement."*"]
+aggregate_by = "geometric_mean"' > .gitperfconfig
+git perf audit -m latency && exit 1
+
+echo Invalid aggregations are rejected
+output=$(git perf audit -m latency -a 'trimmed_mean(50)' 2>&1) && exit 1
+[[ ${output} == *'Invalid trim percentage'* ]] || exit 1
+echo '[measurement."latency"]
+aggregate_by = "geomean"' > .gitperfconfig
+output=$(git perf audit -m latency 2>&1) && exit 1
+[[ ${output} == *"Invalid aggregate_by for measurement 'latency'"* ]] || exit 1
+
+exit 0
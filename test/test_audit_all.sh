@@ -0,0 +1,51 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+git checkout HEAD~2
+git perf add -m timer 1 -k os=linux
+git perf add -m memory 100 -k os=mac
+git checkout master && git checkout HEAD~1
+git perf add -m timer 1.1 -k os=linux
+git perf add -m memory 101 -k os=mac
+git checkout master
+git perf add -m timer 1.05 -k os=linux
+git perf add -m memory 100.5 -k os=mac
+git perf add -m binary_size 1 -k os=linux
+
+echo Audit every measurement of HEAD
+output=$(git perf audit --all 2>&1)
+[[ ${output} == *"memory: "* ]] || exit 1
+[[ ${output} == *"timer: "* ]] || exit 1
+[[ ${output} == *"Audited 3 measurements"* ]] || exit 1
+
+echo Only measurements matching the selectors
+output=$(git perf audit --all -s os=mac 2>&1)
+[[ ${output} == *"memory: "* ]] || exit 1
+[[ ${output} != *"timer: "* ]] || exit 1
+
+echo Measurements only on older commits are not audited
+create_commit
+git perf add -m timer 1.02 -k os=linux
+output=$(git perf audit --all 2>&1)
+[[ ${output} != *"memory: "* ]] || exit 1
+[[ ${output} != *"binary_size: "* ]] || exit 1
+
+echo No measurements on HEAD
+create_commit
+rc=0
+git perf audit --all || rc=$?
+[[ ${rc} -eq 2 ]] || exit 1
+
+echo Mutually exclusive with explicit measurements
+rc=0
+git perf audit --all -m timer || rc=$?
+[[ ${rc} -eq 3 ]] || exit 1
+
+exit 0
@@ -0,0 +1,39 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Audit a feature branch against its target branch
+cd_temp_repo
+git perf add -m timer 10
+create_commit
+git perf add -m timer 11
+create_commit
+git perf add -m timer 10
+git checkout -b feature HEAD~2
+create_commit
+git perf add -m timer 100
+create_commit
+git perf add -m timer 12
+# Against its own history, the earlier outlier hides the regression
+git perf audit -m timer -d 2
+output=$(git perf audit -m timer -d 2 --baseline master 2>&1) && exit 1
+if [[ ${output} != *'differs significantly'* ]]; then
+  echo "Missing failure against baseline in output:"
+  echo "$output"
+  exit 1
+fi
+git perf add -m timer 1
+git perf audit -m timer -d 4 --baseline master && exit 1
+
+echo Audit the baseline itself
+git checkout master
+git perf audit -m timer -d 4 --baseline master
+
+output=$(git perf audit -m timer --baseline does-not-exist 2>&1) && exit 1
+
+exit 0
@@ -0,0 +1,39 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Warn about a step change within the tail measurements
+cd_empty_repo
+for value in 10 11 10 9 10 20 21 19 20 21; do
+  create_commit
+  git perf add -m timer "$value"
+done
+create_commit
+git perf add -m timer 15
+output=$(git perf audit -m timer 2>&1)
+if [[ ${output} != *'step change'* ]]; then
+  echo "Missing step change warning in output:"
+  echo "$output"
+  exit 1
+fi
+git perf audit -m timer --fail-on-change-point && exit 1
+git perf audit -m timer --fail-on warn && exit 1
+git perf audit -m timer --output-format json | grep -q '"change_point": {'
+
+echo No warning for a stable tail
+cd_empty_repo
+for value in 10 11 10 9 10 11 9 10; do
+  create_commit
+  git perf add -m timer "$value"
+done
+create_commit
+git perf add -m timer 10
+output=$(git perf audit -m timer --fail-on-change-point 2>&1)
+[[ ${output} != *'step change'* ]] || exit 1
+
+exit 0
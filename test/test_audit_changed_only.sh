@@ -0,0 +1,39 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_empty_repo
+cat > .gitperfconfig <<'TOML'
+[audit.paths]
+"bench::parser::.*" = ["src/parser/**"]
+TOML
+git add .gitperfconfig
+git commit -m 'config'
+mkdir -p src/parser src/lexer
+echo content > src/parser/mod.rs
+git add src
+git commit -m 'parser'
+git perf add -m bench::parser::parse 1
+git perf add -m bench::lexer::lex 1
+echo content > src/lexer/mod.rs
+git add src
+git commit -m 'lexer'
+git perf add -m bench::parser::parse 1000
+git perf add -m bench::lexer::lex 1000
+
+echo Mapped measurement without changes in its paths is skipped
+output=$(git perf audit -m bench::parser::parse --changed-only --min-measurements 1 2>&1)
+[[ ${output} == *'Skipping test'* ]] || exit 1
+
+echo Without the flag the audit runs
+git perf audit -m bench::parser::parse --min-measurements 1 && exit 1
+
+echo Unmapped measurements are always audited
+git perf audit -m bench::lexer::lex --changed-only --min-measurements 1 && exit 1
+
+exit 0
@@ -0,0 +1,33 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Audit an older commit against its ancestors
+cd_temp_repo
+git checkout HEAD~3
+git perf add -m timer 1
+git checkout master && git checkout HEAD~2
+git perf add -m timer 2
+git checkout master && git checkout HEAD~1
+git perf add -m timer 30
+git checkout master
+git perf add -m timer 2
+# HEAD passes with the outlier in its history
+git perf audit -m timer -d 4
+output=$(git perf audit -m timer -d 4 --commit HEAD~1 2>&1) && exit 1
+if [[ ${output} != *'HEAD~1 differs significantly'* ]]; then
+  echo "Missing failure for HEAD~1 in output:"
+  echo "$output"
+  exit 1
+fi
+# Oldest commit has no history and is skipped
+git perf audit -m timer -d 4 --commit HEAD~3
+
+output=$(git perf audit -m timer --commit does-not-exist 2>&1) && exit 1
+
+exit 0
@@ -0,0 +1,50 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+function audit_exit_code() {
+  local rc=0
+  git perf audit "$@" || rc=$?
+  echo "$rc"
+}
+
+cd_temp_repo
+git checkout HEAD~2
+git perf add -m timer 1
+git checkout master && git checkout HEAD~1
+git perf add -m timer 2
+git checkout master
+git perf add -m timer 3
+
+echo Pass
+[[ $(audit_exit_code -m timer) -eq 0 ]] || exit 1
+
+echo Regression
+[[ $(audit_exit_code -m timer -d 1) -eq 1 ]] || exit 1
+
+echo Missing measurement of HEAD
+[[ $(audit_exit_code -m other) -eq 2 ]] || exit 1
+
+echo Skipped for lack of data only fails with --strict
+create_commit
+git perf add -m rare 1
+[[ $(audit_exit_code -m rare) -eq 0 ]] || exit 1
+[[ $(audit_exit_code -m rare --strict) -eq 2 ]] || exit 1
+
+echo Invalid arguments and configuration
+[[ $(audit_exit_code) -eq 3 ]] || exit 1
+[[ $(audit_exit_code -m timer -n 2 --min-measurements 3) -eq 3 ]] || exit 1
+[[ $(audit_exit_code -m timer --baseline does-not-exist) -eq 3 ]] || exit 1
+git perf audit --help > /dev/null
+
+echo Other commands keep the usual exit codes
+rc=0
+git perf report -o - --does-not-exist || rc=$?
+[[ ${rc} -eq 2 ]] || exit 1
+
+exit 0
@@ -0,0 +1,54 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Audit several measurements with a failure budget
+cd_temp_repo
+for i in 3 2 1; do
+  git checkout "HEAD~$i"
+  git perf add -m stable 10
+  git perf add -m flaky "1$i"
+  git perf add -m broken 10
+  git checkout master
+done
+git perf add -m stable 10
+git perf add -m flaky 30
+git perf add -m broken 50
+
+output=$(git perf audit -m stable -m flaky 2>&1) && exit 1
+[[ ${output} == *'flaky: HEAD differs significantly'* ]] || exit 1
+[[ ${output} == *'Audited 2 measurements: 1 passed, 1 failed, 0 skipped'* ]] || exit 1
+
+output=$(git perf audit -m stable -m flaky --max-failures 1 2>&1)
+[[ ${output} == *'flaky: HEAD differs significantly'* ]] || exit 1
+[[ ${output} == *'Tolerating 1 failed measurements'* ]] || exit 1
+git perf audit -m stable -m flaky -m broken --max-failures 1 && exit 1
+
+echo Failure budget from config
+echo '[audit]
+max_failures = 2' > .gitperfconfig
+git perf audit -m stable -m flaky -m broken
+rm .gitperfconfig
+
+echo JSON output of several measurements is an array
+output=$(git perf audit -m stable -m flaky --max-failures 1 --output-format json)
+[[ ${output} == '['*'"measurement": "stable"'*'"measurement": "flaky"'*']' ]] || exit 1
+
+echo Fail on warnings
+git perf add -m new 1
+git perf audit -m stable -m new
+output=$(git perf audit -m stable -m new --fail-on warn 2>&1) && exit 1
+[[ ${output} == *'1 audits finished with warnings'* ]] || exit 1
+
+echo Warnings count against the failure budget with --fail-on warn
+output=$(git perf audit -m stable -m new --fail-on warn --max-failures 1 2>&1)
+[[ ${output} == *'Tolerating 1 measurements with warnings or failures within the budget of 1.'* ]] || exit 1
+git perf audit -m stable -m new -m flaky --fail-on warn --max-failures 1 && exit 1
+git perf audit -m stable -m new -m flaky --fail-on warn --max-failures 2
+
+exit 0
@@ -0,0 +1,32 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+git checkout HEAD~2
+git perf add -m timer 1
+git checkout master && git checkout HEAD~1
+git perf add -m timer 1.1
+git checkout master
+git perf add -m timer 5
+
+summary=$(mktemp)
+
+echo Without GitHub Actions, no summary is written
+output=$(GITHUB_STEP_SUMMARY='' git perf audit -m timer --gh-summary 2>/dev/null) && exit 1
+[[ -z ${output} ]] || exit 1
+
+echo Failures are appended to the step summary and annotated
+output=$(GITHUB_STEP_SUMMARY=$summary git perf audit -m timer --gh-summary 2>/dev/null) && exit 1
+[[ ${output} == *"::error title=git perf audit::timer: HEAD differs significantly"* ]] || exit 1
+grep -q '^### git perf audit of HEAD$' "$summary"
+grep -q '^| timer | \*\*fail\*\* | 5.000 |' "$summary"
+
+echo Later audits append to the summary
+GITHUB_STEP_SUMMARY=$summary git perf audit -m timer --gh-summary -d 100 >/dev/null 2>&1
+[[ $(grep -c '^### git perf audit' "$summary") -eq 2 ]] || exit 1
@@ -52,4 +52,21 @@ git perf add -m timer 3
 git perf audit -m timer --min-measurements 1 && exit 1
 git perf audit -m timer --min-measurements 2
 
+echo Report coverage and skip audits with too few tail commits with data
+cd_temp_repo
+git checkout HEAD~2
+git perf add -m timer 3
+git checkout master && git checkout HEAD~3
+git perf add -m timer 3.5
+git checkout master
+git perf add -m timer 3.2
+output=$(git perf audit -m timer 2>&1)
+[[ ${output} == *'Coverage: 2/3 commits had data'* ]] || exit 1
+output=$(git perf audit -m timer --min-coverage 0.9 2>&1)
+[[ ${output} == *'Only 2/3 commits had data. Less than requested min_coverage of 0.9'* ]] || exit 1
+output=$(git perf audit -m timer --min-coverage 0.9 --output-format json)
+[[ ${output} == *'"status": "low_coverage"'* ]] || exit 1
+[[ ${output} == *'"with_data": 2'* ]] || exit 1
+git perf audit -m timer --min-coverage 1.1 && exit 1
+
 exit 0
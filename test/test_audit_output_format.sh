@@ -0,0 +1,58 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo JSON output for passing and failing audits
+cd_temp_repo
+git checkout HEAD~3
+git perf add -m timer 1
+git checkout master && git checkout HEAD~2
+git perf add -m timer 2
+git checkout master && git checkout HEAD~1
+git perf add -m timer 3
+git checkout master
+git perf add -m timer 4
+output=$(git perf audit -m timer -d 4 --output-format json)
+[[ ${output} == *'"status": "pass"'* ]] || exit 1
+[[ ${output} == *'"measurement": "timer"'* ]] || exit 1
+[[ ${output} == *'"z_score": 2.0'* ]] || exit 1
+output=$(git perf audit -m timer -d 1 --output-format json) && exit 1
+[[ ${output} == *'"status": "fail"'* ]] || exit 1
+
+echo Markdown output with inline trend images
+output=$(git perf audit -m timer -d 4 --output-format markdown)
+[[ ${output} == *'| Measurement | Status | HEAD | Tail | z-score | Trend |'* ]] || exit 1
+[[ ${output} == *'| timer | pass | 4.000 |'*'](data:image/svg+xml,%3Csvg'* ]] || exit 1
+output=$(git perf audit -m timer -d 1 --output-format markdown) && exit 1
+[[ ${output} == *'| timer | **fail** |'*'%23c62828'* ]] || exit 1
+
+echo Scaling of the trend images
+[[ $(git perf audit -m timer -d 4 --output-format markdown) == *'![▁▃▆█]('* ]] || exit 1
+output=$(git perf audit -m timer -d 4 --output-format markdown --sparkline-width 3)
+[[ ${output} == *'![▁▅█]('* ]] || exit 1
+output=$(git perf audit -m timer -d 4 --output-format markdown --sparkline-scale log)
+[[ ${output} == *'![▁▅▇█]('* ]] || exit 1
+output=$(git perf audit -m timer -d 4 --output-format markdown --sparkline-clamp 0,50)
+[[ ${output} == *'![▁▆██]('* ]] || exit 1
+echo '[sparkline]
+width = 3' > .gitperfconfig
+[[ $(git perf audit -m timer -d 4 --output-format markdown) == *'![▁▅█]('* ]] || exit 1
+output=$(git perf audit -m timer -d 4 --output-format markdown --sparkline-width 4)
+[[ ${output} == *'![▁▃▆█]('* ]] || exit 1
+output=$(git perf audit -m timer --sparkline-clamp 50,5 2>&1) && exit 1
+[[ ${output} == *'Invalid sparkline clamp range'* ]] || exit 1
+rm .gitperfconfig
+
+echo JSON output for skipped audits
+cd_temp_repo
+git perf add -m timer 4
+output=$(git perf audit -m timer --output-format json)
+[[ ${output} == *'"status": "skip"'* ]] || exit 1
+[[ ${output} == *'"skip_reason": "Only 0 measurement found.'* ]] || exit 1
+
+exit 0
@@ -0,0 +1,60 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+function add_history() {
+  cd_empty_repo
+  create_commit
+  git perf add -m timer 100
+  create_commit
+  git perf add -m timer 102
+  create_commit
+  git perf add -m timer 98
+}
+
+echo Deviation without declared expectation fails
+add_history
+create_commit
+git perf add -m timer 108
+git perf audit -m timer -d 2 && exit 1
+
+echo Deviation within declared expectation is accepted
+add_history
+git commit --allow-empty -m 'Slower but safer' --trailer 'Perf-Expect: timer +10%'
+git perf add -m timer 108
+output=$(git perf audit -m timer -d 2 2>&1)
+if [[ ${output} != *"accepted by 'Perf-Expect: timer +10%'"* ]]; then
+  echo "Missing accepted allowance in output:"
+  echo "$output"
+  exit 1
+fi
+output=$(git perf audit -m timer -d 2 --output-format json)
+[[ ${output} == *'"accepted_by": "Perf-Expect: timer +10%"'* ]] || exit 1
+
+echo Deviation beyond or against declared expectation fails
+add_history
+git commit --allow-empty -m 'Slower' --trailer 'Perf-Expect: timer +5%'
+git perf add -m timer 108
+git perf audit -m timer -d 2 && exit 1
+add_history
+git commit --allow-empty -m 'Faster' --trailer 'Perf-Expect: timer -10%'
+git perf add -m timer 108
+git perf audit -m timer -d 2 && exit 1
+
+echo Invalid trailers are reported
+add_history
+git commit --allow-empty -m 'Slower' --trailer 'Perf-Expect: timer 10'
+git perf add -m timer 108
+output=$(git perf audit -m timer -d 2 2>&1) && exit 1
+if [[ ${output} != *'Invalid Perf-Expect trailer'* ]]; then
+  echo "Missing invalid trailer error in output:"
+  echo "$output"
+  exit 1
+fi
+
+exit 0
@@ -37,4 +37,26 @@ git perf audit -m timer -s myselector=test && exit 1
 git perf add -m timer 4 -k myselector=test
 git perf audit -m timer -s myselector=test
 
+echo Negated and regex selectors
+cd_temp_repo
+for i in 3 2 1; do
+  git checkout HEAD~$i
+  git perf add -m timer "1$i" -k os=linux
+  git perf add -m timer "5$i" -k os=windows
+  git checkout master
+done
+git perf add -m timer 12 -k os=linux
+git perf add -m timer 1000 -k os=windows
+git perf audit -m timer -s os!=windows
+git perf audit -m timer -s os=~^lin
+git perf audit -m timer -s os!=linux && exit 1
+git perf audit -m timer -s 'os=~^(mac|win)' && exit 1
+[[ $(git perf report -o - -m timer -k os!=windows | tail -n +2 | wc -l) -eq 4 ]] || exit 1
+[[ $(git perf report -o - -m timer -k 'os=~^(lin|win)' | tail -n +2 | wc -l) -eq 8 ]] || exit 1
+
+echo Invalid selectors are rejected
+output=$(git perf audit -m timer -s 'os=~(' 2>&1) && exit 1
+[[ ${output} == *"invalid regex in selector 'os=~('"* ]] || exit 1
+git perf audit -m timer -s os && exit 1
+
 exit 0
@@ -0,0 +1,42 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Audit each group of measurements separately
+cd_temp_repo
+for i in 3 2 1; do
+  git checkout "HEAD~$i"
+  git perf add -m timer "1$i" -k os=linux
+  git perf add -m timer "5$i" -k os=mac
+  git checkout master
+done
+git perf add -m timer 30 -k os=linux
+git perf add -m timer 52 -k os=mac
+
+# Together, the regression of linux is hidden by the larger values of mac
+git perf audit -m timer -a max
+output=$(git perf audit -m timer -a max --separate-by os 2>&1) && exit 1
+[[ ${output} == *'timer (os=linux): HEAD differs significantly from tail measurements.'* ]] || exit 1
+[[ ${output} != *'timer (os=mac):'* ]] || exit 1
+[[ ${output} == *'Audited 2 measurements: 1 passed, 1 failed, 0 skipped, 0 with warnings'* ]] || exit 1
+
+echo One result per group
+output=$(git perf audit -m timer -a max --separate-by os --output-format json) && exit 1
+[[ ${output} == *'"measurement": "timer (os=linux)"'*'"status": "fail"'*'"measurement": "timer (os=mac)"'*'"status": "pass"'* ]] || exit 1
+
+echo Failures of groups count against the failure budget
+git perf audit -m timer -a max --separate-by os --max-failures 1
+
+echo Groups combined with selectors
+git perf audit -m timer -a max --separate-by os -s os=mac
+
+echo Multiple keys
+output=$(git perf audit -m timer -a max --separate-by os,arch 2>&1) && exit 1
+[[ ${output} == *'No measurements of timer for HEAD with values for os, arch.'* ]] || exit 1
+
+exit 0
@@ -0,0 +1,60 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Sigma per selector from config
+cd_temp_repo
+for i in 3 2 1; do
+  git checkout "HEAD~$i"
+  git perf add -m timer "$((13 - i))" -k os=mac
+  git perf add -m timer "$((13 - i))" -k os=linux
+  git checkout master
+done
+git perf add -m timer 16 -k os=mac
+git perf add -m timer 16 -k os=linux
+
+git perf audit -m timer -s os=mac && exit 1
+git perf audit -m timer -s os=linux && exit 1
+
+echo '[measurement."timer".selector."os=mac"]
+sigma = 6.0' > .gitperfconfig
+git perf audit -m timer -s os=mac
+git perf audit -m timer -s os=linux && exit 1
+
+echo Overrides for all measurements apply when the measurement has none
+echo '[measurement."*".selector."os=linux"]
+sigma = 6.0' > .gitperfconfig
+git perf audit -m timer -s os=linux
+git perf audit -m timer -s os=mac && exit 1
+
+echo Sigma on the command line takes precedence
+git perf audit -m timer -s os=linux -d 4 && exit 1
+
+echo Warning sigma must be less than the sigma of the selector
+output=$(git perf audit -m timer -s os=linux --warn-sigma 5 2>&1)
+[[ ${output} != *'must be less than'* ]] || exit 1
+output=$(git perf audit -m timer -s os=mac --warn-sigma 5 2>&1) && exit 1
+[[ ${output} == *'The warning sigma (5) must be less than the sigma (4)'* ]] || exit 1
+
+echo Sigma per measurement applies without a matching selector override
+rm .gitperfconfig
+git perf config set measurement.timer.sigma 6.0
+git perf config check
+git perf audit -m timer -s os=linux
+echo '[measurement."timer".selector."os=mac"]
+sigma = 3.0' >> .gitperfconfig
+git perf audit -m timer -s os=mac && exit 1
+git perf audit -m timer -s os=linux
+
+echo Invalid selector overrides are reported
+echo '[measurement."timer".selector."os"]
+sigma = 6.0' > .gitperfconfig
+output=$(git perf audit -m timer -s os=mac 2>&1) && exit 1
+[[ ${output} == *"Invalid selector 'os' of measurement 'timer'"* ]] || exit 1
+
+exit 0
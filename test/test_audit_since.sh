@@ -0,0 +1,45 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+commit_at() {
+  local days_ago=$1
+  GIT_COMMITTER_DATE="$(( $(date +%s) - days_ago * 86400 )) +0000" create_commit
+}
+
+cd_empty_repo
+echo Noisy old history
+for val in 5 40 10 35; do
+  commit_at 60
+  git perf add -m timer "$val"
+done
+echo Stable recent history
+for val in 20 20.1 20.2; do
+  commit_at 2
+  git perf add -m timer "$val"
+done
+commit_at 0
+git perf add -m timer 25
+
+echo The commit count reaches back into the noisy history
+git perf audit -m timer
+
+echo The period only covers the recent history
+git perf audit -m timer --since 30d && exit 1
+
+echo Periods older than the history cover all commits
+git perf audit -m timer --since 90d
+
+echo Periods without further commits skip the audit
+git perf audit -m timer --since 1d
+
+echo Count and period are alternatives
+git perf audit -m timer --since 30d -n 10 && exit 1
+git perf audit -m timer --since 30 && exit 1
+
+exit 0
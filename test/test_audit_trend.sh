@@ -0,0 +1,39 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Sustained drift passes the z-score check
+cd_temp_repo
+for i in 4 3 2 1; do
+  create_commit
+done
+value=100
+for i in 7 6 5 4 3 2 1; do
+  git checkout "HEAD~$i"
+  git perf add -m timer "$value"
+  git checkout master
+  value=$((value + 2))
+done
+git perf add -m timer "$value"
+git perf audit -m timer -d 10
+
+echo Fail on a trend steeper than the configured maximum
+echo '[measurement."timer"]
+max_trend_pct_per_commit = 1.5' > .gitperfconfig
+output=$(git perf audit -m timer -d 10 2>&1) && exit 1
+[[ ${output} == *'timer: HEAD continues a drift of 1.87% per commit, more than the max_trend_pct_per_commit of 1.5%.'* ]] || exit 1
+[[ ${output} != *'Head: '* ]] || exit 1
+output=$(git perf audit -m timer -d 10 --output-format json) && exit 1
+[[ ${output} == *'"trend": {'*'"max_pct_per_commit": 1.5'* ]] || exit 1
+
+echo Pass with a trend within the configured maximum
+echo '[measurement."*"]
+max_trend_pct_per_commit = 2' > .gitperfconfig
+git perf audit -m timer -d 10
+
+exit 0
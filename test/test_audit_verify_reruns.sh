@@ -0,0 +1,45 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Verify a failed audit by rerunning the measurement
+cd_empty_repo
+for value in 1000000 2000000 3000000 1500000; do
+  create_commit
+  git perf add -m timer "$value"
+done
+create_commit
+git perf add -m timer 1000000000000
+
+output=$(git perf audit -m timer --verify-reruns 2 2>&1) && exit 1
+[[ ${output} == *'No command configured'* ]] || exit 1
+
+echo '[measurement."timer"]
+command = ["echo", "1500000"]
+extract_regex = "[0-9]+"' > .gitperfconfig
+output=$(git perf audit -m timer --verify-reruns 2 2>&1)
+[[ ${output} == *'Passed after 1 verification reruns'* ]] || exit 1
+output=$(git perf report -o - -m timer -k rerun=1 | tail -n +2)
+[[ $(echo "$output" | wc -l) -eq 1 ]] || exit 1
+[[ ${output} == *1500000* ]] || exit 1
+
+echo Failure persists after reruns
+create_commit
+git perf add -m timer 1000000000000
+echo '[measurement."timer"]
+command = ["sleep", "1"]' > .gitperfconfig
+output=$(git perf audit -m timer --verify-reruns 2 2>&1) && exit 1
+[[ ${output} == *'Still failing after 2 verification reruns'* ]] || exit 1
+[[ $(git perf report -o - -m timer -n 1 | tail -n +2 | wc -l) -eq 3 ]] || exit 1
+
+echo Reruns only for HEAD
+create_commit
+output=$(git perf audit -m timer --verify-reruns 1 --commit HEAD~1 2>&1) && exit 1
+[[ ${output} == *'Only failures of HEAD'* ]] || exit 1
+
+exit 0
@@ -0,0 +1,46 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Warn about deviations between the warning sigma and the sigma
+cd_temp_repo
+for i in 3 2 1; do
+  git checkout "HEAD~$i"
+  git perf add -m timer "$((12 - i))"
+  git checkout master
+done
+git perf add -m timer 13
+
+git perf audit -m timer
+output=$(git perf audit -m timer --warn-sigma 2 2>&1)
+[[ ${output} == *'⚠️ timer: HEAD deviates from tail measurements by more than the warning threshold of 2 sigma.'* ]] || exit 1
+output=$(git perf audit -m timer --warn-sigma 3.5 2>&1)
+[[ ${output} != *'⚠️'* ]] || exit 1
+
+echo Warnings are part of the JSON and markdown output
+output=$(git perf audit -m timer --warn-sigma 2 --output-format json)
+[[ ${output} == *'"warn_sigma": 2.0'*'"status": "warn"'* ]] || exit 1
+output=$(git perf audit -m timer --warn-sigma 2 --output-format markdown)
+[[ ${output} == *'| timer | ⚠️ warn | 13.000 |'* ]] || exit 1
+
+echo Warnings only fail with --fail-on warn
+output=$(git perf audit -m timer --warn-sigma 2 --fail-on warn 2>&1) && exit 1
+[[ ${output} == *'1 audits finished with warnings'* ]] || exit 1
+
+echo Warning sigma from config
+echo '[audit]
+warn_sigma = 2' > .gitperfconfig
+output=$(git perf audit -m timer --output-format json)
+[[ ${output} == *'"status": "warn"'* ]] || exit 1
+rm .gitperfconfig
+
+echo Warning sigma must be less than the sigma
+output=$(git perf audit -m timer --warn-sigma 4 2>&1) && exit 1
+[[ ${output} == *'The warning sigma (4) must be less than the sigma (4)'* ]] || exit 1
+
+exit 0
@@ -0,0 +1,50 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+git checkout HEAD~2
+git perf add -m timer 10 -k os=linux
+git perf add -m timer 12 -k os=linux
+git perf add -m memory 100 -k os=linux
+git tag v1.0.0
+git checkout master
+
+echo No baseline pinned yet
+output=$(git perf audit -m timer --against-baseline 2>&1) && exit 1
+[[ ${output} == *"No baseline pinned"* ]] || exit 1
+
+echo Pin the minimum of every measurement of the tag
+git perf baseline set v1.0.0
+grep -q '^ref = "v1.0.0"$' .gitperfconfig
+output=$(git perf baseline show)
+[[ ${output} == *"timer 10"* ]] || exit 1
+[[ ${output} == *"memory 100"* ]] || exit 1
+
+echo Within the default maximum regression of 5%
+git perf add -m timer 10.4 -k os=linux
+git perf add -m memory 90 -k os=linux
+git perf audit -m timer -m memory --against-baseline
+
+echo Regressions beyond the maximum fail
+git perf audit -m timer --against-baseline --max-regression 3 && exit 1
+git perf audit -m timer --against-baseline --max-regression 10
+echo '[measurement."timer"]
+max_regression_pct = 1' >> .gitperfconfig
+output=$(git perf audit --all --against-baseline 2>&1) && exit 1
+[[ ${output} == *"timer: HEAD regressed by 4.00% against the baseline v1.0.0 (10.000 -> 10.400), more than the allowed 1%."* ]] || exit 1
+[[ ${output} == *"memory: -10.00% against the baseline v1.0.0"* ]] || exit 1
+
+echo Measurements missing from the baseline or HEAD
+git perf add -m binary_size 1
+output=$(git perf audit -m binary_size --against-baseline 2>&1) && exit 1
+[[ ${output} == *"No value of 'binary_size' pinned in the baseline v1.0.0"* ]] || exit 1
+create_commit
+[[ $(git perf audit -m timer --against-baseline >/dev/null 2>&1; echo $?) -eq 2 ]] || exit 1
+
+exit 0
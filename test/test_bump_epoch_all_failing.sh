@@ -0,0 +1,49 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_empty_repo
+create_commit
+git perf add -m stable 10
+git perf add -m regressed 2
+git perf add -m lonely 1
+create_commit
+git perf add -m stable 11
+git perf add -m regressed 3
+create_commit
+git perf add -m stable 10
+git perf add -m regressed 5000
+git perf add -m lonely 1000
+
+echo Either a measurement or all failing measurements
+git perf bump-epoch && exit 1
+git perf bump-epoch -m stable --all-failing && exit 1
+
+echo Only the failing measurement is bumped
+output=$(git perf bump-epoch --all-failing --reason "switched allocator")
+[[ ${output} == regressed ]] || exit 1
+[[ $(git perf config get 'measurement."regressed".epoch') == $(git rev-parse --short=8 HEAD) ]] || exit 1
+git perf config get 'measurement."stable".epoch' && exit 1
+git perf config get 'measurement."lonely".epoch' && exit 1
+epoch=$(git perf config get 'measurement."regressed".epoch')
+[[ $(git perf config get "measurement.\"regressed\".epoch_reasons.\"$epoch\"") == 'switched allocator' ]] || exit 1
+git perf config check
+
+echo The reason is shown in the report
+git add .gitperfconfig
+git commit --amend --no-edit
+git perf add -m regressed 5000
+git perf audit -m regressed
+git perf report -o result.html
+grep -q "epoch $epoch: switched allocator" result.html || exit 1
+
+echo Nothing to bump
+output=$(git perf bump-epoch --all-failing 2>&1)
+[[ ${output} == *'Bumped the epochs of 0 failing measurements'* ]] || exit 1
+
+exit 0
@@ -0,0 +1,46 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+count_measurements() {
+  git perf report -o - "$@" | tail -n +2 | wc -l
+}
+
+cd_temp_repo
+git perf add -m timer 1 -k os=linux
+git perf add -m timer 2 -k os=mac
+git checkout HEAD~1
+git perf add -m timer 3 -k os=linux
+git checkout master
+uncached=$(git perf report -o - | sort)
+cache=.git/git-perf/cache/measurements.json
+
+echo The cache is only used if enabled
+git perf report -o - > /dev/null
+[[ -e $cache ]] && exit 1
+echo '[storage]
+cache = true' > .gitperfconfig
+
+echo Reads through the cache match the notes
+[[ $(git perf report -o - | sort) == "$uncached" ]] || exit 1
+[[ -e $cache ]] || exit 1
+[[ $(git perf report -o - | sort) == "$uncached" ]] || exit 1
+git perf audit -m timer -s os=linux
+
+echo New measurements invalidate the cache
+git perf add -m timer 4 -k os=linux
+[[ $(count_measurements) -eq 4 ]] || exit 1
+git perf remove -m timer --commit HEAD~1
+[[ $(count_measurements) -eq 3 ]] || exit 1
+
+echo Broken caches are rebuilt
+echo 'garbage' > $cache
+[[ $(count_measurements) -eq 3 ]] || exit 1
+grep -q '"key"' $cache
+
+exit 0
@@ -0,0 +1,56 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+unset CI RUNNER_ENVIRONMENT CI_RUNNER_TAGS ImageOS
+
+note() {
+  git notes --ref refs/notes/perf-v3 show HEAD | grep "$1"
+}
+
+cd_temp_repo
+echo No environment without the flag
+git perf add -m plain 1
+note plain | grep -q 'cores=' && exit 1
+
+echo Capture the environment of a local machine
+git perf add -m local 1 --capture-env
+output=$(note local)
+[[ ${output} == *'host_kind=local'* ]] || exit 1
+[[ ${output} == *'os='* ]] || exit 1
+[[ ${output} == *'arch='* ]] || exit 1
+[[ ${output} == *"cores="* ]] || exit 1
+[[ ${output} == *'runner='* ]] && exit 1
+
+echo Explicit key-values take precedence
+git perf add -m explicit 1 --capture-env -k host_kind=bench
+[[ $(note explicit) == *'host_kind=bench'* ]] || exit 1
+[[ $(note explicit) == *'host_kind=local'* ]] && exit 1
+echo 'batch 1 os=custom' | git perf add --stdin --capture-env
+[[ $(note batch) == *'os=custom'* ]] || exit 1
+[[ $(note batch) == *'host_kind=local'* ]] || exit 1
+
+echo CI runners
+CI=true CI_RUNNER_TAGS='["linux", "docker"]' git perf add -m gitlab 1 --capture-env
+[[ $(note gitlab) == *'host_kind=ci'* ]] || exit 1
+[[ $(note gitlab) == *'runner=docker+linux'* ]] || exit 1
+CI=true RUNNER_ENVIRONMENT=github-hosted ImageOS=ubuntu22 git perf add -m github 1 --capture-env
+[[ $(note github) == *'host_kind=github-hosted'* ]] || exit 1
+[[ $(note github) == *'runner=ubuntu22'* ]] || exit 1
+
+echo Capture configured for all measurements
+echo '[measure]
+capture_env = true' > .gitperfconfig
+git perf measure -m configured -- true
+[[ $(note configured) == *'host_kind=local'* ]] || exit 1
+
+echo Separate and select by the captured values
+git perf report -o - -m local -s host_kind | grep -q 'host_kind' || exit 1
+git perf audit -m local -s host_kind=local
+
+exit 0
@@ -0,0 +1,42 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+mkdir -p sort/src other/src
+printf '[workspace]\nmembers = ["sort"]\n' > Cargo.toml
+printf '[package]\nname = "sort"\nversion = "0.1.0"\n' > sort/Cargo.toml
+touch sort/src/lib.rs
+printf '[package]\nname = "other"\nversion = "0.1.0"\n[workspace]\n' > other/Cargo.toml
+touch other/src/lib.rs
+git perf add -m timer 1
+
+echo Forward cargo perf to git perf
+cargo perf report -o report.csv
+[[ $(tail -n +2 report.csv | wc -l) -eq 1 ]] || exit 1
+
+echo Run from the workspace root in a member directory
+rm report.csv
+cd sort
+cargo perf report -o report.csv
+[[ -f ../report.csv ]] || exit 1
+[[ ! -f report.csv ]] || exit 1
+cd ..
+
+echo Run from the workspace root of --manifest-path
+cargo perf --manifest-path other/Cargo.toml report -o report.csv
+[[ -f other/report.csv ]] || exit 1
+cargo perf report --manifest-path=missing/Cargo.toml -o report.csv && exit 1
+
+echo Keep the directory outside of cargo workspaces
+cd_temp_repo
+git perf add -m timer 1
+cargo perf report -o report.csv
+[[ -f report.csv ]] || exit 1
+
+exit 0
@@ -0,0 +1,44 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Compare two branches with a significant difference
+cd_temp_repo
+git checkout -b feature
+create_commit
+git perf add -m timer 10
+create_commit
+git perf add -m timer 11
+git checkout master
+git perf add -m timer 1
+git checkout HEAD~1
+git perf add -m timer 2
+git checkout master
+output=$(git perf compare master feature -m timer -n 2 -d 4)
+[[ ${output} == *"significant"* ]] || exit 1
+[[ ${output} == *"not significant"* ]] && exit 1
+
+echo Compare without any variance
+git checkout -b constant-base master
+git perf add -m constant 5
+git checkout -b constant-head feature
+git perf add -m constant 7
+output=$(git perf compare constant-base constant-head -m constant -n 1)
+[[ ${output} == *"no variance to test significance"* ]] || exit 1
+[[ ${output} == *"inf"* ]] && exit 1
+[[ ${output} == *"NaN"* ]] && exit 1
+git checkout master
+
+echo Compare refs without measurements
+output=$(git perf compare master feature -m other)
+[[ ${output} == *"Not enough measurements"* ]] || exit 1
+
+echo Compare requires a measurement
+git perf compare master feature && exit 1
+
+exit 0
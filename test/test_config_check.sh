@@ -0,0 +1,48 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+
+echo Without configuration
+output=$(git perf config check 2>&1)
+[[ ${output} == *'ok    No .gitperfconfig, using the defaults'* ]] || exit 1
+
+echo Valid configuration
+echo '[storage]
+format = "v4"
+[measurement."build"]
+unit = "ms"' > .gitperfconfig
+output=$(git perf config check 2>&1)
+[[ ${output} == *'ok    .gitperfconfig is valid'* ]] || exit 1
+
+echo Typos and invalid values
+echo '[storag]
+format = "v4"
+[measurement."build"]
+uint = "ms"
+aggregate_by = "medain"' > .gitperfconfig
+output=$(git perf config check 2>&1) && exit 1
+[[ ${output} == *'FAIL  Unknown section [storag]'* ]] || exit 1
+[[ ${output} == *"fix: Did you mean 'storage'?"* ]] || exit 1
+[[ ${output} == *'FAIL  Unknown setting measurement."build".uint'* ]] || exit 1
+[[ ${output} == *"fix: Did you mean 'unit'?"* ]] || exit 1
+[[ ${output} == *'FAIL  Invalid measurement."build".aggregate_by'* ]] || exit 1
+[[ ${output} == *"fix: Did you mean 'median'?"* ]] || exit 1
+[[ ${output} == *'3 of 3 checks failed'* ]] || exit 1
+
+echo Redundant overrides only warn
+echo '[measurement."*"]
+unit = "ms"
+[measurement."build"]
+unit = "ms"' > .gitperfconfig
+output=$(git perf config check 2>&1)
+[[ ${output} == *'warn  measurement."build".unit repeats the default of measurement."*"'* ]] || exit 1
+[[ ${output} == *'fix: Remove the override'* ]] || exit 1
+
+exit 0
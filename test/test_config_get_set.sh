@@ -0,0 +1,39 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+
+echo Unset settings
+git perf config get 'measurement."build".unit' && exit 1
+
+echo Set settings in a new .gitperfconfig
+git perf config set 'measurement."build".unit' ms
+git perf config set 'measurement."build".max_trend_pct_per_commit' 5.0
+git perf config set 'measurement."build".command' '["make", "bench"]'
+[[ $(git perf config get 'measurement."build".unit') == ms ]] || exit 1
+[[ $(git perf config get 'measurement."build".max_trend_pct_per_commit') == 5.0 ]] || exit 1
+[[ $(git perf config get 'measurement."build".command') == '["make", "bench"]' ]] || exit 1
+git perf config check
+
+echo Comments are kept
+echo '# Storage of the measurements
+[storage]
+format = "v4" # latest' > .gitperfconfig
+git perf config set storage.format v3
+grep -q '^# Storage of the measurements$' .gitperfconfig || exit 1
+grep -q '^format = "v3" # latest$' .gitperfconfig || exit 1
+
+echo Unknown and invalid settings are refused
+output=$(git perf config set 'measurement."build".threshold' 5.0 2>&1) && exit 1
+[[ ${output} == *"Not setting measurement.\"build\".threshold: Unknown setting"* ]] || exit 1
+output=$(git perf config set storage.format v5 2>&1) && exit 1
+[[ ${output} == *"Unknown storage format 'v5'"* ]] || exit 1
+[[ $(git perf config get storage.format) == v3 ]] || exit 1
+
+exit 0
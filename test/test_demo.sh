@@ -0,0 +1,39 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Generate demo history
+cd "$(mktemp -d)"
+git perf demo --commits 20 -C demo
+cd demo
+# One additional commit accepting a change with a new epoch
+[[ $(git rev-list --count HEAD) -eq 21 ]] || exit 1
+# 5 samples on 2 platforms per generated commit
+num_measurements=$(git perf report -o - -n 21 -m demo::parse | tail -n +2 | wc -l)
+[[ ${num_measurements} -eq 200 ]] || exit 1
+# Only the current epoch of demo::max_rss is summarized
+num_measurements=$(git perf report -o - -n 21 -m demo::max_rss -s os --csv-mode summary | tail -n +2 | wc -l)
+[[ ${num_measurements} -lt 42 ]] || exit 1
+git perf report -o result.html -a median -s os
+git perf stats -m demo::startup
+git perf audit -m demo::startup -m demo::max_rss -s os=linux
+# The regression in the middle of the history is detected
+git perf audit -m demo::parse -s os=linux -d 4 --commit HEAD~8 && exit 1
+
+echo Same seed, same history
+first=$(git perf report -o - -m demo::parse | cut -f5)
+cd ..
+git perf demo --commits 20 -C demo2
+cd demo2
+[[ $(git perf report -o - -m demo::parse | cut -f5) == "${first}" ]] || exit 1
+
+echo Refuse non-empty directory
+cd ..
+git perf demo --commits 5 -C demo && exit 1
+
+exit 0
@@ -0,0 +1,41 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Digest without recent measurements
+cd_empty_repo
+create_commit
+output=$(git perf digest)
+[[ ${output} == *'No measurements in the last 7 days'* ]] || exit 1
+
+echo Digest of regressions, improvements and failed audits
+cd_empty_repo
+for value in 10 10 11 10; do
+  create_commit
+  git perf add -m timer "$value"
+  git perf add -m memory 100
+done
+create_commit
+git perf add -m timer 20
+git perf add -m memory 50
+echo '[digest]
+commit_url = "https://example.com/commit/{commit}"' > .gitperfconfig
+git perf digest --since 1d --format email-html -o digest.html
+grep -q 'Top regressions' digest.html
+grep -q '+95.12%' digest.html
+grep -q -- '-50.00%' digest.html
+grep -q "href=\"https://example.com/commit/$(git rev-parse HEAD)\"" digest.html
+sed -n '/>Failed audits</,/<.table>/p' digest.html | grep -q '>timer</td>'
+sed -n '/>Failed audits</,/<.table>/p' digest.html | grep -q '<img src="data:image/svg+xml,'
+grep -q '<td[^>]*>5/5</td>' digest.html
+grep -q '<script' digest.html && exit 1
+grep -q '<style' digest.html && exit 1
+
+git perf digest --since 1 && exit 1
+
+exit 0
@@ -0,0 +1,60 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Outside of a repository
+cd "$(mktemp -d)"
+output=$(git perf doctor 2>&1) && exit 1
+[[ ${output} == *'FAIL  Not inside the work tree of a git repository'* ]] || exit 1
+
+echo Repository without remote and measurements
+cd_temp_repo
+output=$(git perf doctor 2>&1)
+[[ ${output} == *'warn  Remote origin is not configured'* ]] || exit 1
+[[ ${output} == *"fix: Add it with 'git remote add origin <url>'"* ]] || exit 1
+[[ ${output} == *'warn  No measurements available locally'* ]] || exit 1
+[[ ${output} == *'ok    No .gitperfconfig, using the defaults'* ]] || exit 1
+
+echo Healthy repository
+repo=$(pwd)
+cd "$(mktemp -d)"
+git init --bare
+orig=$(pwd)
+cd "$repo"
+git remote add origin "$orig"
+git perf add -m timer 1
+output=$(git perf doctor 2>&1)
+[[ ${output} == *'warn'* ]] && exit 1
+[[ ${output} == *"ok    Remote origin at $orig"* ]] || exit 1
+[[ ${output} == *'ok    HEAD is on a branch'* ]] || exit 1
+
+echo Detached HEAD
+git checkout --detach HEAD
+output=$(git perf doctor 2>&1)
+[[ ${output} == *'warn  Detached HEAD'* ]] || exit 1
+git checkout -
+
+echo Invalid settings
+echo '[storage]
+format = "v5"' > .gitperfconfig
+output=$(git perf doctor 2>&1) && exit 1
+[[ ${output} == *"FAIL  Unknown storage format 'v5'"* ]] || exit 1
+[[ ${output} == *'1 of '*' checks failed'* ]] || exit 1
+echo '[storage' > .gitperfconfig
+output=$(git perf doctor 2>&1) && exit 1
+[[ ${output} == *'FAIL  .gitperfconfig is not valid TOML'* ]] || exit 1
+rm .gitperfconfig
+
+echo Broken notes ref
+git symbolic-ref refs/notes/perf-v3-shards/2020 refs/heads/master
+output=$(git perf doctor 2>&1) && exit 1
+[[ ${output} == *'refs/notes/perf-v3-shards/2020'* ]] || exit 1
+git update-ref --no-deref -d refs/notes/perf-v3-shards/2020
+git perf doctor
+
+exit 0
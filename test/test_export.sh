@@ -0,0 +1,55 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+git perf add -m timer 1 -k os=linux
+git perf add -m other 2
+git checkout -b feature HEAD~2
+create_commit
+git perf add -m timer 3 -k os=mac
+git checkout master
+
+echo Export all annotated commits, including those not reachable from HEAD
+output=$(git perf export --format csv)
+[[ $(echo "$output" | wc -l) == 4 ]] || exit 1
+[[ ${output} == 'commit,epoch,name,timestamp,val,key_values'* ]] || exit 1
+[[ ${output} == *',timer,'*',3.0,os=mac'* ]] || exit 1
+
+echo Filter by measurement and key-value
+output=$(git perf export -m timer -k os=linux)
+[[ $(echo "$output" | wc -l) == 1 ]] || exit 1
+[[ ${output} == *'"name":"timer"'*'"val":1.0'* ]] || exit 1
+
+echo Restrict to a commit range
+output=$(git perf export --range master~1..master --format csv -o export.csv)
+[[ $(wc -l < export.csv) == 3 ]] || exit 1
+
+echo Latest value per measurement and key-values as OpenMetrics gauges
+git perf add -m timer 4 -k os=linux
+output=$(git perf export --format openmetrics)
+[[ ${output} == *'# TYPE git_perf_timer gauge'* ]] || exit 1
+[[ ${output} == *'git_perf_timer{os="linux"} 4.0'* ]] || exit 1
+[[ ${output} == *'git_perf_timer{os="mac"} 3.0'* ]] || exit 1
+[[ ${output} == *'git_perf_other 2.0'* ]] || exit 1
+[[ ${output} != *'git_perf_timer{os="linux"} 1.0'* ]] || exit 1
+[[ ${output} == *'# EOF' ]] || exit 1
+
+echo Parquet file
+git perf export --format parquet -o export.parquet
+[[ $(head -c 4 export.parquet) == PAR1 ]] || exit 1
+[[ $(tail -c 4 export.parquet) == PAR1 ]] || exit 1
+grep -q timer export.parquet || exit 1
+
+echo Empty repository
+cd_empty_repo
+git commit --allow-empty -m 'empty'
+output=$(git perf export)
+[[ -z ${output} ]] || exit 1
+
+exit 0
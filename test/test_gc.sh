@@ -0,0 +1,87 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Gc requires a retention policy
+cd_temp_repo
+git perf add -m timer 1
+git perf gc && exit 1
+
+echo Keep only the newest points per measurement
+git checkout HEAD~2
+git perf add -m timer 1
+git tag v1.0
+git checkout master
+git checkout HEAD~1
+git perf add -m timer 2
+git checkout master
+git perf add -m timer 3
+git perf add -m other 1
+cat > .gitperfconfig <<CONFIG
+[retention]
+max_points_per_measurement = 2
+CONFIG
+git perf gc --dry-run 2>&1 | grep -q 'Would remove 2 measurements from 2 commits'
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 4 ]] || exit 1
+git perf gc
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 2 ]] || exit 1
+[[ $(git perf report -o - -m other | tail -n +2 | wc -l) -eq 1 ]] || exit 1
+
+echo Keep measurements of tagged commits
+git checkout v1.0
+git perf add -m timer 4
+git checkout master
+cat > .gitperfconfig <<CONFIG
+[retention]
+max_points_per_measurement = 1
+keep_tagged_commits = true
+CONFIG
+git perf gc
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 2 ]] || exit 1
+[[ $(git notes --ref refs/notes/perf-v3 show v1.0 | wc -l) -eq 1 ]] || exit 1
+
+echo Remove measurements older than max_age
+old_measurement=$(printf '0\034timer\03486400.0\0341.0')
+git notes --ref refs/notes/perf-v3 append -m "$old_measurement" HEAD~1
+cat > .gitperfconfig <<CONFIG
+[retention]
+max_age = "90d"
+CONFIG
+git perf gc 2>&1 | grep -q 'Removed 1 measurements from 1 commits'
+[[ $(git notes --ref refs/notes/perf-v3 list | wc -l) -eq 2 ]] || exit 1
+
+echo Invalid policies are rejected
+cat > .gitperfconfig <<CONFIG
+[retention]
+max_age = "90"
+CONFIG
+git perf gc && exit 1
+
+echo Remove stale temporary refs
+old_commit=$(GIT_COMMITTER_DATE='2000-01-01T00:00:00' git commit-tree 'HEAD^{tree}' -m crashed)
+git update-ref refs/notes/perf-v3-add-crashed "$old_commit"
+git update-ref refs/notes/perf-v3-merge-crashed "$old_commit"
+# Left behind by crashed operations long ago
+touch -t 200001010000 "$(git rev-parse --git-path refs/notes/perf-v3-add-crashed)"
+touch -t 200001010000 "$(git rev-parse --git-path refs/notes/perf-v3-merge-crashed)"
+git update-ref refs/notes/perf-v3-add-running HEAD
+# Just created by a running operation, the age of the commit does not matter
+git update-ref refs/notes/perf-v3-merge-running "$old_commit"
+rm .gitperfconfig
+output=$(git perf gc --refs --dry-run 2>&1)
+[[ ${output} == *'Would remove 2 stale temporary refs'* ]] || exit 1
+[[ ${output} == *'refs/notes/perf-v3-add-crashed'* ]] || exit 1
+[[ $(git for-each-ref 'refs/notes/perf-v3-*-*' | wc -l) -eq 4 ]] || exit 1
+git perf gc --refs 2>&1 | grep -q 'Removed 2 stale temporary refs'
+[[ $(git for-each-ref --format='%(refname)' 'refs/notes/perf-v3-*-*' | xargs) == 'refs/notes/perf-v3-add-running refs/notes/perf-v3-merge-running' ]] || exit 1
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 2 ]] || exit 1
+echo '[gc]
+temp_ref_max_age = 0' > .gitperfconfig
+git perf gc --refs 2>&1 | grep -q 'Removed 2 stale temporary refs'
+
+exit 0
@@ -0,0 +1,214 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Import Google Benchmark JSON
+cd_temp_repo
+cat > gbench.json <<'JSON'
+{
+  "context": {},
+  "benchmarks": [
+    {
+      "name": "BM_Sort/8",
+      "run_name": "BM_Sort/8",
+      "run_type": "iteration",
+      "iterations": 1000,
+      "real_time": 12.5,
+      "cpu_time": 12.0,
+      "time_unit": "us"
+    }
+  ]
+}
+JSON
+git perf import google-benchmark gbench.json -k os=linux
+output=$(git perf report -o -)
+[[ ${output} == *'bench::BM_Sort/8::real_time'*'12500.0'* ]] || exit 1
+[[ ${output} == *'bench::BM_Sort/8::cpu_time'*'12000.0'* ]] || exit 1
+[[ ${output} == *$'\tos\n'*$'\tlinux'* ]] || exit 1
+
+echo Import from stdin
+git perf import google-benchmark < gbench.json
+git perf audit -m bench::BM_Sort/8::real_time
+
+echo Import pytest-benchmark JSON
+cat > pytest.json <<'JSON'
+{
+  "benchmarks": [
+    {
+      "group": null,
+      "name": "test_sort",
+      "stats": { "min": 0.5, "max": 2.0, "mean": 1.0, "stddev": 0.1, "median": 1.0, "rounds": 5 }
+    }
+  ]
+}
+JSON
+git perf import pytest-benchmark pytest.json
+output=$(git perf report -o - -m bench::test_sort::mean)
+[[ ${output} == *'bench::test_sort::mean'*'1000000000.0'* ]] || exit 1
+
+echo Import hyperfine JSON
+cat > hyperfine.json <<'JSON'
+{
+  "results": [
+    { "command": "sleep 0.1", "mean": 0.1, "stddev": 0.0, "median": 0.1, "min": 0.1, "max": 0.2 }
+  ]
+}
+JSON
+git perf import hyperfine hyperfine.json
+output=$(git perf report -o - -m bench::sleep_0.1::max)
+[[ ${output} == *'bench::sleep_0.1::max'*'200000000.0'* ]] || exit 1
+
+echo Import report
+cat > gbench_report.json <<'JSON'
+{
+  "context": {},
+  "benchmarks": [
+    { "name": "BM_A", "run_type": "iteration", "real_time": 1, "cpu_time": 1, "time_unit": "ns" },
+    { "name": "BM_A", "run_type": "iteration", "real_time": 1, "cpu_time": 1, "time_unit": "ns" },
+    { "name": "BM_Broken", "error_occurred": true, "real_time": 0, "cpu_time": 0, "time_unit": "ns" }
+  ]
+}
+JSON
+output=$(git perf import google-benchmark gbench_report.json --verbose --report report.json)
+[[ ${output} == *'Skipped'*'BM_Broken'* ]] || exit 1
+report=$(cat report.json)
+[[ ${report} == *'"format": "google-benchmark"'* ]] || exit 1
+[[ ${report} == *'"parsed": 5'* ]] || exit 1
+[[ ${report} == *'"imported": 2'* ]] || exit 1
+[[ ${report} == *'"duplicates": 2'* ]] || exit 1
+[[ ${report} == *'"bench::BM_A::real_time"'* ]] || exit 1
+[[ ${report} == *'"reason": "benchmark reported an error"'* ]] || exit 1
+
+echo Import multiple files and glob patterns
+cd_temp_repo
+for shard in 1 2 3; do
+  mkdir -p "shards/${shard}"
+  cat > "shards/${shard}/results.json" <<JSON
+{
+  "results": [
+    { "command": "shard_${shard}", "mean": 0.1, "stddev": 0.0, "median": 0.1, "min": 0.1, "max": 0.2 }
+  ]
+}
+JSON
+done
+cp shards/1/results.json single.json
+output=$(git perf import hyperfine 'shards/*/results.json' single.json --verbose --report report.json)
+[[ ${output} == *'from 4 files'* ]] || exit 1
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 16 ]] || exit 1
+# A single notes write for all files
+[[ $(git notes --ref=refs/notes/perf-v3 list | wc -l) -eq 1 ]] || exit 1
+[[ $(git log --format=%s refs/notes/perf-v3 | wc -l) -eq 1 ]] || exit 1
+[[ $(cat report.json) == *'"shards/2/results.json"'* ]] || exit 1
+git perf import hyperfine 'shards/*/missing.json' && exit 1
+
+echo Import cargo-criterion JSON messages with changes against a baseline
+cd_temp_repo
+cat > criterion.json <<'JSON'
+{"reason":"benchmark-complete","id":"sort/1000","unit":"ns","mean":{"estimate":100.5,"lower_bound":99.0,"upper_bound":102.0,"unit":"ns"},"median":{"estimate":100.0,"lower_bound":99.0,"upper_bound":101.0,"unit":"ns"},"slope":null,"change":{"mean":{"estimate":0.05,"lower_bound":0.04,"upper_bound":0.06,"unit":"%"},"median":{"estimate":0.04,"lower_bound":0.03,"upper_bound":0.05,"unit":"%"},"change":"Regressed"}}
+{"reason":"group-complete","group_name":"sort","benchmarks":["sort/1000"]}
+JSON
+git perf import criterion-json criterion.json
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 2 ]] || exit 1
+git perf import criterion-json criterion.json --compare-baseline main
+output=$(git perf report -o - -m bench::sort/1000::mean_change)
+[[ ${output} == *'bench::sort/1000::mean_change'*'5.0'* ]] || exit 1
+[[ ${output} == *$'\tbaseline\n'*$'\tmain'* ]] || exit 1
+git perf import hyperfine --compare-baseline main < criterion.json 2>&1 | grep -q 'not supported for hyperfine'
+
+echo Namespace benchmarks by the owning crate of the cargo workspace
+mkdir -p sort/src
+printf '[workspace]\nmembers = ["sort"]\n' > Cargo.toml
+printf '[package]\nname = "sort"\nversion = "0.1.0"\n' > sort/Cargo.toml
+touch sort/src/lib.rs
+git perf import criterion-json criterion.json --workspace-crates
+output=$(git perf report -o - -m sort::bench::sort/1000::mean)
+[[ ${output} == *$'\tcrate\n'*$'\tsort'* ]] || exit 1
+git perf import criterion-json criterion.json --workspace-crates -k crate=other 2>&1 | grep -q 'reserved'
+
+echo Detect the format of each file
+criterion_json=$(pwd)/criterion.json
+cd_temp_repo
+cp "${criterion_json}" .
+cat > hyperfine.json <<'JSON'
+{
+  "results": [
+    { "command": "sleep 0.1", "mean": 0.1, "stddev": 0.0, "median": 0.1, "min": 0.1, "max": 0.2 }
+  ]
+}
+JSON
+git perf import auto hyperfine.json criterion.json --compare-baseline main
+output=$(git perf report -o -)
+[[ ${output} == *'bench::sleep_0.1::max'* ]] || exit 1
+[[ ${output} == *'bench::sort/1000::mean_change'* ]] || exit 1
+git perf import auto < hyperfine.json
+echo 'results: []' | git perf import auto 2>&1 | grep -q 'neither JSON nor JSON lines'
+echo '{"benchmarks": [{"real_time": 1, "stats": {}}]}' | git perf import auto 2>&1 \
+  | grep -q 'matches google-benchmark and pytest-benchmark'
+
+echo Skip or replace measurements already stored for HEAD
+hyperfine_json=$(pwd)/hyperfine.json
+cd_temp_repo
+cp "${hyperfine_json}" .
+git perf import hyperfine hyperfine.json -k os=linux
+git perf import hyperfine hyperfine.json -k os=linux --dedupe --report report.json
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 4 ]] || exit 1
+[[ $(cat report.json) == *'"already_stored": 4'* ]] || exit 1
+# Other key-values are a different series
+git perf import hyperfine hyperfine.json -k os=mac --dedupe
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 8 ]] || exit 1
+sed -i.bak 's/"max": 0.2/"max": 0.3/' hyperfine.json
+output=$(git perf import hyperfine hyperfine.json -k os=linux --replace --verbose)
+[[ ${output} == *'Replaced 4 measurements stored for HEAD'* ]] || exit 1
+output=$(git perf report -o - -m bench::sleep_0.1::max -k os=linux)
+[[ $(echo "${output}" | tail -n +2 | wc -l) -eq 1 ]] || exit 1
+[[ ${output} == *'300000000.0'* ]] || exit 1
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 8 ]] || exit 1
+git perf import hyperfine hyperfine.json --dedupe --replace && exit 1
+
+echo Import JUnit XML
+cd_temp_repo
+cat > junit.xml <<'XML'
+<?xml version="1.0" encoding="utf-8"?>
+<testsuites>
+  <testsuite name="pytest" tests="3">
+    <testcase classname="tests.test_sort" name="test_small" time="0.5"/>
+    <testcase classname="tests.test_sort" name="test_skip" time="0.0"><skipped/></testcase>
+    <testcase classname="tests.test_sort" name="test_fail" time="0.1"><failure/></testcase>
+  </testsuite>
+</testsuites>
+XML
+output=$(git perf import junit junit.xml --verbose)
+[[ ${output} == *"Skipped 'tests.test_sort.test_skip': skipped"* ]] || exit 1
+[[ ${output} == *"Skipped 'tests.test_sort.test_fail': failed"* ]] || exit 1
+output=$(git perf report -o -)
+[[ $(echo "${output}" | tail -n +2 | wc -l) -eq 1 ]] || exit 1
+[[ ${output} == *'bench::tests.test_sort.test_small::time'*'500000000.0'* ]] || exit 1
+git perf import auto < junit.xml
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 2 ]] || exit 1
+echo '<html></html>' | git perf import auto 2>&1 | grep -q 'Expected a <testsuites> or <testsuite> element'
+git perf import junit junit.xml --compare-baseline main && exit 1
+
+echo Import a large JUnit XML report while it is generated
+cd_temp_repo
+testcases=100000
+awk -v n="${testcases}" 'BEGIN {
+  output = sprintf("%0200d", 0)
+  print "<testsuites><testsuite name=\"generated\">"
+  for (i = 1; i <= n; i++) {
+    printf "<testcase classname=\"generated\" name=\"test_%d\" time=\"0.%d\">", i, i
+    printf "<system-out>%s</system-out></testcase>\n", output
+  }
+  print "</testsuite></testsuites>"
+}' | git perf import junit --report report.json
+[[ $(cat report.json) == *"\"imported\": ${testcases}"* ]] || exit 1
+
+echo Reject invalid input
+echo '{' | git perf import google-benchmark && exit 1
+echo '{"benchmarks": []}' | git perf import google-benchmark && exit 1
+
+exit 0
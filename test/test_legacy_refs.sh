@@ -0,0 +1,48 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+count_measurements() {
+  git perf report -o - "$@" | tail -n +2 | wc -l
+}
+
+cd_temp_repo
+echo Measurements of earlier versions in their own refs
+git notes --ref refs/notes/perf add -m 'timer 1000 1 os=linux' HEAD~3
+git notes --ref refs/notes/perf-v2 add -m '0 timer 2000 1.1 os=linux' HEAD~2
+git notes --ref refs/notes/perf-v2 append -m '0 timer 2001 1.05 os=linux' HEAD~2
+git notes --ref refs/notes/perf-v2 add -m '0 timer 3000 0.95 os=linux' HEAD~1
+git perf add -m timer 5 -k os=linux
+
+echo Legacy refs are ignored without configuration
+[[ $(count_measurements -m timer) -eq 1 ]] || exit 1
+
+cat > .gitperfconfig <<CONFIG
+[storage]
+legacy_refs = ["refs/notes/perf", "refs/notes/perf-v2", "refs/notes/missing/perf"]
+CONFIG
+
+echo Reports merge the legacy measurements
+[[ $(count_measurements -m timer) -eq 5 ]] || exit 1
+git perf report -o - -m timer | grep -q $'\t1000.0\t1.0\tlinux$'
+
+echo Audits use the legacy measurements as history
+output=$(git perf audit -m timer -s os=linux 2>&1) && exit 1
+[[ ${output} == *'differs significantly'* ]] || exit 1
+
+echo Removal does not touch the legacy refs
+git perf remove -m timer
+[[ $(count_measurements -m timer) -eq 4 ]] || exit 1
+
+echo Unknown legacy formats are rejected
+echo '[storage]
+legacy_refs = ["refs/notes/other"]' > .gitperfconfig
+output=$(git perf report -o - 2>&1) && exit 1
+[[ ${output} == *"Cannot tell the format of the legacy notes ref 'refs/notes/other'"* ]] || exit 1
+
+exit 0
@@ -0,0 +1,43 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Concurrent writes are serialized by the local lock
+cd_temp_repo
+cat > .gitperfconfig <<CONFIG
+[storage]
+local_lock = true
+CONFIG
+pids=()
+for i in $(seq 1 10); do
+  git perf add -m timer "$i" &
+  pids+=($!)
+done
+for pid in "${pids[@]}"; do
+  wait "$pid"
+done
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 10 ]] || exit 1
+[[ -f .git/git-perf.lock ]] || exit 1
+
+echo Lock in linked worktrees is shared
+main=$(pwd)
+worktree=$(mktemp -d)/worktree
+git worktree add "$worktree"
+cd "$worktree"
+cp "$main/.gitperfconfig" .
+git perf add -m timer 11
+[[ ! -e "$main/.git/worktrees/worktree/git-perf.lock" ]] || exit 1
+
+echo Pushing takes the lock
+orig=$(mktemp -d)
+git init --bare "$orig"
+git remote add origin "$orig"
+git perf push
+git ls-remote "$orig" | grep -q refs/notes/perf-v3
+
+exit 0
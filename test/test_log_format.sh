@@ -0,0 +1,51 @@
+#!/bin/bash
+
+set -euxo pipefail
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Setup an upstream repo and two working copies
+cd "$(mktemp -d)"
+git init --bare original
+git clone original repo1
+git clone original repo2
+for repo in repo1 repo2; do
+  git -C "$repo" config user.name "$GIT_COMMITTER_NAME"
+  git -C "$repo" config user.email "$GIT_COMMITTER_EMAIL"
+done
+
+pushd repo1
+create_commit
+git push
+popd
+pushd repo2
+git pull
+popd
+
+echo Push with JSON events
+pushd repo1
+git perf add -m echo 0.5 -k repo=first
+output=$(git perf push --log-format json 2>&1)
+[[ ${output} == *'{"level":"info","operation":"push","remote":"origin","duration_ms":'*',"outcome":"success","message":"Pushed to origin"}'* ]] || exit 1
+popd
+
+echo A rejected push is retried after pulling
+pushd repo2
+git perf add -m echo 0.5 -k repo=second
+output=$(git perf push --log-format json 2>&1)
+[[ ${output} == *'"operation":"push","remote":"origin","duration_ms":'*',"outcome":"retry"'* ]] || exit 1
+[[ ${output} == *'"operation":"pull","remote":"origin","duration_ms":'*',"outcome":"success"'* ]] || exit 1
+[[ ${output} == *'"outcome":"success","message":"Pushed to origin"'* ]] || exit 1
+popd
+
+echo The default text format prints no events
+pushd repo1
+git perf add -m echo 0.5 -k repo=first
+git perf pull
+output=$(git perf push 2>&1)
+[[ ${output} != *'"operation"'* ]] || exit 1
+popd
+
+exit 0
@@ -0,0 +1,46 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Stamp matrix dimensions onto measurements
+cd_temp_repo
+git perf add -m timer 1 --matrix os=linux,rust=stable
+git perf add -m timer 2 --matrix os=linux,rust=nightly
+git perf add -m timer 3 --matrix os=mac,rust=stable -k runner=fast
+output=$(git perf report -o -)
+[[ ${output} == $'commit\tepoch\tname\ttimestamp\tval\tmatrix\tos\trunner\trust\n'* ]] || exit 1
+[[ ${output} == *$'\t1.0\tos,rust\tlinux\t\tstable'* ]] || exit 1
+[[ ${output} == *$'\t2.0\tos,rust\tlinux\t\tnightly'* ]] || exit 1
+[[ ${output} == *$'\t3.0\tos,rust\tmac\tfast\tstable'* ]] || exit 1
+
+echo Duplicate keys are rejected
+git perf add -m timer 1 --matrix os=linux,os=mac && exit 1
+git perf add -m timer 1 --matrix os=linux -k os=mac && exit 1
+
+echo Separate report by all matrix dimensions
+git perf report -o separated.html -s os,rust
+git perf report -o separated.html -s os -s rust
+git perf audit -m timer -s os=linux -s rust=stable
+
+echo Separate by the recorded matrix dimensions
+output=$(git perf report -o - --separate-by-matrix -a min)
+[[ ${output} == *$'\tlinux/nightly\t2.0'* ]] || exit 1
+[[ ${output} == *$'\tlinux/stable\t1.0'* ]] || exit 1
+[[ ${output} == *$'\tmac/stable\t3.0'* ]] || exit 1
+git perf report -o - --separate-by-matrix -s os && exit 1
+create_commit
+git perf add -m timer 1 --matrix os=linux,rust=stable
+git perf add -m timer 3 --matrix os=mac,rust=stable
+output=$(git perf audit -m timer --separate-by-matrix --min-measurements 1 2>&1)
+[[ ${output} == *'timer (os=linux,rust=stable)'* ]] || exit 1
+[[ ${output} == *'timer (os=mac,rust=stable)'* ]] || exit 1
+git perf add -m untagged 1
+git perf report -o - -m untagged --separate-by-matrix && exit 1
+git perf audit -m untagged --separate-by-matrix 2>&1 | grep -q 'No build matrix dimensions'
+
+exit 0
@@ -24,13 +24,137 @@ git perf measure -m test-measure -- does-not-exist && exit 1
 echo Valid command, repeated measurements
 cd_temp_repo
 git perf measure -m test-measure -n 5 -- true
-num_measurements=$(git perf report -o - | wc -l)
+num_measurements=$(git perf report -o - | tail -n +2 | wc -l)
 [[ ${num_measurements} -eq 5 ]] || exit 1
 
+echo Concurrent repetitions
+cd_temp_repo
+git perf measure -m test-measure -n 6 -j 3 -- bash -c 'echo start >> markers.txt; sleep 0.5; echo end >> markers.txt'
+[[ $(grep -c start markers.txt) -eq 6 ]] || exit 1
+# Sequential repetitions would alternate between start and end markers
+max_running=$(awk '/start/ { running++ } /end/ { running-- } running > max { max = running } END { print max }' markers.txt)
+[[ ${max_running} -ge 2 ]] || exit 1
+[[ ${max_running} -le 3 ]] || exit 1
+num_measurements=$(git perf report -o - -m test-measure | tail -n +2 | wc -l)
+[[ ${num_measurements} -eq 6 ]] || exit 1
+git perf measure -m test-measure -j 2 --duration 1s -- true && exit 1
+git perf measure -m test-measure -n 4 -j 2 -- false && exit 1
+
+echo Warm-up and outlier trimming
+cd_temp_repo
+git perf measure -m test-measure -n 10 --warmup 3 --discard-outliers 10 -- bash -c 'echo run >> runs.txt'
+[[ $(wc -l < runs.txt) -eq 13 ]] || exit 1
+num_measurements=$(git perf report -o - -m test-measure | tail -n +2 | wc -l)
+[[ ${num_measurements} -eq 8 ]] || exit 1
+git perf measure -m test-measure --discard-outliers 50 -- true && exit 1
+
+echo Time-boxed measurements
+cd_temp_repo
+git perf measure -m test-measure --duration 300ms -- bash -c 'sleep 0.05'
+# How many samples fit into the duration depends on the load of the machine
+num_measurements=$(git perf report -o - -m test-measure | tail -n +2 | wc -l)
+[[ ${num_measurements} -ge 1 ]] || exit 1
+num_measurements=$(git perf report -o - -m test-measure::iterations_per_second | tail -n +2 | wc -l)
+[[ ${num_measurements} -eq 1 ]] || exit 1
+git perf measure -m test-measure --duration 1s -n 2 -- true && exit 1
+git perf measure -m test-measure --duration 10x -- true && exit 1
+
+echo Repetitions until the values are stable
+cd_temp_repo
+git perf measure -m test-measure --target-cv 2% --extract-regex '[0-9]+' -- echo 42
+output=$(git perf report -o - -m test-measure)
+[[ $(echo "$output" | tail -n +2 | wc -l) -eq 3 ]] || exit 1
+[[ ${output} == *$'\tcv\n'*$'\t0.00'* ]] || exit 1
+git perf measure -m test-noisy --target-cv 0.001 --max-repetitions 5 --extract-regex '[0-9]+' -- bash -c 'echo run >> cv_runs.txt; wc -l < cv_runs.txt'
+[[ $(git perf report -o - -m test-noisy | tail -n +2 | wc -l) -eq 5 ]] || exit 1
+git perf measure -m test-noisy --target-cv 0.001 --duration 100ms -- bash -c 'sleep 0.05'
+[[ $(git perf report -o - -m test-noisy | tail -n +2 | wc -l) -le 9 ]] || exit 1
+git perf measure -m test-measure --target-cv 2 -n 3 -- true && exit 1
+git perf measure -m test-measure --target-cv 0% -- true && exit 1
+git perf measure -m test-measure --max-repetitions 3 -- true && exit 1
+
+echo Memory and CPU usage
+cd_temp_repo
+git perf measure -m test-measure -n 2 --with-memory --with-cpu -- bash -c 'for i in $(seq 1000); do :; done'
+# Runtimes and resource usage are stored in a single write
+[[ $(git log --format=%s refs/notes/perf-v3 | wc -l) -eq 1 ]] || exit 1
+num_measurements=$(git perf report -o - -m test-measure::max_rss | tail -n +2 | wc -l)
+[[ ${num_measurements} -eq 2 ]] || exit 1
+num_measurements=$(git perf report -o - -m test-measure::cpu_time | tail -n +2 | wc -l)
+[[ ${num_measurements} -eq 2 ]] || exit 1
+max_rss=$(git perf report -o - -m test-measure::max_rss | tail -n +2 | cut -f5 | head -n 1)
+# At least a megabyte for a bash process
+[[ ${max_rss%.*} -gt 1000000 ]] || exit 1
+git perf measure -m test-measure --duration 100ms --with-memory -- true
+num_measurements=$(git perf report -o - -m test-measure::max_rss | tail -n +2 | wc -l)
+[[ ${num_measurements} -ge 3 ]] || exit 1
+num_measurements=$(git perf report -o - -m test-measure::cpu_time | tail -n +2 | wc -l)
+[[ ${num_measurements} -eq 2 ]] || exit 1
+git perf measure -m test-measure --with-memory -- false && exit 1
+
+echo Energy usage
+cd_temp_repo
+if [[ $(uname -s) == Darwin ]]; then
+  git perf measure -m test-measure -n 2 --with-energy -- bash -c 'for i in $(seq 1000); do :; done'
+  num_measurements=$(git perf report -o - -m test-measure::energy | tail -n +2 | wc -l)
+  [[ ${num_measurements} -eq 2 ]] || exit 1
+  num_measurements=$(git perf report -o - -m test-measure::average_power | tail -n +2 | wc -l)
+  [[ ${num_measurements} -eq 2 ]] || exit 1
+else
+  output=$(git perf measure -m test-measure --with-energy -- true 2>&1) && exit 1
+  [[ ${output} == *'only supported on macOS'* ]] || exit 1
+fi
+
+echo Values extracted from the output
+cd_temp_repo
+git perf measure -m binary-size -n 2 --extract-regex 'size: ([0-9]+)' --unit bytes -- echo 'size: 4096 bytes'
+output=$(git perf report -o - -m binary-size | tail -n +2)
+[[ $(echo "$output" | wc -l) -eq 2 ]] || exit 1
+[[ $(echo "$output" | cut -f5 | sort -u) == '4096.0' ]] || exit 1
+[[ ${output} == *$'\tbytes'* ]] || exit 1
+git perf measure -m binary-size --with-memory --extract-regex '[0-9]+' -- echo 'rss 12'
+[[ $(git perf report -o - -m binary-size | tail -n +2 | cut -f5 | sort -u | head -n 1) == '12.0' ]] || exit 1
+git perf measure -m binary-size --extract-regex 'size: ([0-9]+)' -- echo 'no size' && exit 1
+git perf measure -m binary-size --extract-regex '(' -- true && exit 1
+git perf measure -m binary-size --unit bytes -- true && exit 1
+git perf measure -m binary-size --extract-regex '[0-9]+' --unit bytes -k unit=kb -- echo 1 && exit 1
+
+echo Runtimes in a given time unit
+cd_temp_repo
+git perf measure -m sleep --unit ms -- sleep 0.1
+git perf measure -m sleep -k unit=s -- sleep 0.1
+output=$(git perf report -o - -m sleep | tail -n +2)
+ms=$(echo "$output" | grep $'\tms$' | cut -f5)
+s=$(echo "$output" | grep $'\ts$' | cut -f5)
+[[ ${ms%%.*} -ge 100 && ${ms%%.*} -lt 10000 ]] || exit 1
+[[ ${s} == 0.* ]] || exit 1
+output=$(git perf measure -m sleep --unit bytes -- true 2>&1) && exit 1
+[[ ${output} == *"Unit 'bytes' of the runtime of 'sleep' is not a time unit"* ]] || exit 1
+echo '[measurement."sleep"]
+unit = "ms"' > .gitperfconfig
+git perf measure -m sleep --unit ms -- true
+output=$(git perf measure -m sleep --unit s -- true 2>&1) && exit 1
+[[ ${output} == *"Unit 's' of 'sleep' conflicts with its configured unit 'ms'"* ]] || exit 1
+rm .gitperfconfig
+
+echo Suite of several commands in a single write
+cd_temp_repo
+echo 'true-cmd = ["true"]
+sleep = ["sleep", "0.01"]' > suite.toml
+git perf measure --suite suite.toml -n 3 --unit ms -k os=linux
+output=$(git perf report -o - | tail -n +2)
+[[ $(echo "$output" | grep -c $'\ttrue-cmd\t') -eq 3 ]] || exit 1
+[[ $(echo "$output" | grep -c $'\tsleep\t') -eq 3 ]] || exit 1
+[[ $(echo "$output" | grep -c $'\tlinux\tms$') -eq 6 ]] || exit 1
+git perf measure --suite suite.toml -m other -- true && exit 1
+echo 'broken = "not an array"' > suite.toml
+output=$(git perf measure --suite suite.toml 2>&1) && exit 1
+[[ ${output} == *"Command of 'broken' is not an array of strings"* ]] || exit 1
+
 echo Measurements in nanoseconds
 cd_temp_repo
 git perf measure -m test-measure -- bash -c 'sleep 0.1'
-val=$(git perf report -o - | cut -f4 | head -n 1)
+val=$(git perf report -o - | tail -n +2 | cut -f5 | head -n 1)
 if [[ 1 -eq "$(echo "${val} < 10^(9-1)" | bc)" ]]; then
     echo "Measure is not in nanosecond precision"
     echo "0.1 seconds of sleep + fork + etc. overhead is currently $val"
@@ -0,0 +1,34 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd "$(mktemp -d)"
+
+git init --bare orig
+git clone orig work
+cd work
+create_commit
+git push
+
+echo Fetch and push fail immediately without network
+git perf add -m timer 1
+output=$(git perf --no-network push 2>&1) && exit 1
+[[ ${output} == *'network disabled by --no-network'* ]] || exit 1
+output=$(git perf pull --no-network 2>&1) && exit 1
+[[ ${output} == *'network disabled by --no-network'* ]] || exit 1
+# Nothing was pushed
+[[ -z $(git ls-remote ../orig refs/notes/perf-v3) ]] || exit 1
+
+echo Local reads work without network
+git perf --no-network report -o - | grep timer
+git perf --no-network audit -m timer
+git perf --no-network add -m timer 2
+output=$(git perf --no-network report -o - -m other 2>&1) && exit 1
+[[ ${output} == *'No performance measurements found'* ]] || exit 1
+
+exit 0
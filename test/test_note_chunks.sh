@@ -0,0 +1,66 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+year=$(date -u +%Y)
+count_measurements() {
+  git perf report -o - "$@" | tail -n +2 | wc -l
+}
+
+echo Size budget requires sharding
+cd_temp_repo
+cat > .gitperfconfig <<CONFIG
+[storage]
+max_note_size = 300
+CONFIG
+git perf add -m timer 1 && exit 1
+
+echo Large measurement sets are split across chunk refs
+cat > .gitperfconfig <<CONFIG
+[storage]
+shards = "year"
+max_note_size = 300
+CONFIG
+for i in $(seq 1 10); do
+  git perf add -m timer "$i"
+done
+cat > hyperfine.json <<'JSON'
+{
+  "results": [
+    { "command": "a", "mean": 0.1, "stddev": 0.0, "median": 0.1, "min": 0.1, "max": 0.2 },
+    { "command": "b", "mean": 0.1, "stddev": 0.0, "median": 0.1, "min": 0.1, "max": 0.2 },
+    { "command": "c", "mean": 0.1, "stddev": 0.0, "median": 0.1, "min": 0.1, "max": 0.2 }
+  ]
+}
+JSON
+git perf import hyperfine hyperfine.json
+[[ $(git for-each-ref "refs/notes/perf-v3-shards/$year.*" | wc -l) -ge 2 ]] || exit 1
+for notes_ref in $(git for-each-ref --format='%(refname)' refs/notes/perf-v3-shards/); do
+  [[ $(git notes --ref "$notes_ref" show HEAD | wc -c) -le 300 ]] || exit 1
+done
+[[ $(count_measurements -m timer) -eq 10 ]] || exit 1
+[[ $(count_measurements) -eq 22 ]] || exit 1
+
+echo Chunks are shared through push and pull
+orig=$(mktemp -d)
+git init --bare "$orig"
+git remote add origin "$orig"
+git push origin master
+git perf push
+clone=$(mktemp -d)/clone
+git clone "$orig" "$clone"
+cp .gitperfconfig "$clone"
+cd "$clone"
+git perf pull
+[[ $(count_measurements) -eq 22 ]] || exit 1
+
+echo Removal rewrites all chunks
+git perf remove -m timer
+[[ $(count_measurements) -eq 12 ]] || exit 1
+
+exit 0
@@ -0,0 +1,44 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd "$(mktemp -d)"
+root=$(pwd)
+
+git init --bare orig
+git clone orig work
+cd work
+create_commit
+create_commit
+git push
+
+echo Missing measurements fail fast in offline mode
+output=$(git perf --offline report -o - 2>&1) && exit 1
+[[ ${output} == *'No measurements available locally'* ]] || exit 1
+output=$(git perf audit --offline -m timer 2>&1) && exit 1
+[[ ${output} == *'No measurements available locally'* ]] || exit 1
+
+echo Remote operations are refused in offline mode
+git perf add -m timer 1
+output=$(git perf --offline push 2>&1) && exit 1
+[[ ${output} == *'offline mode'* ]] || exit 1
+output=$(git perf --offline pull 2>&1) && exit 1
+[[ ${output} == *'offline mode'* ]] || exit 1
+git perf push
+
+echo Local measurements are usable in offline mode
+cd "$root"
+git clone orig other
+cd other
+git perf pull
+# Remote is gone, only local refs remain
+rm -rf "$root/orig"
+git perf --offline report -o - | grep timer
+git perf --offline add -m timer 2
+
+exit 0
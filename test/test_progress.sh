@@ -0,0 +1,45 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+# Progress bars and spinners are drawn with carriage returns and escape sequences
+function assert_no_progress() {
+  if grep -q $'[\r\e]' "$1"; then
+    cat -v "$1"
+    exit 1
+  fi
+}
+
+cd "$(mktemp -d)"
+root=$(pwd)
+git init --bare orig
+git clone "$root/orig" work
+cd work
+create_commit
+create_commit
+git push
+git perf add -m timer 1
+
+echo No progress when stderr is not a terminal
+git perf push 2> "$root/push.err"
+assert_no_progress "$root/push.err"
+git perf pull 2> "$root/pull.err"
+assert_no_progress "$root/pull.err"
+git perf report -o "$root/result.html" 2> "$root/report.err"
+assert_no_progress "$root/report.err"
+
+echo No progress with --no-progress
+git perf add -m timer 2
+git perf push --no-progress 2> "$root/push.err"
+assert_no_progress "$root/push.err"
+git perf --no-progress pull 2> "$root/pull.err"
+assert_no_progress "$root/pull.err"
+git perf prune --no-progress 2> "$root/prune.err"
+assert_no_progress "$root/prune.err"
+
+exit 0
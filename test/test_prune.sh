@@ -49,6 +49,18 @@ if [[ $nr_notes -ne 1 ]]; then
 fi
 git reflog expire --expire-unreachable=now --all
 git prune --expire=now
+# A dry run lists the pruned object and keeps its note
+output=$(git perf prune --dry-run 2>&1)
+if [[ ${output} != *'Would remove the notes of 1 objects ('*' bytes)'* ]]; then
+  echo "Missing dry run summary:"
+  echo "$output"
+  exit 1
+fi
+nr_notes=$(git notes --ref=refs/notes/perf-v3 list | wc -l)
+if [[ $nr_notes -ne 1 ]]; then
+  echo "Expected the dry run to keep 1 note but found '$nr_notes' instead"
+  exit 1
+fi
 git perf prune
 nr_notes=$(git notes --ref=refs/notes/perf-v3 list | wc -l)
 if [[ $nr_notes -ne 0 ]]; then
@@ -0,0 +1,49 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd "$(mktemp -d)"
+git init --bare orig
+orig=$(pwd)/orig
+git clone "$orig" repo
+cd repo
+git config user.name "$GIT_COMMITTER_NAME"
+git config user.email "$GIT_COMMITTER_EMAIL"
+
+create_commit
+git perf add -m echo 0.5
+create_commit
+create_commit
+git perf add -m echo 0.5
+git push
+
+echo A dry run lists the notes refs without pushing them
+output=$(git perf push --dry-run)
+if [[ ${output} != *'origin'*'refs/notes/perf-v3:refs/notes/perf-v3'* ]]; then
+  echo "Missing notes ref in dry run:"
+  echo "$output"
+  exit 1
+fi
+if git ls-remote "$orig" | grep -q refs/notes/perf-v3; then
+  echo "Dry run pushed the notes"
+  exit 1
+fi
+
+echo Refuse implausible measurements with audit before push
+git perf add -m echo 5000
+git perf push --audit-first && exit 1
+if git ls-remote "$orig" | grep -q refs/notes/perf-v3; then
+  echo "Refused push pushed the notes"
+  exit 1
+fi
+git perf add -m echo 0.6 -k fixed=true
+git perf push --audit-first && exit 1
+git perf push
+git ls-remote "$orig" | grep -q refs/notes/perf-v3
+
+exit 0
@@ -71,14 +71,14 @@ echo Pushing in second working copy should automatically pull and reconcile
 pushd repo2
 git perf push
 git perf report -o -
-num_measurements=$(git perf report -o - | wc -l)
+num_measurements=$(git perf report -o - | tail -n +2 | wc -l)
 [[ ${num_measurements} -eq 3 ]] || exit 1
 popd
 
 echo In the first working copy, we should see all three measurements now
 pushd repo1
 git perf pull
-num_measurements=$(git perf report -o - | wc -l)
+num_measurements=$(git perf report -o - | tail -n +2 | wc -l)
 [[ ${num_measurements} -eq 3 ]] || exit 1
 popd
 
@@ -100,12 +100,12 @@ pushd repo2
 git pull
 create_commit
 git perf add -m echo 0.5 -k repo=second
-num_measurements=$(git perf report -o - | wc -l)
+num_measurements=$(git perf report -o - | tail -n +2 | wc -l)
 [[ ${num_measurements} -eq 1 ]] || exit 1
 git push
 # There is a conflict, it should automatically pull first
 git perf push
-num_measurements=$(git perf report -o - | wc -l)
+num_measurements=$(git perf report -o - | tail -n +2 | wc -l)
 [[ ${num_measurements} -eq 2 ]] || exit 1
 popd
 
@@ -113,7 +113,7 @@ echo In the first working copy, we should also see both measurements on separate
 pushd repo1
 git pull
 git perf pull
-num_measurements=$(git perf report -o - | wc -l)
+num_measurements=$(git perf report -o - | tail -n +2 | wc -l)
 [[ ${num_measurements} -eq 2 ]] || exit 1
 popd
 
@@ -0,0 +1,64 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd "$(mktemp -d)"
+root=$(pwd)
+
+git init --bare orig
+git init --bare mirror
+orig=$root/orig
+mirror=$root/mirror
+
+git clone "$orig" work
+cd work
+git config user.name "$GIT_COMMITTER_NAME"
+git config user.email "$GIT_COMMITTER_EMAIL"
+create_commit
+git push
+git push "$mirror" master
+git remote add mirror "$mirror"
+
+echo Push to multiple remotes given on the command line
+git perf add -m timer 1
+git perf push --remote origin --remote mirror
+git ls-remote "$orig" | grep -q refs/notes/perf-v3
+git ls-remote "$mirror" | grep -q refs/notes/perf-v3
+
+echo Push to remotes configured in .gitperfconfig
+cat > .gitperfconfig <<'TOML'
+[remote]
+push = ["origin", "mirror"]
+TOML
+git perf add -m timer 2
+git perf push
+for remote in "$orig" "$mirror"; do
+  cd "$(mktemp -d)"
+  git clone "$remote" clone
+  cd clone
+  git perf pull
+  [[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 2 ]] || exit 1
+done
+cd "$root/work"
+
+echo Failing remote does not prevent pushing to the others
+git perf add -m timer 3
+output=$(git perf push --remote does-not-exist --remote mirror 2>&1) && exit 1
+[[ ${output} == *'Failed to push to 1 of 2 remotes'* ]] || exit 1
+cd "$(mktemp -d)"
+git clone "$mirror" clone
+cd clone
+git perf pull --remote origin
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 3 ]] || exit 1
+
+echo Pull from multiple remotes
+git remote add orig "$orig"
+git perf pull --remote origin --remote orig
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 3 ]] || exit 1
+
+exit 0
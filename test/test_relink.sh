@@ -0,0 +1,56 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Relink measurements after a rebase
+cd_temp_repo
+git checkout -b feature
+for i in 1 2 3; do
+  create_commit
+  git perf add -m timer "$i"
+done
+git checkout master
+create_commit
+git checkout feature
+old=$(git rev-parse HEAD)
+git rebase master
+
+output=$(git perf report -o - -n 4 2>&1 1>/dev/null) && exit 1
+[[ ${output} == *'3 of the last 4 commits have no measurements'* ]] || exit 1
+[[ ${output} == *'git perf relink'* ]] || exit 1
+git perf add -m timer 3
+output=$(git perf audit -m timer -n 4 2>&1 1>/dev/null)
+[[ ${output} == *'2 of the last 4 commits have no measurements'* ]] || exit 1
+
+output=$(git perf relink --from "master..${old}" --to master..HEAD 2>&1)
+[[ ${output} == *'Copied measurements to 3 commits'* ]] || exit 1
+[[ $(git perf report -o - -n 4 -m timer | tail -n +2 | cut -f5 | sort | tr '\n' ' ') == '1.0 2.0 3.0 3.0 ' ]] || exit 1
+output=$(git perf report -o - -n 4 2>&1 1>/dev/null)
+[[ ${output} != *'Warning'* ]] || exit 1
+
+echo Relinking again copies nothing
+output=$(git perf relink --from "master..${old}" --to master..HEAD 2>&1)
+[[ ${output} == *'Copied measurements to 0 commits'* ]] || exit 1
+[[ $(git perf report -o - -n 4 -m timer | tail -n +2 | wc -l) -eq 4 ]] || exit 1
+
+echo Match amended commits by Change-Id
+cd_temp_repo
+echo change >> a
+git add a
+git commit -m 'change' -m 'Change-Id: I1234'
+git perf add -m timer 5
+old=$(git rev-parse HEAD)
+echo amended >> a
+git add a
+git commit --amend -m 'amended change' -m 'Change-Id: I1234'
+git perf relink --from "${old}~1..${old}" --to HEAD~1..HEAD
+[[ $(git perf report -o - -n 1 -m timer | tail -n +2 | cut -f5) == '5.0' ]] || exit 1
+
+git perf relink --from does-not-exist --to HEAD~1..HEAD && exit 1
+
+exit 0
@@ -0,0 +1,59 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Remove measurements by name and filter
+cd_temp_repo
+git checkout HEAD~1
+git perf add -m timer 1
+git perf add -m old_timer 1
+git perf add -m bench::parse::mean 1
+git checkout master
+git perf add -m timer 2
+git perf add -m old_timer 2
+git perf add -m bench::parse::max 2
+git perf add -m bench::lex::mean 2
+
+git perf remove -m old_timer
+[[ $(git perf report -o - -m old_timer | tail -n +2 | wc -l) -eq 0 ]] || exit 1
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 2 ]] || exit 1
+
+git perf remove --filter 'bench::parse::.*'
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 3 ]] || exit 1
+git perf report -o - -m bench::lex::mean | grep -q bench::lex::mean
+
+echo Remove all measurements of a commit
+git perf remove -m timer -m bench::lex::mean
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 0 ]] || exit 1
+[[ $(git notes --ref refs/notes/perf-v3 list | wc -l) -eq 0 ]] || exit 1
+
+echo Remove measurements of single commits
+cd_temp_repo
+git checkout HEAD~2
+git perf add -m timer 1 -k os=linux
+git checkout master && git checkout HEAD~1
+git perf add -m timer 100 -k os=linux
+git perf add -m memory 5
+git checkout master
+git perf add -m timer 2 -k os=linux
+output=$(git perf remove --commit HEAD~1 --dry-run)
+[[ ${output} == *"$(git rev-parse HEAD~1) timer 100 os=linux"* ]] || exit 1
+[[ ${output} == *"$(git rev-parse HEAD~1) memory 5"* ]] || exit 1
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 4 ]] || exit 1
+git perf remove --commit HEAD~1 -m timer
+[[ $(git perf report -o - -m timer | tail -n +2 | wc -l) -eq 2 ]] || exit 1
+[[ $(git perf report -o - -m memory | tail -n +2 | wc -l) -eq 1 ]] || exit 1
+git perf remove --commit HEAD~1 --commit HEAD
+[[ $(git perf report -o - | tail -n +2 | wc -l) -eq 1 ]] || exit 1
+git perf remove --commit does-not-exist && exit 1
+
+echo Selection is required
+git perf remove && exit 1
+git perf remove --filter '(' && exit 1
+
+exit 0
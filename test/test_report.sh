@@ -31,12 +31,132 @@ git perf add -m timer 4.3 -k os=mac
 git perf add -m timer2 2 -k os=mac
 
 git perf report -o all_result.html
+grep -q 'location.hash' all_result.html
 git perf report -o separated_result.html -s os
 git perf report -o single_result.html -m timer
 git perf report -o separated_single_result.html -m timer -s os
+git perf report -o threshold_result.html -m timer -s os --threshold-bands
+grep -q tonexty threshold_result.html
+git perf report -o threshold_all_epochs_result.html -m timer --threshold-bands --ignore-epochs
+grep -q tonexty threshold_all_epochs_result.html
+git perf report -o threshold_aggregated_result.html -a median --threshold-bands
+grep -q 'MAD: ' threshold_aggregated_result.html
+# The bands use the sigma configured for the audit
+git perf report -o threshold_default.html -m timer -k os=mac --threshold-bands
+cat > .gitperfconfig <<'TOML'
+[measurement."timer".selector."os=mac"]
+sigma = 1.0
+TOML
+git perf report -o threshold_configured.html -m timer -k os=mac --threshold-bands
+cmp -s threshold_default.html threshold_configured.html && exit 1
+rm .gitperfconfig
+git perf report -o bar_result.html -s os --chart-type bar-latest
+grep -q '"type": "bar"' bar_result.html
+grep -q '"barmode": "group"' bar_result.html
+grep -q '"name": "ubuntu"' bar_result.html
+grep -q "Performance Measurements ($(git rev-parse --short=6 HEAD))" bar_result.html
+grep -q 'error_y' bar_result.html
+output=$(git perf report -o bar_result.csv --chart-type bar-latest 2>&1) && exit 1
+[[ ${output} == *'Bar charts are only supported in HTML reports'* ]] || exit 1
+git perf report -o time_result.html -s os --x-axis time
+grep -q '"type": "date"' time_result.html
+grep -q "$(git log -1 --format=%ct)000" time_result.html
+# Author, date, and subject of the commits on hover
+date=$(git log -1 --date=format-local:'%Y-%m-%d %H:%M' --format=%ad)
+grep -qF "commit: $(git rev-parse HEAD)\\u003cbr\\u003egithub-actions[bot], ${date}\\u003cbr\\u003emy commit" time_result.html
+output=$(git perf report -o time_result.csv --x-axis time 2>&1) && exit 1
+[[ ${output} == *'A time x-axis is only supported for time series in HTML reports'* ]] || exit 1
+echo '[report]
+compact_hover = true' > .gitperfconfig
+git perf report -o compact_aggregated_result.html -a median
+grep -q 'MAD: ' compact_aggregated_result.html && exit 1
+rm .gitperfconfig
+echo '[report]
+commit_url_template = "https://example.com/commit/{sha}"' > .gitperfconfig
+git perf report -o linked_result.html
+grep -q "https://example.com/commit/$(git rev-parse HEAD)" linked_result.html
+grep -q plotly_click linked_result.html
+rm .gitperfconfig
+grep -q plotly_click all_result.html && exit 1
+grep -q "\"$(git rev-parse --short=6 HEAD)\"" all_result.html
+grep -q "commit: $(git rev-parse HEAD)" all_result.html
+echo '[report]
+sha_length = 10' > .gitperfconfig
+git perf report -o long_sha_result.html
+grep -q "\"$(git rev-parse --short=10 HEAD)\"" long_sha_result.html
+git perf report -o full_sha_result.html --sha-length 40
+grep -q "\"$(git rev-parse HEAD)\"" full_sha_result.html
+rm .gitperfconfig
+git perf add -m build_time 2500000000
+echo '[measurement."build_time"]
+unit = "ns"' > .gitperfconfig
+git perf report -o scaled_result.html -m build_time
+grep -q 'build_time \[s\]' scaled_result.html
+grep -q '2,500,000,000.000 ns' scaled_result.html
+git perf report -o unscaled_result.html -m build_time --no-auto-scale
+grep -q 'build_time \[s\]' unscaled_result.html && exit 1
+echo '[report]
+auto_scale_units = false' >> .gitperfconfig
+git perf report -o unscaled_result.html -m build_time
+grep -q 'build_time \[s\]' unscaled_result.html && exit 1
+rm .gitperfconfig
+git perf report -o result.md -m timer -s os
+grep -q '^### timer' result.md
+grep -q '^| mac | ' result.md
+git perf report -o result.json -m timer -s os -a median
+grep -q '"group": "mac"' result.json
+grep -q '"n": 2' result.json
+git perf report -o result.svg -m timer -s os
+grep -q '<svg' result.svg
+grep -q 'timer mac' result.svg
+git perf report -o result.png -m timer -a median
+[[ $(head -c 4 result.png | tail -c 3) == PNG ]] || exit 1
+output=$(git perf report -o - -f markdown -m timer2)
+if [[ ${output} != *'| - | 2.000 | - |'* ]]; then
+  echo "Missing markdown table for timer2"
+  echo "$output"
+  exit 1
+fi
+
+output=$(git perf report -o - -m timer)
+[[ $(echo "$output" | head -n 1) == $'commit\tepoch\tname\ttimestamp\tval\tos' ]] || exit 1
+[[ $(echo "$output" | wc -l) -eq 17 ]] || exit 1
+# Deterministic output for downstream diffs
+[[ $(git perf report -o - -m timer) == "$output" ]] || exit 1
+output=$(git perf report -o - -m timer --csv-mode summary -s os)
+[[ $(echo "$output" | head -n 1) == $'commit\tepoch\tname\tgroup\tval\tn\tstddev\tmad' ]] || exit 1
+[[ $(echo "$output" | wc -l) -eq 9 ]] || exit 1
+[[ ${output} == *$'\ttimer\tmac\t4.3\t2\t'* ]] || exit 1
+output=$(git perf report -o - -m timer -a median)
+[[ $(echo "$output" | wc -l) -eq 5 ]] || exit 1
+output=$(git perf report -o - -m timer -a median --csv-mode raw)
+[[ $(echo "$output" | wc -l) -eq 17 ]] || exit 1
+git perf report -o result.html --csv-mode raw && exit 1
+
+echo Aggregate by percentiles
+for i in $(seq 1 10); do
+  git perf add -m latency "$i"
+done
+output=$(git perf report -o - -m latency -a p90)
+[[ $(echo "$output" | tail -n +2 | wc -l) -eq 1 ]] || exit 1
+[[ ${output} == *$'	latency	'*$'	9.1'* ]] || exit 1
+git perf audit -m latency -a p99
+git perf report -o - -m latency -a p42 && exit 1
+
 # TODO(kaihowl) kill group by
 # git perf report -o single_result_different_group.html -m timer -g os
 
+echo Report the history of another ref
+git checkout -b feature HEAD~2
+create_commit
+git perf add -m feature_timer 1
+git checkout -
+git perf report -o - -m feature_timer && exit 1
+output=$(git perf report -o - -m feature_timer --ref feature)
+[[ $(echo "$output" | tail -n +2 | wc -l) -eq 1 ]] || exit 1
+[[ $(git perf report -o - -m timer --ref feature -n 2 | tail -n +2 | wc -l) -eq 4 ]] || exit 1
+git perf report -o - --ref does-not-exist && exit 1
+
 output=$(git perf report -m timer-does-not-exist 2>&1 1>/dev/null) && exit 1
 if [[ ${output} != *'no performance measurements'* ]]; then
   echo "No warning for missing measurements"
@@ -58,3 +178,10 @@ fi
 #   echo "$output"
 #   exit 1
 # fi
+
+echo Parsing with a bounded number of threads
+[[ $(git perf --parse-jobs 1 report -o - | sort) == "$(git perf report -o - | sort)" ]] || exit 1
+git perf report -o - --parse-jobs 2 > /dev/null
+git perf --parse-jobs 0 report -o - && exit 1
+
+exit 0
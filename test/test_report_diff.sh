@@ -0,0 +1,33 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_empty_repo
+create_commit
+git perf add -m timer 1
+git perf add -m timer 1.1
+git perf add -m gone 3
+git perf report -o old.json
+
+create_commit
+git perf add -m timer 10
+git perf add -m timer 10.1
+git perf add -m fresh 5
+git perf report -o new.json -n 1
+
+output=$(git perf report-diff old.json new.json)
+[[ ${output} == *'### New measurements'*'`fresh`'* ]] || exit 1
+[[ ${output} == *'### Disappeared measurements'*'`gone`'* ]] || exit 1
+[[ ${output} == *'| `timer` |'* ]] || exit 1
+
+output=$(git perf report-diff old.json old.json)
+[[ ${output} == *'No changes.'* ]] || exit 1
+
+git perf report-diff old.json does-not-exist.json && exit 1
+
+exit 0
@@ -0,0 +1,119 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+for i in 4 3 2 1; do
+  create_commit
+done
+for i in 7 6 5 4 3 2 1; do
+  git checkout "HEAD~$i"
+  if [[ $i -gt 3 ]]; then
+    git perf add -m bench::parse 10 -k os=linux
+  else
+    git perf add -m bench::parse 15 -k os=linux
+  fi
+  git perf add -m bench::lex 20 -k os=linux
+  git perf add -m build_time 30
+  git checkout master
+done
+git perf add -m bench::parse 15 -k os=linux
+git perf add -m bench::lex 20 -k os=linux
+git perf add -m build_time 30
+
+echo Sections require configuration
+output=$(git perf report --sections -o report.html 2>&1) && exit 1
+[[ ${output} == *'No report sections configured'* ]] || exit 1
+
+cat > .gitperfconfig <<'CONF'
+[report.section.benchmarks]
+title = "Benchmarks"
+filter = "bench::.*"
+separate_by = ["os"]
+aggregate_by = "median"
+show_changes = true
+
+[report.section.build]
+filter = "build_time"
+depth = 3
+CONF
+
+echo Markdown report with a section per configured block
+output=$(git perf report --sections -o report.md && cat report.md)
+[[ ${output} == '## Benchmarks'*'### bench::parse'*'### bench::lex'*'## build'*'### build_time'* ]] || exit 1
+[[ $(echo "$output" | grep -c '^### build_time') -eq 1 ]] || exit 1
+
+echo HTML report with a plot per section
+git perf report --sections -o report.html
+[[ $(grep -c '<html' report.html) -eq 1 ]] || exit 1
+grep -q 'Benchmarks' report.html
+grep -q '<div id="section-build"' report.html
+echo Only the benchmarks section marks step changes
+[[ $(grep -c 'step change: 10.000 → 15.000' report.html) -eq 1 ]] || exit 1
+
+echo Key-value filters apply to all sections
+output=$(git perf report --sections -k os=linux -o report.md 2>&1) && exit 1
+[[ ${output} == *"Failed to report section 'build'"* ]] || exit 1
+
+echo Sections cannot be combined with options of a single report
+git perf report --sections -m build_time -o report.html && exit 1
+git perf report --sections -o - && exit 1
+
+echo Sections on different branches
+git checkout -b release HEAD~2
+create_commit
+git perf add -m build_time 40
+create_commit
+git perf add -m build_time 50
+git checkout master
+cat > .gitperfconfig <<'CONF'
+[report.section.main]
+filter = "build_time"
+depth = 3
+
+[report.section.release]
+filter = "build_time"
+ref = "release"
+depth = 3
+
+[report.section.backports]
+filter = "build_time"
+range = "master..release"
+CONF
+git perf report --sections -o report.html
+grep -q '<div id="section-release"' report.html
+grep -q '<div id="section-backports"' report.html
+git perf report --sections -o report.md
+main_section=$(sed -n '/^## main/,/^## release/p' report.md)
+release_section=$(sed -n '/^## release/,/^## backports/p' report.md)
+backports_section=$(sed -n '/^## backports/,$p' report.md)
+[[ ${main_section} == *'| - | 30.000 | +0.00% | ![▁▁▁]'* ]] || exit 1
+[[ ${release_section} == *'| - | 50.000 | +25.00% | ![▁▅█]'* ]] || exit 1
+[[ ${backports_section} == *'| - | 50.000 | +25.00% | ![▁█]'* ]] || exit 1
+
+echo Sections with missing refs or ranges
+echo '[report.section.missing]
+ref = "does-not-exist"' > .gitperfconfig
+output=$(git perf report --sections -o report.md 2>&1) && exit 1
+[[ ${output} == *"Failed to report section 'missing'"* ]] || exit 1
+echo '[report.section.missing]
+range = "master..does-not-exist"' > .gitperfconfig
+git perf report --sections -o report.md && exit 1
+echo '[report.section.both]
+ref = "master"
+range = "master..release"' > .gitperfconfig
+output=$(git perf report --sections -o report.md 2>&1) && exit 1
+[[ ${output} == *'report.section.both can only have one of ref and range'* ]] || exit 1
+
+echo Invalid section configuration
+echo '[report.section.invalid]
+depth = -1' > .gitperfconfig
+output=$(git perf report --sections -o report.html 2>&1) && exit 1
+[[ ${output} == *'report.section.invalid.depth must be a positive integer'* ]] || exit 1
+
+exit 0
@@ -0,0 +1,47 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_temp_repo
+git perf add -m timer 1 -k os=linux
+git perf add -m timer 2 -k os=mac
+
+log=$(mktemp)
+git perf serve --address 127.0.0.1:0 > "$log" &
+server=$!
+trap 'kill $server' EXIT
+
+for _ in $(seq 50); do
+  grep -q 'Serving reports on' "$log" && break
+  sleep 0.1
+done
+url=$(sed -n 's/^Serving reports on //p' "$log")
+[[ -n ${url} ]] || exit 1
+
+echo Interactive HTML report by default
+curl -sf "${url}" | grep -q 'Plotly.newPlot'
+
+echo Filters and format from the query string
+output=$(curl -sf "${url}?m=timer&k=os%3Dmac&format=csv")
+[[ $(echo "$output" | tail -n +2 | wc -l) -eq 1 ]] || exit 1
+[[ ${output} == *$'\ttimer\t'*$'\t2.0\tmac'* ]] || exit 1
+output=$(curl -sf "${url}?s=os&a=median&n=1&format=json")
+[[ ${output} == *'"group": "linux"'* ]] || exit 1
+
+echo Errors
+[[ $(curl -s -o /dev/null -w '%{http_code}' "${url}?n=many") == 400 ]] || exit 1
+[[ $(curl -s -o /dev/null -w '%{http_code}' "${url}?m=missing") == 500 ]] || exit 1
+[[ $(curl -s -o /dev/null -w '%{http_code}' "${url}missing") == 404 ]] || exit 1
+
+echo Refuse options as start and foreign hosts
+[[ $(curl -s -o /dev/null -w '%{http_code}' "${url}?start=--output%3Dinjected") == 400 ]] || exit 1
+[[ ! -e injected ]] || exit 1
+[[ $(curl -s -o /dev/null -w '%{http_code}' "${url}?start=HEAD") == 200 ]] || exit 1
+[[ $(curl -s -o /dev/null -w '%{http_code}' -H 'Host: evil.example' "${url}") == 403 ]] || exit 1
+
+exit 0
@@ -0,0 +1,76 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+year=$(date -u +%Y)
+count_measurements() {
+  git perf report -o - "$@" | tail -n +2 | wc -l
+}
+
+cd "$(mktemp -d)"
+root=$(pwd)
+git init --bare orig
+orig=$root/orig
+
+cd_empty_repo
+repo1=$(pwd)
+create_commit
+create_commit
+create_commit
+git remote add origin "$orig"
+git push origin master
+
+echo Migrate existing measurements into shards
+git perf add -m timer 1
+old_measurement=$(printf '0\034timer\03486400.0\0341.0')
+git notes --ref refs/notes/perf-v3 append -m "$old_measurement" HEAD~1
+git perf migrate-shards && exit 1
+cat > .gitperfconfig <<CONFIG
+[storage]
+shards = "year"
+CONFIG
+git perf migrate-shards 2>&1 | grep -q 'Moved the measurements of 2 commits into shards'
+git rev-parse --verify --quiet refs/notes/perf-v3 && exit 1
+git notes --ref refs/notes/perf-v3-shards/1970 show HEAD~1 | grep -q timer
+git notes --ref "refs/notes/perf-v3-shards/$year" show HEAD | grep -q timer
+[[ $(count_measurements -m timer) -eq 2 ]] || exit 1
+git perf migrate-shards 2>&1 | grep -q 'Moved the measurements of 0 commits into shards'
+
+echo New measurements are written to the shard of their year
+git perf add -m other 2
+git notes --ref "refs/notes/perf-v3-shards/$year" show HEAD | grep -q other
+[[ $(count_measurements) -eq 3 ]] || exit 1
+
+echo Push and pull all shards
+git perf push
+cd "$root"
+git clone "$orig" repo2
+cd repo2
+git config user.name "$GIT_COMMITTER_NAME"
+git config user.email "$GIT_COMMITTER_EMAIL"
+cp "$repo1/.gitperfconfig" .
+git perf pull
+[[ $(count_measurements) -eq 3 ]] || exit 1
+git for-each-ref refs/notes/perf-v3-merge-shards | grep -q . && exit 1
+
+echo Concurrent additions are merged
+git perf add -m timer 3
+git perf push
+cd "$repo1"
+git perf add -m timer 4
+git perf push
+cd "$root/repo2"
+git perf pull
+[[ $(count_measurements -m timer) -eq 4 ]] || exit 1
+
+echo Removal rewrites every shard
+git perf remove -m timer
+[[ $(count_measurements) -eq 1 ]] || exit 1
+git notes --ref refs/notes/perf-v3-shards/1970 list | grep -q . && exit 1
+
+exit 0
@@ -0,0 +1,38 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+cd_empty_repo
+echo Without measurements
+output=$(git perf size)
+[[ ${output} == *'0 annotated commits, 0 bytes of measurements'* ]] || exit 1
+
+echo Measurements of several months
+GIT_COMMITTER_DATE='2024-01-15T12:00:00Z' create_commit
+GIT_COMMITTER_DATE='2024-01-15T12:00:00Z' git perf add -m timer 1
+GIT_COMMITTER_DATE='2024-03-15T12:00:00Z' create_commit
+GIT_COMMITTER_DATE='2024-03-15T12:00:00Z' git perf add -m timer 2 -k os=linux
+GIT_COMMITTER_DATE='2024-03-16T12:00:00Z' git perf add -m timer 3 -k os=linux
+total=$(git notes --ref refs/notes/perf-v3 list | cut -d' ' -f1 | git cat-file --batch-check='%(objectsize)' | awk '{ total += $1 } END { print total }')
+
+output=$(git perf size)
+[[ ${output} == *"2 annotated commits, ${total} bytes of measurements"* ]] || exit 1
+
+echo Largest commits first
+output=$(git perf size --by-commit --top 1)
+[[ $(grep -c ' bytes (' <<< "${output}") -eq 1 ]] || exit 1
+[[ ${output} == *"$(git rev-parse HEAD) "* ]] || exit 1
+git perf size --top 1 && exit 1
+
+echo Growth per month
+output=$(git perf size --trend)
+[[ ${output} == *'2024-01 1 commits '* ]] || exit 1
+[[ ${output} == *"2024-03 2 commits ${total} bytes (+"* ]] || exit 1
+[[ ${output} == *'2024-02'* ]] && exit 1
+
+exit 0
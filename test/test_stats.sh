@@ -0,0 +1,34 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+echo Summary statistics per measurement
+cd_temp_repo
+git checkout HEAD~1
+git perf add -m timer 1 -k os=linux
+git perf add -m timer 3 -k os=mac
+git checkout master
+git perf add -m timer 2 -k os=linux
+git perf add -m other 5
+
+output=$(git perf stats)
+[[ ${output} == *'measurement'*'median'*'MAD'* ]] || exit 1
+[[ $(echo "$output" | wc -l) -eq 3 ]] || exit 1
+[[ $(echo "$output" | grep '^timer') == 'timer '*'3'*'2.000'*'2.000'* ]] || exit 1
+
+output=$(git perf stats -m timer -s os=linux --format json)
+[[ ${output} == *'"name": "timer"'* ]] || exit 1
+[[ ${output} == *'"count": 2'* ]] || exit 1
+[[ ${output} == *'"max": 2.0'* ]] || exit 1
+[[ ${output} != *'"name": "other"'* ]] || exit 1
+
+echo Limited history
+output=$(git perf stats -n 1 -m timer --format json)
+[[ ${output} == *'"count": 1'* ]] || exit 1
+
+exit 0
@@ -0,0 +1,59 @@
+#!/bin/bash
+
+set -e
+set -x
+
+script_dir=$(dirname "$0")
+# shellcheck source=test/common.sh
+source "$script_dir/common.sh"
+
+count_measurements() {
+  git perf report -o - "$@" | tail -n +2 | wc -l
+}
+
+cd_empty_repo
+create_commit
+git perf add -m timer 1 -k os=linux
+git perf add -m timer 2 -k os=linux
+create_commit
+git perf add -m timer 3 -k os=linux
+git perf add -m timer 4 -k os=mac
+git perf add -m build 5
+before=$(git perf report -o - | sort)
+
+echo Convert the history to v4
+git perf migrate-storage --dry-run 2>&1 | grep -q 'Would convert the measurements of 2 commits to v4'
+git notes --ref refs/notes/perf-v3 show HEAD | grep -q '^v4' && exit 1
+git perf migrate-storage 2>&1 | grep -q 'Converted the measurements of 2 commits to v4'
+[[ $(git notes --ref refs/notes/perf-v3 show HEAD~1 | wc -l) -eq 1 ]] || exit 1
+[[ $(git notes --ref refs/notes/perf-v3 show HEAD | grep -c '^v4') -eq 3 ]] || exit 1
+[[ $(git perf report -o - | sort) == "$before" ]] || exit 1
+git perf migrate-storage 2>&1 | grep -q 'Converted the measurements of 0 commits to v4'
+
+echo New measurements are added in the configured format
+git perf add -m timer 6 -k os=linux
+git notes --ref refs/notes/perf-v3 show HEAD | grep -q '^0' || exit 1
+cat > .gitperfconfig <<CONFIG
+[storage]
+format = "v4"
+CONFIG
+git perf measure -m slept -n 3 -- sleep 0.01
+git notes --ref refs/notes/perf-v3 show HEAD | grep -q '^v4.*slept' || exit 1
+[[ $(count_measurements -m slept) -eq 3 ]] || exit 1
+
+echo Removal of v4 lines
+git perf remove -m timer --commit HEAD~1
+[[ $(count_measurements -m timer) -eq 3 ]] || exit 1
+
+echo Convert back to v3
+git perf migrate-storage --to v3 2>&1 | grep -q 'Converted the measurements of 1 commits to v3'
+git notes --ref refs/notes/perf-v3 show HEAD | grep -q '^v4' && exit 1
+[[ $(count_measurements) -eq 7 ]] || exit 1
+
+echo Unknown formats are rejected
+echo '[storage]
+format = "v5"' > .gitperfconfig
+output=$(git perf add -m timer 1 2>&1) && exit 1
+[[ ${output} == *"Unknown storage format 'v5'"* ]] || exit 1
+
+exit 0